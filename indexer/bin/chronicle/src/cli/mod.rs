@@ -1,24 +1,88 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
 use chronicle_primitives::{
-    config::return_me_indexer_config,
-    db::{entities::system::create_system_table, raw_chronicle_event::create_db_instance},
+    config::{interpolate_env, return_me_indexer_config},
+    db::{
+        connection_pool::{create_db_pool, get_db_client},
+        entities::system::create_system_table,
+        migrations::{migration_status, revert_migrations, run_pending_migrations},
+    },
     Config,
 };
 use chronicle_tasks::{indexer::IndexerTask, server::ServerTask, spawn_tasks};
+use clap::{Parser, Subcommand};
 use tracing_subscriber::{filter::LevelFilter, util::SubscriberInitExt};
 
+/// Chronicle, a light weight blockchain indexer
+#[derive(Parser, Debug)]
+#[command(name = "chronicle", version, about)]
+pub struct Cli {
+    /// Path to the TOML config file holding the `[[indexer]]` and `[server]` tables.
+    /// `${ENV_VAR}` placeholders in the file are replaced from the environment.
+    /// When not provided, the config is read from the environment.
+    #[arg(long, short)]
+    pub config_path: Option<PathBuf>,
+
+    /// When no command is given, the pending migrations are applied and chronicle is started
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Applies or reverts the schema migrations
+    Migrate {
+        #[command(subcommand)]
+        direction: MigrateDirection,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum MigrateDirection {
+    /// Applies the pending migrations
+    Up {
+        /// The version to migrate up to, all the pending migrations are applied when not provided
+        #[arg(long)]
+        to: Option<i64>,
+    },
+    /// Reverts the last applied migrations
+    Down {
+        /// The number of migrations to revert
+        #[arg(long, default_value_t = 1)]
+        steps: usize,
+    },
+    /// Lists the migrations and whether they were applied
+    Status,
+}
+
 /// Main entry point for the CLI
 ///
 /// Parses the CLI arguments and runs the appropriate subcommand.
 /// Listens for a ctrl-c signal and shuts down all components when received.
 pub async fn run() -> Result<(), Box<dyn std::error::Error>> {
     logger_setup()?;
-    let config: Config = return_me_indexer_config();
+    let cli = Cli::parse();
+    let config: Config = match cli.config_path {
+        Some(path) => load_config(&path)?,
+        None => return_me_indexer_config(),
+    };
+    config.validate()?;
+
+    // the server and the indexers share this pool of database connections
+    let db_pool = create_db_pool(&config.db_url, &config.db_pool)?;
+
+    // create system db is one has not been created,
+    // the connection goes back to the pool before the tasks start
+    {
+        let mut db_client = get_db_client(&db_pool).await?;
+        create_system_table(&mut db_client).await?;
 
-    // create system db is one has not been created
-    let mut db_client = create_db_instance(&config.db_url)
-        .await
-        .expect("Could not create db instance");
-    create_system_table(&mut db_client).await?;
+        if let Some(Command::Migrate { direction }) = cli.command {
+            return Ok(migrate(direction, &mut db_client).await?);
+        }
+        run_pending_migrations(None, &mut db_client).await?;
+    }
 
     // server config
     let server_config = config.clone().server;
@@ -27,10 +91,16 @@ pub async fn run() -> Result<(), Box<dyn std::error::Error>> {
 
     tracing::info!("Starting Chronicle with config: {:?}", config.clone());
 
-    let mut tasks = vec![ServerTask::new(server_config, config.db_url.clone()).boxed()];
+    // the events decoded from an ABI get their GraphQL schema
+    let abi_event_tables = indexer_configs
+        .iter()
+        .filter_map(|indexer_config| indexer_config.abi_event_table().transpose())
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut tasks = vec![ServerTask::new(server_config, db_pool.clone(), abi_event_tables).boxed()];
 
     for indexer_config in indexer_configs {
-        tasks.push(IndexerTask::new(indexer_config, config.db_url.clone()).boxed());
+        tasks.push(IndexerTask::new(indexer_config, db_pool.clone())?.boxed());
     }
 
     spawn_tasks(tasks, tokio::signal::ctrl_c()).await;
@@ -38,6 +108,43 @@ pub async fn run() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
+/// This function runs the `migrate` subcommand
+pub async fn migrate(
+    direction: MigrateDirection,
+    db_client: &mut tokio_postgres::Client,
+) -> Result<(), anyhow::Error> {
+    match direction {
+        MigrateDirection::Up { to } => {
+            let applied = run_pending_migrations(to, db_client).await?;
+            println!("Applied {} migration(s)", applied.len());
+        }
+        MigrateDirection::Down { steps } => {
+            let reverted = revert_migrations(steps, db_client).await?;
+            println!("Reverted {} migration(s)", reverted.len());
+        }
+        MigrateDirection::Status => {
+            for status in migration_status(db_client).await? {
+                let state = if status.applied { "applied" } else { "pending" };
+                println!("{state}\t{}", status.migration.name);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// This function reads the config file, fills in the `${ENV_VAR}` placeholders
+/// and parses it into the chronicle [`Config`]
+pub fn load_config(path: &Path) -> Result<Config, anyhow::Error> {
+    let raw = std::fs::read_to_string(path)
+        .with_context(|| format!("Could not read config file {}", path.display()))?;
+    let raw = interpolate_env(&raw)?;
+    let config: Config = toml::from_str(&raw)
+        .with_context(|| format!("Could not parse config file {}", path.display()))?;
+
+    Ok(config)
+}
+
 // this function is for setting up the logging process
 pub fn logger_setup() -> Result<(), anyhow::Error> {
     let filter =
@@ -0,0 +1,193 @@
+//! This module walks historical blocks in bounded `eth_getLogs` windows.
+//! Providers reject unbounded or very large log queries, so the window shrinks
+//! when a range is rejected and grows back once requests succeed again.
+use std::time::Duration;
+
+use alloy::{
+    primitives::{Address, B256},
+    providers::RootProvider,
+    transports::BoxTransport,
+};
+use chronicle_primitives::{
+    db::{
+        connection_pool::{get_db_client, DbPool},
+        entities::{
+            group_events_by_block, store_block_events,
+            system::{update_last_block_number, CheckpointKey},
+        },
+    },
+    registry::EventHandlerRegistry,
+    BackfillConfig,
+};
+use tracing::{info, warn};
+
+use super::{
+    factory::{query_factory_window, FactoryWindow},
+    utils::query_events,
+};
+
+/// This function indexes every event emitted in `[from_block, to_block]`, one window
+/// at a time. The events of each block are written in one transaction with the checkpoint,
+/// so a crash mid-backfill resumes from the last completed block.
+/// With a `factory`, the children it deploys are added to `addr` as they are found,
+/// and returned. A connection is taken from `db_pool` for the writes of each window.
+#[allow(clippy::too_many_arguments)]
+pub async fn backfill_events(
+    provider: &RootProvider<BoxTransport>,
+    mut addr: Vec<Address>,
+    event_sig: B256,
+    factory: Option<Address>,
+    from_block: u64,
+    to_block: u64,
+    config: &BackfillConfig,
+    registry: &EventHandlerRegistry,
+    db_pool: &DbPool,
+    checkpoint: &CheckpointKey,
+) -> Result<Vec<Address>, anyhow::Error> {
+    let mut cursor = from_block;
+    let mut new_children = Vec::new();
+    let mut window = config.max_block_range.max(1);
+    let mut retries = 0;
+
+    while cursor <= to_block {
+        let window_end = cursor.saturating_add(window - 1).min(to_block);
+
+        let result = match factory {
+            Some(factory) => {
+                query_factory_window(provider, factory, &addr, event_sig, cursor, window_end).await
+            }
+            None => query_events(provider, addr.clone(), event_sig, cursor, window_end)
+                .await
+                .map(|events| FactoryWindow {
+                    events,
+                    ..Default::default()
+                }),
+        };
+        let window_logs = match result {
+            Ok(window_logs) => window_logs,
+            Err(err) => {
+                let message = err.to_string();
+                if is_block_range_error(&message) && window > config.min_block_range {
+                    window = shrink_window(window, config.min_block_range);
+                    warn!("eth_getLogs rejected {cursor}..={window_end}, window is now {window}: {message}");
+                    continue;
+                }
+
+                retries += 1;
+                if retries > config.max_retries {
+                    return Err(err.context(format!(
+                        "eth_getLogs failed for {cursor}..={window_end} after {retries} attempts"
+                    )));
+                }
+                let delay = retry_backoff(config.retry_backoff_ms, retries);
+                warn!("eth_getLogs failed for {cursor}..={window_end}, retrying in {delay:?}: {message}");
+                tokio::time::sleep(delay).await;
+                continue;
+            }
+        };
+
+        // The deployments of a block come before the logs their children emit later in it
+        let logs = window_logs
+            .deployments
+            .into_iter()
+            .chain(window_logs.events)
+            .collect();
+        let mut db_client = get_db_client(db_pool).await?;
+        for block in group_events_by_block(logs) {
+            store_block_events(&block, &mut db_client, registry, checkpoint).await?;
+        }
+        addr.extend(window_logs.new_children.iter().copied());
+        new_children.extend(window_logs.new_children);
+        update_last_block_number(checkpoint, window_end.to_string(), &mut db_client).await?;
+        info!(
+            "Backfilled {} blocks {cursor}..={window_end}",
+            checkpoint.indexer_name
+        );
+
+        retries = 0;
+        cursor = window_end + 1;
+        window = grow_window(window, config.max_block_range);
+    }
+
+    Ok(new_children)
+}
+
+/// This function checks if a provider error means the requested block range or
+/// result set was too large, as opposed to a transient failure
+pub fn is_block_range_error(message: &str) -> bool {
+    let message = message.to_lowercase();
+    if message.contains("rate limit") || message.contains("429") {
+        return false;
+    }
+
+    [
+        "more than",
+        "too many",
+        "block range",
+        "range is too",
+        "range too",
+        "exceed",
+        "too large",
+        "response size",
+        "limited to",
+    ]
+    .iter()
+    .any(|pattern| message.contains(pattern))
+}
+
+/// Halves the window, never going below `min_block_range`
+pub fn shrink_window(window: u64, min_block_range: u64) -> u64 {
+    (window / 2).max(min_block_range).max(1)
+}
+
+/// Doubles the window, never going above `max_block_range`
+pub fn grow_window(window: u64, max_block_range: u64) -> u64 {
+    window.saturating_mul(2).min(max_block_range).max(1)
+}
+
+/// Exponential backoff for the `attempt`-th retry, starting at `base_ms`
+pub fn retry_backoff(base_ms: u64, attempt: u32) -> Duration {
+    let factor = 2u64.saturating_pow(attempt.saturating_sub(1));
+    Duration::from_millis(base_ms.saturating_mul(factor))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_block_range_error_detects_provider_limits() {
+        assert!(is_block_range_error(
+            "server returned an error response: error code -32005: query returned more than 10000 results"
+        ));
+        assert!(is_block_range_error(
+            "Log response size exceeded. You can make eth_getLogs requests with up to a 2K block range"
+        ));
+        assert!(is_block_range_error("block range is too wide"));
+        assert!(is_block_range_error(
+            "eth_getLogs is limited to a 10,000 range"
+        ));
+
+        assert!(!is_block_range_error("connection reset by peer"));
+        assert!(!is_block_range_error("rate limit exceeded"));
+        assert!(!is_block_range_error("HTTP error 429 Too Many Requests"));
+    }
+
+    #[test]
+    fn test_window_shrinks_and_grows_within_bounds() {
+        assert_eq!(shrink_window(2_000, 1), 1_000);
+        assert_eq!(shrink_window(3, 2), 2);
+        assert_eq!(shrink_window(1, 1), 1);
+
+        assert_eq!(grow_window(1_000, 2_000), 2_000);
+        assert_eq!(grow_window(1_500, 2_000), 2_000);
+        assert_eq!(grow_window(10, 2_000), 20);
+    }
+
+    #[test]
+    fn test_retry_backoff_is_exponential() {
+        assert_eq!(retry_backoff(500, 1), Duration::from_millis(500));
+        assert_eq!(retry_backoff(500, 2), Duration::from_millis(1_000));
+        assert_eq!(retry_backoff(500, 4), Duration::from_millis(4_000));
+    }
+}
@@ -0,0 +1,158 @@
+//! This module holds live logs back until their block is deep enough.
+//! Logs above the confirmed head wait in the pending buffer, they are applied to the
+//! entity tables once the confirmed head passes their block and the block is still canonical.
+use std::time::Duration;
+
+use alloy::{
+    primitives::{Address, B256},
+    providers::{Provider, RootProvider},
+    rpc::types::eth::BlockNumberOrTag,
+    transports::BoxTransport,
+};
+use chronicle_primitives::{
+    db::{
+        connection_pool::{get_db_client, DbPool},
+        entities::{
+            apply_block_events, begin_block_transaction, finish_block_transaction,
+            group_events_by_block,
+            system::{get_last_block_number, update_last_block_number, CheckpointKey},
+        },
+        pending_event::{
+            buffer_pending_event, get_pending_events_up_to, mark_event_confirmed,
+            remove_pending_event,
+        },
+    },
+    registry::EventHandlerRegistry,
+    BackfillConfig, Confirmations, FinalityTag,
+};
+
+use super::{
+    factory::{query_factory_window, FactoryWindow},
+    reorg::canonical_block_hash,
+    utils::query_events,
+};
+
+/// This is how often the confirmed head is checked for pending logs to apply
+pub const CONFIRMATION_POLL_INTERVAL: Duration = Duration::from_secs(4);
+
+/// This function returns the newest block whose logs can be applied to the entity tables
+pub async fn confirmed_head(
+    provider: &RootProvider<BoxTransport>,
+    confirmations: &Confirmations,
+) -> Result<u64, anyhow::Error> {
+    let tag = match confirmations {
+        Confirmations::Blocks(depth) => {
+            let head = provider.get_block_number().await?;
+            return Ok(head.saturating_sub(*depth));
+        }
+        Confirmations::Tag(FinalityTag::Safe) => BlockNumberOrTag::Safe,
+        Confirmations::Tag(FinalityTag::Finalized) => BlockNumberOrTag::Finalized,
+    };
+
+    let block = provider
+        .get_block_by_number(tag, false)
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("The node did not return the {tag} block"))?;
+
+    Ok(block.header.number)
+}
+
+/// This function holds every log emitted in `[from_block, to_block]` in the pending buffer.
+/// With a `factory`, its deployments are buffered too and the children found are returned.
+#[allow(clippy::too_many_arguments)]
+pub async fn buffer_pending_range(
+    provider: &RootProvider<BoxTransport>,
+    mut addr: Vec<Address>,
+    event_sig: B256,
+    factory: Option<Address>,
+    from_block: u64,
+    to_block: u64,
+    config: &BackfillConfig,
+    db_pool: &DbPool,
+    checkpoint: &CheckpointKey,
+) -> Result<Vec<Address>, anyhow::Error> {
+    let mut cursor = from_block;
+    let window = config.max_block_range.max(1);
+    let mut new_children = Vec::new();
+
+    while cursor <= to_block {
+        let window_end = cursor.saturating_add(window - 1).min(to_block);
+        let window_logs = match factory {
+            Some(factory) => {
+                query_factory_window(provider, factory, &addr, event_sig, cursor, window_end)
+                    .await?
+            }
+            None => FactoryWindow {
+                events: query_events(provider, addr.clone(), event_sig, cursor, window_end).await?,
+                ..Default::default()
+            },
+        };
+
+        let mut db_client = get_db_client(db_pool).await?;
+        for event in window_logs.deployments.iter().chain(&window_logs.events) {
+            buffer_pending_event(event, checkpoint, &mut db_client).await?;
+        }
+        addr.extend(window_logs.new_children.iter().copied());
+        new_children.extend(window_logs.new_children);
+        cursor = window_end + 1;
+    }
+
+    Ok(new_children)
+}
+
+/// This function applies the pending logs at or below `confirmed_head` to the entity tables.
+/// The logs of a block are applied and marked confirmed in one transaction with the checkpoint.
+/// Logs whose block is no longer canonical were orphaned while pending and are dropped.
+pub async fn confirm_pending_events(
+    provider: &RootProvider<BoxTransport>,
+    confirmed_head: u64,
+    registry: &EventHandlerRegistry,
+    db_pool: &DbPool,
+    checkpoint: &CheckpointKey,
+) -> Result<(), anyhow::Error> {
+    let events = get_pending_events_up_to(
+        confirmed_head,
+        checkpoint,
+        &mut get_db_client(db_pool).await?,
+    )
+    .await?;
+
+    for block in group_events_by_block(events) {
+        let block_number = block[0].block_number;
+        let canonical_hash = canonical_block_hash(provider, block_number).await?;
+
+        // Logs of an orphaned block can sit next to the canonical ones in the buffer
+        let (canonical, orphaned): (Vec<_>, Vec<_>) = block
+            .into_iter()
+            .partition(|event| canonical_hash == Some(event.block_hash.to_string()));
+        let mut db_client = get_db_client(db_pool).await?;
+        for event in &orphaned {
+            remove_pending_event(event, checkpoint, &mut db_client).await?;
+        }
+        if canonical.is_empty() {
+            continue;
+        }
+
+        begin_block_transaction(&mut db_client).await?;
+        let result = async {
+            apply_block_events(&canonical, &mut db_client, registry, checkpoint).await?;
+            for event in &canonical {
+                mark_event_confirmed(event, checkpoint, &mut db_client).await?;
+            }
+            Ok(())
+        }
+        .await;
+        finish_block_transaction(result, &mut db_client).await?;
+    }
+
+    // Blocks up to the confirmed head have nothing left to apply
+    let mut db_client = get_db_client(db_pool).await?;
+    let last_indexed_block: u64 = get_last_block_number(checkpoint, &mut db_client)
+        .await?
+        .parse()?;
+    if confirmed_head > last_indexed_block {
+        update_last_block_number(checkpoint, confirmed_head.to_string(), &mut db_client).await?;
+    }
+
+    Ok(())
+}
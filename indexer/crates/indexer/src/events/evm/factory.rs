@@ -0,0 +1,203 @@
+//! This module follows the contracts deployed by a factory.
+//! The factory deployments and the logs of the known children are forwarded into one
+//! channel, when a new child is deployed the children forwarder is replaced by one
+//! whose filter includes it, so the indexer picks it up without a restart.
+use alloy::{
+    primitives::{Address, B256},
+    providers::RootProvider,
+    rpc::types::eth::{BlockNumberOrTag, Filter, Log},
+    transports::BoxTransport,
+};
+use chronicle_primitives::{
+    config::EVENT_SIX_SIGNATURE,
+    db::entities::{
+        gateway::query_gateways_by_factory, hooks::on_gateway_deployed::decode_gateway_deployed,
+    },
+    indexer::ChronicleEvent,
+};
+use tokio::sync::mpsc;
+
+use super::utils::{forward_logs, query_events, LogForwarder, LogSource};
+
+/// This is the event a factory emits for every child it deploys
+pub const CHILD_DEPLOYED_SIGNATURE: B256 = EVENT_SIX_SIGNATURE;
+
+pub struct FactoryWatcher {
+    /// This is the address of the factory
+    pub factory: Address,
+    /// This is the event indexed on the children
+    pub event_sig: B256,
+    /// These are the children covered by the live subscription
+    pub children: Vec<Address>,
+    source: LogSource,
+    sender: mpsc::WeakUnboundedSender<Log>,
+    factory_subscription: LogForwarder,
+    children_subscription: Option<LogForwarder>,
+}
+
+impl FactoryWatcher {
+    /// This function subscribes to the deployments of the factory and to the logs of
+    /// `children`, both are forwarded into the returned channel
+    pub async fn subscribe(
+        provider: &RootProvider<BoxTransport>,
+        factory: Address,
+        event_sig: B256,
+        children: Vec<Address>,
+        source: LogSource,
+    ) -> Result<(Self, mpsc::UnboundedReceiver<Log>), anyhow::Error> {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let filter = Filter::new()
+            .address(factory)
+            .event_signature(CHILD_DEPLOYED_SIGNATURE)
+            .from_block(BlockNumberOrTag::Latest);
+        let factory_subscription =
+            forward_logs(provider, &filter, source, None, sender.clone()).await?;
+
+        // Only the subscriptions hold the channel open, it closes when they end
+        let mut watcher = Self {
+            factory,
+            event_sig,
+            children: Vec::new(),
+            source,
+            sender: sender.downgrade(),
+            factory_subscription,
+            children_subscription: None,
+        };
+        watcher.extend_children(provider, children).await?;
+
+        Ok((watcher, receiver))
+    }
+
+    /// Checks if the log is a deployment emitted by the factory
+    pub fn is_deployment(&self, event: &ChronicleEvent) -> bool {
+        event.address == self.factory && event.topics.first() == Some(&CHILD_DEPLOYED_SIGNATURE)
+    }
+
+    /// This function adds the children that are not covered yet to the live subscription and
+    /// returns them. The extended subscription is running before the previous one is dropped,
+    /// so no log is missed in between, a poller continues from the block the previous one reached.
+    pub async fn extend_children(
+        &mut self,
+        provider: &RootProvider<BoxTransport>,
+        children: Vec<Address>,
+    ) -> Result<Vec<Address>, anyhow::Error> {
+        let mut new_children = Vec::new();
+        for child in children {
+            if !self.children.contains(&child) && !new_children.contains(&child) {
+                new_children.push(child);
+            }
+        }
+        if new_children.is_empty() {
+            return Ok(new_children);
+        }
+
+        let sender = self
+            .sender
+            .upgrade()
+            .ok_or_else(|| anyhow::anyhow!("The live log subscription has ended"))?;
+        let mut children = self.children.clone();
+        children.extend(new_children.iter().copied());
+        let filter = Filter::new()
+            .address(children.clone())
+            .event_signature(self.event_sig)
+            .from_block(BlockNumberOrTag::Latest);
+        let subscription = forward_logs(
+            provider,
+            &filter,
+            self.source,
+            self.children_subscription.as_ref(),
+            sender,
+        )
+        .await?;
+
+        if let Some(previous) = self.children_subscription.replace(subscription) {
+            previous.abort();
+        }
+        self.children = children;
+
+        Ok(new_children)
+    }
+}
+
+impl Drop for FactoryWatcher {
+    fn drop(&mut self) {
+        self.factory_subscription.abort();
+        if let Some(subscription) = &self.children_subscription {
+            subscription.abort();
+        }
+    }
+}
+
+/// This function returns the children of the factory stored so far on the chain
+pub async fn stored_children(
+    factory: Address,
+    chain_id: u64,
+    db_client: &mut tokio_postgres::Client,
+) -> Result<Vec<Address>, anyhow::Error> {
+    let gateways =
+        query_gateways_by_factory(factory.to_string(), Some(chain_id as i64), db_client).await?;
+
+    gateways
+        .iter()
+        .map(|gateway| {
+            gateway
+                .gateway_address
+                .parse::<Address>()
+                .map_err(anyhow::Error::from)
+        })
+        .collect()
+}
+
+/// These are the logs of a backfill window of a factory indexer
+#[derive(Debug, Default)]
+pub struct FactoryWindow {
+    /// These are the deployments emitted by the factory
+    pub deployments: Vec<ChronicleEvent>,
+    /// These are the children deployed in the window
+    pub new_children: Vec<Address>,
+    /// These are the logs of the known and the newly deployed children
+    pub events: Vec<ChronicleEvent>,
+}
+
+/// This function queries a backfill window of a factory indexer. The deployments are
+/// queried first, so logs emitted by children deployed inside the window are included.
+pub async fn query_factory_window(
+    provider: &RootProvider<BoxTransport>,
+    factory: Address,
+    children: &[Address],
+    event_sig: B256,
+    from_block: u64,
+    to_block: u64,
+) -> Result<FactoryWindow, anyhow::Error> {
+    let deployments = query_events(
+        provider,
+        vec![factory],
+        CHILD_DEPLOYED_SIGNATURE,
+        from_block,
+        to_block,
+    )
+    .await?;
+
+    let mut new_children = Vec::new();
+    for deployment in &deployments {
+        let child = decode_gateway_deployed(deployment)?.gateway;
+        if !children.contains(&child) && !new_children.contains(&child) {
+            new_children.push(child);
+        }
+    }
+
+    let mut addr = children.to_vec();
+    addr.extend(new_children.iter().copied());
+    // An empty address list would match the logs of every contract
+    let events = if addr.is_empty() {
+        Vec::new()
+    } else {
+        query_events(provider, addr, event_sig, from_block, to_block).await?
+    };
+
+    Ok(FactoryWindow {
+        deployments,
+        new_children,
+        events,
+    })
+}
@@ -1,27 +1,495 @@
+pub mod backfill;
+pub mod confirmations;
+pub mod factory;
+pub mod reconnect;
+pub mod reorg;
 pub mod utils;
 
+use std::{sync::Arc, time::Duration};
+
 use alloy::{
     primitives::{Address, B256},
-    providers::RootProvider,
-    pubsub::PubSubFrontend,
-    rpc::types::eth::BlockNumberOrTag,
+    providers::{Provider, RootProvider},
+    rpc::types::eth::{BlockNumberOrTag, Log},
+    transports::BoxTransport,
 };
 use async_trait::async_trait;
 use chronicle_primitives::{
-    db::entities::{
-        pre_entity_store,
-        system::{create_system_if_does_not_exist, get_last_block_number},
+    db::{
+        connection_pool::{get_db_client, DbPool},
+        entities::{
+            hooks::on_gateway_deployed::decode_gateway_deployed,
+            store_block_events,
+            system::{create_system_if_does_not_exist, get_last_block_number, CheckpointKey},
+        },
+        pending_event::{buffer_pending_event, remove_pending_event, remove_pending_events_up_to},
+        reorg::{prune_reorg_window, rollback_from_block},
     },
+    indexer::ChronicleEvent,
     interfaces::ChronicleEventIndexer,
+    registry::EventHandlerRegistry,
+    BackfillConfig, Confirmations,
+};
+use tokio::sync::mpsc;
+use tracing::{info, warn};
+
+use self::{
+    backfill::backfill_events,
+    confirmations::{
+        buffer_pending_range, confirm_pending_events, confirmed_head, CONFIRMATION_POLL_INTERVAL,
+    },
+    factory::{stored_children, FactoryWatcher},
+    reorg::detect_reorg,
+    utils::{query_events, subscribe_to_logs, LogSource, SeenLogs, SEEN_LOGS_BLOCK_WINDOW},
 };
 
-use self::utils::{query_events, subscribe_to_events};
+/// This is how often the head is checked for the live block to be complete
+pub const LIVE_BLOCK_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+pub struct EvmEventIndexer {
+    /// This is the name of the indexer, it is used to key the indexer checkpoint
+    pub indexer_name: String,
+    /// This controls the window size and retries of the historical backfill
+    pub backfill: BackfillConfig,
+    /// This is how many blocks back a reorg can be rolled back
+    pub reorg_window: u64,
+    /// This is how deep a log must be before it is applied to the entity tables
+    pub confirmations: Confirmations,
+    /// When set, the indexed address is a factory and the event is indexed on its children
+    pub factory: bool,
+    /// These are the handlers the indexed events are dispatched to
+    pub registry: Arc<EventHandlerRegistry>,
+    /// This is how the live logs are received, subscribed to or polled
+    pub log_source: LogSource,
+}
+
+/// This is the state of the live stream of an indexer
+pub struct LiveState {
+    /// This is the head recorded before the backfill, the backfill stored the logs up to it
+    pub backfilled_to: Option<u64>,
+    /// These are the logs stored from the most recent blocks
+    pub seen_logs: SeenLogs,
+    /// This is the newest block a live log was applied from
+    pub latest_block: u64,
+    /// These are the live logs of the newest block, they are written together once the block is complete
+    pub live_block: Vec<ChronicleEvent>,
+    /// This follows the children of the factory, for factory indexers
+    pub watcher: Option<FactoryWatcher>,
+}
+
+impl LiveState {
+    pub fn new(watcher: Option<FactoryWatcher>) -> Self {
+        Self {
+            backfilled_to: None,
+            seen_logs: SeenLogs::new(SEEN_LOGS_BLOCK_WINDOW),
+            latest_block: 0,
+            live_block: Vec::new(),
+            watcher,
+        }
+    }
+
+    /// Returns the addresses the event is indexed on, and the factory deploying them if any
+    pub fn log_sources(&self, addr: &[Address]) -> (Vec<Address>, Option<Address>) {
+        match &self.watcher {
+            Some(watcher) => (watcher.children.clone(), Some(watcher.factory)),
+            None => (addr.to_vec(), None),
+        }
+    }
+}
+
+impl EvmEventIndexer {
+    pub fn new(
+        indexer_name: String,
+        backfill: BackfillConfig,
+        reorg_window: u64,
+        confirmations: Confirmations,
+        factory: bool,
+        registry: Arc<EventHandlerRegistry>,
+        log_source: LogSource,
+    ) -> Self {
+        Self {
+            indexer_name,
+            backfill,
+            reorg_window,
+            confirmations,
+            factory,
+            registry,
+            log_source,
+        }
+    }
+
+    /// This function builds the key of the checkpoint owned by this indexer
+    /// on the chain the provider is connected to
+    pub async fn checkpoint_key(
+        &self,
+        provider: &RootProvider<BoxTransport>,
+        addr: &[Address],
+        event_sig: B256,
+    ) -> Result<CheckpointKey, anyhow::Error> {
+        let chain_id = provider.get_chain_id().await?;
+        let contract_address = addr
+            .iter()
+            .map(|addr| addr.to_string())
+            .collect::<Vec<String>>()
+            .join(",");
+
+        Ok(CheckpointKey::new(
+            self.indexer_name.clone(),
+            chain_id,
+            contract_address,
+            event_sig.to_string(),
+        ))
+    }
+
+    /// This function subscribes to the live logs, or starts polling them. Factory indexers
+    /// follow the deployments of the factory and the logs of the children stored so far.
+    pub async fn subscribe(
+        &self,
+        provider: &RootProvider<BoxTransport>,
+        addr: Vec<Address>,
+        event_sig: B256,
+        db_pool: &DbPool,
+    ) -> Result<(LiveState, mpsc::UnboundedReceiver<Log>), anyhow::Error> {
+        if !self.factory {
+            let live_logs = subscribe_to_logs(provider, addr, event_sig, self.log_source).await?;
+            return Ok((LiveState::new(None), live_logs));
+        }
+
+        let factory = *addr
+            .first()
+            .ok_or_else(|| anyhow::anyhow!("A factory indexer needs the factory address"))?;
+        let chain_id = provider.get_chain_id().await?;
+        let children =
+            stored_children(factory, chain_id, &mut get_db_client(db_pool).await?).await?;
+        let (watcher, live_logs) =
+            FactoryWatcher::subscribe(provider, factory, event_sig, children, self.log_source)
+                .await?;
+
+        Ok((LiveState::new(Some(watcher)), live_logs))
+    }
+
+    /// This function stores the live logs until the subscription ends.
+    /// Buffered logs at or below `state.backfilled_to` were already stored by the backfill
+    /// and are skipped. Redelivered logs are skipped using their (transaction hash, log index).
+    /// When a reorg is detected the orphaned blocks are rolled back and the canonical ones
+    /// are backfilled again.
+    /// The logs of a block are written in one transaction, once a log of a newer block
+    /// arrives or the head moves past the block.
+    /// With a confirmation depth the logs are held in the pending buffer, and applied
+    /// as the confirmed head moves past them.
+    /// A connection is taken from `db_pool` for each log, none is held while waiting for logs.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn store_live_events(
+        &self,
+        provider: &RootProvider<BoxTransport>,
+        addr: Vec<Address>,
+        event_sig: B256,
+        mut live_logs: mpsc::UnboundedReceiver<Log>,
+        mut state: LiveState,
+        db_pool: &DbPool,
+        checkpoint: &CheckpointKey,
+    ) -> Result<(), anyhow::Error> {
+        let mut confirmation_tick = tokio::time::interval(CONFIRMATION_POLL_INTERVAL);
+        let mut live_block_tick = tokio::time::interval(LIVE_BLOCK_POLL_INTERVAL);
+
+        loop {
+            let log = tokio::select! {
+                // Logs already delivered are handled before the live block is checked
+                biased;
+                log = live_logs.recv() => match log {
+                    Some(log) => log,
+                    None => break,
+                },
+                _ = live_block_tick.tick(), if !state.live_block.is_empty() => {
+                    let head = provider.get_block_number().await?;
+                    if state.live_block.iter().all(|event| event.block_number < head) {
+                        self.flush_live_block(&mut state, db_pool, checkpoint).await?;
+                    }
+                    continue;
+                }
+                _ = confirmation_tick.tick(), if !self.confirmations.is_immediate() => {
+                    let confirmed_head = confirmed_head(provider, &self.confirmations).await?;
+                    confirm_pending_events(provider, confirmed_head, &self.registry, db_pool, checkpoint)
+                        .await?;
+                    continue;
+                }
+            };
+            let event: ChronicleEvent = log.into();
+
+            if let Some(fork_block) = detect_reorg(provider, &event, checkpoint, db_pool).await? {
+                // The rollback covers the live block when it is orphaned too
+                self.flush_live_block(&mut state, db_pool, checkpoint)
+                    .await?;
+                self.handle_reorg(
+                    provider, &addr, event_sig, fork_block, &mut state, db_pool, checkpoint,
+                )
+                .await?;
+                continue;
+            }
+            if event.removed {
+                state.live_block.retain(|live| {
+                    (live.transaction_hash, live.log_index)
+                        != (event.transaction_hash, event.log_index)
+                });
+                remove_pending_event(&event, checkpoint, &mut get_db_client(db_pool).await?)
+                    .await?;
+                continue;
+            }
+            if state
+                .backfilled_to
+                .is_some_and(|head| event.block_number <= head)
+            {
+                continue;
+            }
+            if !state
+                .seen_logs
+                .insert(event.block_number, event.transaction_hash, event.log_index)
+            {
+                continue;
+            }
+
+            let deployed_child = match &state.watcher {
+                Some(watcher) if watcher.is_deployment(&event) => {
+                    Some(decode_gateway_deployed(&event)?.gateway)
+                }
+                _ => None,
+            };
+            match deployed_child {
+                Some(child) => {
+                    self.apply_live_event(&event, &mut state, db_pool, checkpoint)
+                        .await?;
+                    self.cover_new_children(
+                        provider,
+                        &mut state,
+                        vec![child],
+                        event.block_number,
+                        db_pool,
+                        checkpoint,
+                    )
+                    .await?;
+                }
+                None => {
+                    self.apply_live_event(&event, &mut state, db_pool, checkpoint)
+                        .await?;
+                }
+            }
+        }
+
+        self.flush_live_block(&mut state, db_pool, checkpoint).await
+    }
+
+    /// This function adds a live log to the live block, the previous live block is written
+    /// first when the log is from another block. With a confirmation depth the log is held
+    /// in the pending buffer instead.
+    async fn apply_live_event(
+        &self,
+        event: &ChronicleEvent,
+        state: &mut LiveState,
+        db_pool: &DbPool,
+        checkpoint: &CheckpointKey,
+    ) -> Result<(), anyhow::Error> {
+        if !self.confirmations.is_immediate() {
+            return buffer_pending_event(event, checkpoint, &mut get_db_client(db_pool).await?)
+                .await;
+        }
+
+        if state
+            .live_block
+            .first()
+            .is_some_and(|live| live.block_number != event.block_number)
+        {
+            self.flush_live_block(state, db_pool, checkpoint).await?;
+        }
+        state.live_block.push(event.clone());
 
-pub struct EvmEventIndexer;
+        Ok(())
+    }
+
+    /// This function writes the live block with the checkpoint in one transaction
+    async fn flush_live_block(
+        &self,
+        state: &mut LiveState,
+        db_pool: &DbPool,
+        checkpoint: &CheckpointKey,
+    ) -> Result<(), anyhow::Error> {
+        let Some(block_number) = state.live_block.first().map(|event| event.block_number) else {
+            return Ok(());
+        };
+        let mut events = std::mem::take(&mut state.live_block);
+        events.sort_by_key(|event| event.log_index);
+        let mut db_client = get_db_client(db_pool).await?;
+        store_block_events(&events, &mut db_client, &self.registry, checkpoint).await?;
+
+        if block_number > state.latest_block {
+            state.latest_block = block_number;
+            let keep_from_block = state.latest_block.saturating_sub(self.reorg_window);
+            prune_reorg_window(checkpoint, keep_from_block, &mut db_client).await?;
+        }
+
+        Ok(())
+    }
+
+    /// This function extends the live subscription of a factory indexer to `children`, and
+    /// stores the logs the new children emitted from `from_block` up to the current head,
+    /// before the extended subscription started
+    pub async fn cover_new_children(
+        &self,
+        provider: &RootProvider<BoxTransport>,
+        state: &mut LiveState,
+        children: Vec<Address>,
+        from_block: u64,
+        db_pool: &DbPool,
+        checkpoint: &CheckpointKey,
+    ) -> Result<(), anyhow::Error> {
+        let Some(watcher) = state.watcher.as_mut() else {
+            return Ok(());
+        };
+        let event_sig = watcher.event_sig;
+        let new_children = watcher.extend_children(provider, children).await?;
+        if new_children.is_empty() {
+            return Ok(());
+        }
+        info!(
+            "{} now follows {} new children of {}",
+            checkpoint.indexer_name,
+            new_children.len(),
+            watcher.factory
+        );
+
+        let head = provider.get_block_number().await?;
+        if from_block > head {
+            return Ok(());
+        }
+        let events = query_events(provider, new_children, event_sig, from_block, head).await?;
+        for event in events {
+            if state
+                .seen_logs
+                .insert(event.block_number, event.transaction_hash, event.log_index)
+            {
+                self.apply_live_event(&event, state, db_pool, checkpoint)
+                    .await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// This function rolls back the entity changes from `fork_block` onwards and backfills
+    /// the canonical chain up to the current head
+    #[allow(clippy::too_many_arguments)]
+    pub async fn handle_reorg(
+        &self,
+        provider: &RootProvider<BoxTransport>,
+        addr: &[Address],
+        event_sig: B256,
+        fork_block: u64,
+        state: &mut LiveState,
+        db_pool: &DbPool,
+        checkpoint: &CheckpointKey,
+    ) -> Result<(), anyhow::Error> {
+        warn!(
+            "Reorg detected for {}, rolling back from block {fork_block}",
+            checkpoint.indexer_name
+        );
+        rollback_from_block(checkpoint, fork_block, &mut get_db_client(db_pool).await?).await?;
+
+        let head = provider.get_block_number().await?;
+        let (addr, factory) = state.log_sources(addr);
+        let new_children = self
+            .catch_up(
+                provider, addr, event_sig, factory, fork_block, head, db_pool, checkpoint,
+            )
+            .await?;
+        state.backfilled_to = Some(head);
+        state.seen_logs.forget_from(fork_block);
+
+        self.cover_new_children(provider, state, new_children, head + 1, db_pool, checkpoint)
+            .await
+    }
+
+    /// This function indexes the events emitted in `[from_block, to_block]`. With a
+    /// confirmation depth only the confirmed blocks are backfilled into the entity
+    /// tables, the events of the newer blocks are held in the pending buffer.
+    /// The children a `factory` deployed in the range are returned.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn catch_up(
+        &self,
+        provider: &RootProvider<BoxTransport>,
+        addr: Vec<Address>,
+        event_sig: B256,
+        factory: Option<Address>,
+        from_block: u64,
+        to_block: u64,
+        db_pool: &DbPool,
+        checkpoint: &CheckpointKey,
+    ) -> Result<Vec<Address>, anyhow::Error> {
+        if self.confirmations.is_immediate() {
+            return backfill_events(
+                provider,
+                addr,
+                event_sig,
+                factory,
+                from_block,
+                to_block,
+                &self.backfill,
+                &self.registry,
+                db_pool,
+                checkpoint,
+            )
+            .await;
+        }
+
+        // The backfill applies whatever is canonical up to the confirmed head,
+        // logs buffered for those blocks by an earlier run are stale
+        let confirmed_head = confirmed_head(provider, &self.confirmations)
+            .await?
+            .min(to_block);
+        remove_pending_events_up_to(
+            confirmed_head,
+            checkpoint,
+            &mut get_db_client(db_pool).await?,
+        )
+        .await?;
+
+        let mut addr = addr;
+        let mut new_children = Vec::new();
+        if from_block <= confirmed_head {
+            new_children = backfill_events(
+                provider,
+                addr.clone(),
+                event_sig,
+                factory,
+                from_block,
+                confirmed_head,
+                &self.backfill,
+                &self.registry,
+                db_pool,
+                checkpoint,
+            )
+            .await?;
+            addr.extend(new_children.iter().copied());
+        }
+        let pending_children = buffer_pending_range(
+            provider,
+            addr,
+            event_sig,
+            factory,
+            from_block.max(confirmed_head + 1),
+            to_block,
+            &self.backfill,
+            db_pool,
+            checkpoint,
+        )
+        .await?;
+        new_children.extend(pending_children);
+
+        Ok(new_children)
+    }
+}
 
 #[async_trait]
 impl ChronicleEventIndexer for EvmEventIndexer {
-    type SubProvider = RootProvider<PubSubFrontend>;
+    type SubProvider = RootProvider<BoxTransport>;
     type ContractAddress = Address;
     type EventSignature = B256;
     type BlockNumber = BlockNumberOrTag;
@@ -34,33 +502,72 @@ impl ChronicleEventIndexer for EvmEventIndexer {
         addr: Self::ContractAddress,
         event_sig: Self::EventSignature,
         block_number: Self::BlockNumber,
-        db_client: &mut tokio_postgres::Client,
+        db_pool: &DbPool,
     ) -> Result<(), anyhow::Error> {
-        // TODO: This should check if the system record has bee created,
-        // if not create it and store the last indexed block as block_number
-        let last_indexed_block: u64 = (get_last_block_number(db_client).await?).parse()?;
+        let checkpoint = self.checkpoint_key(&provider, &[addr], event_sig).await?;
+
+        // Resume from this indexer's own checkpoint, other indexers progress is not considered
+        let mut db_client = get_db_client(db_pool).await?;
+        let last_indexed_block: u64 =
+            (get_last_block_number(&checkpoint, &mut db_client).await?).parse()?;
         let block_number = if last_indexed_block > block_number.as_number().expect("Infallible") {
             BlockNumberOrTag::Number(last_indexed_block + 1)
         } else {
             create_system_if_does_not_exist(
+                &checkpoint,
                 block_number.as_number().unwrap_or(0).to_string(),
-                db_client,
+                &mut db_client,
             )
             .await?;
             block_number
         };
+        drop(db_client);
 
-        // Query existing events from the specified block number
-        let events = query_events(provider.clone(), addr, event_sig, block_number).await?;
-
-        // Store all this event is the database
-        for event in events {
-            pre_entity_store(&event, db_client, event_sig).await?;
-        }
+        // Subscribe before recording the head, the logs emitted while the backfill runs
+        // are buffered and nothing is lost between the backfill and the live stream
+        let (mut state, live_logs) = self
+            .subscribe(&provider, vec![addr], event_sig, db_pool)
+            .await?;
 
-        // Now subsbribing the events
-        self.subscribe_to_events(provider, vec![addr], event_sig, db_client)
+        // Backfill existing events from the specified block number up to the recorded head
+        let head = provider.get_block_number().await?;
+        let (backfill_addr, factory) = state.log_sources(&[addr]);
+        let new_children = self
+            .catch_up(
+                &provider,
+                backfill_addr,
+                event_sig,
+                factory,
+                block_number.as_number().unwrap_or(0),
+                head,
+                db_pool,
+                &checkpoint,
+            )
             .await?;
+        state.backfilled_to = Some(head);
+
+        // Children found by the backfill join the live subscription
+        self.cover_new_children(
+            &provider,
+            &mut state,
+            new_children,
+            head + 1,
+            db_pool,
+            &checkpoint,
+        )
+        .await?;
+
+        // Drain the buffered logs, then keep storing the live ones
+        self.store_live_events(
+            &provider,
+            vec![addr],
+            event_sig,
+            live_logs,
+            state,
+            db_pool,
+            &checkpoint,
+        )
+        .await?;
 
         Ok(())
     }
@@ -70,9 +577,22 @@ impl ChronicleEventIndexer for EvmEventIndexer {
         provider: Self::SubProvider,
         addr: Vec<Self::ContractAddress>,
         event_sig: Self::EventSignature,
-        db_client: &mut tokio_postgres::Client,
+        db_pool: &DbPool,
     ) -> Result<(), anyhow::Error> {
-        subscribe_to_events(provider, addr, event_sig, db_client).await;
+        let checkpoint = self.checkpoint_key(&provider, &addr, event_sig).await?;
+        let (state, live_logs) = self
+            .subscribe(&provider, addr.clone(), event_sig, db_pool)
+            .await?;
+        self.store_live_events(
+            &provider,
+            addr,
+            event_sig,
+            live_logs,
+            state,
+            db_pool,
+            &checkpoint,
+        )
+        .await?;
 
         Ok(())
     }
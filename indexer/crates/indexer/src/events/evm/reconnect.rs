@@ -0,0 +1,220 @@
+//! This module keeps an indexer running across dropped connections.
+//! When the live stream ends or the connection fails, the indexer reconnects with an
+//! exponential backoff, backfills from its checkpoint up to the new head and resumes the
+//! live stream, the checkpoint makes sure no block is skipped or applied twice.
+use std::{
+    future::Future,
+    time::{Duration, Instant},
+};
+
+use alloy::{
+    primitives::{Address, B256},
+    providers::{ProviderBuilder, RootProvider},
+    rpc::types::eth::BlockNumberOrTag,
+    transports::{BoxTransport, RpcError, TransportError},
+};
+use chronicle_primitives::{
+    db::connection_pool::DbPool,
+    interfaces::ChronicleEventIndexer,
+    metrics::{metrics, INDEXER_CONNECTION_FAILURES, INDEXER_RECONNECTS},
+    ReconnectConfig,
+};
+use tracing::{info, warn};
+
+use super::EvmEventIndexer;
+
+/// This function connects to `rpc_url`, a WebSocket, HTTP or IPC endpoint
+pub async fn connect(rpc_url: &str) -> Result<RootProvider<BoxTransport>, anyhow::Error> {
+    Ok(ProviderBuilder::new().on_builtin(rpc_url).await?)
+}
+
+impl EvmEventIndexer {
+    /// This function indexes the event from `block_number` like
+    /// [`ChronicleEventIndexer::query_then_subscribe_to_events`], reconnecting whenever
+    /// the live stream ends or the node can not be reached. Errors not caused by the
+    /// connection stop the indexer.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn index_with_reconnect(
+        &self,
+        rpc_url: &str,
+        addr: Address,
+        event_sig: B256,
+        block_number: BlockNumberOrTag,
+        config: &ReconnectConfig,
+        db_pool: &DbPool,
+    ) -> Result<(), anyhow::Error> {
+        run_with_reconnect(
+            &self.indexer_name,
+            config,
+            is_connection_error,
+            || connect(rpc_url),
+            move |provider| async move {
+                // The checkpoint is resumed from, the blocks missed while disconnected are backfilled
+                self.query_then_subscribe_to_events(
+                    provider,
+                    addr,
+                    event_sig,
+                    block_number,
+                    db_pool,
+                )
+                .await?;
+
+                warn!("The live stream of {} ended", self.indexer_name);
+                Ok(())
+            },
+        )
+        .await
+    }
+}
+
+/// This function runs `index` on the connections opened by `connect`, and reconnects with an
+/// exponential backoff whenever `index` returns or fails with an error `is_connection_error`
+/// accepts. A connection that can not be opened is retried, the other errors are returned.
+pub async fn run_with_reconnect<T, C, I>(
+    indexer_name: &str,
+    config: &ReconnectConfig,
+    is_connection_error: fn(&anyhow::Error) -> bool,
+    mut connect: impl FnMut() -> C,
+    mut index: impl FnMut(T) -> I,
+) -> Result<(), anyhow::Error>
+where
+    C: Future<Output = Result<T, anyhow::Error>>,
+    I: Future<Output = Result<(), anyhow::Error>>,
+{
+    let mut attempt = 0;
+
+    loop {
+        let connected_at = Instant::now();
+        match connect().await {
+            Ok(connection) => {
+                if attempt > 0 {
+                    let reconnects = metrics().increment(INDEXER_RECONNECTS, indexer_name);
+                    info!(
+                        "{indexer_name} reconnected after {attempt} attempts, {reconnects} reconnects so far"
+                    );
+                }
+
+                match index(connection).await {
+                    Ok(()) => {}
+                    Err(err) if is_connection_error(&err) => {
+                        warn!("{indexer_name} lost its connection: {err}")
+                    }
+                    Err(err) => return Err(err),
+                }
+            }
+            // The node could not be reached, this is retried whatever the error
+            Err(err) => {
+                metrics().increment(INDEXER_CONNECTION_FAILURES, indexer_name);
+                warn!("{indexer_name} could not connect: {err}")
+            }
+        }
+
+        // A connection that stayed up for a while starts the backoff over
+        if connected_at.elapsed() >= Duration::from_millis(config.max_backoff_ms) {
+            attempt = 0;
+        }
+        attempt += 1;
+        if config
+            .max_attempts
+            .is_some_and(|max_attempts| attempt > max_attempts)
+        {
+            anyhow::bail!(
+                "{indexer_name} could not reconnect after {} attempts",
+                attempt - 1
+            );
+        }
+
+        let delay = reconnect_backoff(config, attempt);
+        warn!("Reconnecting {indexer_name} in {delay:?}, attempt {attempt}");
+        tokio::time::sleep(delay).await;
+    }
+}
+
+/// This function checks if an error was caused by the connection to the node, a dropped
+/// WebSocket, a refused or reset HTTP connection or an HTTP error status, as opposed to the
+/// database, a handler or an error response of a reachable node
+pub fn is_connection_error(err: &anyhow::Error) -> bool {
+    err.chain().any(|cause| {
+        matches!(
+            cause.downcast_ref::<TransportError>(),
+            Some(RpcError::Transport(_))
+        )
+    })
+}
+
+/// Exponential backoff for the `attempt`-th reconnection, never above `max_backoff_ms`
+pub fn reconnect_backoff(config: &ReconnectConfig, attempt: u32) -> Duration {
+    let factor = 2u64.saturating_pow(attempt.saturating_sub(1));
+    Duration::from_millis(
+        config
+            .initial_backoff_ms
+            .saturating_mul(factor)
+            .min(config.max_backoff_ms),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy::{
+        rpc::json_rpc::ErrorPayload,
+        transports::{HttpError, TransportErrorKind},
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_reconnect_backoff_is_exponential_and_capped() {
+        let config = ReconnectConfig {
+            initial_backoff_ms: 1_000,
+            max_backoff_ms: 5_000,
+            max_attempts: None,
+        };
+
+        assert_eq!(reconnect_backoff(&config, 1), Duration::from_millis(1_000));
+        assert_eq!(reconnect_backoff(&config, 3), Duration::from_millis(4_000));
+        assert_eq!(reconnect_backoff(&config, 4), Duration::from_millis(5_000));
+        assert_eq!(reconnect_backoff(&config, 80), Duration::from_millis(5_000));
+    }
+
+    #[test]
+    fn test_only_transport_failures_are_connection_errors() {
+        let closed: anyhow::Error = TransportErrorKind::backend_gone().into();
+        assert!(is_connection_error(&closed));
+        assert!(is_connection_error(
+            &closed.context("eth_getLogs failed for 10..=20")
+        ));
+        let unavailable: anyhow::Error = TransportErrorKind::pubsub_unavailable().into();
+        assert!(is_connection_error(&unavailable));
+        // HTTP nodes that can not be reached fail in the HTTP client, or answer with an error status
+        let refused: anyhow::Error =
+            TransportErrorKind::custom_str("error sending request: connection refused").into();
+        assert!(is_connection_error(&refused));
+        let bad_gateway: anyhow::Error =
+            TransportError::Transport(TransportErrorKind::HttpError(HttpError {
+                status: 502,
+                body: "Bad Gateway".to_string(),
+            }))
+            .into();
+        assert!(is_connection_error(&bad_gateway));
+
+        assert!(!is_connection_error(&anyhow::anyhow!(
+            "No handler is registered for this event"
+        )));
+        // A healthy node answering with an error would answer with it again
+        let error_response: anyhow::Error = TransportError::ErrorResp(ErrorPayload {
+            code: -32005,
+            message: "query returned more than 10000 results".into(),
+            data: None,
+        })
+        .into();
+        assert!(!is_connection_error(&error_response));
+        let null_response: anyhow::Error = TransportError::NullResp.into();
+        assert!(!is_connection_error(&null_response));
+        let malformed_response: anyhow::Error = TransportError::deser_err(
+            serde_json::from_str::<u64>("\"0x1\"").unwrap_err(),
+            "\"0x1\"",
+        )
+        .into();
+        assert!(!is_connection_error(&malformed_response));
+    }
+}
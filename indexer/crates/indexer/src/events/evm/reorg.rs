@@ -0,0 +1,133 @@
+//! This module detects chain reorganizations while indexing live events.
+//! A reorg shows up either as a log delivered again with `removed: true`, or as a block
+//! whose hash no longer matches the one recorded when its events were applied.
+use alloy::{
+    providers::{Provider, RootProvider},
+    transports::BoxTransport,
+};
+use chronicle_primitives::{
+    db::{
+        connection_pool::{get_db_client, DbPool},
+        entities::system::CheckpointKey,
+        reorg::{get_block_hash, get_recent_block_hashes},
+    },
+    indexer::ChronicleEvent,
+};
+
+/// This function returns the first orphaned block if the event shows the chain
+/// the indexer applied events from has been reorganized, `None` otherwise
+pub async fn detect_reorg(
+    provider: &RootProvider<BoxTransport>,
+    event: &ChronicleEvent,
+    checkpoint: &CheckpointKey,
+    db_pool: &DbPool,
+) -> Result<Option<u64>, anyhow::Error> {
+    // The connection is given back before the recorded blocks are checked against the node
+    let (recorded_hash, recent) = {
+        let mut db_client = get_db_client(db_pool).await?;
+        (
+            get_block_hash(checkpoint, event.block_number, &mut db_client).await?,
+            get_recent_block_hashes(checkpoint, &mut db_client).await?,
+        )
+    };
+
+    // The block was applied, but the log was removed or now comes with a different hash
+    let orphaned = recorded_hash.is_some_and(|recorded_hash| {
+        event.removed || recorded_hash != event.block_hash.to_string()
+    });
+    if orphaned {
+        let fork_block = find_fork_point(provider, &recent).await?;
+
+        return Ok(Some(
+            fork_block.map_or(event.block_number, |fork| fork.min(event.block_number)),
+        ));
+    }
+    if event.removed {
+        return Ok(None);
+    }
+
+    // A new block, the newest applied block must still be canonical
+    match recent.first() {
+        Some((latest_block, _)) if *latest_block < event.block_number => {
+            find_fork_point(provider, &recent).await
+        }
+        _ => Ok(None),
+    }
+}
+
+/// This function walks the recorded block hashes, newest first, and returns the first
+/// block that is no longer canonical. `None` means the newest recorded block is canonical.
+/// When none of the recorded blocks is canonical the oldest one is returned, the reorg is
+/// deeper than the reorg window and can not be undone any further.
+pub async fn find_fork_point(
+    provider: &RootProvider<BoxTransport>,
+    recent_block_hashes: &[(u64, String)],
+) -> Result<Option<u64>, anyhow::Error> {
+    let mut fork_block = None;
+
+    for (block_number, block_hash) in recent_block_hashes {
+        let canonical_hash = canonical_block_hash(provider, *block_number).await?;
+        if canonical_hash.as_deref() == Some(block_hash.as_str()) {
+            return Ok(fork_block);
+        }
+        fork_block = Some(*block_number);
+    }
+
+    Ok(fork_block)
+}
+
+/// This function returns the hash of the canonical block at `block_number`
+pub async fn canonical_block_hash(
+    provider: &RootProvider<BoxTransport>,
+    block_number: u64,
+) -> Result<Option<String>, anyhow::Error> {
+    let block = provider
+        .get_block_by_number(block_number.into(), false)
+        .await?;
+
+    Ok(block.map(|block| block.header.hash.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy::{node_bindings::Anvil, providers::ProviderBuilder};
+
+    use super::*;
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_find_fork_point_after_anvil_reorg() {
+        let anvil = Anvil::new().try_spawn().unwrap();
+        let provider = ProviderBuilder::new()
+            .on_builtin(&anvil.ws_endpoint())
+            .await
+            .unwrap();
+
+        let _: () = provider
+            .raw_request("anvil_mine".into(), (10u64,))
+            .await
+            .unwrap();
+        let tip = provider.get_block_number().await.unwrap();
+
+        let mut recorded = Vec::new();
+        for block_number in (tip - 5..=tip).rev() {
+            let hash = canonical_block_hash(&provider, block_number)
+                .await
+                .unwrap()
+                .unwrap();
+            recorded.push((block_number, hash));
+        }
+        assert_eq!(find_fork_point(&provider, &recorded).await.unwrap(), None);
+
+        // Replace the last 3 blocks
+        let _: () = provider
+            .raw_request("anvil_reorg".into(), (3u64, Vec::<()>::new()))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            find_fork_point(&provider, &recorded).await.unwrap(),
+            Some(tip - 2)
+        );
+    }
+}
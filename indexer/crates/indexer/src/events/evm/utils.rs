@@ -1,50 +1,243 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
 use alloy::{
     dyn_abi::{DecodedEvent, DynSolEvent, DynSolType},
     primitives::{Address, Bytes, LogData, B256},
     providers::{Provider, RootProvider},
-    pubsub::PubSubFrontend,
-    rpc::types::eth::{BlockNumberOrTag, Filter},
+    rpc::types::eth::{BlockNumberOrTag, Filter, Log},
+    transports::BoxTransport,
 };
-use chronicle_primitives::{db::entities::pre_entity_store, indexer::ChronicleEvent};
+use chronicle_primitives::indexer::ChronicleEvent;
 use futures_util::stream::StreamExt;
+use tokio::{sync::mpsc, task::JoinHandle, time::MissedTickBehavior};
+use tracing::warn;
+
+/// This is how many blocks back live logs are remembered for de-duplication
+pub const SEEN_LOGS_BLOCK_WINDOW: u64 = 128;
 
+/// This function queries the events emitted in `[from_block, to_block]`, callers are
+/// expected to keep the range small enough for the provider, see [`super::backfill`]
 pub async fn query_events(
-    provider: RootProvider<PubSubFrontend>,
-    addr: Address,
+    provider: &RootProvider<BoxTransport>,
+    addr: Vec<Address>,
     event_sig: B256,
-    block_number: BlockNumberOrTag,
+    from_block: u64,
+    to_block: u64,
 ) -> Result<Vec<ChronicleEvent>, anyhow::Error> {
     let filter = Filter::new()
         .address(addr)
         .event_signature(event_sig)
-        .from_block(block_number);
+        .from_block(from_block)
+        .to_block(to_block);
     let log = provider.get_logs(&filter).await?;
     let chronicle_logs: Vec<ChronicleEvent> = log.into_iter().map(|log| log.into()).collect();
 
     Ok(chronicle_logs)
 }
 
-pub async fn subscribe_to_events(
-    provider: RootProvider<PubSubFrontend>,
+/// This is how the live logs are received from the node
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogSource {
+    /// The logs are pushed by an `eth_subscribe` subscription
+    Subscribe,
+    /// The head is polled every `interval` and the logs of the new blocks are fetched with
+    /// `eth_getLogs`, at most `max_block_range` blocks per call
+    Poll {
+        interval: Duration,
+        max_block_range: u64,
+    },
+}
+
+/// This is a task forwarding the logs matching a filter into a channel
+#[derive(Debug)]
+pub struct LogForwarder {
+    task: JoinHandle<()>,
+    /// This is the next block a poller fetches the logs of, unset for subscriptions
+    next_block: Option<Arc<AtomicU64>>,
+}
+
+impl LogForwarder {
+    /// This function stops forwarding the logs
+    pub fn abort(&self) {
+        self.task.abort();
+    }
+
+    /// This function returns the next block the logs are polled from, if the logs are polled
+    pub fn next_block(&self) -> Option<u64> {
+        self.next_block
+            .as_ref()
+            .map(|next_block| next_block.load(Ordering::SeqCst))
+    }
+}
+
+/// This function subscribes to the live logs and forwards them into an unbounded channel
+/// straight away, so logs emitted while a backfill is still running are buffered instead of lost
+pub async fn subscribe_to_logs(
+    provider: &RootProvider<BoxTransport>,
     addr: Vec<Address>,
     event_sig: B256,
-    client: &mut tokio_postgres::Client,
-) {
+    source: LogSource,
+) -> Result<mpsc::UnboundedReceiver<Log>, anyhow::Error> {
     let filter = Filter::new()
         .address(addr)
         .event_signature(event_sig)
         .from_block(BlockNumberOrTag::Latest);
 
-    let sub = provider
-        .subscribe_logs(&filter)
-        .await
-        .expect("Failed to subscribe to logs");
-    let mut stream = sub.into_stream();
+    let (sender, receiver) = mpsc::unbounded_channel();
+    // The forwarder stops on its own once the receiver is dropped
+    forward_logs(provider, &filter, source, None, sender).await?;
 
-    while let Some(log) = stream.next().await {
-        pre_entity_store(&log.into(), client, event_sig)
-            .await
-            .expect("Failed to store event to db");
+    Ok(receiver)
+}
+
+/// This function forwards the logs matching the filter into `sender`, until the subscription
+/// ends, the channel is closed or the returned forwarder is aborted. A poller replacing the
+/// forwarder of `resume` continues from the block it had reached, otherwise it starts after
+/// the current head.
+pub async fn forward_logs(
+    provider: &RootProvider<BoxTransport>,
+    filter: &Filter,
+    source: LogSource,
+    resume: Option<&LogForwarder>,
+    sender: mpsc::UnboundedSender<Log>,
+) -> Result<LogForwarder, anyhow::Error> {
+    let (interval, max_block_range) = match source {
+        LogSource::Subscribe => {
+            let sub = provider.subscribe_logs(filter).await?;
+            let task = tokio::spawn(async move {
+                let mut stream = sub.into_stream();
+                while let Some(log) = stream.next().await {
+                    if sender.send(log).is_err() {
+                        break;
+                    }
+                }
+            });
+
+            return Ok(LogForwarder {
+                task,
+                next_block: None,
+            });
+        }
+        LogSource::Poll {
+            interval,
+            max_block_range,
+        } => (interval, max_block_range),
+    };
+
+    let start_block = match resume.and_then(LogForwarder::next_block) {
+        Some(next_block) => next_block,
+        None => provider.get_block_number().await? + 1,
+    };
+    let next_block = Arc::new(AtomicU64::new(start_block));
+    let task = tokio::spawn(poll_logs(
+        provider.clone(),
+        filter.clone(),
+        interval,
+        max_block_range.max(1),
+        next_block.clone(),
+        sender,
+    ));
+
+    Ok(LogForwarder {
+        task,
+        next_block: Some(next_block),
+    })
+}
+
+/// This function polls the head every `interval` and forwards the logs of the blocks from
+/// `next_block` up to the head. A failing call is retried on the next tick from the block
+/// it failed at, so no block is skipped.
+async fn poll_logs(
+    provider: RootProvider<BoxTransport>,
+    filter: Filter,
+    interval: Duration,
+    max_block_range: u64,
+    next_block: Arc<AtomicU64>,
+    sender: mpsc::UnboundedSender<Log>,
+) {
+    let mut ticker = tokio::time::interval(interval);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    while !sender.is_closed() {
+        ticker.tick().await;
+        let head = match provider.get_block_number().await {
+            Ok(head) => head,
+            Err(err) => {
+                warn!("Could not poll the head: {err}");
+                continue;
+            }
+        };
+
+        let mut from_block = next_block.load(Ordering::SeqCst);
+        while from_block <= head {
+            let to_block = head.min(from_block.saturating_add(max_block_range - 1));
+            let range_filter = filter.clone().from_block(from_block).to_block(to_block);
+            let logs = match provider.get_logs(&range_filter).await {
+                Ok(logs) => logs,
+                Err(err) => {
+                    warn!("Could not poll the logs of {from_block}..={to_block}: {err}");
+                    break;
+                }
+            };
+            for log in logs {
+                if sender.send(log).is_err() {
+                    return;
+                }
+            }
+
+            from_block = to_block + 1;
+            next_block.store(from_block, Ordering::SeqCst);
+        }
+    }
+}
+
+/// This keeps the (transaction hash, log index) of the logs stored from the most
+/// recent blocks, older blocks are forgotten as new blocks come in
+#[derive(Debug, Default)]
+pub struct SeenLogs {
+    block_window: u64,
+    blocks: BTreeMap<u64, HashSet<(B256, u64)>>,
+}
+
+impl SeenLogs {
+    pub fn new(block_window: u64) -> Self {
+        Self {
+            block_window,
+            blocks: BTreeMap::new(),
+        }
+    }
+
+    /// Records the log, returning `false` if it has been seen before
+    pub fn insert(&mut self, block_number: u64, transaction_hash: B256, log_index: u64) -> bool {
+        let inserted = self
+            .blocks
+            .entry(block_number)
+            .or_default()
+            .insert((transaction_hash, log_index));
+
+        let newest_block = self
+            .blocks
+            .keys()
+            .next_back()
+            .copied()
+            .unwrap_or(block_number);
+        let cutoff = newest_block.saturating_sub(self.block_window);
+        self.blocks = self.blocks.split_off(&cutoff);
+
+        inserted
+    }
+
+    /// Forgets the logs of `block_number` and later, they are expected to be delivered
+    /// again from the canonical chain after a reorg
+    pub fn forget_from(&mut self, block_number: u64) {
+        self.blocks.split_off(&block_number);
     }
 }
 
@@ -73,14 +266,22 @@ pub fn decode_event(
 
 #[cfg(test)]
 pub mod tests {
+    use std::sync::Arc;
+
     use alloy::{
+        node_bindings::Anvil,
         primitives::{address, b256},
         providers::ProviderBuilder,
         rpc::client::WsConnect,
     };
-    use chronicle_primitives::db::raw_chronicle_event::create_db_instance;
+    use chronicle_primitives::{
+        db::connection_pool::create_db_pool, interfaces::ChronicleEventIndexer,
+        registry::EventHandlerRegistry, BackfillConfig, Confirmations, DbPoolConfig,
+        UnmatchedEventPolicy,
+    };
 
     use super::*;
+    use crate::events::evm::EvmEventIndexer;
 
     const DB_URL: &str = "host=localhost user=postgres";
 
@@ -90,8 +291,7 @@ pub mod tests {
         let rpc_url = "wss://eth.merkle.io";
 
         // Create the provider.
-        let ws = WsConnect::new(rpc_url);
-        let provider = ProviderBuilder::new().on_ws(ws).await.unwrap();
+        let provider = ProviderBuilder::new().on_builtin(rpc_url).await.unwrap();
 
         let block_num = 19664198u64;
         let uniswap_token_address = address!("1f9840a85d5aF5bf1D1762F925BDADdC4201F984");
@@ -99,10 +299,11 @@ pub mod tests {
             b256!("ddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef");
 
         let events = query_events(
-            provider,
-            uniswap_token_address,
+            &provider,
+            vec![uniswap_token_address],
             tranfer_event_signature,
-            block_num.into(),
+            block_num,
+            block_num + 100,
         )
         .await
         .unwrap();
@@ -146,24 +347,108 @@ pub mod tests {
         let rpc_url = "wss://eth.merkle.io";
 
         // Create the provider.
-        let ws = WsConnect::new(rpc_url);
-        let provider = ProviderBuilder::new().on_ws(ws).await.unwrap();
+        let provider = ProviderBuilder::new().on_builtin(rpc_url).await.unwrap();
 
         let uniswap_token_address = address!("1f9840a85d5aF5bf1D1762F925BDADdC4201F984");
         let transfer_event_signature =
             b256!("ddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef");
 
-        let mut client = create_db_instance(&DB_URL.into())
+        let db_pool =
+            create_db_pool(DB_URL, &DbPoolConfig::default()).expect("Could not create db pool");
+
+        let evm_event_indexer = EvmEventIndexer::new(
+            "uniswap_transfer".to_string(),
+            BackfillConfig::default(),
+            64,
+            Confirmations::default(),
+            false,
+            Arc::new(EventHandlerRegistry::new().with_unmatched_events(UnmatchedEventPolicy::Raw)),
+            LogSource::Subscribe,
+        );
+
+        evm_event_indexer
+            .subscribe_to_events(
+                provider,
+                vec![uniswap_token_address],
+                transfer_event_signature,
+                &db_pool,
+            )
             .await
-            .expect("Could not create db instance");
+            .unwrap();
+    }
 
-        subscribe_to_events(
-            provider,
-            vec![uniswap_token_address],
-            transfer_event_signature,
-            &mut client,
-        )
-        .await;
+    #[tokio::test]
+    #[ignore]
+    async fn test_polled_logs_are_forwarded_over_http() {
+        let anvil = Anvil::new().try_spawn().unwrap();
+        let provider = ProviderBuilder::new()
+            .on_builtin(&anvil.endpoint())
+            .await
+            .unwrap();
+        let source = LogSource::Poll {
+            interval: Duration::from_millis(100),
+            max_block_range: 2,
+        };
+
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        let filter = Filter::new().from_block(BlockNumberOrTag::Latest);
+        let poller = forward_logs(&provider, &filter, source, None, sender.clone())
+            .await
+            .unwrap();
+        let start_block = poller.next_block().unwrap();
+
+        // The init code `PUSH1 0 PUSH1 0 LOG0 STOP` emits an empty log when the contract is created
+        let _: B256 = provider
+            .raw_request(
+                "eth_sendTransaction".into(),
+                (serde_json::json!({
+                    "from": anvil.addresses()[0],
+                    "data": "0x60006000a000",
+                }),),
+            )
+            .await
+            .unwrap();
+        let _: () = provider
+            .raw_request("anvil_mine".into(), (4u64,))
+            .await
+            .unwrap();
+
+        let log = tokio::time::timeout(Duration::from_secs(5), receiver.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(log.block_number, Some(start_block));
+
+        let head = provider.get_block_number().await.unwrap();
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        assert_eq!(poller.next_block(), Some(head + 1));
+
+        // A replacing poller continues where the previous one stopped
+        poller.abort();
+        let resumed = forward_logs(&provider, &filter, source, Some(&poller), sender)
+            .await
+            .unwrap();
+        assert_eq!(resumed.next_block(), Some(head + 1));
+        resumed.abort();
+    }
+
+    #[test]
+    fn test_seen_logs_skips_duplicates_within_window() {
+        let tx_hash = b256!("811ba4f47d01fd272e3d2802db97f87077b49f5831e0355e6a3dd8844244e0c5");
+        let mut seen_logs = SeenLogs::new(2);
+
+        assert!(seen_logs.insert(10, tx_hash, 0));
+        assert!(!seen_logs.insert(10, tx_hash, 0));
+        assert!(seen_logs.insert(10, tx_hash, 1));
+
+        // Block 10 falls out of the window once block 13 is seen
+        assert!(seen_logs.insert(13, tx_hash, 0));
+        assert!(seen_logs.insert(10, tx_hash, 0));
+
+        // Forgotten blocks are stored again when the canonical logs come in
+        assert!(!seen_logs.insert(13, tx_hash, 0));
+        seen_logs.forget_from(13);
+        assert!(seen_logs.insert(13, tx_hash, 0));
     }
 
     #[tokio::test]
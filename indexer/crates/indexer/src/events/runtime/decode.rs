@@ -0,0 +1,92 @@
+//! This module turns the fields of a runtime event, decoded with the runtime metadata,
+//! into JSON. Like the EVM values, integers are kept as decimal strings so no precision
+//! is lost, and byte arrays are hex strings.
+use alloy::primitives::{hex, I256, U256};
+use scale_info::{PortableRegistry, TypeDef, TypeDefPrimitive};
+use subxt::ext::scale_value::{Composite, Primitive, Value, ValueDef};
+
+/// This function converts the fields of an event, named fields are an object
+/// and unnamed fields an array
+pub fn fields_to_json(fields: &Composite<u32>, types: &PortableRegistry) -> serde_json::Value {
+    match fields {
+        Composite::Named(fields) => fields
+            .iter()
+            .map(|(name, value)| (name.clone(), value_to_json(value, types)))
+            .collect::<serde_json::Map<String, serde_json::Value>>()
+            .into(),
+        Composite::Unnamed(values) => values
+            .iter()
+            .map(|value| value_to_json(value, types))
+            .collect(),
+    }
+}
+
+/// This function converts a decoded value, its context is the id of its type in `types`
+pub fn value_to_json(value: &Value<u32>, types: &PortableRegistry) -> serde_json::Value {
+    match &value.value {
+        ValueDef::Composite(composite) => {
+            if is_byte_array(value.context, types) {
+                return bytes_to_json(composite);
+            }
+            match composite {
+                // Wrappers like `AccountId32([u8; 32])` are unwrapped
+                Composite::Unnamed(values) if values.len() == 1 => value_to_json(&values[0], types),
+                composite => fields_to_json(composite, types),
+            }
+        }
+        ValueDef::Variant(variant) => {
+            if variant.values.is_empty() {
+                return variant.name.clone().into();
+            }
+            let fields = match &variant.values {
+                Composite::Unnamed(values) if values.len() == 1 => value_to_json(&values[0], types),
+                values => fields_to_json(values, types),
+            };
+            serde_json::json!({ variant.name.clone(): fields })
+        }
+        ValueDef::BitSequence(bits) => bits
+            .iter()
+            .map(|bit| if bit { '1' } else { '0' })
+            .collect::<String>()
+            .into(),
+        ValueDef::Primitive(primitive) => primitive_to_json(primitive),
+    }
+}
+
+fn primitive_to_json(primitive: &Primitive) -> serde_json::Value {
+    match primitive {
+        Primitive::Bool(boolean) => (*boolean).into(),
+        Primitive::Char(character) => character.to_string().into(),
+        Primitive::String(text) => text.clone().into(),
+        Primitive::U128(uint) => uint.to_string().into(),
+        Primitive::I128(int) => int.to_string().into(),
+        Primitive::U256(bytes) => U256::from_le_bytes(*bytes).to_string().into(),
+        Primitive::I256(bytes) => I256::from_raw(U256::from_le_bytes(*bytes))
+            .to_string()
+            .into(),
+    }
+}
+
+/// This function checks if the type is a `[u8; N]` or a `Vec<u8>`
+fn is_byte_array(type_id: u32, types: &PortableRegistry) -> bool {
+    let type_param = match types.resolve(type_id).map(|ty| &ty.type_def) {
+        Some(TypeDef::Array(array)) => array.type_param,
+        Some(TypeDef::Sequence(sequence)) => sequence.type_param,
+        _ => return false,
+    };
+
+    matches!(
+        types.resolve(type_param.id).map(|ty| &ty.type_def),
+        Some(TypeDef::Primitive(TypeDefPrimitive::U8))
+    )
+}
+
+fn bytes_to_json(composite: &Composite<u32>) -> serde_json::Value {
+    let bytes = composite
+        .values()
+        .filter_map(|value| value.as_u128())
+        .map(|byte| byte as u8)
+        .collect::<Vec<u8>>();
+
+    hex::encode_prefixed(bytes).into()
+}
@@ -0,0 +1,153 @@
+//! This module is a mock Substrate node answering from recorded responses, so the runtime
+//! indexer can be run without a node. A fixture is a JSON file like
+//!
+//! ```json
+//! {
+//!     "requests": [
+//!         { "method": "chain_getBlockHash", "params": [0], "result": "0x91b1..." }
+//!     ],
+//!     "subscriptions": {
+//!         "chain_subscribeFinalizedHeads": [{ "parentHash": "0x...", "number": "0x10" }]
+//!     }
+//! }
+//! ```
+//!
+//! the requests and their results can be recorded from a node with any JSON-RPC client.
+//! A subscription pushes its recorded notifications and then ends.
+use std::collections::HashMap;
+
+use futures_util::stream;
+use serde::{Deserialize, Serialize};
+use serde_json::value::{to_raw_value, RawValue};
+use subxt::{
+    backend::rpc::{RawRpcFuture, RawRpcSubscription, RpcClient, RpcClientT},
+    error::RpcError,
+};
+
+/// This is a request to the node and the result it answered with
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RecordedRequest {
+    pub method: String,
+    #[serde(default)]
+    pub params: Vec<serde_json::Value>,
+    pub result: serde_json::Value,
+}
+
+/// This is the recorded traffic of a node
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct NodeFixture {
+    #[serde(default)]
+    pub requests: Vec<RecordedRequest>,
+    /// These are the notifications of each subscription, by subscribe method
+    #[serde(default)]
+    pub subscriptions: HashMap<String, Vec<serde_json::Value>>,
+}
+
+impl NodeFixture {
+    /// This function reads a fixture file
+    pub fn load(path: &str) -> Result<Self, anyhow::Error> {
+        let raw = std::fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("Could not read the node fixture {path}: {e}"))?;
+
+        Ok(serde_json::from_str(&raw)?)
+    }
+
+    /// This function records the result of a request
+    pub fn with_request(
+        mut self,
+        method: &str,
+        params: Vec<serde_json::Value>,
+        result: serde_json::Value,
+    ) -> Self {
+        self.requests.push(RecordedRequest {
+            method: method.to_string(),
+            params,
+            result,
+        });
+        self
+    }
+
+    /// This function records the notifications of a subscription
+    pub fn with_subscription(
+        mut self,
+        method: &str,
+        notifications: Vec<serde_json::Value>,
+    ) -> Self {
+        self.subscriptions.insert(method.to_string(), notifications);
+        self
+    }
+
+    /// This function returns the RPC client of the mock node
+    pub fn into_rpc_client(self) -> RpcClient {
+        RpcClient::new(FixtureNode { fixture: self })
+    }
+
+    fn result(&self, method: &str, params: &[serde_json::Value]) -> Option<&serde_json::Value> {
+        self.requests
+            .iter()
+            .find(|request| request.method == method && request.params == params)
+            .map(|request| &request.result)
+    }
+}
+
+/// This answers requests from a [`NodeFixture`], a request that was not recorded is rejected
+pub struct FixtureNode {
+    fixture: NodeFixture,
+}
+
+impl RpcClientT for FixtureNode {
+    fn request_raw<'a>(
+        &'a self,
+        method: &'a str,
+        params: Option<Box<RawValue>>,
+    ) -> RawRpcFuture<'a, Box<RawValue>> {
+        Box::pin(async move {
+            let params = parse_params(params)?;
+            let result = self.fixture.result(method, &params).ok_or_else(|| {
+                RpcError::RequestRejected(format!(
+                    "No response to {method} {} was recorded",
+                    serde_json::Value::from(params.clone())
+                ))
+            })?;
+
+            to_raw_value(result).map_err(|e| RpcError::ClientError(Box::new(e)))
+        })
+    }
+
+    fn subscribe_raw<'a>(
+        &'a self,
+        sub: &'a str,
+        _params: Option<Box<RawValue>>,
+        _unsub: &'a str,
+    ) -> RawRpcFuture<'a, RawRpcSubscription> {
+        Box::pin(async move {
+            let notifications = self
+                .fixture
+                .subscriptions
+                .get(sub)
+                .ok_or_else(|| {
+                    RpcError::RequestRejected(format!("No {sub} notifications were recorded"))
+                })?
+                .iter()
+                .map(|notification| {
+                    to_raw_value(notification).map_err(|e| RpcError::ClientError(Box::new(e)))
+                })
+                .collect::<Vec<_>>();
+
+            Ok(RawRpcSubscription {
+                stream: Box::pin(stream::iter(notifications)),
+                id: Some(sub.to_string()),
+            })
+        })
+    }
+}
+
+/// The params are sent as a JSON array, or not at all
+fn parse_params(params: Option<Box<RawValue>>) -> Result<Vec<serde_json::Value>, RpcError> {
+    match params {
+        Some(params) => {
+            serde_json::from_str(params.get()).map_err(|e| RpcError::ClientError(Box::new(e)))
+        }
+        None => Ok(Vec::new()),
+    }
+}
@@ -0,0 +1,444 @@
+//! This module indexes the events of a Substrate runtime, of a relay chain or a parachain.
+//! The node is followed through its finalized heads, finalized blocks are never reverted
+//! so unlike the EVM indexer there is no reorg window or confirmation depth.
+//! The events are decoded with the runtime metadata and filtered by pallet and event name.
+pub mod decode;
+pub mod fixture;
+pub mod node;
+
+use alloy::primitives::B256;
+use chronicle_primitives::{
+    db::{
+        connection_pool::{get_db_client, DbPool},
+        entities::{
+            runtime_event::store_block_runtime_events,
+            system::{
+                create_system_if_does_not_exist, get_last_block_number, update_last_block_number,
+                CheckpointKey,
+            },
+        },
+    },
+    indexer::RuntimeEventFilter,
+    ReconnectConfig,
+};
+use futures_util::stream::{self, StreamExt};
+use tracing::{info, warn};
+
+use self::node::{is_connection_error, RuntimeNode};
+use crate::{
+    events::evm::reconnect::run_with_reconnect,
+    tx::evm::{BLOCK_FETCH_CONCURRENCY, CHECKPOINT_BLOCK_INTERVAL},
+};
+
+pub struct RuntimeEventIndexer {
+    /// This is the name of the indexer, it is used to key the indexer checkpoint
+    pub indexer_name: String,
+    /// These select the stored events, every event is stored when there are none
+    pub filters: Vec<RuntimeEventFilter>,
+}
+
+impl RuntimeEventIndexer {
+    pub fn new(indexer_name: String, filters: Vec<RuntimeEventFilter>) -> Self {
+        Self {
+            indexer_name,
+            filters,
+        }
+    }
+
+    /// The checkpoint is keyed by the genesis hash of the chain, in place of a contract address,
+    /// and by the event filters, in place of an event signature
+    fn checkpoint_key(&self, genesis_hash: B256) -> CheckpointKey {
+        let filters = self
+            .filters
+            .iter()
+            .map(|filter| match &filter.event {
+                Some(event) => format!("{}.{event}", filter.pallet),
+                None => filter.pallet.clone(),
+            })
+            .collect::<Vec<String>>()
+            .join(",");
+
+        CheckpointKey::new(
+            self.indexer_name.clone(),
+            0,
+            genesis_hash.to_string(),
+            filters,
+        )
+    }
+
+    /// This function stores the selected events in `[from_block, to_block]`.
+    /// The blocks are fetched concurrently but written in order, each with the checkpoint.
+    pub async fn backfill_events(
+        &self,
+        node: &RuntimeNode,
+        genesis_hash: B256,
+        from_block: u64,
+        to_block: u64,
+        db_pool: &DbPool,
+        checkpoint: &CheckpointKey,
+    ) -> Result<(), anyhow::Error> {
+        if from_block > to_block {
+            return Ok(());
+        }
+
+        let mut blocks = stream::iter(from_block..=to_block)
+            .map(|block_number| async move {
+                let events = node
+                    .block_events(genesis_hash, block_number, &self.filters)
+                    .await?;
+                Ok::<_, anyhow::Error>((block_number, events))
+            })
+            .buffered(BLOCK_FETCH_CONCURRENCY);
+
+        while let Some(block) = blocks.next().await {
+            let (block_number, events) = block?;
+            if !events.is_empty() {
+                store_block_runtime_events(&events, &mut get_db_client(db_pool).await?, checkpoint)
+                    .await?;
+            } else if block_number % CHECKPOINT_BLOCK_INTERVAL == 0 {
+                update_last_block_number(
+                    checkpoint,
+                    block_number.to_string(),
+                    &mut get_db_client(db_pool).await?,
+                )
+                .await?;
+                info!(
+                    "{} indexed runtime events up to block {block_number}",
+                    checkpoint.indexer_name
+                );
+            }
+        }
+        update_last_block_number(
+            checkpoint,
+            to_block.to_string(),
+            &mut get_db_client(db_pool).await?,
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    /// This function stores the selected events from `block_number`, or from its checkpoint when
+    /// it is further, up to the finalized head and then follows the finalized heads until the
+    /// subscription ends. No connection is held from `db_pool` while the next head is awaited.
+    pub async fn index_events(
+        &self,
+        node: &RuntimeNode,
+        block_number: u64,
+        db_pool: &DbPool,
+    ) -> Result<(), anyhow::Error> {
+        let genesis_hash = node.genesis_hash().await?;
+        let checkpoint = self.checkpoint_key(genesis_hash);
+
+        // Resume from this indexer's own checkpoint, other indexers progress is not considered
+        let mut db_client = get_db_client(db_pool).await?;
+        let last_indexed_block: u64 =
+            (get_last_block_number(&checkpoint, &mut db_client).await?).parse()?;
+        let mut next_block = if last_indexed_block > block_number {
+            last_indexed_block + 1
+        } else {
+            create_system_if_does_not_exist(&checkpoint, block_number.to_string(), &mut db_client)
+                .await?;
+            block_number
+        };
+        drop(db_client);
+
+        // The heads finalized while the backfill runs are caught up with on the first notification
+        let mut heads = node.subscribe_finalized_heads().await?;
+        let finalized_block = node.finalized_block_number().await?;
+        self.backfill_events(
+            node,
+            genesis_hash,
+            next_block,
+            finalized_block,
+            db_pool,
+            &checkpoint,
+        )
+        .await?;
+        next_block = next_block.max(finalized_block + 1);
+
+        while let Some(head) = heads.next().await {
+            let finalized_block = head?.block_number()?;
+            if finalized_block < next_block {
+                continue;
+            }
+            self.backfill_events(
+                node,
+                genesis_hash,
+                next_block,
+                finalized_block,
+                db_pool,
+                &checkpoint,
+            )
+            .await?;
+            next_block = finalized_block + 1;
+        }
+
+        warn!("The finalized heads of {} ended", self.indexer_name);
+        Ok(())
+    }
+
+    /// This function indexes the events like [`Self::index_events`], reconnecting whenever
+    /// the finalized heads end or the node can not be reached. Errors not caused by the
+    /// connection stop the indexer.
+    pub async fn index_with_reconnect(
+        &self,
+        rpc_url: &str,
+        block_number: u64,
+        config: &ReconnectConfig,
+        db_pool: &DbPool,
+    ) -> Result<(), anyhow::Error> {
+        run_with_reconnect(
+            &self.indexer_name,
+            config,
+            is_connection_error,
+            || RuntimeNode::connect(rpc_url),
+            move |node| async move {
+                // The checkpoint is resumed from, the blocks finalized while disconnected are backfilled
+                self.index_events(&node, block_number, db_pool).await
+            },
+        )
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy::primitives::{b256, hex};
+    use codec::Encode;
+    use frame_metadata::{
+        v15::{
+            CustomMetadata, ExtrinsicMetadata, OuterEnums, PalletEventMetadata, PalletMetadata,
+            RuntimeMetadataV15,
+        },
+        RuntimeMetadataPrefixed,
+    };
+    use scale_info::{meta_type, TypeInfo};
+    use serde_json::json;
+
+    use super::{fixture::NodeFixture, node::SYSTEM_EVENTS_STORAGE_KEY, *};
+
+    #[derive(Encode, TypeInfo)]
+    enum SystemEvent {
+        ExtrinsicSuccess { weight: u64 },
+    }
+
+    #[derive(Encode, TypeInfo)]
+    enum BalancesEvent {
+        Transfer {
+            from: [u8; 32],
+            to: [u8; 32],
+            amount: u128,
+        },
+        Deposit {
+            who: [u8; 32],
+            amount: u128,
+        },
+    }
+
+    #[derive(Encode, TypeInfo)]
+    enum RuntimeEvent {
+        #[codec(index = 0)]
+        System(SystemEvent),
+        #[codec(index = 5)]
+        Balances(BalancesEvent),
+    }
+
+    #[derive(Encode)]
+    enum Phase {
+        ApplyExtrinsic(u32),
+        Finalization,
+    }
+
+    #[derive(Encode)]
+    struct EventRecord {
+        phase: Phase,
+        event: RuntimeEvent,
+        topics: Vec<[u8; 32]>,
+    }
+
+    const GENESIS_HASH: B256 =
+        b256!("91b171bb158e2d3848fa23a9f1c25182fb8e20313b2c1eb49219da7a70ce90c3");
+    const BLOCK_ONE_HASH: B256 =
+        b256!("c0096358534ec8d21d01d34b836eed476a1c343f8724fa2153dc0725ad797a90");
+    const BLOCK_TWO_HASH: B256 =
+        b256!("409d0bfe677594d7558101d574633d5808a6fe373cce4d5d2e38c6b3d6f3f1b8");
+
+    fn pallet(
+        name: &'static str,
+        index: u8,
+        event: scale_info::MetaType,
+    ) -> PalletMetadata<scale_info::form::MetaForm> {
+        PalletMetadata {
+            name,
+            storage: None,
+            calls: None,
+            event: Some(PalletEventMetadata { ty: event }),
+            constants: vec![],
+            error: None,
+            index,
+            docs: vec![],
+        }
+    }
+
+    /// This is the metadata a node of the test runtime answers `state_getMetadata` with
+    fn runtime_metadata() -> Vec<u8> {
+        let metadata = RuntimeMetadataV15::new(
+            vec![
+                pallet("System", 0, meta_type::<SystemEvent>()),
+                pallet("Balances", 5, meta_type::<BalancesEvent>()),
+            ],
+            ExtrinsicMetadata {
+                version: 4,
+                address_ty: meta_type::<()>(),
+                call_ty: meta_type::<()>(),
+                signature_ty: meta_type::<()>(),
+                extra_ty: meta_type::<()>(),
+                signed_extensions: vec![],
+            },
+            meta_type::<()>(),
+            vec![],
+            OuterEnums {
+                call_enum_ty: meta_type::<()>(),
+                event_enum_ty: meta_type::<RuntimeEvent>(),
+                error_enum_ty: meta_type::<()>(),
+            },
+            CustomMetadata {
+                map: Default::default(),
+            },
+        );
+
+        RuntimeMetadataPrefixed::from(metadata).encode()
+    }
+
+    /// This is a node whose first block has a fee payment, a transfer and a deposit,
+    /// block 2 has no events and block 3 is pushed as the next finalized head
+    fn fixture_node() -> RuntimeNode {
+        let events = vec![
+            EventRecord {
+                phase: Phase::ApplyExtrinsic(0),
+                event: RuntimeEvent::System(SystemEvent::ExtrinsicSuccess { weight: 125_000 }),
+                topics: vec![],
+            },
+            EventRecord {
+                phase: Phase::ApplyExtrinsic(1),
+                event: RuntimeEvent::Balances(BalancesEvent::Transfer {
+                    from: [1; 32],
+                    to: [2; 32],
+                    amount: 10_000_000_000_000_000_000_000,
+                }),
+                topics: vec![],
+            },
+            EventRecord {
+                phase: Phase::Finalization,
+                event: RuntimeEvent::Balances(BalancesEvent::Deposit {
+                    who: [3; 32],
+                    amount: 42,
+                }),
+                topics: vec![],
+            },
+        ];
+
+        let fixture = NodeFixture::default()
+            .with_request("chain_getBlockHash", vec![json!(0)], json!(GENESIS_HASH))
+            .with_request("chain_getBlockHash", vec![json!(1)], json!(BLOCK_ONE_HASH))
+            .with_request("chain_getBlockHash", vec![json!(2)], json!(BLOCK_TWO_HASH))
+            .with_request("chain_getFinalizedHead", vec![], json!(BLOCK_TWO_HASH))
+            .with_request(
+                "chain_getHeader",
+                vec![json!(BLOCK_TWO_HASH)],
+                json!({ "parentHash": BLOCK_ONE_HASH, "number": "0x2" }),
+            )
+            .with_request(
+                "state_getStorage",
+                vec![json!(SYSTEM_EVENTS_STORAGE_KEY), json!(BLOCK_ONE_HASH)],
+                json!(hex::encode_prefixed(events.encode())),
+            )
+            .with_request(
+                "state_getStorage",
+                vec![json!(SYSTEM_EVENTS_STORAGE_KEY), json!(BLOCK_TWO_HASH)],
+                json!(null),
+            )
+            .with_request(
+                "state_getRuntimeVersion",
+                vec![json!(BLOCK_ONE_HASH)],
+                json!({ "specName": "test", "specVersion": 1_002_000 }),
+            )
+            .with_request(
+                "state_getMetadata",
+                vec![json!(BLOCK_ONE_HASH)],
+                json!(hex::encode_prefixed(runtime_metadata())),
+            )
+            .with_subscription(
+                "chain_subscribeFinalizedHeads",
+                vec![json!({ "parentHash": BLOCK_TWO_HASH, "number": "0x3" })],
+            );
+
+        RuntimeNode::new(fixture.into_rpc_client())
+    }
+
+    #[tokio::test]
+    async fn test_block_events_are_decoded_with_the_runtime_metadata_and_filtered() {
+        let node = fixture_node();
+        assert_eq!(node.genesis_hash().await.unwrap(), GENESIS_HASH);
+        assert_eq!(node.finalized_block_number().await.unwrap(), 2);
+
+        let filters = vec!["Balances".parse().unwrap()];
+        let events = node.block_events(GENESIS_HASH, 1, &filters).await.unwrap();
+        assert_eq!(events.len(), 2);
+
+        let transfer = &events[0];
+        assert_eq!(transfer.block_hash, BLOCK_ONE_HASH);
+        assert_eq!(
+            (transfer.pallet.as_str(), transfer.event.as_str()),
+            ("Balances", "Transfer")
+        );
+        assert_eq!(transfer.phase, "ApplyExtrinsic");
+        assert_eq!(transfer.extrinsic_index, Some(1));
+        assert_eq!(transfer.event_index, 1);
+        assert_eq!(
+            transfer.fields,
+            json!({
+                "from": hex::encode_prefixed([1u8; 32]),
+                "to": hex::encode_prefixed([2u8; 32]),
+                "amount": "10000000000000000000000",
+            })
+        );
+
+        let deposit = &events[1];
+        assert_eq!(deposit.event, "Deposit");
+        assert_eq!(deposit.phase, "Finalization");
+        assert_eq!(deposit.extrinsic_index, None);
+        assert_eq!(deposit.event_index, 2);
+
+        let filters = vec!["System.ExtrinsicSuccess".parse().unwrap()];
+        let events = node.block_events(GENESIS_HASH, 1, &filters).await.unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].fields, json!({ "weight": "125000" }));
+
+        assert_eq!(
+            node.block_events(GENESIS_HASH, 1, &[]).await.unwrap().len(),
+            3
+        );
+        assert!(node
+            .block_events(GENESIS_HASH, 2, &[])
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_finalized_heads_are_followed_until_the_subscription_ends() {
+        let node = fixture_node();
+        let mut heads = node.subscribe_finalized_heads().await.unwrap();
+
+        let head = heads.next().await.unwrap().unwrap();
+        assert_eq!(head.block_number().unwrap(), 3);
+        assert_eq!(head.parent_hash, BLOCK_TWO_HASH);
+        assert!(heads.next().await.is_none());
+
+        // Block 3 was not recorded, the mock node rejects the request like a node without it
+        let err = node.block_events(GENESIS_HASH, 3, &[]).await.unwrap_err();
+        assert!(!is_connection_error(&err));
+    }
+}
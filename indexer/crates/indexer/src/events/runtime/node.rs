@@ -0,0 +1,217 @@
+//! This module reads blocks and their events from a Substrate node over JSON-RPC.
+//! The events of a block are read from the `System.Events` storage and decoded with the
+//! metadata of the runtime the block was built with, so blocks from before a runtime
+//! upgrade are decoded with the metadata they were built with.
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use alloy::primitives::{hex, B256};
+use chronicle_primitives::indexer::{ChronicleRuntimeEvent, RuntimeEventFilter};
+use serde::Deserialize;
+use subxt::{
+    backend::rpc::{rpc_params, RpcClient, RpcSubscription},
+    error::RpcError,
+    events::{Events, Phase},
+    ext::codec::Decode,
+    Metadata, PolkadotConfig,
+};
+
+use super::decode::fields_to_json;
+
+/// This is the storage key of `System.Events`, `twox128("System") ++ twox128("Events")`
+pub const SYSTEM_EVENTS_STORAGE_KEY: &str =
+    "0x26aa394eea5630e07c48ae0c9558cef780d41e5e16056765bc8461851072c9d7";
+
+/// This is the header of a block, only the fields the indexer reads
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct RuntimeHeader {
+    pub parent_hash: B256,
+    /// This is the hex encoded block number
+    pub number: String,
+}
+
+impl RuntimeHeader {
+    pub fn block_number(&self) -> Result<u64, anyhow::Error> {
+        let number = self.number.trim_start_matches("0x");
+        u64::from_str_radix(number, 16)
+            .map_err(|e| anyhow::anyhow!("Invalid block number `{}`: {e}", self.number))
+    }
+}
+
+/// This is the version of the runtime a block was built with
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct RuntimeVersion {
+    pub spec_version: u32,
+}
+
+/// This is a Substrate node, the metadata of the runtimes it was asked about is kept
+/// by spec version
+#[derive(Clone)]
+pub struct RuntimeNode {
+    rpc: RpcClient,
+    metadata: Arc<Mutex<HashMap<u32, Metadata>>>,
+}
+
+impl RuntimeNode {
+    /// This function connects to `rpc_url`, a WebSocket endpoint
+    pub async fn connect(rpc_url: &str) -> Result<Self, anyhow::Error> {
+        Ok(Self::new(RpcClient::from_insecure_url(rpc_url).await?))
+    }
+
+    /// This creates a node reading from `rpc`, a live connection or a fixture
+    pub fn new(rpc: RpcClient) -> Self {
+        Self {
+            rpc,
+            metadata: Arc::default(),
+        }
+    }
+
+    /// This function returns the hash of the first block of the chain, it identifies the chain
+    pub async fn genesis_hash(&self) -> Result<B256, anyhow::Error> {
+        self.block_hash(0)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("The node has no genesis block"))
+    }
+
+    /// This function returns the hash of the block, `None` if the node does not have it yet
+    pub async fn block_hash(&self, block_number: u64) -> Result<Option<B256>, anyhow::Error> {
+        Ok(self
+            .rpc
+            .request("chain_getBlockHash", rpc_params![block_number])
+            .await?)
+    }
+
+    /// This function returns the number of the last finalized block
+    pub async fn finalized_block_number(&self) -> Result<u64, anyhow::Error> {
+        let hash: B256 = self
+            .rpc
+            .request("chain_getFinalizedHead", rpc_params![])
+            .await?;
+        let header: Option<RuntimeHeader> = self
+            .rpc
+            .request("chain_getHeader", rpc_params![hash])
+            .await?;
+
+        header
+            .ok_or_else(|| {
+                anyhow::anyhow!("The node has no header for the finalized block {hash}")
+            })?
+            .block_number()
+    }
+
+    /// This function subscribes to the finalized heads, a head can be several blocks ahead
+    /// of the previous one
+    pub async fn subscribe_finalized_heads(
+        &self,
+    ) -> Result<RpcSubscription<RuntimeHeader>, anyhow::Error> {
+        Ok(self
+            .rpc
+            .subscribe(
+                "chain_subscribeFinalizedHeads",
+                rpc_params![],
+                "chain_unsubscribeFinalizedHeads",
+            )
+            .await?)
+    }
+
+    /// This function returns the metadata of the runtime the block was built with
+    pub async fn metadata_at(&self, block_hash: B256) -> Result<Metadata, anyhow::Error> {
+        let version: RuntimeVersion = self
+            .rpc
+            .request("state_getRuntimeVersion", rpc_params![block_hash])
+            .await?;
+        if let Some(metadata) = self.cached_metadata(version.spec_version) {
+            return Ok(metadata);
+        }
+
+        let raw: String = self
+            .rpc
+            .request("state_getMetadata", rpc_params![block_hash])
+            .await?;
+        let metadata = Metadata::decode(&mut &hex::decode(raw)?[..])?;
+        self.metadata
+            .lock()
+            .expect("metadata lock poisoned")
+            .insert(version.spec_version, metadata.clone());
+
+        Ok(metadata)
+    }
+
+    fn cached_metadata(&self, spec_version: u32) -> Option<Metadata> {
+        self.metadata
+            .lock()
+            .expect("metadata lock poisoned")
+            .get(&spec_version)
+            .cloned()
+    }
+
+    /// This function returns the events of the block selected by `filters`, in the order
+    /// they were emitted
+    pub async fn block_events(
+        &self,
+        genesis_hash: B256,
+        block_number: u64,
+        filters: &[RuntimeEventFilter],
+    ) -> Result<Vec<ChronicleRuntimeEvent>, anyhow::Error> {
+        let block_hash = self.block_hash(block_number).await?.ok_or_else(|| {
+            anyhow::anyhow!("The node has no block {block_number}, it is not finalized yet")
+        })?;
+        let raw: Option<String> = self
+            .rpc
+            .request(
+                "state_getStorage",
+                rpc_params![SYSTEM_EVENTS_STORAGE_KEY, block_hash],
+            )
+            .await?;
+        let Some(raw) = raw else {
+            return Ok(Vec::new());
+        };
+        let metadata = self.metadata_at(block_hash).await?;
+
+        let mut events = Vec::new();
+        let decoded = Events::<PolkadotConfig>::decode_from(hex::decode(raw)?, metadata.clone());
+        for event in decoded.iter() {
+            let event = event?;
+            if !RuntimeEventFilter::any_matches(filters, event.pallet_name(), event.variant_name())
+            {
+                continue;
+            }
+            let (phase, extrinsic_index) = match event.phase() {
+                Phase::ApplyExtrinsic(index) => ("ApplyExtrinsic", Some(index)),
+                Phase::Initialization => ("Initialization", None),
+                Phase::Finalization => ("Finalization", None),
+            };
+
+            events.push(ChronicleRuntimeEvent {
+                genesis_hash,
+                block_number,
+                block_hash,
+                phase: phase.to_string(),
+                extrinsic_index,
+                event_index: event.index(),
+                pallet: event.pallet_name().to_string(),
+                event: event.variant_name().to_string(),
+                fields: fields_to_json(&event.field_values()?, metadata.types()),
+            });
+        }
+
+        Ok(events)
+    }
+}
+
+/// This function checks if an error was caused by the connection to the node,
+/// as opposed to the database or the decoding of a block
+pub fn is_connection_error(err: &anyhow::Error) -> bool {
+    err.chain().any(|cause| {
+        matches!(
+            cause.downcast_ref::<subxt::Error>(),
+            Some(subxt::Error::Rpc(
+                RpcError::ClientError(_) | RpcError::SubscriptionDropped
+            ))
+        )
+    })
+}
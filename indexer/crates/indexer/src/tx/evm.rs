@@ -1,68 +1,496 @@
+//! This module indexes the transactions sent from or to a set of addresses.
+//! Unlike logs, transactions can not be filtered by the node: every block is fetched with its
+//! full transactions, the matching ones are kept and their receipts are fetched.
+//! New blocks are only used as a signal, the block headers pushed by `eth_subscribe` do not
+//! carry the transactions.
+//! The calldata of the stored transactions is decoded, and failed transactions are replayed
+//! to read the reason they reverted with.
 use alloy::{
-    primitives::Address,
+    primitives::{Address, Bytes, TxKind},
     providers::{Provider, RootProvider},
-    pubsub::PubSubFrontend,
-    rpc::types::eth::{BlockTransactions, Transaction},
+    pubsub::SubscriptionStream,
+    rpc::{
+        json_rpc::{ErrorPayload, RpcError},
+        types::eth::{Block, BlockId, BlockTransactions, TransactionInput, TransactionRequest},
+    },
+    transports::BoxTransport,
 };
-use futures_util::stream::StreamExt;
-
-pub async fn subscribe_transactions<F>(
-    index_address: Address,
-    provider: RootProvider<PubSubFrontend>,
-    mut callback: F,
-) -> Result<(), anyhow::Error>
-where
-    F: FnMut(Vec<Transaction>),
-{
-    let subscription = provider.subscribe_blocks().await?;
-    let mut stream = subscription.into_stream();
-
-    while let Some(block) = stream.next().await {
-        match block.transactions {
-            BlockTransactions::Full(txs) => {
-                let filtered_txs = txs
-                    .into_iter()
-                    .filter(|tx| {
-                        println!("Captured Tx: {:?}", tx.hash);
-                        let to_address = match tx.to {
-                            Some(to) => to,
-                            None => Address::ZERO,
-                        };
-
-                        tx.from == index_address || to_address == index_address
-                    })
-                    .collect::<Vec<Transaction>>();
-
-                callback(filtered_txs);
+use async_trait::async_trait;
+use chronicle_primitives::{
+    calldata::CalldataDecoder,
+    db::{
+        connection_pool::{get_db_client, DbPool},
+        entities::{
+            system::{
+                create_system_if_does_not_exist, get_last_block_number, update_last_block_number,
+                CheckpointKey,
+            },
+            transaction::store_block_transactions,
+        },
+        reorg::{get_recent_block_hashes, prune_reorg_window, rollback_from_block},
+    },
+    indexer::ChronicleTransaction,
+    interfaces::ChronicleTransactionIndexer,
+    Confirmations, ReconnectConfig,
+};
+use futures_util::stream::{self, StreamExt};
+use tokio::time::{Interval, MissedTickBehavior};
+use tracing::{info, warn};
+
+use crate::events::evm::{
+    confirmations::confirmed_head,
+    reconnect::{connect, is_connection_error, run_with_reconnect},
+    reorg::find_fork_point,
+    utils::LogSource,
+};
+
+/// This is the event signature the checkpoint of a transaction indexer is keyed with
+pub const TRANSACTION_CHECKPOINT_SIGNATURE: &str = "transactions";
+
+/// This is how many blocks are fetched at the same time during a backfill
+pub const BLOCK_FETCH_CONCURRENCY: usize = 8;
+
+/// This is how many blocks the backfill goes through between two checkpoint updates,
+/// when none of them had a matching transaction
+pub const CHECKPOINT_BLOCK_INTERVAL: u64 = 1_000;
+
+pub struct EvmTransactionIndexer {
+    /// This is the name of the indexer, it is used to key the indexer checkpoint
+    pub indexer_name: String,
+    /// This is how many blocks back a reorg can be rolled back
+    pub reorg_window: u64,
+    /// This is how deep a block must be before its transactions are stored
+    pub confirmations: Confirmations,
+    /// This is how new blocks are noticed, pushed by a subscription or polled
+    pub block_source: LogSource,
+    /// This decodes the calldata and the revert data of the stored transactions
+    pub decoder: CalldataDecoder,
+}
+
+/// This follows the head of the chain
+pub enum HeadWatcher {
+    /// New block headers are pushed by an `eth_subscribe` subscription
+    Subscribe(SubscriptionStream<Block>),
+    /// The head is polled on every tick
+    Poll(Interval),
+}
+
+impl HeadWatcher {
+    pub async fn new(
+        provider: &RootProvider<BoxTransport>,
+        source: LogSource,
+    ) -> Result<Self, anyhow::Error> {
+        match source {
+            LogSource::Subscribe => Ok(Self::Subscribe(
+                provider.subscribe_blocks().await?.into_stream(),
+            )),
+            LogSource::Poll { interval, .. } => {
+                let mut interval = tokio::time::interval(interval);
+                interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+                Ok(Self::Poll(interval))
+            }
+        }
+    }
+
+    /// This function waits until the head may have moved, it returns false once the subscription ended
+    pub async fn next_block(&mut self) -> bool {
+        match self {
+            Self::Subscribe(stream) => stream.next().await.is_some(),
+            Self::Poll(interval) => {
+                interval.tick().await;
+                true
+            }
+        }
+    }
+}
+
+impl EvmTransactionIndexer {
+    pub fn new(
+        indexer_name: String,
+        reorg_window: u64,
+        confirmations: Confirmations,
+        block_source: LogSource,
+        decoder: CalldataDecoder,
+    ) -> Self {
+        Self {
+            indexer_name,
+            reorg_window,
+            confirmations,
+            block_source,
+            decoder,
+        }
+    }
+
+    /// This function builds the key of the checkpoint owned by this indexer
+    /// on the chain the provider is connected to
+    pub async fn checkpoint_key(
+        &self,
+        provider: &RootProvider<BoxTransport>,
+        addresses: &[Address],
+    ) -> Result<CheckpointKey, anyhow::Error> {
+        let chain_id = provider.get_chain_id().await?;
+        let contract_address = addresses
+            .iter()
+            .map(|address| address.to_string())
+            .collect::<Vec<String>>()
+            .join(",");
+
+        Ok(CheckpointKey::new(
+            self.indexer_name.clone(),
+            chain_id,
+            contract_address,
+            TRANSACTION_CHECKPOINT_SIGNATURE.to_string(),
+        ))
+    }
+
+    /// This function returns the transactions of a block sent from or to `addresses`,
+    /// with the outcome read from their receipts and their decoded calldata
+    pub async fn block_transactions(
+        &self,
+        provider: &RootProvider<BoxTransport>,
+        block_number: u64,
+        addresses: &[Address],
+        chain_id: u64,
+    ) -> Result<Vec<ChronicleTransaction>, anyhow::Error> {
+        let block = provider
+            .get_block_by_number(block_number.into(), true)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("The node did not return block {block_number}"))?;
+        let block_timestamp = block.header.timestamp;
+        let BlockTransactions::Full(transactions) = block.transactions else {
+            anyhow::bail!("The node did not return the transactions of block {block_number}");
+        };
+
+        let mut indexed = Vec::new();
+        for transaction in transactions {
+            let transaction = ChronicleTransaction {
+                chain_id,
+                block_timestamp,
+                ..transaction.into()
+            };
+            if !transaction.involves_any(addresses) {
+                continue;
+            }
+
+            let receipt = provider
+                .get_transaction_receipt(transaction.hash)
+                .await?
+                .ok_or_else(|| {
+                    anyhow::anyhow!(
+                        "The node did not return the receipt of {}",
+                        transaction.hash
+                    )
+                })?;
+            let transaction = transaction
+                .with_receipt(&receipt)
+                .with_decoded_call(&self.decoder);
+            if transaction.status {
+                indexed.push(transaction);
+                continue;
+            }
+
+            match replay_revert_data(provider, &transaction).await? {
+                Some(revert_data) => {
+                    indexed.push(transaction.with_revert_data(revert_data, &self.decoder))
+                }
+                None => indexed.push(transaction),
+            }
+        }
+
+        Ok(indexed)
+    }
+
+    /// This function stores the transactions sent from or to `addresses` in `[from_block, to_block]`.
+    /// The blocks are fetched concurrently but written in order, each with the checkpoint.
+    pub async fn backfill_transactions(
+        &self,
+        provider: &RootProvider<BoxTransport>,
+        addresses: &[Address],
+        from_block: u64,
+        to_block: u64,
+        db_pool: &DbPool,
+        checkpoint: &CheckpointKey,
+    ) -> Result<(), anyhow::Error> {
+        if from_block > to_block {
+            return Ok(());
+        }
+
+        let mut blocks = stream::iter(from_block..=to_block)
+            .map(|block_number| async move {
+                let transactions = self
+                    .block_transactions(provider, block_number, addresses, checkpoint.chain_id)
+                    .await?;
+                Ok::<_, anyhow::Error>((block_number, transactions))
+            })
+            .buffered(BLOCK_FETCH_CONCURRENCY);
+
+        while let Some(block) = blocks.next().await {
+            let (block_number, transactions) = block?;
+            if !transactions.is_empty() {
+                store_block_transactions(
+                    &transactions,
+                    &mut get_db_client(db_pool).await?,
+                    checkpoint,
+                )
+                .await?;
+            } else if block_number % CHECKPOINT_BLOCK_INTERVAL == 0 {
+                update_last_block_number(
+                    checkpoint,
+                    block_number.to_string(),
+                    &mut get_db_client(db_pool).await?,
+                )
+                .await?;
+                info!(
+                    "{} indexed transactions up to block {block_number}",
+                    checkpoint.indexer_name
+                );
+            }
+        }
+        update_last_block_number(
+            checkpoint,
+            to_block.to_string(),
+            &mut get_db_client(db_pool).await?,
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    /// This function stores the transactions sent from or to `addresses` from `block_number`,
+    /// or from its checkpoint when it is further, and then follows the head until the block
+    /// stream ends. Blocks are stored once they are confirmed, when a reorg is detected the
+    /// orphaned transactions are rolled back and the canonical blocks are indexed again.
+    /// No connection is held from `db_pool` while the next block is awaited.
+    pub async fn index_transactions(
+        &self,
+        provider: &RootProvider<BoxTransport>,
+        addresses: Vec<Address>,
+        block_number: u64,
+        db_pool: &DbPool,
+    ) -> Result<(), anyhow::Error> {
+        let checkpoint = self.checkpoint_key(provider, &addresses).await?;
+
+        // Resume from this indexer's own checkpoint, other indexers progress is not considered
+        let mut db_client = get_db_client(db_pool).await?;
+        let last_indexed_block: u64 =
+            (get_last_block_number(&checkpoint, &mut db_client).await?).parse()?;
+        let mut next_block = if last_indexed_block > block_number {
+            last_indexed_block + 1
+        } else {
+            create_system_if_does_not_exist(&checkpoint, block_number.to_string(), &mut db_client)
+                .await?;
+            block_number
+        };
+        drop(db_client);
+
+        let mut heads = HeadWatcher::new(provider, self.block_source).await?;
+        loop {
+            let recent =
+                get_recent_block_hashes(&checkpoint, &mut get_db_client(db_pool).await?).await?;
+            if let Some(fork_block) = find_fork_point(provider, &recent).await? {
+                warn!(
+                    "Reorg detected for {}, rolling back from block {fork_block}",
+                    checkpoint.indexer_name
+                );
+                rollback_from_block(&checkpoint, fork_block, &mut get_db_client(db_pool).await?)
+                    .await?;
+                next_block = next_block.min(fork_block);
+            }
+
+            let confirmed_head = confirmed_head(provider, &self.confirmations).await?;
+            if next_block <= confirmed_head {
+                self.backfill_transactions(
+                    provider,
+                    &addresses,
+                    next_block,
+                    confirmed_head,
+                    db_pool,
+                    &checkpoint,
+                )
+                .await?;
+                next_block = confirmed_head + 1;
+
+                let keep_from_block = confirmed_head.saturating_sub(self.reorg_window);
+                prune_reorg_window(
+                    &checkpoint,
+                    keep_from_block,
+                    &mut get_db_client(db_pool).await?,
+                )
+                .await?;
+            }
+
+            if !heads.next_block().await {
+                warn!("The block stream of {} ended", self.indexer_name);
+                return Ok(());
             }
-            _ => {}
         }
     }
 
-    Ok(())
+    /// This function indexes the transactions like [`Self::index_transactions`], reconnecting
+    /// whenever the block stream ends or the node can not be reached. Errors not caused by
+    /// the connection stop the indexer.
+    pub async fn index_with_reconnect(
+        &self,
+        rpc_url: &str,
+        addresses: Vec<Address>,
+        block_number: u64,
+        config: &ReconnectConfig,
+        db_pool: &DbPool,
+    ) -> Result<(), anyhow::Error> {
+        let addresses = &addresses;
+
+        run_with_reconnect(
+            &self.indexer_name,
+            config,
+            is_connection_error,
+            || connect(rpc_url),
+            move |provider| async move {
+                // The checkpoint is resumed from, the blocks missed while disconnected are backfilled
+                self.index_transactions(&provider, addresses.clone(), block_number, db_pool)
+                    .await
+            },
+        )
+        .await
+    }
+}
+
+/// This function replays a failed transaction with `eth_call` on the state of the block before
+/// it, and returns the data it reverted with. The transactions before it in its block are not
+/// replayed, so `None` is returned when the replay does not revert, and when the node returns
+/// no revert data, like nodes that do not keep the state of old blocks.
+pub async fn replay_revert_data(
+    provider: &RootProvider<BoxTransport>,
+    transaction: &ChronicleTransaction,
+) -> Result<Option<Bytes>, anyhow::Error> {
+    let to = match transaction.contract_address {
+        Some(_) => TxKind::Create,
+        None => TxKind::Call(transaction.to),
+    };
+    let request = TransactionRequest {
+        from: Some(transaction.from),
+        to: Some(to),
+        value: Some(transaction.value),
+        gas: Some(transaction.gas),
+        input: TransactionInput::new(transaction.data.clone()),
+        ..Default::default()
+    };
+    let block = BlockId::number(transaction.block_number.saturating_sub(1));
+
+    match provider.call(&request).block(block).await {
+        Ok(_) => Ok(None),
+        Err(RpcError::ErrorResp(payload)) => Ok(revert_data(&payload)),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// This function reads the revert data a node returns with an `execution reverted` error
+pub fn revert_data(payload: &ErrorPayload) -> Option<Bytes> {
+    let data = payload.data.as_ref()?;
+    serde_json::from_str::<Bytes>(data.get())
+        .ok()
+        .filter(|revert_data| !revert_data.is_empty())
+}
+
+#[async_trait]
+impl ChronicleTransactionIndexer for EvmTransactionIndexer {
+    type SubProvider = RootProvider<BoxTransport>;
+    type TargetAddress = Vec<Address>;
+
+    async fn subscribe_transactions<F>(
+        &self,
+        index_address: Self::TargetAddress,
+        provider: Self::SubProvider,
+        mut callback: F,
+    ) -> Result<(), anyhow::Error>
+    where
+        F: FnMut(Vec<ChronicleTransaction>) + Send,
+    {
+        let chain_id = provider.get_chain_id().await?;
+        let mut heads = HeadWatcher::new(&provider, self.block_source).await?;
+        let mut next_block = confirmed_head(&provider, &self.confirmations).await? + 1;
+
+        while heads.next_block().await {
+            let confirmed_head = confirmed_head(&provider, &self.confirmations).await?;
+            for block_number in next_block..=confirmed_head {
+                let transactions = self
+                    .block_transactions(&provider, block_number, &index_address, chain_id)
+                    .await?;
+                if !transactions.is_empty() {
+                    callback(transactions);
+                }
+            }
+            next_block = next_block.max(confirmed_head + 1);
+        }
+
+        Ok(())
+    }
 }
 
 #[cfg(test)]
 pub mod tests {
-    use alloy::{primitives::address, providers::ProviderBuilder, rpc::client::WsConnect};
+    use alloy::primitives::address;
 
     use super::*;
 
+    #[test]
+    fn test_transactions_match_their_sender_or_recipient() {
+        let indexed = address!("a0b86991c6218b36c1d19d4a2e9eb0ce3606eb48");
+        let other = address!("2C123047B23809DbCCDA2d34bB5158D2563221E3");
+        let transaction = |from, to| ChronicleTransaction {
+            from,
+            to,
+            ..Default::default()
+        };
+
+        assert!(transaction(indexed, other).involves_any(&[indexed]));
+        assert!(transaction(other, indexed).involves_any(&[other, indexed]));
+        assert!(!transaction(other, other).involves_any(&[indexed]));
+        // Contract creations have no recipient, they never match `address(0)`
+        assert!(!transaction(other, Address::ZERO).involves_any(&[Address::ZERO]));
+    }
+
+    #[test]
+    fn test_revert_data_is_read_from_the_error_data() {
+        let payload = |data: Option<&str>| ErrorPayload {
+            code: 3,
+            message: "execution reverted".into(),
+            data: data
+                .map(|data| serde_json::value::RawValue::from_string(data.to_string()).unwrap()),
+        };
+
+        assert_eq!(
+            revert_data(&payload(Some(
+                "\"0x4e487b710000000000000000000000000000000000000000000000000000000000000011\""
+            ))),
+            Some(Bytes::from_static(&[
+                0x4e, 0x48, 0x7b, 0x71, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+                0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x11,
+            ]))
+        );
+        assert_eq!(revert_data(&payload(Some("\"0x\""))), None);
+        assert_eq!(revert_data(&payload(Some("{\"reason\": 1}"))), None);
+        assert_eq!(revert_data(&payload(None)), None);
+    }
+
     #[tokio::test]
     #[ignore]
     pub async fn test_subscribe_transactions_works() {
-        let rpc_url = "wss://eth.merkle.io";
-
-        // Create the provider.
-        let ws = WsConnect::new(rpc_url);
-        let provider = ProviderBuilder::new().on_ws(ws).await.unwrap();
+        let provider = connect("wss://eth.merkle.io").await.unwrap();
         let usdc_token_address = address!("a0b86991c6218b36c1d19d4a2e9eb0ce3606eb48");
+        let indexer = EvmTransactionIndexer::new(
+            "USDC transactions".to_string(),
+            64,
+            Confirmations::default(),
+            LogSource::Subscribe,
+            CalldataDecoder::open_gateway(),
+        );
 
-        let callback = |tx: Vec<Transaction>| {
+        let callback = |tx: Vec<ChronicleTransaction>| {
             println!("Received Tx: {:?}", tx);
         };
 
-        subscribe_transactions(usdc_token_address, provider, callback)
+        indexer
+            .subscribe_transactions(vec![usdc_token_address], provider, callback)
             .await
             .unwrap();
     }
@@ -0,0 +1,731 @@
+//! This module indexes the native ETH moved into or out of a set of addresses by any call,
+//! not only by the transactions themselves. A contract sending ETH leaves no log and no
+//! transaction, it is only seen in the call traces of its block, so every block is traced
+//! with `debug_traceBlockByHash` and the `callTracer`, the block level `debug_traceTransaction`,
+//! or with the Parity style `trace_block`.
+//! The transfers are stored, and handed to the handlers as `InternalTransfer` events of the
+//! indexed addresses, so the entities see the ETH a gateway receives from a contract.
+use std::sync::Arc;
+
+use alloy::{
+    primitives::{Address, B256},
+    providers::{Provider, RootProvider},
+    rpc::types::{
+        eth::{BlockNumberOrTag, BlockTransactions},
+        trace::{
+            geth::CallFrame,
+            parity::{Action, CallType, LocalizedTransactionTrace, TraceOutput},
+        },
+    },
+    transports::BoxTransport,
+};
+use chronicle_primitives::{
+    db::{
+        connection_pool::{get_db_client, DbPool},
+        entities::{
+            internal_transfer::store_block_internal_transfers,
+            system::{
+                create_system_if_does_not_exist, get_last_block_number, update_last_block_number,
+                CheckpointKey,
+            },
+        },
+        reorg::{get_recent_block_hashes, prune_reorg_window, rollback_from_block},
+    },
+    indexer::ChronicleInternalTransfer,
+    registry::EventHandlerRegistry,
+    Confirmations, ReconnectConfig, TraceApi,
+};
+use futures_util::stream::{self, StreamExt};
+use serde::Deserialize;
+use tracing::{info, warn};
+
+use super::evm::{HeadWatcher, BLOCK_FETCH_CONCURRENCY, CHECKPOINT_BLOCK_INTERVAL};
+use crate::events::evm::{
+    confirmations::confirmed_head,
+    reconnect::{connect, is_connection_error, run_with_reconnect},
+    reorg::find_fork_point,
+    utils::LogSource,
+};
+
+/// This is the event signature the checkpoint of a trace indexer is keyed with
+pub const TRACE_CHECKPOINT_SIGNATURE: &str = "traces";
+
+pub struct EvmTraceIndexer {
+    /// This is the name of the indexer, it is used to key the indexer checkpoint
+    pub indexer_name: String,
+    /// This is how many blocks back a reorg can be rolled back
+    pub reorg_window: u64,
+    /// This is how deep a block must be before its transfers are stored
+    pub confirmations: Confirmations,
+    /// This is how new blocks are noticed, pushed by a subscription or polled
+    pub block_source: LogSource,
+    /// This is the node API the blocks are traced with
+    pub trace_api: TraceApi,
+    /// These are the handlers the transfers are dispatched to
+    pub registry: Arc<EventHandlerRegistry>,
+}
+
+/// This is the trace of one transaction returned by `debug_traceBlockByHash`, a transaction
+/// the node could not trace has an error instead of a result
+#[derive(Deserialize, Debug, Clone)]
+struct GethTransactionTrace {
+    result: Option<CallFrame>,
+    error: Option<String>,
+}
+
+impl EvmTraceIndexer {
+    pub fn new(
+        indexer_name: String,
+        reorg_window: u64,
+        confirmations: Confirmations,
+        block_source: LogSource,
+        trace_api: TraceApi,
+        registry: Arc<EventHandlerRegistry>,
+    ) -> Self {
+        Self {
+            indexer_name,
+            reorg_window,
+            confirmations,
+            block_source,
+            trace_api,
+            registry,
+        }
+    }
+
+    /// This function builds the key of the checkpoint owned by this indexer
+    /// on the chain the provider is connected to
+    pub async fn checkpoint_key(
+        &self,
+        provider: &RootProvider<BoxTransport>,
+        addresses: &[Address],
+    ) -> Result<CheckpointKey, anyhow::Error> {
+        let chain_id = provider.get_chain_id().await?;
+        let contract_address = addresses
+            .iter()
+            .map(|address| address.to_string())
+            .collect::<Vec<String>>()
+            .join(",");
+
+        Ok(CheckpointKey::new(
+            self.indexer_name.clone(),
+            chain_id,
+            contract_address,
+            TRACE_CHECKPOINT_SIGNATURE.to_string(),
+        ))
+    }
+
+    /// This function returns the native ETH moved into or out of `addresses` by the calls of a block
+    pub async fn block_internal_transfers(
+        &self,
+        provider: &RootProvider<BoxTransport>,
+        block_number: u64,
+        addresses: &[Address],
+        chain_id: u64,
+    ) -> Result<Vec<ChronicleInternalTransfer>, anyhow::Error> {
+        let block = provider
+            .get_block_by_number(block_number.into(), false)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("The node did not return block {block_number}"))?;
+        let BlockTransactions::Hashes(transaction_hashes) = block.transactions else {
+            anyhow::bail!("The node did not return the transaction hashes of block {block_number}");
+        };
+
+        let transfers = match self.trace_api {
+            TraceApi::Debug => {
+                // The block is traced by hash, so the traces are of the block that was fetched
+                let traces: Vec<GethTransactionTrace> = provider
+                    .raw_request(
+                        "debug_traceBlockByHash".into(),
+                        (
+                            block.header.hash,
+                            serde_json::json!({ "tracer": "callTracer" }),
+                        ),
+                    )
+                    .await?;
+                geth_block_transfers(block_number, &traces, &transaction_hashes)?
+            }
+            TraceApi::Parity => {
+                let traces: Vec<LocalizedTransactionTrace> = provider
+                    .raw_request(
+                        "trace_block".into(),
+                        (BlockNumberOrTag::Number(block_number),),
+                    )
+                    .await?;
+                parity_transfers(&traces)
+            }
+        };
+
+        Ok(transfers
+            .into_iter()
+            .map(|transfer| ChronicleInternalTransfer {
+                chain_id,
+                block_number,
+                block_hash: block.header.hash,
+                block_timestamp: block.header.timestamp,
+                ..transfer
+            })
+            .filter(|transfer| transfer.involves_any(addresses))
+            .collect())
+    }
+
+    /// This function stores the transfers into or out of `addresses` in `[from_block, to_block]`.
+    /// The blocks are traced concurrently but written in order, each with the checkpoint.
+    pub async fn backfill_transfers(
+        &self,
+        provider: &RootProvider<BoxTransport>,
+        addresses: &[Address],
+        from_block: u64,
+        to_block: u64,
+        db_pool: &DbPool,
+        checkpoint: &CheckpointKey,
+    ) -> Result<(), anyhow::Error> {
+        if from_block > to_block {
+            return Ok(());
+        }
+
+        let mut blocks = stream::iter(from_block..=to_block)
+            .map(|block_number| async move {
+                let transfers = self
+                    .block_internal_transfers(
+                        provider,
+                        block_number,
+                        addresses,
+                        checkpoint.chain_id,
+                    )
+                    .await?;
+                Ok::<_, anyhow::Error>((block_number, transfers))
+            })
+            .buffered(BLOCK_FETCH_CONCURRENCY);
+
+        while let Some(block) = blocks.next().await {
+            let (block_number, transfers) = block?;
+            if !transfers.is_empty() {
+                store_block_internal_transfers(
+                    &transfers,
+                    addresses,
+                    &mut get_db_client(db_pool).await?,
+                    &self.registry,
+                    checkpoint,
+                )
+                .await?;
+            } else if block_number % CHECKPOINT_BLOCK_INTERVAL == 0 {
+                update_last_block_number(
+                    checkpoint,
+                    block_number.to_string(),
+                    &mut get_db_client(db_pool).await?,
+                )
+                .await?;
+                info!(
+                    "{} indexed traces up to block {block_number}",
+                    checkpoint.indexer_name
+                );
+            }
+        }
+        update_last_block_number(
+            checkpoint,
+            to_block.to_string(),
+            &mut get_db_client(db_pool).await?,
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    /// This function stores the transfers into or out of `addresses` from `block_number`,
+    /// or from its checkpoint when it is further, and then follows the head until the block
+    /// stream ends. Blocks are stored once they are confirmed, when a reorg is detected the
+    /// orphaned transfers and their entity changes are rolled back and the canonical blocks
+    /// are traced again. No connection is held from `db_pool` while the next block is awaited.
+    pub async fn index_transfers(
+        &self,
+        provider: &RootProvider<BoxTransport>,
+        addresses: Vec<Address>,
+        block_number: u64,
+        db_pool: &DbPool,
+    ) -> Result<(), anyhow::Error> {
+        let checkpoint = self.checkpoint_key(provider, &addresses).await?;
+
+        // Resume from this indexer's own checkpoint, other indexers progress is not considered
+        let mut db_client = get_db_client(db_pool).await?;
+        let last_indexed_block: u64 =
+            (get_last_block_number(&checkpoint, &mut db_client).await?).parse()?;
+        let mut next_block = if last_indexed_block > block_number {
+            last_indexed_block + 1
+        } else {
+            create_system_if_does_not_exist(&checkpoint, block_number.to_string(), &mut db_client)
+                .await?;
+            block_number
+        };
+        drop(db_client);
+
+        let mut heads = HeadWatcher::new(provider, self.block_source).await?;
+        loop {
+            let recent =
+                get_recent_block_hashes(&checkpoint, &mut get_db_client(db_pool).await?).await?;
+            if let Some(fork_block) = find_fork_point(provider, &recent).await? {
+                warn!(
+                    "Reorg detected for {}, rolling back from block {fork_block}",
+                    checkpoint.indexer_name
+                );
+                rollback_from_block(&checkpoint, fork_block, &mut get_db_client(db_pool).await?)
+                    .await?;
+                next_block = next_block.min(fork_block);
+            }
+
+            let confirmed_head = confirmed_head(provider, &self.confirmations).await?;
+            if next_block <= confirmed_head {
+                self.backfill_transfers(
+                    provider,
+                    &addresses,
+                    next_block,
+                    confirmed_head,
+                    db_pool,
+                    &checkpoint,
+                )
+                .await?;
+                next_block = confirmed_head + 1;
+
+                let keep_from_block = confirmed_head.saturating_sub(self.reorg_window);
+                prune_reorg_window(
+                    &checkpoint,
+                    keep_from_block,
+                    &mut get_db_client(db_pool).await?,
+                )
+                .await?;
+            }
+
+            if !heads.next_block().await {
+                warn!("The block stream of {} ended", self.indexer_name);
+                return Ok(());
+            }
+        }
+    }
+
+    /// This function indexes the transfers like [`Self::index_transfers`], reconnecting
+    /// whenever the block stream ends or the node can not be reached. Errors not caused by
+    /// the connection stop the indexer.
+    pub async fn index_with_reconnect(
+        &self,
+        rpc_url: &str,
+        addresses: Vec<Address>,
+        block_number: u64,
+        config: &ReconnectConfig,
+        db_pool: &DbPool,
+    ) -> Result<(), anyhow::Error> {
+        let addresses = &addresses;
+
+        run_with_reconnect(
+            &self.indexer_name,
+            config,
+            is_connection_error,
+            || connect(rpc_url),
+            move |provider| async move {
+                // The checkpoint is resumed from, the blocks missed while disconnected are backfilled
+                self.index_transfers(&provider, addresses.clone(), block_number, db_pool)
+                    .await
+            },
+        )
+        .await
+    }
+}
+
+/// This function returns the transfers of the `callTracer` traces of a block, the traces are
+/// in the order of the transactions of the block
+fn geth_block_transfers(
+    block_number: u64,
+    traces: &[GethTransactionTrace],
+    transaction_hashes: &[B256],
+) -> Result<Vec<ChronicleInternalTransfer>, anyhow::Error> {
+    if traces.len() != transaction_hashes.len() {
+        anyhow::bail!(
+            "The node returned {} traces for the {} transactions of block {block_number}",
+            traces.len(),
+            transaction_hashes.len()
+        );
+    }
+
+    let mut transfers = Vec::new();
+    for (transaction_index, (trace, transaction_hash)) in
+        traces.iter().zip(transaction_hashes).enumerate()
+    {
+        let Some(frame) = &trace.result else {
+            anyhow::bail!(
+                "The node could not trace {transaction_hash}: {}",
+                trace.error.as_deref().unwrap_or("no result")
+            );
+        };
+        transfers.extend(call_frame_transfers(frame).into_iter().map(|transfer| {
+            ChronicleInternalTransfer {
+                transaction_hash: *transaction_hash,
+                transaction_index: transaction_index as u64,
+                ..transfer
+            }
+        }));
+    }
+
+    Ok(transfers)
+}
+
+/// This function returns the ETH moved by the calls of a `callTracer` trace, depth first.
+/// Reverted calls moved nothing, they are skipped with the calls they made, and so are the
+/// calls that can not move ETH, like `DELEGATECALL`.
+pub fn call_frame_transfers(frame: &CallFrame) -> Vec<ChronicleInternalTransfer> {
+    let mut transfers = Vec::new();
+    let mut trace_index = 0;
+    collect_call_frame_transfers(frame, Vec::new(), &mut trace_index, &mut transfers);
+
+    transfers
+}
+
+fn collect_call_frame_transfers(
+    frame: &CallFrame,
+    trace_address: Vec<u64>,
+    trace_index: &mut u64,
+    transfers: &mut Vec<ChronicleInternalTransfer>,
+) {
+    let index = *trace_index;
+    *trace_index += 1;
+    if frame.error.is_some() {
+        // The calls made by a reverted call are reverted too, they keep their place in the tree
+        *trace_index += count_call_frames(&frame.calls);
+        return;
+    }
+
+    let call_type = frame.typ.to_uppercase();
+    let value = frame.value.unwrap_or_default();
+    let moves_value = matches!(
+        call_type.as_str(),
+        "CALL" | "CREATE" | "CREATE2" | "SELFDESTRUCT"
+    );
+    if moves_value && !value.is_zero() {
+        if let Some(to) = frame.to {
+            transfers.push(ChronicleInternalTransfer {
+                trace_index: index,
+                trace_address: trace_address.clone(),
+                call_type,
+                from: frame.from,
+                to,
+                value,
+                input: frame.input.clone(),
+                ..Default::default()
+            });
+        }
+    }
+
+    for (position, call) in frame.calls.iter().enumerate() {
+        let mut call_address = trace_address.clone();
+        call_address.push(position as u64);
+        collect_call_frame_transfers(call, call_address, trace_index, transfers);
+    }
+}
+
+fn count_call_frames(calls: &[CallFrame]) -> u64 {
+    calls
+        .iter()
+        .map(|call| 1 + count_call_frames(&call.calls))
+        .sum()
+}
+
+/// This function returns the ETH moved by the calls of `trace_block` traces. The traces of a
+/// transaction are in depth first order, the calls of a reverted call are skipped with it.
+pub fn parity_transfers(traces: &[LocalizedTransactionTrace]) -> Vec<ChronicleInternalTransfer> {
+    let mut transfers = Vec::new();
+    let mut current_transaction = None;
+    let mut trace_index = 0;
+    let mut reverted: Vec<&[usize]> = Vec::new();
+
+    for localized in traces {
+        // Block rewards are not made by a transaction
+        let (Some(transaction_hash), Some(transaction_index)) =
+            (localized.transaction_hash, localized.transaction_position)
+        else {
+            continue;
+        };
+        if current_transaction != Some(transaction_hash) {
+            current_transaction = Some(transaction_hash);
+            trace_index = 0;
+            reverted.clear();
+        }
+        let index = trace_index;
+        trace_index += 1;
+
+        let trace = &localized.trace;
+        if trace.error.is_some() {
+            reverted.push(&trace.trace_address);
+            continue;
+        }
+        if reverted
+            .iter()
+            .any(|reverted| trace.trace_address.starts_with(reverted))
+        {
+            continue;
+        }
+
+        let (call_type, from, to, value, input) = match (&trace.action, &trace.result) {
+            (Action::Call(call), _) if matches!(call.call_type, CallType::Call) => {
+                ("CALL", call.from, call.to, call.value, call.input.clone())
+            }
+            (Action::Create(create), Some(TraceOutput::Create(output))) => (
+                "CREATE",
+                create.from,
+                output.address,
+                create.value,
+                create.init.clone(),
+            ),
+            (Action::Selfdestruct(selfdestruct), _) => (
+                "SELFDESTRUCT",
+                selfdestruct.address,
+                selfdestruct.refund_address,
+                selfdestruct.balance,
+                Default::default(),
+            ),
+            _ => continue,
+        };
+        if value.is_zero() {
+            continue;
+        }
+
+        transfers.push(ChronicleInternalTransfer {
+            transaction_hash,
+            transaction_index,
+            trace_index: index,
+            trace_address: trace
+                .trace_address
+                .iter()
+                .map(|position| *position as u64)
+                .collect(),
+            call_type: call_type.to_string(),
+            from,
+            to,
+            value,
+            input,
+            ..Default::default()
+        });
+    }
+
+    transfers
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy::primitives::{address, b256, U256};
+
+    use super::*;
+
+    const ROUTER: Address = address!("d8da6bf26964af9d7eed9e03e53415d37aa96045");
+    const GATEWAY: Address = address!("88da6bf26964af9d7eed9e03e53415d37aa96045");
+    const USER: Address = address!("2c123047b23809dbccda2d34bb5158d2563221e3");
+
+    #[test]
+    fn test_call_frame_transfers_skip_reverted_and_valueless_calls() {
+        // The user calls the router with 3 ETH, the router forwards 1 ETH to the gateway,
+        // delegates with a value, and makes a call that reverts after sending ETH on
+        let frame: CallFrame = serde_json::from_value(serde_json::json!({
+            "type": "CALL",
+            "from": USER,
+            "to": ROUTER,
+            "value": "0x29a2241af62c0000",
+            "gas": "0x30000",
+            "gasUsed": "0x20000",
+            "input": "0xd0e30db0",
+            "calls": [
+                {
+                    "type": "CALL",
+                    "from": ROUTER,
+                    "to": GATEWAY,
+                    "value": "0xde0b6b3a7640000",
+                    "gas": "0x10000",
+                    "gasUsed": "0x5208",
+                    "input": "0x"
+                },
+                {
+                    "type": "DELEGATECALL",
+                    "from": ROUTER,
+                    "to": USER,
+                    "value": "0xde0b6b3a7640000",
+                    "gas": "0x10000",
+                    "gasUsed": "0x100",
+                    "input": "0x"
+                },
+                {
+                    "type": "CALL",
+                    "from": ROUTER,
+                    "to": USER,
+                    "value": "0x0",
+                    "gas": "0x10000",
+                    "gasUsed": "0x100",
+                    "input": "0x",
+                    "error": "execution reverted",
+                    "calls": [
+                        {
+                            "type": "CALL",
+                            "from": USER,
+                            "to": GATEWAY,
+                            "value": "0xde0b6b3a7640000",
+                            "gas": "0x1000",
+                            "gasUsed": "0x100",
+                            "input": "0x"
+                        }
+                    ]
+                },
+                {
+                    "type": "SELFDESTRUCT",
+                    "from": ROUTER,
+                    "to": GATEWAY,
+                    "value": "0xde0b6b3a7640000",
+                    "gas": "0x0",
+                    "gasUsed": "0x0",
+                    "input": "0x"
+                }
+            ]
+        }))
+        .unwrap();
+
+        let transfers = call_frame_transfers(&frame);
+        let summary = transfers
+            .iter()
+            .map(|transfer| {
+                (
+                    transfer.trace_index,
+                    transfer.trace_address.clone(),
+                    transfer.call_type.as_str(),
+                    transfer.from,
+                    transfer.to,
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summary,
+            vec![
+                (0, vec![], "CALL", USER, ROUTER),
+                (1, vec![0], "CALL", ROUTER, GATEWAY),
+                (5, vec![3], "SELFDESTRUCT", ROUTER, GATEWAY),
+            ]
+        );
+        assert_eq!(
+            transfers[0].value,
+            U256::from(3_000_000_000_000_000_000u128)
+        );
+        assert!(transfers[1].input.is_empty());
+        assert!(!transfers[1].involves_any(&[USER]));
+    }
+
+    #[test]
+    fn test_parity_transfers_skip_reverted_calls_and_rewards() {
+        let transaction_hash =
+            b256!("5c504ed432cb51138bcf09aa5e8a410dd4a1e204ef84bfed1be16dfba1b22060");
+        let trace = |trace_address: Vec<usize>, action: serde_json::Value, error: Option<&str>| {
+            let trace_type = action["callType"].as_str().map_or("suicide", |_| "call");
+            let mut trace = serde_json::json!({
+                "action": action,
+                "blockHash": B256::ZERO,
+                "blockNumber": 19_000_000,
+                "result": { "gasUsed": "0x0", "output": "0x" },
+                "subtraces": 0,
+                "traceAddress": trace_address,
+                "transactionHash": transaction_hash,
+                "transactionPosition": 7,
+                "type": trace_type
+            });
+            if let Some(error) = error {
+                trace["error"] = error.into();
+            }
+            trace
+        };
+        let call = |from: Address, to: Address, value: &str| {
+            serde_json::json!({
+                "callType": "call",
+                "from": from,
+                "to": to,
+                "value": value,
+                "gas": "0x10000",
+                "input": "0x"
+            })
+        };
+        let traces: Vec<LocalizedTransactionTrace> = serde_json::from_value(serde_json::json!([
+            trace(vec![], call(USER, ROUTER, "0x0"), None),
+            trace(vec![0], call(ROUTER, GATEWAY, "0xde0b6b3a7640000"), None),
+            trace(vec![1], call(ROUTER, USER, "0x0"), Some("Reverted")),
+            trace(vec![1, 0], call(USER, GATEWAY, "0xde0b6b3a7640000"), None),
+            trace(
+                vec![2],
+                serde_json::json!({
+                    "address": ROUTER,
+                    "balance": "0xde0b6b3a7640000",
+                    "refundAddress": GATEWAY
+                }),
+                None
+            ),
+            {
+                "action": { "author": USER, "rewardType": "block", "value": "0x1bc16d674ec80000" },
+                "blockHash": B256::ZERO,
+                "blockNumber": 19_000_000,
+                "result": null,
+                "subtraces": 0,
+                "traceAddress": [],
+                "transactionHash": null,
+                "transactionPosition": null,
+                "type": "reward"
+            }
+        ]))
+        .unwrap();
+
+        let transfers = parity_transfers(&traces);
+        let summary = transfers
+            .iter()
+            .map(|transfer| {
+                (
+                    transfer.trace_index,
+                    transfer.call_type.as_str(),
+                    transfer.from,
+                    transfer.to,
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summary,
+            vec![
+                (1, "CALL", ROUTER, GATEWAY),
+                (4, "SELFDESTRUCT", ROUTER, GATEWAY)
+            ]
+        );
+        assert_eq!(transfers[0].transaction_hash, transaction_hash);
+        assert_eq!(transfers[0].transaction_index, 7);
+        assert_eq!(transfers[0].trace_address, vec![0]);
+    }
+
+    #[test]
+    fn test_geth_traces_must_match_the_block_transactions() {
+        let frame: CallFrame = serde_json::from_value(serde_json::json!({
+            "type": "CALL",
+            "from": USER,
+            "to": GATEWAY,
+            "value": "0xde0b6b3a7640000",
+            "gas": "0x5208",
+            "gasUsed": "0x5208",
+            "input": "0x"
+        }))
+        .unwrap();
+        let hashes = [
+            b256!("5c504ed432cb51138bcf09aa5e8a410dd4a1e204ef84bfed1be16dfba1b22060"),
+            b256!("9e8b2c1d32c4a5e0e9a7a7bdf3d1d5a57d2e0c9b2c0f6f6b0e5a3a8b7c6d5e4f"),
+        ];
+        let traced = GethTransactionTrace {
+            result: Some(frame),
+            error: None,
+        };
+        let failed = GethTransactionTrace {
+            result: None,
+            error: Some("execution timeout".to_string()),
+        };
+
+        let transfers =
+            geth_block_transfers(1, &[traced.clone(), traced.clone()], &hashes).unwrap();
+        assert_eq!(transfers.len(), 2);
+        assert_eq!(transfers[1].transaction_hash, hashes[1]);
+        assert_eq!(transfers[1].transaction_index, 1);
+
+        assert!(geth_block_transfers(1, &[traced.clone()], &hashes).is_err());
+        assert!(geth_block_transfers(1, &[traced, failed], &hashes).is_err());
+    }
+}
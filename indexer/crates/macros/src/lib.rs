@@ -0,0 +1,584 @@
+//! This crate holds the derive macros of chronicle.
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote};
+use syn::{
+    parse_macro_input, Data, DeriveInput, Expr, Field, Fields, GenericArgument, LitStr, Path,
+    PathArguments, Type,
+};
+
+/// This derives `ChronicleEntity` for a record, generating its table DDL, its insert,
+/// its named-column row mapping, and typed counts, lookups and pagination.
+///
+/// ```ignore
+/// #[derive(ChronicleEntity)]
+/// #[chronicle(table = PAYMENT_TABLE_NAME, unique(transaction_hash, log_index))]
+/// pub struct PaymentRecord {
+///     #[chronicle(lookup, ignore_case)]
+///     pub payer: String,
+///     #[chronicle(created_at)]
+///     pub create_at: NaiveDateTime,
+/// }
+/// ```
+///
+/// Struct attributes:
+/// - `table = PATH`, the `&str` constant holding the table name, without the `DB_VERSION` suffix
+/// - `unique(a, b)`, a unique constraint over several columns
+/// - `index(a, b)`, an index over several columns
+///
+/// Field attributes:
+/// - `column = "name"`, the column name when it is not the field name
+/// - `sql_type = "TYPE"`, the column type when it is not derived from the field type
+/// - `numeric`, a decimal string stored as `NUMERIC(78, 0)`, so 256 bit integers keep their precision
+/// - `as_text`, the column is stored as its `sql_type` but bound and read as text,
+///   like a `String` block number stored as `BIGINT`
+/// - `select = "SQL"`, the field is not stored, it is read as text from the SQL expression.
+///   The SQL can also be a Rust expression building it, like `format!("... {PRECISION}")`
+/// - `nullable`, the column is `NULL`. `Option` fields are always nullable
+/// - `unique`, the column is `UNIQUE`
+/// - `created_at`, the insertion timestamp set by the database, stored in the `created_at` column
+/// - `chain_id`, the chain the entity was indexed from. The counts, pages and lookups then take
+///   an optional chain id, and `count_per_chain` is generated
+/// - `skip`, the field is not stored and is filled with its default value
+/// - `with = PATH`, the field is stored as text through `PATH::encode` and `PATH::decode`
+/// - `lookup`, generates `query_by_`, `query_one_by_`, `query_by_*_paginated`, `query_by_*_range`,
+///   `count_by_` and `exists_by_` functions for the column
+/// - `index`, the column is indexed, on `LOWER(column)` with `ignore_case`
+/// - `ignore_case`, lookups and the index compare the column case-insensitively
+#[proc_macro_derive(ChronicleEntity, attributes(chronicle))]
+pub fn derive_chronicle_entity(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match expand_chronicle_entity(input) {
+        Ok(expanded) => expanded.into(),
+        Err(error) => error.to_compile_error().into(),
+    }
+}
+
+#[derive(Default)]
+struct ColumnAttributes {
+    column: Option<String>,
+    sql_type: Option<String>,
+    numeric: bool,
+    as_text: bool,
+    select: Option<Expr>,
+    nullable: bool,
+    unique: bool,
+    created_at: bool,
+    chain_id: bool,
+    skip: bool,
+    with: Option<Path>,
+    lookup: bool,
+    index: bool,
+    ignore_case: bool,
+}
+
+impl ColumnAttributes {
+    fn parse(field: &Field) -> syn::Result<Self> {
+        let mut attributes = Self::default();
+        for attr in field
+            .attrs
+            .iter()
+            .filter(|attr| attr.path().is_ident("chronicle"))
+        {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("column") {
+                    attributes.column = Some(meta.value()?.parse::<LitStr>()?.value());
+                } else if meta.path.is_ident("sql_type") {
+                    attributes.sql_type = Some(meta.value()?.parse::<LitStr>()?.value());
+                } else if meta.path.is_ident("select") {
+                    attributes.select = Some(meta.value()?.parse::<Expr>()?);
+                } else if meta.path.is_ident("with") {
+                    attributes.with = Some(meta.value()?.parse::<Path>()?);
+                } else if meta.path.is_ident("numeric") {
+                    attributes.numeric = true;
+                } else if meta.path.is_ident("as_text") {
+                    attributes.as_text = true;
+                } else if meta.path.is_ident("nullable") {
+                    attributes.nullable = true;
+                } else if meta.path.is_ident("unique") {
+                    attributes.unique = true;
+                } else if meta.path.is_ident("created_at") {
+                    attributes.created_at = true;
+                } else if meta.path.is_ident("chain_id") {
+                    attributes.chain_id = true;
+                } else if meta.path.is_ident("skip") {
+                    attributes.skip = true;
+                } else if meta.path.is_ident("lookup") {
+                    attributes.lookup = true;
+                } else if meta.path.is_ident("index") {
+                    attributes.index = true;
+                } else if meta.path.is_ident("ignore_case") {
+                    attributes.ignore_case = true;
+                } else {
+                    return Err(meta.error("unsupported chronicle field attribute"));
+                }
+                Ok(())
+            })?;
+        }
+
+        Ok(attributes)
+    }
+}
+
+fn expand_chronicle_entity(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let name = &input.ident;
+
+    let mut table: Option<Path> = None;
+    let mut unique_constraints: Vec<String> = Vec::new();
+    let mut indexes: Vec<(String, String)> = Vec::new();
+    for attr in input
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("chronicle"))
+    {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("table") {
+                table = Some(meta.value()?.parse::<Path>()?);
+            } else if meta.path.is_ident("unique") {
+                let mut columns = Vec::new();
+                meta.parse_nested_meta(|column| {
+                    columns.push(column.path.require_ident()?.to_string());
+                    Ok(())
+                })?;
+                unique_constraints.push(columns.join(", "));
+            } else if meta.path.is_ident("index") {
+                let mut columns = Vec::new();
+                meta.parse_nested_meta(|column| {
+                    columns.push(column.path.require_ident()?.to_string());
+                    Ok(())
+                })?;
+                indexes.push((columns.join("_"), columns.join(", ")));
+            } else {
+                return Err(meta.error("unsupported chronicle attribute"));
+            }
+            Ok(())
+        })?;
+    }
+    let table = table.ok_or_else(|| {
+        syn::Error::new_spanned(name, "missing `#[chronicle(table = TABLE_NAME)]`")
+    })?;
+
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    name,
+                    "entities must have named fields",
+                ))
+            }
+        },
+        _ => return Err(syn::Error::new_spanned(name, "entities must be structs")),
+    };
+
+    let mut columns = Vec::new();
+    let mut select_expressions = Vec::new();
+    let mut insert_columns = Vec::new();
+    let mut insert_casts = Vec::new();
+    let mut column_definitions = Vec::new();
+    let mut insert_values = Vec::new();
+    let mut row_fields = Vec::new();
+    let mut lookups = Vec::new();
+    let mut chain_column: Option<String> = None;
+
+    for field in fields {
+        let ident = field.ident.as_ref().expect("named fields have an ident");
+        let attributes = ColumnAttributes::parse(field)?;
+        if attributes.skip {
+            row_fields.push(quote! { #ident: ::core::default::Default::default() });
+            continue;
+        }
+
+        let column = match (&attributes.column, attributes.created_at) {
+            (Some(column), _) => column.clone(),
+            (None, true) => "created_at".to_string(),
+            (None, false) => ident.to_string(),
+        };
+
+        if let Some(select) = &attributes.select {
+            select_expressions
+                .push(quote! { ::std::format!("({})::TEXT AS {}", #select, #column) });
+            row_fields.push(quote! { #ident: row.try_get(#column)? });
+            continue;
+        }
+        columns.push(column.clone());
+        let select_expression = if attributes.numeric || attributes.as_text {
+            format!("{column}::TEXT AS {column}")
+        } else {
+            column.clone()
+        };
+        select_expressions.push(quote! { ::std::string::String::from(#select_expression) });
+
+        if attributes.created_at {
+            column_definitions.push(format!("{column} TIMESTAMP DEFAULT CURRENT_TIMESTAMP"));
+            row_fields.push(quote! { #ident: row.try_get(#column)? });
+            continue;
+        }
+
+        if attributes.chain_id {
+            if chain_column.is_some() {
+                return Err(syn::Error::new_spanned(
+                    ident,
+                    "an entity has a single `chain_id` field",
+                ));
+            }
+            chain_column = Some(column.clone());
+        }
+
+        let (inner_type, is_option) = option_inner(&field.ty);
+        let sql_type = match (&attributes.sql_type, &attributes.with) {
+            (Some(sql_type), _) => sql_type.clone(),
+            (None, _) if attributes.numeric => "NUMERIC(78, 0)".to_string(),
+            (None, Some(_)) => "VARCHAR".to_string(),
+            (None, None) => sql_type_of(inner_type).ok_or_else(|| {
+                syn::Error::new_spanned(
+                    &field.ty,
+                    "no SQL type for this field type, set `#[chronicle(sql_type = \"...\")]`",
+                )
+            })?,
+        };
+        let nullability = if attributes.nullable || is_option {
+            "NULL"
+        } else {
+            "NOT NULL"
+        };
+        let unique = if attributes.unique { " UNIQUE" } else { "" };
+        column_definitions.push(format!("{column} {sql_type} {nullability}{unique}"));
+        insert_columns.push(column.clone());
+        let cast = if attributes.numeric {
+            "::TEXT::NUMERIC".to_string()
+        } else if attributes.as_text {
+            format!("::TEXT::{sql_type}")
+        } else {
+            String::new()
+        };
+        insert_casts.push(cast.clone());
+
+        match &attributes.with {
+            Some(with) => {
+                insert_values
+                    .push(quote! { ::std::boxed::Box::new(#with::encode(&self.#ident)) as _ });
+                row_fields.push(quote! { #ident: #with::decode(row.try_get(#column)?) });
+            }
+            None => {
+                insert_values.push(quote! { ::std::boxed::Box::new(self.#ident.clone()) as _ });
+                row_fields.push(quote! { #ident: row.try_get(#column)? });
+            }
+        }
+
+        if attributes.index {
+            let expression = if attributes.ignore_case {
+                format!("LOWER({column})")
+            } else {
+                column.clone()
+            };
+            indexes.push((column.clone(), expression));
+        }
+
+        if attributes.lookup {
+            if attributes.with.is_some() {
+                return Err(syn::Error::new_spanned(
+                    ident,
+                    "`lookup` can not be used on a `with` field",
+                ));
+            }
+            let condition = if attributes.ignore_case {
+                format!("LOWER({column}) = LOWER($1{cast})")
+            } else {
+                format!("{column} = $1{cast}")
+            };
+            lookups.push((ident.clone(), inner_type.clone(), condition));
+        }
+    }
+
+    let chain = chain_column.as_deref().map(ChainFilter::new);
+    let lookups = lookups
+        .iter()
+        .map(|(ident, ty, condition)| expand_lookup(ident, ty, condition, chain.as_ref()))
+        .collect::<Vec<_>>();
+    let listing = expand_listing(chain.as_ref());
+    let (index_names, index_expressions): (Vec<_>, Vec<_>) = indexes.into_iter().unzip();
+
+    Ok(quote! {
+        impl ::chronicle_primitives::db::entity::ChronicleEntity for #name {
+            const TABLE_NAME: &'static str = #table;
+            const COLUMNS: &'static [&'static str] = &[#(#columns),*];
+            const INSERT_COLUMNS: &'static [&'static str] = &[#(#insert_columns),*];
+            const INSERT_CASTS: &'static [&'static str] = &[#(#insert_casts),*];
+            const COLUMN_DEFINITIONS: &'static [&'static str] = &[#(#column_definitions),*];
+            const UNIQUE_CONSTRAINTS: &'static [&'static str] = &[#(#unique_constraints),*];
+            const INDEXES: &'static [(&'static str, &'static str)] =
+                &[#((#index_names, #index_expressions)),*];
+
+            fn select_expressions() -> ::std::vec::Vec<::std::string::String> {
+                ::std::vec![#(#select_expressions),*]
+            }
+
+            fn insert_values(
+                &self,
+            ) -> ::std::vec::Vec<::std::boxed::Box<dyn ::chronicle_primitives::db::entity::ToSql + Sync + Send>> {
+                let values: ::std::vec::Vec<
+                    ::std::boxed::Box<dyn ::chronicle_primitives::db::entity::ToSql + Sync + Send>,
+                > = ::std::vec![#(#insert_values),*];
+                values
+            }
+
+            fn from_row(
+                row: &::chronicle_primitives::db::entity::Row,
+            ) -> ::core::result::Result<Self, ::chronicle_primitives::db::entity::SqlError> {
+                ::core::result::Result::Ok(Self {
+                    #(#row_fields),*
+                })
+            }
+        }
+
+        impl #name {
+            /// This function creates the table of this entity if one does not exist already
+            pub async fn create_table(
+                db_client: &mut ::chronicle_primitives::db::entity::Client,
+            ) -> ::chronicle_primitives::db::entity::EntityResult<()> {
+                ::chronicle_primitives::db::entity::create_entity_table::<Self>(db_client).await
+            }
+
+            /// This function stores this entity, an entity conflicting with a stored one is ignored.
+            /// It returns whether the entity was stored.
+            pub async fn insert(
+                &self,
+                db_client: &mut ::chronicle_primitives::db::entity::Client,
+            ) -> ::chronicle_primitives::db::entity::EntityResult<bool> {
+                ::chronicle_primitives::db::entity::insert_entity(self, db_client).await
+            }
+
+            #listing
+
+            #(#lookups)*
+        }
+    })
+}
+
+/// This is the chain column of an entity, the chain id is bound after the other values
+struct ChainFilter {
+    column: String,
+}
+
+impl ChainFilter {
+    fn new(column: &str) -> Self {
+        Self {
+            column: column.to_string(),
+        }
+    }
+
+    /// This is the condition of the entities on the chain bound to `$placeholder`,
+    /// it matches every chain when the chain id is `NULL`
+    fn condition(&self, placeholder: usize) -> String {
+        format!(
+            "(${placeholder}::BIGINT IS NULL OR {} = ${placeholder})",
+            self.column
+        )
+    }
+
+    /// This is the `chain_id` argument of the generated functions
+    fn argument(&self) -> TokenStream2 {
+        quote! { chain_id: ::core::option::Option<i64>, }
+    }
+
+    /// This is the binding of the `chain_id` argument
+    fn binding(&self) -> TokenStream2 {
+        quote! { &chain_id as &(dyn ::chronicle_primitives::db::entity::ToSql + Sync) }
+    }
+}
+
+/// This generates the counts and pagination over every stored entity
+fn expand_listing(chain: Option<&ChainFilter>) -> TokenStream2 {
+    let Some(chain) = chain else {
+        return quote! {
+            /// This function returns the count of stored entities
+            pub async fn count(
+                db_client: &mut ::chronicle_primitives::db::entity::Client,
+            ) -> ::chronicle_primitives::db::entity::EntityResult<i64> {
+                ::chronicle_primitives::db::entity::count_entities::<Self>(None, &[], db_client).await
+            }
+
+            /// This function returns a page of the stored entities, newest first
+            pub async fn query_paginated(
+                db_client: &mut ::chronicle_primitives::db::entity::Client,
+                page_number: i64,
+                page_size: i64,
+            ) -> ::chronicle_primitives::db::entity::EntityResult<::std::vec::Vec<Self>> {
+                ::chronicle_primitives::db::entity::query_entities_paginated::<Self>(
+                    None, &[], page_number, page_size, db_client,
+                )
+                .await
+            }
+        };
+    };
+
+    let condition = chain.condition(1);
+    let column = &chain.column;
+    let argument = chain.argument();
+    let binding = chain.binding();
+
+    quote! {
+        /// This function returns the count of the entities stored from the chain,
+        /// or from every chain when `chain_id` is `None`
+        pub async fn count(
+            #argument
+            db_client: &mut ::chronicle_primitives::db::entity::Client,
+        ) -> ::chronicle_primitives::db::entity::EntityResult<i64> {
+            ::chronicle_primitives::db::entity::count_entities::<Self>(Some(#condition), &[#binding], db_client).await
+        }
+
+        /// This function returns the count of the stored entities of each chain, by chain id
+        pub async fn count_per_chain(
+            db_client: &mut ::chronicle_primitives::db::entity::Client,
+        ) -> ::chronicle_primitives::db::entity::EntityResult<::std::vec::Vec<::chronicle_primitives::db::entity::ChainCount>> {
+            ::chronicle_primitives::db::entity::count_entities_per_chain::<Self>(#column, db_client).await
+        }
+
+        /// This function returns a page of the entities stored from the chain, newest first
+        pub async fn query_paginated(
+            #argument
+            db_client: &mut ::chronicle_primitives::db::entity::Client,
+            page_number: i64,
+            page_size: i64,
+        ) -> ::chronicle_primitives::db::entity::EntityResult<::std::vec::Vec<Self>> {
+            ::chronicle_primitives::db::entity::query_entities_paginated::<Self>(
+                Some(#condition), &[#binding], page_number, page_size, db_client,
+            )
+            .await
+        }
+    }
+}
+
+/// This generates the typed lookups of a column, restricted to a chain for entities with one
+fn expand_lookup(
+    ident: &syn::Ident,
+    ty: &Type,
+    condition: &str,
+    chain: Option<&ChainFilter>,
+) -> TokenStream2 {
+    let query_by = format_ident!("query_by_{ident}");
+    let query_one_by = format_ident!("query_one_by_{ident}");
+    let query_by_paginated = format_ident!("query_by_{ident}_paginated");
+    let query_by_range = format_ident!("query_by_{ident}_range");
+    let count_by = format_ident!("count_by_{ident}");
+    let exists_by = format_ident!("exists_by_{ident}");
+
+    let (condition, argument, binding) = match chain {
+        Some(chain) => {
+            let binding = chain.binding();
+            (
+                format!("{condition} AND {}", chain.condition(2)),
+                chain.argument(),
+                quote! { , #binding },
+            )
+        }
+        None => (condition.to_string(), quote! {}, quote! {}),
+    };
+    let params = quote! {
+        &[value as &(dyn ::chronicle_primitives::db::entity::ToSql + Sync) #binding]
+    };
+
+    quote! {
+        /// This function returns the entities matching the value, oldest first
+        pub async fn #query_by(
+            value: &#ty,
+            #argument
+            db_client: &mut ::chronicle_primitives::db::entity::Client,
+        ) -> ::chronicle_primitives::db::entity::EntityResult<::std::vec::Vec<Self>> {
+            ::chronicle_primitives::db::entity::query_entities::<Self>(Some(#condition), #params, db_client).await
+        }
+
+        /// This function returns the first stored entity matching the value,
+        /// it fails when there is none
+        pub async fn #query_one_by(
+            value: &#ty,
+            #argument
+            db_client: &mut ::chronicle_primitives::db::entity::Client,
+        ) -> ::chronicle_primitives::db::entity::EntityResult<Self> {
+            ::chronicle_primitives::db::entity::query_one_entity::<Self>(Some(#condition), #params, db_client).await
+        }
+
+        /// This function returns a page of the entities matching the value, newest first
+        pub async fn #query_by_paginated(
+            value: &#ty,
+            #argument
+            db_client: &mut ::chronicle_primitives::db::entity::Client,
+            page_number: i64,
+            page_size: i64,
+        ) -> ::chronicle_primitives::db::entity::EntityResult<::std::vec::Vec<Self>> {
+            ::chronicle_primitives::db::entity::query_entities_paginated::<Self>(
+                Some(#condition), #params, page_number, page_size, db_client,
+            )
+            .await
+        }
+
+        /// This function returns `limit` entities matching the value from `offset`, newest first
+        pub async fn #query_by_range(
+            value: &#ty,
+            #argument
+            db_client: &mut ::chronicle_primitives::db::entity::Client,
+            offset: i64,
+            limit: i64,
+        ) -> ::chronicle_primitives::db::entity::EntityResult<::std::vec::Vec<Self>> {
+            ::chronicle_primitives::db::entity::query_entities_range::<Self>(
+                Some(#condition), #params, offset, limit, db_client,
+            )
+            .await
+        }
+
+        /// This function returns the count of entities matching the value
+        pub async fn #count_by(
+            value: &#ty,
+            #argument
+            db_client: &mut ::chronicle_primitives::db::entity::Client,
+        ) -> ::chronicle_primitives::db::entity::EntityResult<i64> {
+            ::chronicle_primitives::db::entity::count_entities::<Self>(Some(#condition), #params, db_client).await
+        }
+
+        /// This function returns whether an entity matching the value is stored
+        pub async fn #exists_by(
+            value: &#ty,
+            #argument
+            db_client: &mut ::chronicle_primitives::db::entity::Client,
+        ) -> ::chronicle_primitives::db::entity::EntityResult<bool> {
+            ::core::result::Result::Ok(::chronicle_primitives::db::entity::count_entities::<Self>(Some(#condition), #params, db_client).await? > 0)
+        }
+    }
+}
+
+/// This returns the type wrapped by an `Option`, and whether it was wrapped
+fn option_inner(ty: &Type) -> (&Type, bool) {
+    if let Type::Path(type_path) = ty {
+        if let Some(segment) = type_path.path.segments.last() {
+            if segment.ident == "Option" {
+                if let PathArguments::AngleBracketed(arguments) = &segment.arguments {
+                    if let Some(GenericArgument::Type(inner)) = arguments.args.first() {
+                        return (inner, true);
+                    }
+                }
+            }
+        }
+    }
+
+    (ty, false)
+}
+
+/// This returns the SQL type a field type is stored as
+fn sql_type_of(ty: &Type) -> Option<String> {
+    let Type::Path(type_path) = ty else {
+        return None;
+    };
+    let sql_type = match type_path.path.segments.last()?.ident.to_string().as_str() {
+        "String" => "VARCHAR",
+        "bool" => "BOOLEAN",
+        "i16" => "SMALLINT",
+        "i32" => "INTEGER",
+        "i64" => "BIGINT",
+        "f64" => "DOUBLE PRECISION",
+        "NaiveDateTime" => "TIMESTAMP",
+        _ => return None,
+    };
+
+    Some(sql_type.to_string())
+}
@@ -0,0 +1 @@
+ALTER TABLE redemption{DB_VERSION} DROP COLUMN IF EXISTS log_index;
@@ -0,0 +1,2 @@
+-- The redemptions stored before this migration keep a NULL log index
+ALTER TABLE redemption{DB_VERSION} ADD COLUMN IF NOT EXISTS log_index VARCHAR NULL;
@@ -0,0 +1 @@
+ALTER TABLE redemption{DB_VERSION} DROP CONSTRAINT IF EXISTS redemption{DB_VERSION}_onchain_tx_hash_log_index_key;
@@ -0,0 +1,9 @@
+-- A redemption log replayed before this migration was stored again, keep the first row
+DELETE FROM redemption{DB_VERSION} duplicate
+USING redemption{DB_VERSION} original
+WHERE duplicate.id > original.id
+    AND duplicate.onchain_tx_hash = original.onchain_tx_hash
+    AND duplicate.log_index = original.log_index;
+
+ALTER TABLE redemption{DB_VERSION} DROP CONSTRAINT IF EXISTS redemption{DB_VERSION}_onchain_tx_hash_log_index_key;
+ALTER TABLE redemption{DB_VERSION} ADD CONSTRAINT redemption{DB_VERSION}_onchain_tx_hash_log_index_key UNIQUE (onchain_tx_hash, log_index);
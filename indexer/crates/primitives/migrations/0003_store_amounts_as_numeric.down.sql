@@ -0,0 +1,17 @@
+ALTER TABLE pool{DB_VERSION}
+    ALTER COLUMN current_amount_of_reward_tokens TYPE VARCHAR USING current_amount_of_reward_tokens::TEXT,
+    ALTER COLUMN current_amount_of_me_tokens TYPE VARCHAR USING current_amount_of_me_tokens::TEXT,
+    ALTER COLUMN r_optimal TYPE VARCHAR USING r_optimal::TEXT;
+
+ALTER TABLE redemption{DB_VERSION}
+    ALTER COLUMN source_amount TYPE VARCHAR USING source_amount::TEXT,
+    ALTER COLUMN dest_amount TYPE VARCHAR USING dest_amount::TEXT;
+
+ALTER TABLE reward{DB_VERSION}
+    ALTER COLUMN initial_supply TYPE VARCHAR USING initial_supply::TEXT;
+
+ALTER TABLE payment{DB_VERSION}
+    ALTER COLUMN amount TYPE VARCHAR USING amount::TEXT;
+
+ALTER TABLE chronicle_system{DB_VERSION}
+    ALTER COLUMN last_block_number TYPE VARCHAR USING last_block_number::TEXT;
@@ -0,0 +1,18 @@
+-- Amounts were decimal strings, empty ones become NULL
+ALTER TABLE pool{DB_VERSION}
+    ALTER COLUMN current_amount_of_reward_tokens TYPE NUMERIC(78, 0) USING NULLIF(TRIM(current_amount_of_reward_tokens::TEXT), '')::NUMERIC(78, 0),
+    ALTER COLUMN current_amount_of_me_tokens TYPE NUMERIC(78, 0) USING NULLIF(TRIM(current_amount_of_me_tokens::TEXT), '')::NUMERIC(78, 0),
+    ALTER COLUMN r_optimal TYPE NUMERIC(78, 0) USING NULLIF(TRIM(r_optimal::TEXT), '')::NUMERIC(78, 0);
+
+ALTER TABLE redemption{DB_VERSION}
+    ALTER COLUMN source_amount TYPE NUMERIC(78, 0) USING NULLIF(TRIM(source_amount::TEXT), '')::NUMERIC(78, 0),
+    ALTER COLUMN dest_amount TYPE NUMERIC(78, 0) USING NULLIF(TRIM(dest_amount::TEXT), '')::NUMERIC(78, 0);
+
+ALTER TABLE reward{DB_VERSION}
+    ALTER COLUMN initial_supply TYPE NUMERIC(78, 0) USING NULLIF(TRIM(initial_supply::TEXT), '')::NUMERIC(78, 0);
+
+ALTER TABLE payment{DB_VERSION}
+    ALTER COLUMN amount TYPE NUMERIC(78, 0) USING NULLIF(TRIM(amount::TEXT), '')::NUMERIC(78, 0);
+
+ALTER TABLE chronicle_system{DB_VERSION}
+    ALTER COLUMN last_block_number TYPE BIGINT USING NULLIF(TRIM(last_block_number::TEXT), '')::BIGINT;
@@ -0,0 +1,35 @@
+-- Dropping the chain id drops the unique constraints it is part of,
+-- the records must be unique across chains again for the old ones to be added back
+DO $$
+DECLARE
+    entity RECORD;
+BEGIN
+    FOR entity IN
+        SELECT rel.relname AS table_name, keys.columns
+        FROM (VALUES
+            ('pool', 'reward_token'),
+            ('brand', NULL),
+            ('redemption', 'onchain_tx_hash, log_index'),
+            ('reward', NULL),
+            ('payment', 'transaction_hash, log_index'),
+            ('gateway', 'gateway_address'),
+            ('token_whitelist', 'gateway_address, token_address'),
+            ('token_whitelist_history', 'transaction_hash, log_index, token_address'),
+            ('treasury_ledger', 'transaction_hash, log_index'),
+            ('chronicle_raw_event', NULL)
+        ) AS keys(name, columns)
+        JOIN pg_class rel ON rel.relname = LOWER(keys.name || '{DB_VERSION}')
+        WHERE rel.relkind = 'r' AND pg_table_is_visible(rel.oid)
+        UNION ALL
+        SELECT rel.relname, 'transaction_hash, log_index'
+        FROM pg_class rel
+        WHERE rel.relname LIKE LOWER('event\_%{DB_VERSION}')
+            AND rel.relkind = 'r' AND pg_table_is_visible(rel.oid)
+    LOOP
+        EXECUTE format('ALTER TABLE %I DROP COLUMN IF EXISTS chain_id', entity.table_name);
+
+        IF entity.columns IS NOT NULL THEN
+            EXECUTE format('ALTER TABLE %I ADD UNIQUE (%s)', entity.table_name, entity.columns);
+        END IF;
+    END LOOP;
+END $$;
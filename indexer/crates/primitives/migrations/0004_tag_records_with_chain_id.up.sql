@@ -0,0 +1,64 @@
+-- Every record is tagged with the chain it was indexed from, and is unique per chain.
+-- The records stored before this migration belong to the chain of the checkpoints
+-- when the indexers ran on a single chain, they are tagged with chain 0 otherwise.
+DO $$
+DECLARE
+    stored_chain_id BIGINT;
+    entity RECORD;
+    unique_constraint RECORD;
+BEGIN
+    IF to_regclass('chronicle_system{DB_VERSION}') IS NOT NULL THEN
+        SELECT MIN(chain_id) INTO stored_chain_id
+        FROM chronicle_system{DB_VERSION}
+        HAVING COUNT(DISTINCT chain_id) = 1;
+    END IF;
+
+    FOR entity IN
+        SELECT rel.relname AS table_name, keys.columns
+        FROM (VALUES
+            ('pool', 'reward_token'),
+            ('brand', NULL),
+            ('redemption', 'onchain_tx_hash, log_index'),
+            ('reward', NULL),
+            ('payment', 'transaction_hash, log_index'),
+            ('gateway', 'gateway_address'),
+            ('token_whitelist', 'gateway_address, token_address'),
+            ('token_whitelist_history', 'transaction_hash, log_index, token_address'),
+            ('treasury_ledger', 'transaction_hash, log_index'),
+            ('chronicle_raw_event', NULL)
+        ) AS keys(name, columns)
+        JOIN pg_class rel ON rel.relname = LOWER(keys.name || '{DB_VERSION}')
+        WHERE rel.relkind = 'r' AND pg_table_is_visible(rel.oid)
+        UNION ALL
+        -- The tables of the events decoded from an ABI
+        SELECT rel.relname, 'transaction_hash, log_index'
+        FROM pg_class rel
+        WHERE rel.relname LIKE LOWER('event\_%{DB_VERSION}')
+            AND rel.relkind = 'r' AND pg_table_is_visible(rel.oid)
+    LOOP
+        EXECUTE format(
+            'ALTER TABLE %I ADD COLUMN IF NOT EXISTS chain_id BIGINT NOT NULL DEFAULT %s',
+            entity.table_name,
+            COALESCE(stored_chain_id, 0)
+        );
+        EXECUTE format('ALTER TABLE %I ALTER COLUMN chain_id DROP DEFAULT', entity.table_name);
+
+        IF entity.columns IS NOT NULL THEN
+            FOR unique_constraint IN
+                SELECT conname FROM pg_constraint
+                WHERE conrelid = entity.table_name::regclass AND contype = 'u'
+            LOOP
+                EXECUTE format(
+                    'ALTER TABLE %I DROP CONSTRAINT %I',
+                    entity.table_name,
+                    unique_constraint.conname
+                );
+            END LOOP;
+            EXECUTE format(
+                'ALTER TABLE %I ADD UNIQUE (chain_id, %s)',
+                entity.table_name,
+                entity.columns
+            );
+        END IF;
+    END LOOP;
+END $$;
@@ -0,0 +1,5 @@
+ALTER TABLE chronicle_transaction{DB_VERSION} DROP COLUMN IF EXISTS revert_reason;
+ALTER TABLE chronicle_transaction{DB_VERSION} DROP COLUMN IF EXISTS revert_data;
+ALTER TABLE chronicle_transaction{DB_VERSION} DROP COLUMN IF EXISTS function_args;
+ALTER TABLE chronicle_transaction{DB_VERSION} DROP COLUMN IF EXISTS function_name;
+ALTER TABLE chronicle_transaction{DB_VERSION} DROP COLUMN IF EXISTS function_selector;
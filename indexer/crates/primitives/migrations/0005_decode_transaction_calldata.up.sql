@@ -0,0 +1,6 @@
+-- The transactions stored before this migration keep NULL calls and revert reasons
+ALTER TABLE chronicle_transaction{DB_VERSION} ADD COLUMN IF NOT EXISTS function_selector VARCHAR NULL;
+ALTER TABLE chronicle_transaction{DB_VERSION} ADD COLUMN IF NOT EXISTS function_name VARCHAR NULL;
+ALTER TABLE chronicle_transaction{DB_VERSION} ADD COLUMN IF NOT EXISTS function_args JSONB NULL;
+ALTER TABLE chronicle_transaction{DB_VERSION} ADD COLUMN IF NOT EXISTS revert_data VARCHAR NULL;
+ALTER TABLE chronicle_transaction{DB_VERSION} ADD COLUMN IF NOT EXISTS revert_reason VARCHAR NULL;
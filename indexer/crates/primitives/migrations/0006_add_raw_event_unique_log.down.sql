@@ -0,0 +1,2 @@
+ALTER TABLE chronicle_raw_event{DB_VERSION} DROP CONSTRAINT IF EXISTS chronicle_raw_event{DB_VERSION}_unique_log;
+ALTER TABLE chronicle_raw_event{DB_VERSION} DROP COLUMN IF EXISTS log_index;
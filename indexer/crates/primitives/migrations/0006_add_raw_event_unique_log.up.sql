@@ -0,0 +1,6 @@
+-- The raw events stored before this migration keep a NULL log index, so they do not
+-- conflict with each other or with the same logs stored again
+ALTER TABLE chronicle_raw_event{DB_VERSION} ADD COLUMN IF NOT EXISTS log_index BIGINT NULL;
+
+ALTER TABLE chronicle_raw_event{DB_VERSION} DROP CONSTRAINT IF EXISTS chronicle_raw_event{DB_VERSION}_unique_log;
+ALTER TABLE chronicle_raw_event{DB_VERSION} ADD CONSTRAINT chronicle_raw_event{DB_VERSION}_unique_log UNIQUE (chain_id, transaction_hash, log_index);
@@ -0,0 +1,4 @@
+ALTER TABLE payment{DB_VERSION}
+    ALTER COLUMN block_number TYPE VARCHAR USING block_number::TEXT,
+    ALTER COLUMN block_timestamp TYPE VARCHAR USING block_timestamp::TEXT,
+    ALTER COLUMN log_index TYPE VARCHAR USING log_index::TEXT;
@@ -0,0 +1,5 @@
+-- Block numbers, timestamps and log indexes were decimal strings, sorted and compared as text
+ALTER TABLE payment{DB_VERSION}
+    ALTER COLUMN block_number TYPE BIGINT USING NULLIF(TRIM(block_number::TEXT), '')::BIGINT,
+    ALTER COLUMN block_timestamp TYPE BIGINT USING NULLIF(TRIM(block_timestamp::TEXT), '')::BIGINT,
+    ALTER COLUMN log_index TYPE BIGINT USING NULLIF(TRIM(log_index::TEXT), '')::BIGINT;
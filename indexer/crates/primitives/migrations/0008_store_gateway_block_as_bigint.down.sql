@@ -0,0 +1 @@
+ALTER TABLE gateway{DB_VERSION} ALTER COLUMN block_number TYPE VARCHAR USING block_number::TEXT;
@@ -0,0 +1,2 @@
+-- The deployment block was a decimal string, sorted and compared as text
+ALTER TABLE gateway{DB_VERSION} ALTER COLUMN block_number TYPE BIGINT USING NULLIF(TRIM(block_number::TEXT), '')::BIGINT;
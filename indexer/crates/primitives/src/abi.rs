@@ -0,0 +1,423 @@
+//! This module derives what is needed to index an arbitrary event from its ABI.
+//! An event is resolved from a human-readable signature or from a JSON ABI file, and every
+//! parameter becomes a typed column of the event table, so no Rust code is written per event.
+use alloy::{
+    dyn_abi::{DynSolType, DynSolValue, Specifier},
+    json_abi::{Event, JsonAbi},
+    primitives::hex,
+};
+use anyhow::{anyhow, bail};
+
+use crate::{indexer::ChronicleEvent, utils::decode_event};
+
+/// These are the columns every event table has, parameters with the same name are prefixed
+pub const METADATA_COLUMNS: [&str; 9] = [
+    "id",
+    "chain_id",
+    "address",
+    "block_number",
+    "block_hash",
+    "transaction_hash",
+    "log_index",
+    "block_timestamp",
+    "created_at",
+];
+
+/// This function resolves the event to index. `event` is either a human-readable signature like
+/// `Transfer(address indexed from,address indexed to,uint256 value)`, or with an `abi_path`,
+/// the name or the signature of an event of the JSON ABI (a bare ABI or a compiler artifact).
+pub fn resolve_event(event: &str, abi_path: Option<&str>) -> Result<Event, anyhow::Error> {
+    let resolved = match abi_path {
+        None => Event::parse(event).map_err(|e| anyhow!("Invalid event `{event}`: {e}"))?,
+        Some(abi_path) => {
+            let raw = std::fs::read_to_string(abi_path)
+                .map_err(|e| anyhow!("Could not read the ABI file {abi_path}: {e}"))?;
+            let abi = parse_json_abi(&raw)
+                .map_err(|e| anyhow!("Could not parse the ABI file {abi_path}: {e}"))?;
+            find_event(&abi, event)?
+        }
+    };
+    if resolved.anonymous {
+        bail!("The event `{event}` is anonymous, it has no topic0 to be indexed by");
+    }
+
+    Ok(resolved)
+}
+
+/// This function parses a JSON ABI, compiler artifacts holding the ABI under `abi` are accepted
+pub fn parse_json_abi(raw: &str) -> Result<JsonAbi, anyhow::Error> {
+    let mut value: serde_json::Value = serde_json::from_str(raw)?;
+    if let Some(abi) = value.get_mut("abi") {
+        value = abi.take();
+    }
+
+    Ok(serde_json::from_value(value)?)
+}
+
+/// This function finds an event of the ABI by its name, or by its signature when it is overloaded
+pub fn find_event(abi: &JsonAbi, event: &str) -> Result<Event, anyhow::Error> {
+    if event.contains('(') {
+        let wanted = Event::parse(event).map_err(|e| anyhow!("Invalid event `{event}`: {e}"))?;
+        return abi
+            .events()
+            .find(|candidate| candidate.selector() == wanted.selector())
+            .cloned()
+            .ok_or_else(|| anyhow!("The ABI has no event `{event}`"));
+    }
+
+    match abi.events.get(event).map(Vec::as_slice) {
+        Some([found]) => Ok(found.clone()),
+        Some([]) | None => bail!("The ABI has no event `{event}`"),
+        Some(_) => bail!("The event `{event}` is overloaded in the ABI, use its full signature"),
+    }
+}
+
+/// This is the Postgres type a decoded event parameter is stored as
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AbiColumnType {
+    /// Unsigned and signed integers of up to 256 bits
+    Numeric,
+    /// Addresses, strings, fixed bytes and the topic hash of indexed reference types
+    Text,
+    Boolean,
+    /// Dynamic bytes
+    Bytea,
+    /// Arrays and tuples
+    Jsonb,
+}
+
+impl AbiColumnType {
+    /// This function returns the column type of a parameter, indexed reference types
+    /// are only emitted as the hash of their value
+    pub fn of(ty: &DynSolType, indexed: bool) -> Self {
+        match ty {
+            DynSolType::Uint(_) | DynSolType::Int(_) => Self::Numeric,
+            DynSolType::Bool => Self::Boolean,
+            DynSolType::Address | DynSolType::FixedBytes(_) | DynSolType::Function => Self::Text,
+            _ if indexed => Self::Text,
+            DynSolType::Bytes => Self::Bytea,
+            DynSolType::String => Self::Text,
+            _ => Self::Jsonb,
+        }
+    }
+
+    pub fn sql_type(&self) -> &'static str {
+        match self {
+            Self::Numeric => "NUMERIC(78, 0)",
+            Self::Text => "TEXT",
+            Self::Boolean => "BOOLEAN",
+            Self::Bytea => "BYTEA",
+            Self::Jsonb => "JSONB",
+        }
+    }
+
+    /// This is the insert placeholder of the `index`-th value,
+    /// numbers and JSON are bound as text and cast by Postgres
+    pub fn placeholder(&self, index: usize) -> String {
+        match self {
+            Self::Numeric => format!("${index}::TEXT::NUMERIC"),
+            Self::Jsonb => format!("${index}::TEXT::JSONB"),
+            _ => format!("${index}"),
+        }
+    }
+}
+
+/// This is a column of an event table, holding one parameter of the event
+#[derive(Debug, Clone)]
+pub struct AbiColumn {
+    /// This is the column name, the snake cased parameter name
+    pub name: String,
+    /// This is the parameter name in the ABI
+    pub param: String,
+    pub ty: DynSolType,
+    pub indexed: bool,
+    pub column_type: AbiColumnType,
+}
+
+/// This is a decoded parameter, bound to the insert of its column
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AbiColumnValue {
+    Text(String),
+    Boolean(bool),
+    Bytes(Vec<u8>),
+}
+
+/// This holds the event an indexer decodes and the table its events are stored in
+#[derive(Debug, Clone)]
+pub struct AbiEventTable {
+    pub table_name: String,
+    pub event: Event,
+    pub columns: Vec<AbiColumn>,
+}
+
+impl AbiEventTable {
+    pub fn new(table_name: String, event: Event) -> Result<Self, anyhow::Error> {
+        let mut columns: Vec<AbiColumn> = Vec::with_capacity(event.inputs.len());
+        for (position, input) in event.inputs.iter().enumerate() {
+            let ty = input.resolve()?;
+            let mut name = match to_snake_case(&input.name) {
+                name if name.is_empty() => format!("param_{position}"),
+                name => name,
+            };
+            if METADATA_COLUMNS.contains(&name.as_str()) {
+                name = format!("arg_{name}");
+            }
+            if columns.iter().any(|column| column.name == name) {
+                name = format!("{name}_{position}");
+            }
+
+            columns.push(AbiColumn {
+                name,
+                param: input.name.clone(),
+                column_type: AbiColumnType::of(&ty, input.indexed),
+                ty,
+                indexed: input.indexed,
+            });
+        }
+
+        Ok(Self {
+            table_name,
+            event,
+            columns,
+        })
+    }
+
+    /// This function decodes the parameters of the event, in column order
+    pub fn decode(&self, event: &ChronicleEvent) -> Result<Vec<AbiColumnValue>, anyhow::Error> {
+        let body = self
+            .columns
+            .iter()
+            .filter(|column| !column.indexed)
+            .map(|column| column.ty.clone())
+            .collect();
+        // Topics hold the hash of indexed reference types instead of their value
+        let indexed = self
+            .columns
+            .iter()
+            .filter(|column| column.indexed)
+            .map(|column| match column.column_type {
+                AbiColumnType::Text if !is_value_type(&column.ty) => DynSolType::FixedBytes(32),
+                _ => column.ty.clone(),
+            })
+            .collect();
+
+        let decoded_event = decode_event(
+            event.topics.clone(),
+            event.data.clone(),
+            DynSolType::Tuple(body),
+            indexed,
+        )?;
+        let mut indexed_values = decoded_event.indexed.iter();
+        let mut body_values = decoded_event.body.iter();
+
+        self.columns
+            .iter()
+            .map(|column| {
+                let value = if column.indexed {
+                    indexed_values.next()
+                } else {
+                    body_values.next()
+                }
+                .ok_or_else(|| anyhow!("Failed to decode {}", column.param))?;
+
+                Ok(column_value(value))
+            })
+            .collect()
+    }
+}
+
+/// This function builds the table name of the events of an indexer from its `event_name`
+pub fn abi_event_table_name(event_name: &str) -> String {
+    format!("event_{}", to_snake_case(event_name))
+}
+
+/// Converts a parameter or event name to a snake cased identifier
+pub fn to_snake_case(name: &str) -> String {
+    let mut snake = String::with_capacity(name.len());
+    let mut previous_is_lower = false;
+    for character in name.chars() {
+        if character.is_ascii_uppercase() {
+            if previous_is_lower {
+                snake.push('_');
+            }
+            snake.push(character.to_ascii_lowercase());
+            previous_is_lower = false;
+        } else if character.is_ascii_alphanumeric() {
+            snake.push(character);
+            previous_is_lower = character.is_ascii_lowercase() || character.is_ascii_digit();
+        } else {
+            if !snake.is_empty() && !snake.ends_with('_') {
+                snake.push('_');
+            }
+            previous_is_lower = false;
+        }
+    }
+    let snake = snake.trim_end_matches('_').to_string();
+    if snake.starts_with(|character: char| character.is_ascii_digit()) {
+        return format!("_{snake}");
+    }
+
+    snake
+}
+
+fn is_value_type(ty: &DynSolType) -> bool {
+    matches!(
+        ty,
+        DynSolType::Uint(_)
+            | DynSolType::Int(_)
+            | DynSolType::Bool
+            | DynSolType::Address
+            | DynSolType::FixedBytes(_)
+            | DynSolType::Function
+    )
+}
+
+fn column_value(value: &DynSolValue) -> AbiColumnValue {
+    if let Some(boolean) = value.as_bool() {
+        return AbiColumnValue::Boolean(boolean);
+    }
+    if let Some(bytes) = value.as_bytes() {
+        return AbiColumnValue::Bytes(bytes.to_vec());
+    }
+    match to_json(value) {
+        serde_json::Value::String(text) => AbiColumnValue::Text(text),
+        json => AbiColumnValue::Text(json.to_string()),
+    }
+}
+
+/// This function converts a decoded value to JSON, integers are kept as decimal strings
+/// so no precision is lost
+pub fn to_json(value: &DynSolValue) -> serde_json::Value {
+    if let Some((uint, _)) = value.as_uint() {
+        return uint.to_string().into();
+    }
+    if let Some((int, _)) = value.as_int() {
+        return int.to_string().into();
+    }
+    if let Some(address) = value.as_address() {
+        return address.to_string().into();
+    }
+    if let Some(boolean) = value.as_bool() {
+        return boolean.into();
+    }
+    if let Some((word, size)) = value.as_fixed_bytes() {
+        return hex::encode_prefixed(&word[..size]).into();
+    }
+    if let Some(bytes) = value.as_bytes() {
+        return hex::encode_prefixed(bytes).into();
+    }
+    if let Some(text) = value.as_str() {
+        return text.into();
+    }
+    if let Some(function) = value.as_function() {
+        return hex::encode_prefixed(function).into();
+    }
+    match value
+        .as_array()
+        .or(value.as_fixed_array())
+        .or(value.as_tuple())
+    {
+        Some(values) => values.iter().map(to_json).collect(),
+        None => serde_json::Value::Null,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy::primitives::{address, Address, U256};
+
+    use super::*;
+
+    #[test]
+    fn test_event_table_maps_parameters_to_typed_columns() {
+        let event = resolve_event(
+            "Swap(address indexed sender, uint256 amountIn, int24 tick, bytes data, address[] path, string indexed memo)",
+            None,
+        )
+        .unwrap();
+        let table = AbiEventTable::new(abi_event_table_name("My Swaps"), event).unwrap();
+
+        assert_eq!(table.table_name, "event_my_swaps");
+        let columns: Vec<(&str, AbiColumnType)> = table
+            .columns
+            .iter()
+            .map(|column| (column.name.as_str(), column.column_type))
+            .collect();
+        assert_eq!(
+            columns,
+            vec![
+                ("sender", AbiColumnType::Text),
+                ("amount_in", AbiColumnType::Numeric),
+                ("tick", AbiColumnType::Numeric),
+                ("data", AbiColumnType::Bytea),
+                ("path", AbiColumnType::Jsonb),
+                ("memo", AbiColumnType::Text),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parameters_named_like_metadata_columns_are_prefixed() {
+        let event = resolve_event(
+            "Bridged(uint256 indexed chainId, bytes32 blockHash, address to)",
+            None,
+        )
+        .unwrap();
+        let table = AbiEventTable::new("event_bridged".to_string(), event).unwrap();
+
+        let names: Vec<&str> = table
+            .columns
+            .iter()
+            .map(|column| column.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["arg_chain_id", "arg_block_hash", "to"]);
+    }
+
+    #[test]
+    fn test_decode_keeps_the_parameter_order() {
+        let event = resolve_event(
+            "Paid(uint256 amount, address indexed payer, address[] tokens)",
+            None,
+        )
+        .unwrap();
+        let table = AbiEventTable::new("event_paid".to_string(), event.clone()).unwrap();
+        let payer = address!("d8da6bf26964af9d7eed9e03e53415d37aa96045");
+
+        let data = DynSolValue::Tuple(vec![
+            DynSolValue::Uint(U256::from(42u64), 256),
+            DynSolValue::Array(vec![DynSolValue::Address(Address::ZERO)]),
+        ])
+        .abi_encode_params();
+        let log = ChronicleEvent {
+            topics: vec![event.selector(), payer.into_word()],
+            data: data.into(),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            table.decode(&log).unwrap(),
+            vec![
+                AbiColumnValue::Text("42".to_string()),
+                AbiColumnValue::Text(payer.to_string()),
+                AbiColumnValue::Text(format!("[\"{}\"]", Address::ZERO)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_find_event_by_name_or_signature() {
+        let abi = parse_json_abi(
+            r#"{"abi": [{"type": "event", "name": "Transfer", "anonymous": false, "inputs": [
+                {"name": "from", "type": "address", "indexed": true},
+                {"name": "to", "type": "address", "indexed": true},
+                {"name": "value", "type": "uint256", "indexed": false}
+            ]}]}"#,
+        )
+        .unwrap();
+
+        let by_name = find_event(&abi, "Transfer").unwrap();
+        let by_signature = find_event(&abi, "Transfer(address,address,uint256)").unwrap();
+        assert_eq!(by_name.selector(), by_signature.selector());
+        assert!(find_event(&abi, "Approval").is_err());
+    }
+}
@@ -0,0 +1,271 @@
+//! This module decodes the calldata of indexed transactions with the functions of an ABI,
+//! and the revert data of the failed ones with its custom errors.
+//! The OpenGateway and OpenGatewayFactory functions and the errors they can revert with are
+//! known without an ABI file, the functions and errors of an `abi_path` are added to them.
+use std::collections::HashMap;
+
+use alloy::{
+    dyn_abi::{DynSolType, DynSolValue, JsonAbiExt},
+    json_abi::{Error, Function, JsonAbi, Param},
+    primitives::{hex, Selector},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::abi::to_json;
+
+/// These are the functions of the OpenGateway and the OpenGatewayFactory
+pub const OPEN_GATEWAY_FUNCTIONS: [&str; 10] = [
+    "function makePayment(uint256 amount, bytes32 paymentId, address tokenAddress, address payer, string metadata)",
+    "function makePaymentWithPermit(uint256 amount, bytes32 paymentId, address tokenAddress, address payer, uint256 deadline, uint8 v, bytes32 r, bytes32 s, string metadata)",
+    "function updateConfirmationBlockHeight(uint40 newConfirmationBlockHeight)",
+    "function whitelistToken(address[] tokens)",
+    "function delistToken(address[] tokens)",
+    "function withdrawERC20(address token, address recipient)",
+    "function withdrawNative(address recipient)",
+    "function transferOwnership(address newOwner)",
+    "function renounceOwnership()",
+    "function deployGateway(bytes32 salt, address owner, uint40 confirmationBlockHeight, address[] tokens)",
+];
+
+/// These are the custom errors an OpenGateway call can revert with,
+/// its own and the ones of the tokens it moves
+pub const OPEN_GATEWAY_ERRORS: [&str; 8] = [
+    "error OwnableUnauthorizedAccount(address account)",
+    "error OwnableInvalidOwner(address owner)",
+    "error EnforcedPause()",
+    "error ExpectedPause()",
+    "error ERC20InsufficientBalance(address sender, uint256 balance, uint256 needed)",
+    "error ERC20InsufficientAllowance(address spender, uint256 allowance, uint256 needed)",
+    "error ERC2612ExpiredSignature(uint256 deadline)",
+    "error ERC2612InvalidSigner(address signer, address owner)",
+];
+
+/// This is the selector of `Error(string)`, the error of `require` and `revert` with a message
+pub const ERROR_STRING_SELECTOR: Selector = Selector::new([0x08, 0xc3, 0x79, 0xa0]);
+
+/// This is the selector of `Panic(uint256)`, the error of failed assertions and arithmetic
+pub const PANIC_SELECTOR: Selector = Selector::new([0x4e, 0x48, 0x7b, 0x71]);
+
+/// This is the function a transaction called, with its decoded arguments
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct DecodedCall {
+    pub name: String,
+    /// This is the full signature, like `withdrawNative(address)`
+    pub signature: String,
+    /// These are the arguments keyed by parameter name, integers are decimal strings
+    pub args: serde_json::Value,
+}
+
+/// This decodes calldata and revert data by their selector
+#[derive(Debug, Clone, Default)]
+pub struct CalldataDecoder {
+    functions: HashMap<Selector, Function>,
+    errors: HashMap<Selector, Error>,
+}
+
+impl CalldataDecoder {
+    /// This creates a decoder for the functions and errors of the ABI
+    pub fn new(abi: &JsonAbi) -> Self {
+        let mut decoder = Self::default();
+        decoder.extend(abi);
+        decoder
+    }
+
+    /// This creates a decoder for the OpenGateway and OpenGatewayFactory calls
+    pub fn open_gateway() -> Self {
+        let functions = OPEN_GATEWAY_FUNCTIONS
+            .iter()
+            .map(|function| Function::parse(function).expect("Infallible"))
+            .map(|function| (function.selector(), function))
+            .collect();
+        let errors = OPEN_GATEWAY_ERRORS
+            .iter()
+            .map(|error| Error::parse(error).expect("Infallible"))
+            .map(|error| (error.selector(), error))
+            .collect();
+
+        Self { functions, errors }
+    }
+
+    /// This function adds the functions and errors of the ABI, they replace the known ones
+    /// with the same selector
+    pub fn extend(&mut self, abi: &JsonAbi) {
+        for function in abi.functions() {
+            self.functions.insert(function.selector(), function.clone());
+        }
+        for error in abi.errors() {
+            self.errors.insert(error.selector(), error.clone());
+        }
+    }
+
+    /// This function decodes the function called by `input`, `None` when its selector is
+    /// unknown or the arguments do not match the function
+    pub fn decode_call(&self, input: &[u8]) -> Option<DecodedCall> {
+        let (selector, data) = split_selector(input)?;
+        let function = self.functions.get(&selector)?;
+        let values = function.abi_decode_input(data, true).ok()?;
+
+        Some(DecodedCall {
+            name: function.name.clone(),
+            signature: function.signature(),
+            args: named_args(&function.inputs, &values),
+        })
+    }
+
+    /// This function decodes the reason a call reverted with, from an `Error(string)` message,
+    /// a `Panic(uint256)` code or a known custom error. `None` is returned for empty revert
+    /// data and unknown errors.
+    pub fn decode_revert(&self, data: &[u8]) -> Option<String> {
+        let (selector, data) = split_selector(data)?;
+        if selector == ERROR_STRING_SELECTOR {
+            let reason = DynSolType::Tuple(vec![DynSolType::String])
+                .abi_decode_params(data)
+                .ok()?;
+            return reason.as_tuple()?.first()?.as_str().map(str::to_string);
+        }
+        if selector == PANIC_SELECTOR {
+            let code = DynSolType::Tuple(vec![DynSolType::Uint(256)])
+                .abi_decode_params(data)
+                .ok()?;
+            let (code, _) = code.as_tuple()?.first()?.as_uint()?;
+            return Some(format!("Panic(0x{:02x})", u64::try_from(code).ok()?));
+        }
+
+        let error = self.errors.get(&selector)?;
+        let values = error.abi_decode_input(data, true).ok()?;
+        let args = values
+            .iter()
+            .map(|value| match to_json(value) {
+                serde_json::Value::String(text) => text,
+                json => json.to_string(),
+            })
+            .collect::<Vec<String>>()
+            .join(", ");
+
+        Some(format!("{}({args})", error.name))
+    }
+}
+
+/// This function splits the 4 byte selector from the encoded arguments
+pub fn split_selector(data: &[u8]) -> Option<(Selector, &[u8])> {
+    if data.len() < 4 {
+        return None;
+    }
+    let (selector, rest) = data.split_at(4);
+
+    Some((Selector::from_slice(selector), rest))
+}
+
+/// This function returns the hex selector of the calldata, `None` for plain transfers
+pub fn calldata_selector(input: &[u8]) -> Option<String> {
+    split_selector(input).map(|(selector, _)| hex::encode_prefixed(selector))
+}
+
+/// This function keys the decoded values by their parameter name,
+/// unnamed parameters are keyed by their position
+fn named_args(params: &[Param], values: &[DynSolValue]) -> serde_json::Value {
+    params
+        .iter()
+        .zip(values)
+        .enumerate()
+        .map(|(position, (param, value))| {
+            let name = match param.name.as_str() {
+                "" => format!("param_{position}"),
+                name => name.to_string(),
+            };
+            (name, to_json(value))
+        })
+        .collect::<serde_json::Map<String, serde_json::Value>>()
+        .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy::primitives::{address, b256, Address, U256};
+
+    use super::*;
+
+    #[test]
+    fn test_decode_call_names_the_function_and_its_arguments() {
+        let decoder = CalldataDecoder::open_gateway();
+        let recipient = address!("d8da6bf26964af9d7eed9e03e53415d37aa96045");
+        let function = Function::parse("withdrawERC20(address,address)").unwrap();
+        let input = function
+            .abi_encode_input(&[
+                DynSolValue::Address(Address::ZERO),
+                DynSolValue::Address(recipient),
+            ])
+            .unwrap();
+
+        let call = decoder.decode_call(&input).unwrap();
+        assert_eq!(call.name, "withdrawERC20");
+        assert_eq!(call.signature, "withdrawERC20(address,address)");
+        assert_eq!(
+            call.args,
+            serde_json::json!({
+                "token": Address::ZERO.to_string(),
+                "recipient": recipient.to_string(),
+            })
+        );
+        assert_eq!(calldata_selector(&input).as_deref(), Some("0x9456fbcc"));
+
+        assert!(decoder.decode_call(&[0xde, 0xad, 0xbe, 0xef]).is_none());
+        assert!(decoder.decode_call(&input[..4]).is_none());
+        assert_eq!(calldata_selector(&[]), None);
+    }
+
+    #[test]
+    fn test_decode_call_keeps_large_amounts_as_decimal_strings() {
+        let decoder = CalldataDecoder::open_gateway();
+        let function = Function::parse(OPEN_GATEWAY_FUNCTIONS[0]).unwrap();
+        let payment_id = b256!("00000000000000000000000000000000000000000000000000000000000000aa");
+        let input = function
+            .abi_encode_input(&[
+                DynSolValue::Uint(U256::MAX, 256),
+                DynSolValue::FixedBytes(payment_id, 32),
+                DynSolValue::Address(Address::ZERO),
+                DynSolValue::Address(Address::ZERO),
+                DynSolValue::String("order 42".to_string()),
+            ])
+            .unwrap();
+
+        let call = decoder.decode_call(&input).unwrap();
+        assert_eq!(call.name, "makePayment");
+        assert_eq!(call.args["amount"], U256::MAX.to_string());
+        assert_eq!(call.args["paymentId"], payment_id.to_string());
+        assert_eq!(call.args["metadata"], "order 42");
+    }
+
+    #[test]
+    fn test_decode_revert_reads_messages_panics_and_custom_errors() {
+        let decoder = CalldataDecoder::open_gateway();
+
+        let mut message = ERROR_STRING_SELECTOR.to_vec();
+        message
+            .extend(DynSolValue::String("Token not whitelisted".to_string()).abi_encode_params());
+        assert_eq!(
+            decoder.decode_revert(&message).as_deref(),
+            Some("Token not whitelisted")
+        );
+
+        let mut panic = PANIC_SELECTOR.to_vec();
+        panic.extend(DynSolValue::Uint(U256::from(0x11), 256).abi_encode_params());
+        assert_eq!(
+            decoder.decode_revert(&panic).as_deref(),
+            Some("Panic(0x11)")
+        );
+
+        let account = address!("d8da6bf26964af9d7eed9e03e53415d37aa96045");
+        let error = Error::parse(OPEN_GATEWAY_ERRORS[0]).unwrap();
+        let custom = error
+            .abi_encode_input(&[DynSolValue::Address(account)])
+            .unwrap();
+        assert_eq!(
+            decoder.decode_revert(&custom),
+            Some(format!("OwnableUnauthorizedAccount({account})"))
+        );
+
+        assert_eq!(decoder.decode_revert(&[]), None);
+        assert_eq!(decoder.decode_revert(&[0x12, 0x34, 0x56, 0x78]), None);
+    }
+}
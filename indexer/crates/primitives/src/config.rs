@@ -1,6 +1,18 @@
-use alloy::primitives::{address, b256, Address, B256};
+use std::collections::HashSet;
 
-use crate::{Config, IndexerConfig, ServerConfig};
+use alloy::{
+    json_abi::Event,
+    primitives::{address, b256, Address, B256},
+};
+use anyhow::{anyhow, bail};
+
+use crate::{
+    abi::{abi_event_table_name, parse_json_abi, resolve_event, AbiEventTable},
+    calldata::CalldataDecoder,
+    indexer::{ChronicleIndexingMode, RuntimeEventFilter},
+    BackfillConfig, Config, Confirmations, DbPoolConfig, IndexerConfig, IngestionMode,
+    ReconnectConfig, ServerConfig, TraceApi, UnmatchedEventPolicy,
+};
 // =====================================
 // EVENT SIGNATURES
 // =====================================
@@ -35,6 +47,56 @@ pub const EVENT_THREE_SIGNATURE: B256 =
 pub const EVENT_FOUR_SIGNATURE: B256 =
     b256!("8ae268bd07c1784370b1d77d72548763ada0729264a966a234f895e03ae4c33c");
 
+/// This is for payments made through an OpenGateway
+/// This event is emitted when a payment is completed, `tokenAddress` is `address(0)` for native ETH
+/// event-name ['PaymentCompleted']
+/// event-signature ['PaymentCompleted(bytes32,address,address,uint256)']
+pub const EVENT_FIVE_SIGNATURE: B256 =
+    b256!("e0d9d264eda78796a25a78ebaea9a9924cd238fb274688c97236afe58eae19da");
+
+/// This is for the deployment of new OpenGateways
+/// This event is emitted by the OpenGatewayFactory for every merchant gateway it deploys
+/// event-name ['GatewayDeployed']
+/// event-signature ['GatewayDeployed(address,address,uint40,address[],bytes32)']
+pub const EVENT_SIX_SIGNATURE: B256 =
+    b256!("f1aea80f2b1432313fed2a79d93f1ff0f4fe811a3ac7d5269323a189e4e455f7");
+
+/// This is for the tokens an OpenGateway accepts
+/// This event is emitted when the merchant whitelists tokens on their gateway
+/// event-name ['TokenWhitelisted']
+/// event-signature ['TokenWhitelisted(address[],uint256)']
+pub const EVENT_SEVEN_SIGNATURE: B256 =
+    b256!("649235f87c7cd840f5953396943bf36a9e526072af182efa2d185908a9114657");
+
+/// This is for the tokens an OpenGateway stops accepting
+/// This event is emitted when the merchant delists tokens from their gateway
+/// event-name ['TokenDelisted']
+/// event-signature ['TokenDelisted(address[],uint256)']
+pub const EVENT_EIGHT_SIGNATURE: B256 =
+    b256!("fa2021d625cc29b24995744af7dd87d6fb74fb1517ac3f7cca580a9f02a855e7");
+
+/// This is for the native ETH leaving an OpenGateway
+/// This event is emitted when the merchant withdraws the native balance of their gateway
+/// event-name ['NativeWithdrawn']
+/// event-signature ['NativeWithdrawn(address,uint256)']
+pub const EVENT_NINE_SIGNATURE: B256 =
+    b256!("c303ca808382409472acbbf899c316cf439f409f6584aae22df86dfa3c9ed504");
+
+/// This is for the withdrawals an OpenGateway could not complete
+/// This event is emitted when sending a withdrawal to the recipient fails
+/// event-name ['WithdrawalFailed']
+/// event-signature ['WithdrawalFailed(address,uint256)']
+pub const EVENT_TEN_SIGNATURE: B256 =
+    b256!("92873d130824b495f22ad10f7f14028200557770e5986714318e78c54f3aa83c");
+
+/// This is for the native ETH moved by a call, read from the call traces of a block
+/// It is not emitted by a contract, the trace indexer hands every value transfer into or out of
+/// an indexed address to the handlers as this event of the address
+/// event-name ['InternalTransfer']
+/// event-signature ['InternalTransfer(address,address,uint256,bool)']
+pub const INTERNAL_TRANSFER_SIGNATURE: B256 =
+    b256!("89ea3e461d0236a878a4a056750702ff8395185f18f23a675510751f60817043");
+
 // =====================================
 // CONTRACT ADDRESSES
 // =====================================
@@ -53,7 +115,21 @@ pub fn return_me_indexer_config() -> Config {
                 rpc_url: get_rpc_from_evm_rpc_from_env(),
                 address: OPEN_REWARD_DIAMOND.to_string(),
                 event_signature: EVENT_ONE_SIGNATURE.to_string(),
+                event: None,
+                abi_path: None,
                 block_number: get_start_block_from_env(),
+                backfill: BackfillConfig::default(),
+                reorg_window: 64,
+                confirmations: Confirmations::default(),
+                factory: false,
+                unmatched_events: UnmatchedEventPolicy::default(),
+                reconnect: ReconnectConfig::default(),
+                ingestion: IngestionMode::default(),
+                poll_interval_ms: 2_000,
+                indexing_mode: ChronicleIndexingMode::default(),
+                addresses: Vec::new(),
+                runtime_events: Vec::new(),
+                trace_api: TraceApi::default(),
             },
             IndexerConfig {
                 event_name: "On Current Pool State".to_string(),
@@ -61,7 +137,21 @@ pub fn return_me_indexer_config() -> Config {
                 rpc_url: get_rpc_from_evm_rpc_from_env(),
                 address: OPEN_REWARD_DIAMOND.to_string(),
                 event_signature: EVENT_TWO_SIGNATURE.to_string(),
+                event: None,
+                abi_path: None,
                 block_number: get_start_block_from_env(),
+                backfill: BackfillConfig::default(),
+                reorg_window: 64,
+                confirmations: Confirmations::default(),
+                factory: false,
+                unmatched_events: UnmatchedEventPolicy::default(),
+                reconnect: ReconnectConfig::default(),
+                ingestion: IngestionMode::default(),
+                poll_interval_ms: 2_000,
+                indexing_mode: ChronicleIndexingMode::default(),
+                addresses: Vec::new(),
+                runtime_events: Vec::new(),
+                trace_api: TraceApi::default(),
             },
             IndexerConfig {
                 event_name: "Register Brand".to_string(),
@@ -69,7 +159,21 @@ pub fn return_me_indexer_config() -> Config {
                 rpc_url: get_rpc_from_evm_rpc_from_env(),
                 address: OPEN_REWARD_DIAMOND.to_string(),
                 event_signature: EVENT_THREE_SIGNATURE.to_string(),
+                event: None,
+                abi_path: None,
                 block_number: get_start_block_from_env(),
+                backfill: BackfillConfig::default(),
+                reorg_window: 64,
+                confirmations: Confirmations::default(),
+                factory: false,
+                unmatched_events: UnmatchedEventPolicy::default(),
+                reconnect: ReconnectConfig::default(),
+                ingestion: IngestionMode::default(),
+                poll_interval_ms: 2_000,
+                indexing_mode: ChronicleIndexingMode::default(),
+                addresses: Vec::new(),
+                runtime_events: Vec::new(),
+                trace_api: TraceApi::default(),
             },
             IndexerConfig {
                 event_name: "On Fungible Reward Created Successfully".to_string(),
@@ -77,13 +181,29 @@ pub fn return_me_indexer_config() -> Config {
                 rpc_url: get_rpc_from_evm_rpc_from_env(),
                 address: OPEN_REWARD_DIAMOND.to_string(),
                 event_signature: EVENT_FOUR_SIGNATURE.to_string(),
+                event: None,
+                abi_path: None,
                 block_number: get_start_block_from_env(),
+                backfill: BackfillConfig::default(),
+                reorg_window: 64,
+                confirmations: Confirmations::default(),
+                factory: false,
+                unmatched_events: UnmatchedEventPolicy::default(),
+                reconnect: ReconnectConfig::default(),
+                ingestion: IngestionMode::default(),
+                poll_interval_ms: 2_000,
+                indexing_mode: ChronicleIndexingMode::default(),
+                addresses: Vec::new(),
+                runtime_events: Vec::new(),
+                trace_api: TraceApi::default(),
             },
         ],
         server: ServerConfig {
             server_url: get_server_url_from_env(),
+            rpc_url: Some(get_rpc_from_evm_rpc_from_env()),
         },
         db_url: get_db_url_from_env(),
+        db_pool: DbPoolConfig::default(),
     }
 }
 
@@ -107,3 +227,544 @@ fn get_start_block_from_env() -> u64 {
         .parse::<u64>()
         .expect("START_BLOCK must be a number")
 }
+
+// =====================================
+// CONFIG FILE LOADING
+// =====================================
+
+/// This function replaces every `${ENV_VAR}` placeholder in the raw config
+/// with the value of the matching environment variable, this keeps secrets
+/// like RPC urls and db credentials out of the config file.
+/// Comments are copied as they are, a commented out placeholder does not need its variable set.
+pub fn interpolate_env(raw: &str) -> Result<String, anyhow::Error> {
+    let mut interpolated = String::with_capacity(raw.len());
+    let mut rest = raw;
+    // This is the quote of the TOML string being read, a `#` inside a string does not start a comment
+    let mut string_quote: Option<&str> = None;
+
+    while let Some(c) = rest.chars().next() {
+        if let Some(after) = rest.strip_prefix("${") {
+            let end = after
+                .find('}')
+                .ok_or_else(|| anyhow!("Unclosed `${{` in config file"))?;
+            let var_name = &after[..end];
+            if var_name.is_empty() {
+                bail!("Empty `${{}}` placeholder in config file");
+            }
+            let value = std::env::var(var_name).map_err(|_| {
+                anyhow!("Environment variable `{var_name}` referenced in config is not set")
+            })?;
+            interpolated.push_str(&value);
+            rest = &after[end + 1..];
+            continue;
+        }
+
+        let len = match string_quote {
+            // The comment runs to the end of its line
+            None if c == '#' => rest.find('\n').unwrap_or(rest.len()),
+            None => match [r#"""""#, "'''", "\"", "'"]
+                .into_iter()
+                .find(|quote| rest.starts_with(quote))
+            {
+                Some(quote) => {
+                    string_quote = Some(quote);
+                    quote.len()
+                }
+                None => c.len_utf8(),
+            },
+            // An escaped quote does not close a basic string, literal strings have no escapes
+            Some(quote) if c == '\\' && quote.starts_with('"') => {
+                c.len_utf8() + rest[1..].chars().next().map_or(0, char::len_utf8)
+            }
+            Some(quote) if rest.starts_with(quote) => {
+                string_quote = None;
+                quote.len()
+            }
+            Some(_) => c.len_utf8(),
+        };
+        interpolated.push_str(&rest[..len]);
+        rest = &rest[len..];
+    }
+
+    Ok(interpolated)
+}
+
+impl Config {
+    /// This function checks the config up front, so a bad address or event
+    /// signature fails at startup instead of inside a running indexer task
+    pub fn validate(&self) -> Result<(), anyhow::Error> {
+        if self.db_url.trim().is_empty() {
+            bail!("`db_url` must be set");
+        }
+        if self.server.server_url.trim().is_empty() {
+            bail!("`server.server_url` must be set");
+        }
+        // Every indexer holds a connection of the pool, the server needs at least one more
+        if self.db_pool.max_size <= self.indexer.len() {
+            bail!(
+                "`db_pool.max_size` must be larger than the number of indexers ({})",
+                self.indexer.len()
+            );
+        }
+        let mut event_names = HashSet::new();
+        for indexer in &self.indexer {
+            indexer.validate()?;
+            // The indexer name keys the indexer checkpoint, so it has to be unique
+            if !event_names.insert(indexer.event_name.as_str()) {
+                bail!("Duplicate indexer event_name `{}`", indexer.event_name);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// This function checks if `url` is an HTTP JSON-RPC endpoint
+fn is_http_url(url: &str) -> bool {
+    let url = url.trim().to_ascii_lowercase();
+    url.starts_with("http://") || url.starts_with("https://")
+}
+
+impl IndexerConfig {
+    /// This function checks that this indexer config can be used to start an indexer
+    pub fn validate(&self) -> Result<(), anyhow::Error> {
+        let name = &self.event_name;
+        if name.trim().is_empty() {
+            bail!("Indexer `event_name` must be set");
+        }
+        if !matches!(self.state_machine.as_str(), "EVM" | "RUNTIME") {
+            bail!(
+                "Indexer `{name}`: invalid state_machine `{}`, expected `EVM` or `RUNTIME`",
+                self.state_machine
+            );
+        }
+        if self.rpc_url.trim().is_empty() {
+            bail!("Indexer `{name}`: `rpc_url` must be set");
+        }
+        if self.state_machine == "RUNTIME" {
+            self.validate_runtime()
+                .map_err(|e| anyhow!("Indexer `{name}`: {e}"))?;
+        } else {
+            self.validate_evm()?;
+        }
+        if self.backfill.min_block_range == 0
+            || self.backfill.min_block_range > self.backfill.max_block_range
+        {
+            bail!("Indexer `{name}`: backfill block range must satisfy 0 < min_block_range <= max_block_range");
+        }
+        if self.reconnect.initial_backoff_ms == 0
+            || self.reconnect.initial_backoff_ms > self.reconnect.max_backoff_ms
+        {
+            bail!("Indexer `{name}`: reconnect backoff must satisfy 0 < initial_backoff_ms <= max_backoff_ms");
+        }
+        if self.ingestion == IngestionMode::Subscribe && is_http_url(&self.rpc_url) {
+            bail!("Indexer `{name}`: logs can not be subscribed to over HTTP, use `ingestion = \"poll\"`");
+        }
+        if self.polls() && self.poll_interval_ms == 0 {
+            bail!("Indexer `{name}`: `poll_interval_ms` must be larger than 0");
+        }
+
+        Ok(())
+    }
+
+    /// This function checks the contract and event, or the addresses, an EVM indexer indexes
+    fn validate_evm(&self) -> Result<(), anyhow::Error> {
+        let name = &self.event_name;
+        self.address
+            .parse::<Address>()
+            .map_err(|e| anyhow!("Indexer `{name}`: invalid address `{}`: {e}", self.address))?;
+        match self.indexing_mode {
+            ChronicleIndexingMode::Event => {
+                self.event_topic()
+                    .map_err(|e| anyhow!("Indexer `{name}`: {e}"))?;
+            }
+            ChronicleIndexingMode::Transaction => {
+                if self.factory || self.event.is_some() {
+                    bail!("Indexer `{name}`: `factory` and `event` can not be set in the transaction indexing mode");
+                }
+                self.transaction_addresses()
+                    .map_err(|e| anyhow!("Indexer `{name}`: {e}"))?;
+                self.calldata_decoder()
+                    .map_err(|e| anyhow!("Indexer `{name}`: {e}"))?;
+            }
+            ChronicleIndexingMode::Trace => {
+                if self.factory || self.event.is_some() || self.abi_path.is_some() {
+                    bail!("Indexer `{name}`: `factory`, `event` and `abi_path` can not be set in the trace indexing mode");
+                }
+                self.transaction_addresses()
+                    .map_err(|e| anyhow!("Indexer `{name}`: {e}"))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// This function checks the runtime events a `RUNTIME` indexer indexes, they are decoded
+    /// with the runtime metadata and followed with a finalized heads subscription
+    fn validate_runtime(&self) -> Result<(), anyhow::Error> {
+        if self.indexing_mode != ChronicleIndexingMode::Event {
+            bail!("only events can be indexed on a `RUNTIME` state machine");
+        }
+        if self.factory || self.event.is_some() || self.abi_path.is_some() {
+            bail!("`factory`, `event` and `abi_path` can not be set on a `RUNTIME` state machine, runtime events are decoded with the runtime metadata");
+        }
+        if self.polls() {
+            bail!("runtime events follow a finalized heads subscription, `rpc_url` must be a WebSocket endpoint");
+        }
+        self.runtime_event_filters()?;
+
+        Ok(())
+    }
+
+    /// This function returns the filters of `runtime_events`, the runtime events a `RUNTIME`
+    /// indexer stores
+    pub fn runtime_event_filters(&self) -> Result<Vec<RuntimeEventFilter>, anyhow::Error> {
+        self.runtime_events
+            .iter()
+            .map(|filter| filter.parse())
+            .collect()
+    }
+
+    /// This function checks if the live logs of this indexer are polled rather than subscribed to
+    pub fn polls(&self) -> bool {
+        match self.ingestion {
+            IngestionMode::Auto => is_http_url(&self.rpc_url),
+            IngestionMode::Subscribe => false,
+            IngestionMode::Poll => true,
+        }
+    }
+
+    /// This function returns `address` and `addresses`, the addresses whose transactions
+    /// are indexed in the transaction indexing mode, or whose value transfers in the trace one
+    pub fn transaction_addresses(&self) -> Result<Vec<Address>, anyhow::Error> {
+        let mut addresses = vec![self.address.parse::<Address>()?];
+        for address in &self.addresses {
+            let address = address
+                .parse::<Address>()
+                .map_err(|e| anyhow!("invalid address `{address}`: {e}"))?;
+            if !addresses.contains(&address) {
+                addresses.push(address);
+            }
+        }
+
+        Ok(addresses)
+    }
+
+    /// This function builds the decoder of the transaction calldata, it knows the OpenGateway
+    /// calls and the functions and errors of `abi_path`
+    pub fn calldata_decoder(&self) -> Result<CalldataDecoder, anyhow::Error> {
+        let mut decoder = CalldataDecoder::open_gateway();
+        if let Some(abi_path) = &self.abi_path {
+            let raw = std::fs::read_to_string(abi_path)
+                .map_err(|e| anyhow!("Could not read the ABI file {abi_path}: {e}"))?;
+            let abi = parse_json_abi(&raw)
+                .map_err(|e| anyhow!("Could not parse the ABI file {abi_path}: {e}"))?;
+            decoder.extend(&abi);
+        }
+
+        Ok(decoder)
+    }
+
+    /// This function resolves the ABI event of `event`, if the events of
+    /// this indexer are decoded from an ABI
+    pub fn abi_event(&self) -> Result<Option<Event>, anyhow::Error> {
+        match (&self.event, &self.abi_path) {
+            (Some(event), abi_path) => Ok(Some(resolve_event(event, abi_path.as_deref())?)),
+            (None, Some(_)) => bail!("`abi_path` is set but `event` is missing"),
+            (None, None) => Ok(None),
+        }
+    }
+
+    /// This function returns the table the events of this indexer are decoded into,
+    /// if they are decoded from an ABI
+    pub fn abi_event_table(&self) -> Result<Option<AbiEventTable>, anyhow::Error> {
+        self.abi_event()?
+            .map(|event| AbiEventTable::new(abi_event_table_name(&self.event_name), event))
+            .transpose()
+    }
+
+    /// This function returns the topic0 of the indexed event, taken from `event_signature`
+    /// or derived from the ABI event. When both are set they must agree.
+    pub fn event_topic(&self) -> Result<B256, anyhow::Error> {
+        let abi_event = self.abi_event()?;
+        if self.event_signature.trim().is_empty() {
+            return abi_event
+                .map(|event| event.selector())
+                .ok_or_else(|| anyhow!("either `event_signature` or `event` must be set"));
+        }
+
+        let event_signature = self
+            .event_signature
+            .parse::<B256>()
+            .map_err(|e| anyhow!("invalid event_signature `{}`: {e}", self.event_signature))?;
+        if let Some(event) = abi_event {
+            if event.selector() != event_signature {
+                bail!(
+                    "event_signature `{event_signature}` does not match `{}`, whose topic0 is {}",
+                    event.signature(),
+                    event.selector()
+                );
+            }
+        }
+
+        Ok(event_signature)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FinalityTag;
+
+    fn indexer_config() -> IndexerConfig {
+        IndexerConfig {
+            event_name: "Transfer".to_string(),
+            state_machine: "EVM".to_string(),
+            rpc_url: "wss://eth.merkle.io".to_string(),
+            address: OPEN_REWARD_DIAMOND.to_string(),
+            event_signature: EVENT_ONE_SIGNATURE.to_string(),
+            event: None,
+            abi_path: None,
+            block_number: 0,
+            backfill: BackfillConfig::default(),
+            reorg_window: 64,
+            confirmations: Confirmations::default(),
+            factory: false,
+            unmatched_events: UnmatchedEventPolicy::default(),
+            reconnect: ReconnectConfig::default(),
+            ingestion: IngestionMode::default(),
+            poll_interval_ms: 2_000,
+            indexing_mode: ChronicleIndexingMode::default(),
+            addresses: Vec::new(),
+            runtime_events: Vec::new(),
+            trace_api: TraceApi::default(),
+        }
+    }
+
+    #[test]
+    fn test_interpolate_env_replaces_placeholders() {
+        std::env::set_var("CHRONICLE_TEST_RPC", "wss://rpc.example");
+        let raw = "rpc_url = \"${CHRONICLE_TEST_RPC}/v2\"";
+
+        assert_eq!(
+            interpolate_env(raw).unwrap(),
+            "rpc_url = \"wss://rpc.example/v2\""
+        );
+    }
+
+    #[test]
+    fn test_interpolate_env_fails_on_missing_or_unclosed_placeholder() {
+        assert!(interpolate_env("${CHRONICLE_TEST_DOES_NOT_EXIST}").is_err());
+        assert!(interpolate_env("${CHRONICLE_TEST_RPC").is_err());
+    }
+
+    #[test]
+    fn test_interpolate_env_skips_placeholders_in_comments() {
+        std::env::set_var("CHRONICLE_TEST_DB_URL", "postgres://localhost/chronicle");
+        let raw = r#"# rpc_url = "${CHRONICLE_TEST_DOES_NOT_EXIST}"
+db_url = "${CHRONICLE_TEST_DB_URL}#main" # was ${CHRONICLE_TEST_DOES_NOT_EXIST}
+path = 'C:\' # ${CHRONICLE_TEST_DOES_NOT_EXIST}
+"#;
+
+        assert_eq!(
+            interpolate_env(raw).unwrap(),
+            r#"# rpc_url = "${CHRONICLE_TEST_DOES_NOT_EXIST}"
+db_url = "postgres://localhost/chronicle#main" # was ${CHRONICLE_TEST_DOES_NOT_EXIST}
+path = 'C:\' # ${CHRONICLE_TEST_DOES_NOT_EXIST}
+"#
+        );
+    }
+
+    #[test]
+    fn test_validate_rejects_bad_address_and_signature() {
+        assert!(indexer_config().validate().is_ok());
+
+        let mut config = indexer_config();
+        config.address = "0x1234".to_string();
+        assert!(config.validate().is_err());
+
+        let mut config = indexer_config();
+        config.event_signature = "Transfer(address,address,uint256)".to_string();
+        assert!(config.validate().is_err());
+
+        let mut config = indexer_config();
+        config.state_machine = "SOLANA".to_string();
+        assert!(config.validate().is_err());
+
+        let mut config = indexer_config();
+        config.reconnect.initial_backoff_ms = config.reconnect.max_backoff_ms + 1;
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_ingestion_mode_follows_the_rpc_url_scheme() {
+        let mut config = indexer_config();
+        assert!(!config.polls());
+
+        config.rpc_url = "HTTPS://eth.merkle.io".to_string();
+        assert!(config.polls());
+        assert!(config.validate().is_ok());
+
+        config.ingestion = IngestionMode::Subscribe;
+        assert!(config.validate().is_err());
+
+        config.ingestion = IngestionMode::Poll;
+        config.rpc_url = "wss://eth.merkle.io".to_string();
+        assert!(config.polls());
+        config.poll_interval_ms = 0;
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_event_topic_is_derived_from_the_abi_event() {
+        let transfer = b256!("ddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef");
+        let mut config = indexer_config();
+        config.event_signature = String::new();
+        config.event =
+            Some("Transfer(address indexed from,address indexed to,uint256 value)".to_string());
+        assert_eq!(config.event_topic().unwrap(), transfer);
+
+        config.event_signature = transfer.to_string();
+        assert!(config.validate().is_ok());
+
+        config.event_signature = EVENT_ONE_SIGNATURE.to_string();
+        assert!(config.validate().is_err());
+
+        config.event = None;
+        config.abi_path = Some("erc20.json".to_string());
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_transaction_mode_needs_no_event_but_valid_addresses() {
+        let mut config = indexer_config();
+        config.indexing_mode = ChronicleIndexingMode::Transaction;
+        config.event_signature = String::new();
+        config.addresses = vec![
+            "0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48".to_string(),
+            OPEN_REWARD_DIAMOND.to_string(),
+        ];
+        assert!(config.validate().is_ok());
+        assert_eq!(
+            config.transaction_addresses().unwrap(),
+            vec![
+                OPEN_REWARD_DIAMOND,
+                address!("a0b86991c6218b36c1d19d4a2e9eb0ce3606eb48")
+            ]
+        );
+
+        config.addresses.push("0x1234".to_string());
+        assert!(config.validate().is_err());
+
+        let mut config = indexer_config();
+        config.indexing_mode = ChronicleIndexingMode::Transaction;
+        config.factory = true;
+        assert!(config.validate().is_err());
+
+        config.factory = false;
+        config.abi_path = Some("does-not-exist.json".to_string());
+        assert!(config.validate().is_err());
+
+        let mode: ChronicleIndexingMode = serde_json::from_str("\"transaction\"").unwrap();
+        assert_eq!(mode, ChronicleIndexingMode::Transaction);
+        assert_eq!(
+            ChronicleIndexingMode::default(),
+            ChronicleIndexingMode::Event
+        );
+    }
+
+    #[test]
+    fn test_trace_mode_needs_valid_addresses_and_reads_the_trace_api() {
+        let mut config = indexer_config();
+        config.indexing_mode = ChronicleIndexingMode::Trace;
+        config.event_signature = String::new();
+        config.addresses = vec!["0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48".to_string()];
+        assert!(config.validate().is_ok());
+        assert_eq!(config.trace_api, TraceApi::Debug);
+
+        config.addresses.push("0x1234".to_string());
+        assert!(config.validate().is_err());
+
+        config.addresses.pop();
+        config.abi_path = Some("erc20.json".to_string());
+        assert!(config.validate().is_err());
+
+        let mode: ChronicleIndexingMode = serde_json::from_str("\"trace\"").unwrap();
+        assert_eq!(mode, ChronicleIndexingMode::Trace);
+        let trace_api: TraceApi = serde_json::from_str("\"parity\"").unwrap();
+        assert_eq!(trace_api, TraceApi::Parity);
+    }
+
+    #[test]
+    fn test_runtime_indexer_needs_no_address_but_valid_event_filters() {
+        let mut config = indexer_config();
+        config.state_machine = "RUNTIME".to_string();
+        config.rpc_url = "wss://rpc.polkadot.io".to_string();
+        config.address = String::new();
+        config.event_signature = String::new();
+        config.runtime_events = vec!["Balances.Transfer".to_string(), "Assets".to_string()];
+        assert!(config.validate().is_ok());
+
+        let filters = config.runtime_event_filters().unwrap();
+        assert_eq!(
+            filters[0],
+            RuntimeEventFilter {
+                pallet: "Balances".to_string(),
+                event: Some("Transfer".to_string()),
+            }
+        );
+        assert!(RuntimeEventFilter::any_matches(
+            &filters, "Assets", "Issued"
+        ));
+        assert!(!RuntimeEventFilter::any_matches(
+            &filters, "Balances", "Deposit"
+        ));
+        assert!(RuntimeEventFilter::any_matches(
+            &[],
+            "System",
+            "ExtrinsicSuccess"
+        ));
+
+        config.runtime_events.push("Balances.".to_string());
+        assert!(config.validate().is_err());
+
+        config.runtime_events.pop();
+        config.rpc_url = "https://rpc.polkadot.io".to_string();
+        assert!(config.validate().is_err());
+
+        config.rpc_url = "wss://rpc.polkadot.io".to_string();
+        config.indexing_mode = ChronicleIndexingMode::Transaction;
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_validate_keeps_a_pooled_connection_for_the_server() {
+        let mut config = Config {
+            name: None,
+            indexer: vec![indexer_config()],
+            server: ServerConfig {
+                server_url: "127.0.0.1:8080".to_string(),
+                rpc_url: None,
+            },
+            db_url: "postgres://localhost/chronicle".to_string(),
+            db_pool: DbPoolConfig::default(),
+        };
+        assert!(config.validate().is_ok());
+
+        config.db_pool.max_size = 1;
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_confirmations_accepts_depth_or_finality_tag() {
+        let depth: Confirmations = serde_json::from_str("12").unwrap();
+        assert_eq!(depth, Confirmations::Blocks(12));
+
+        let finalized: Confirmations = serde_json::from_str("\"finalized\"").unwrap();
+        assert_eq!(finalized, Confirmations::Tag(FinalityTag::Finalized));
+
+        assert!(serde_json::from_str::<Confirmations>("\"latest\"").is_err());
+        assert!(Confirmations::default().is_immediate());
+        assert!(!Confirmations::Tag(FinalityTag::Safe).is_immediate());
+    }
+}
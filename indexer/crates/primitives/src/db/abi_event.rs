@@ -0,0 +1,558 @@
+//! This module stores the events decoded from an ABI, one typed table per indexed event,
+//! and queries them back with filters, ordering and cursor pagination.
+use alloy::{dyn_abi::DynSolType, primitives::hex};
+use anyhow::{anyhow, bail};
+use async_trait::async_trait;
+use tokio_postgres::types::ToSql;
+
+use super::{entities::DB_VERSION, reorg::attach_journal_trigger};
+use crate::{
+    abi::{AbiColumnType, AbiColumnValue, AbiEventTable},
+    indexer::ChronicleEvent,
+    interfaces::ChronicleEventHandler,
+};
+
+/// This function creates the table of an ABI event if one does not exist already,
+/// with a column per event parameter after the log metadata
+pub async fn create_abi_event_table(
+    table: &AbiEventTable,
+    db_client: &mut tokio_postgres::Client,
+) -> Result<(), anyhow::Error> {
+    let table_name = format!("{}{DB_VERSION}", table.table_name);
+    let parameter_columns: String = table
+        .columns
+        .iter()
+        .map(|column| {
+            format!(
+                "\"{}\" {} NULL,\n",
+                column.name,
+                column.column_type.sql_type()
+            )
+        })
+        .collect();
+    let executable = format!(
+        "
+            CREATE TABLE IF NOT EXISTS {table_name} (
+                id                  SERIAL PRIMARY KEY,
+                chain_id            BIGINT NOT NULL,
+                address             VARCHAR NOT NULL,
+                block_number        BIGINT NOT NULL,
+                block_hash          VARCHAR NOT NULL,
+                transaction_hash    VARCHAR NOT NULL,
+                log_index           BIGINT NOT NULL,
+                block_timestamp     BIGINT NOT NULL,
+                {parameter_columns}
+                created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+                UNIQUE (chain_id, transaction_hash, log_index)
+            )
+        "
+    );
+    db_client.batch_execute(&executable).await?;
+    attach_journal_trigger(&table_name, db_client).await?;
+
+    Ok(())
+}
+
+/// This function decodes the event and stores it in the table of its ABI event,
+/// a log that was already stored is ignored
+pub async fn store_abi_event(
+    table: &AbiEventTable,
+    event: &ChronicleEvent,
+    db_client: &mut tokio_postgres::Client,
+) -> Result<(), anyhow::Error> {
+    let decoded = table.decode(event)?;
+
+    let metadata_count = 7;
+    let column_names: String = table
+        .columns
+        .iter()
+        .map(|column| format!(", \"{}\"", column.name))
+        .collect();
+    let placeholders: String = table
+        .columns
+        .iter()
+        .enumerate()
+        .map(|(index, column)| {
+            format!(
+                ", {}",
+                column.column_type.placeholder(metadata_count + index + 1)
+            )
+        })
+        .collect();
+    let executable = format!(
+        "
+            INSERT INTO {}{DB_VERSION} (chain_id, address, block_number, block_hash, transaction_hash, log_index, block_timestamp{column_names})
+            VALUES ($1, $2, $3, $4, $5, $6, $7{placeholders})
+            ON CONFLICT (chain_id, transaction_hash, log_index) DO NOTHING
+        ",
+        table.table_name
+    );
+
+    let chain_id = event.chain_id as i64;
+    let address = event.address.to_string();
+    let block_number = event.block_number as i64;
+    let block_hash = event.block_hash.to_string();
+    let transaction_hash = event.transaction_hash.to_string();
+    let log_index = event.log_index as i64;
+    let block_timestamp = event.block_timestamp as i64;
+    let mut params: Vec<&(dyn ToSql + Sync)> = vec![
+        &chain_id,
+        &address,
+        &block_number,
+        &block_hash,
+        &transaction_hash,
+        &log_index,
+        &block_timestamp,
+    ];
+    params.extend(decoded.iter().map(|value| match value {
+        AbiColumnValue::Text(text) => text as &(dyn ToSql + Sync),
+        AbiColumnValue::Boolean(boolean) => boolean,
+        AbiColumnValue::Bytes(bytes) => bytes,
+    }));
+
+    db_client.execute(&executable, &params).await?;
+
+    Ok(())
+}
+
+/// This handles the events of an [`AbiEventTable`], storing them decoded in its table
+#[derive(Debug, Clone)]
+pub struct AbiEventHandler {
+    pub table: AbiEventTable,
+}
+
+impl AbiEventHandler {
+    pub fn new(table: AbiEventTable) -> Self {
+        Self { table }
+    }
+}
+
+#[async_trait]
+impl ChronicleEventHandler for AbiEventHandler {
+    async fn handle(
+        &self,
+        event: &ChronicleEvent,
+        db_client: &mut tokio_postgres::Client,
+    ) -> Result<(), anyhow::Error> {
+        store_abi_event(&self.table, event, db_client).await
+    }
+}
+
+/// This is the largest page an ABI event query returns
+pub const MAX_ABI_EVENT_PAGE_SIZE: i64 = 1000;
+
+/// This is a column an ABI event table can be filtered and ordered by
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AbiQueryColumn {
+    pub name: String,
+    pub column_type: AbiColumnType,
+    /// Addresses are compared case-insensitively and can be filtered by a list
+    pub is_address: bool,
+}
+
+impl AbiQueryColumn {
+    /// Only numbers and text have an order, and a cursor value that round trips through text
+    pub fn is_orderable(&self) -> bool {
+        matches!(
+            self.column_type,
+            AbiColumnType::Numeric | AbiColumnType::Text
+        )
+    }
+
+    fn cast(&self) -> &'static str {
+        match self.column_type {
+            AbiColumnType::Numeric => "::TEXT::NUMERIC",
+            AbiColumnType::Boolean => "::TEXT::BOOLEAN",
+            _ => "::TEXT",
+        }
+    }
+}
+
+/// This function returns the columns of an ABI event table that can be queried,
+/// the log metadata first then the event parameters
+pub fn abi_event_query_columns(table: &AbiEventTable) -> Vec<AbiQueryColumn> {
+    let metadata = [
+        ("chain_id", AbiColumnType::Numeric, false),
+        ("address", AbiColumnType::Text, true),
+        ("block_number", AbiColumnType::Numeric, false),
+        ("block_hash", AbiColumnType::Text, false),
+        ("transaction_hash", AbiColumnType::Text, false),
+        ("log_index", AbiColumnType::Numeric, false),
+        ("block_timestamp", AbiColumnType::Numeric, false),
+    ]
+    .into_iter()
+    .map(|(name, column_type, is_address)| AbiQueryColumn {
+        name: name.to_string(),
+        column_type,
+        is_address,
+    });
+    let parameters = table.columns.iter().map(|column| AbiQueryColumn {
+        name: column.name.clone(),
+        column_type: column.column_type,
+        is_address: column.ty == DynSolType::Address,
+    });
+
+    metadata.chain(parameters).collect()
+}
+
+/// This is how a filter compares a column with its value
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AbiFilterOp {
+    Eq,
+    Gt,
+    Gte,
+    Lt,
+    Lte,
+    /// The column is one of a list of addresses
+    In,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AbiFilterValue {
+    Text(String),
+    Boolean(bool),
+    TextList(Vec<String>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AbiEventFilter {
+    pub column: String,
+    pub op: AbiFilterOp,
+    pub value: AbiFilterValue,
+}
+
+/// This is the position of a row in an ordered ABI event query, the value of the
+/// order column breaks ties on the row `id`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AbiEventCursor {
+    pub id: i32,
+    pub value: String,
+}
+
+impl AbiEventCursor {
+    /// This encodes the cursor into the opaque string handed to clients
+    pub fn encode(&self) -> String {
+        hex::encode(format!("{}:{}", self.id, self.value))
+    }
+
+    pub fn decode(cursor: &str) -> Result<Self, anyhow::Error> {
+        let invalid = || anyhow!("Invalid cursor `{cursor}`");
+        let raw = String::from_utf8(hex::decode(cursor).map_err(|_| invalid())?)
+            .map_err(|_| invalid())?;
+        let (id, value) = raw.split_once(':').ok_or_else(invalid)?;
+
+        Ok(Self {
+            id: id.parse().map_err(|_| invalid())?,
+            value: value.to_string(),
+        })
+    }
+}
+
+/// This is a page request over an ABI event table
+#[derive(Debug, Clone)]
+pub struct AbiEventQuery {
+    pub filters: Vec<AbiEventFilter>,
+    pub order_by: String,
+    pub descending: bool,
+    /// Rows are returned from right after this cursor
+    pub after: Option<AbiEventCursor>,
+    pub first: i64,
+}
+
+impl Default for AbiEventQuery {
+    fn default() -> Self {
+        Self {
+            filters: Vec::new(),
+            order_by: "block_number".to_string(),
+            descending: true,
+            after: None,
+            first: 100,
+        }
+    }
+}
+
+/// This is a stored ABI event, the parameter values are in column order
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AbiEventRow {
+    pub id: i32,
+    pub chain_id: i64,
+    pub address: String,
+    pub block_number: i64,
+    pub block_hash: String,
+    pub transaction_hash: String,
+    pub log_index: i64,
+    pub block_timestamp: i64,
+    /// Numbers, bytes and JSON are returned as text, bytes hex encoded
+    pub values: Vec<Option<AbiColumnValue>>,
+    pub cursor: AbiEventCursor,
+}
+
+/// This is a page of ABI events
+#[derive(Debug, Clone)]
+pub struct AbiEventPage {
+    pub rows: Vec<AbiEventRow>,
+    pub has_next_page: bool,
+}
+
+enum QueryParam {
+    Text(String),
+    Boolean(bool),
+    TextList(Vec<String>),
+    Id(i32),
+    Limit(i64),
+}
+
+/// This function builds the SQL of an ABI event query and the values it binds,
+/// filters and the order column are checked against the columns of the table
+fn build_abi_event_query(
+    table: &AbiEventTable,
+    query: &AbiEventQuery,
+) -> Result<(String, Vec<QueryParam>), anyhow::Error> {
+    let columns = abi_event_query_columns(table);
+    let find_column = |name: &str| {
+        columns
+            .iter()
+            .find(|column| column.name == name)
+            .ok_or_else(|| anyhow!("Unknown column `{name}` of {}", table.table_name))
+    };
+
+    let mut params = Vec::new();
+    let mut conditions = Vec::new();
+    for filter in &query.filters {
+        let column = find_column(&filter.column)?;
+        let name = format!("\"{}\"", column.name);
+        let comparison = match filter.op {
+            AbiFilterOp::Eq => "=",
+            AbiFilterOp::Gt => ">",
+            AbiFilterOp::Gte => ">=",
+            AbiFilterOp::Lt => "<",
+            AbiFilterOp::Lte => "<=",
+            AbiFilterOp::In => "IN",
+        };
+        let condition = match (&filter.op, &filter.value, column.column_type) {
+            (AbiFilterOp::In, AbiFilterValue::TextList(values), _) if column.is_address => {
+                params.push(QueryParam::TextList(
+                    values.iter().map(|value| value.to_lowercase()).collect(),
+                ));
+                format!("LOWER({name}) = ANY(${})", params.len())
+            }
+            (AbiFilterOp::Eq, AbiFilterValue::Text(value), AbiColumnType::Text) => {
+                params.push(QueryParam::Text(value.clone()));
+                match column.is_address {
+                    true => format!("LOWER({name}) = LOWER(${})", params.len()),
+                    false => format!("{name} = ${}", params.len()),
+                }
+            }
+            (AbiFilterOp::Eq, AbiFilterValue::Boolean(value), AbiColumnType::Boolean) => {
+                params.push(QueryParam::Boolean(*value));
+                format!("{name} = ${}", params.len())
+            }
+            (AbiFilterOp::In, ..) | (_, AbiFilterValue::TextList(_), _) => {
+                bail!(
+                    "Only address columns can be filtered by a list, not `{}`",
+                    column.name
+                )
+            }
+            (_, AbiFilterValue::Text(value), AbiColumnType::Numeric) => {
+                params.push(QueryParam::Text(value.clone()));
+                format!("{name} {comparison} ${}::TEXT::NUMERIC", params.len())
+            }
+            _ => bail!("`{}` can not be filtered with {:?}", column.name, filter.op),
+        };
+        conditions.push(condition);
+    }
+
+    let order_column = find_column(&query.order_by)?;
+    if !order_column.is_orderable() {
+        bail!("Events can not be ordered by `{}`", order_column.name);
+    }
+    let order_name = format!("\"{}\"", order_column.name);
+    let direction = if query.descending { "DESC" } else { "ASC" };
+    if let Some(after) = &query.after {
+        params.push(QueryParam::Text(after.value.clone()));
+        params.push(QueryParam::Id(after.id));
+        conditions.push(format!(
+            "({order_name}, id) {} (${}{}, ${})",
+            if query.descending { "<" } else { ">" },
+            params.len() - 1,
+            order_column.cast(),
+            params.len()
+        ));
+    }
+
+    let parameter_columns: String = table
+        .columns
+        .iter()
+        .map(|column| match column.column_type {
+            AbiColumnType::Numeric | AbiColumnType::Jsonb => format!(", \"{}\"::TEXT", column.name),
+            AbiColumnType::Bytea => format!(", '0x' || ENCODE(\"{}\", 'hex')", column.name),
+            _ => format!(", \"{}\"", column.name),
+        })
+        .collect();
+    let where_clause = match conditions.is_empty() {
+        true => String::new(),
+        false => format!("WHERE {}", conditions.join(" AND ")),
+    };
+    // One extra row is fetched to tell whether there is a next page
+    params.push(QueryParam::Limit(
+        query.first.clamp(1, MAX_ABI_EVENT_PAGE_SIZE) + 1,
+    ));
+    let executable = format!(
+        "
+            SELECT id, chain_id, address, block_number, block_hash, transaction_hash, log_index, block_timestamp,
+                {order_name}::TEXT{parameter_columns}
+            FROM {}{DB_VERSION}
+            {where_clause}
+            ORDER BY {order_name} {direction}, id {direction}
+            LIMIT ${}
+        ",
+        table.table_name,
+        params.len()
+    );
+
+    Ok((executable, params))
+}
+
+/// This function returns a page of the events stored in an ABI event table
+pub async fn query_abi_events(
+    table: &AbiEventTable,
+    query: &AbiEventQuery,
+    db_client: &mut tokio_postgres::Client,
+) -> Result<AbiEventPage, anyhow::Error> {
+    let (executable, params) = build_abi_event_query(table, query)?;
+    let params: Vec<&(dyn ToSql + Sync)> = params
+        .iter()
+        .map(|param| match param {
+            QueryParam::Text(text) => text as &(dyn ToSql + Sync),
+            QueryParam::Boolean(boolean) => boolean,
+            QueryParam::TextList(texts) => texts,
+            QueryParam::Id(id) => id,
+            QueryParam::Limit(limit) => limit,
+        })
+        .collect();
+
+    let result = db_client.query(&executable, &params).await?;
+
+    let page_size = query.first.clamp(1, MAX_ABI_EVENT_PAGE_SIZE) as usize;
+    let has_next_page = result.len() > page_size;
+    let rows = result
+        .iter()
+        .take(page_size)
+        .map(|record| {
+            let id: i32 = record.get(0);
+            let values = table
+                .columns
+                .iter()
+                .enumerate()
+                .map(|(index, column)| match column.column_type {
+                    AbiColumnType::Boolean => record
+                        .get::<_, Option<bool>>(9 + index)
+                        .map(AbiColumnValue::Boolean),
+                    _ => record
+                        .get::<_, Option<String>>(9 + index)
+                        .map(AbiColumnValue::Text),
+                })
+                .collect();
+
+            AbiEventRow {
+                id,
+                chain_id: record.get(1),
+                address: record.get(2),
+                block_number: record.get(3),
+                block_hash: record.get(4),
+                transaction_hash: record.get(5),
+                log_index: record.get(6),
+                block_timestamp: record.get(7),
+                values,
+                cursor: AbiEventCursor {
+                    id,
+                    value: record.get::<_, Option<String>>(8).unwrap_or_default(),
+                },
+            }
+        })
+        .collect();
+
+    Ok(AbiEventPage {
+        rows,
+        has_next_page,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::abi::{abi_event_table_name, resolve_event};
+
+    fn transfer_table() -> AbiEventTable {
+        let event = resolve_event(
+            "Transfer(address indexed from, address indexed to, uint256 value)",
+            None,
+        )
+        .unwrap();
+        AbiEventTable::new(abi_event_table_name("transfers"), event).unwrap()
+    }
+
+    #[test]
+    fn test_build_abi_event_query_binds_filters_and_cursor() {
+        let query = AbiEventQuery {
+            filters: vec![
+                AbiEventFilter {
+                    column: "from".to_string(),
+                    op: AbiFilterOp::In,
+                    value: AbiFilterValue::TextList(vec!["0xAB".to_string()]),
+                },
+                AbiEventFilter {
+                    column: "value".to_string(),
+                    op: AbiFilterOp::Gte,
+                    value: AbiFilterValue::Text("1000".to_string()),
+                },
+            ],
+            order_by: "value".to_string(),
+            descending: false,
+            after: Some(AbiEventCursor {
+                id: 7,
+                value: "1500".to_string(),
+            }),
+            first: 10,
+        };
+
+        let (executable, params) = build_abi_event_query(&transfer_table(), &query).unwrap();
+        assert!(executable.contains("LOWER(\"from\") = ANY($1)"));
+        assert!(executable.contains("\"value\" >= $2::TEXT::NUMERIC"));
+        assert!(executable.contains("(\"value\", id) > ($3::TEXT::NUMERIC, $4)"));
+        assert!(executable.contains("ORDER BY \"value\" ASC, id ASC"));
+        assert!(matches!(params[0], QueryParam::TextList(ref list) if list == &["0xab"]));
+        assert!(matches!(params[4], QueryParam::Limit(11)));
+    }
+
+    #[test]
+    fn test_build_abi_event_query_rejects_unknown_or_mismatched_columns() {
+        let table = transfer_table();
+        let unknown = AbiEventQuery {
+            order_by: "amount".to_string(),
+            ..Default::default()
+        };
+        assert!(build_abi_event_query(&table, &unknown).is_err());
+
+        let list_on_number = AbiEventQuery {
+            filters: vec![AbiEventFilter {
+                column: "value".to_string(),
+                op: AbiFilterOp::In,
+                value: AbiFilterValue::TextList(vec!["1".to_string()]),
+            }],
+            ..Default::default()
+        };
+        assert!(build_abi_event_query(&table, &list_on_number).is_err());
+    }
+
+    #[test]
+    fn test_cursor_round_trips() {
+        let cursor = AbiEventCursor {
+            id: 42,
+            value: "0xdead:beef".to_string(),
+        };
+
+        assert_eq!(AbiEventCursor::decode(&cursor.encode()).unwrap(), cursor);
+        assert!(AbiEventCursor::decode("not a cursor").is_err());
+    }
+}
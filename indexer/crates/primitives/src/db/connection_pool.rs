@@ -0,0 +1,66 @@
+//! This module holds the pool of database connections shared by the server and the indexers.
+//! A pooled client derefs to a [`tokio_postgres::Client`], so it is passed to the entity
+//! functions as `&mut db_client`, and it goes back to the pool when it is dropped.
+//! Clients are taken for a request or a block write, not held while waiting on the chain.
+use std::time::Duration;
+
+use deadpool_postgres::{Manager, ManagerConfig, RecyclingMethod, Runtime};
+use tokio_postgres::NoTls;
+
+use crate::DbPoolConfig;
+
+pub type DbPool = deadpool_postgres::Pool;
+
+pub type PooledDbClient = deadpool_postgres::Object;
+
+/// This resets the session of a returned connection before it is handed out again.
+/// `DISCARD ALL` can not run inside a transaction, so a connection returned in the middle of a
+/// block write, by a task cancelled on shutdown, fails to recycle and is closed, which rolls the
+/// write back. Connections dropped by the database fail it too and are replaced.
+const RESET_CONNECTION_SQL: &str = "DISCARD ALL";
+
+/// This function creates the connection pool of the database at `db_url`,
+/// connections are opened when they are first needed
+pub fn create_db_pool(db_url: &str, config: &DbPoolConfig) -> Result<DbPool, anyhow::Error> {
+    let pg_config = db_url.parse::<tokio_postgres::Config>()?;
+    let manager = Manager::from_config(
+        pg_config,
+        NoTls,
+        ManagerConfig {
+            recycling_method: RecyclingMethod::Custom(RESET_CONNECTION_SQL.to_string()),
+        },
+    );
+
+    let pool = DbPool::builder(manager)
+        .max_size(config.max_size)
+        .wait_timeout(Some(Duration::from_millis(config.wait_timeout_ms)))
+        .create_timeout(Some(Duration::from_millis(config.create_timeout_ms)))
+        .recycle_timeout(Some(Duration::from_millis(config.recycle_timeout_ms)))
+        .runtime(Runtime::Tokio1)
+        .build()?;
+
+    Ok(pool)
+}
+
+/// This function takes a connection from the pool, waiting for one to be returned when all are in use
+pub async fn get_db_client(db_pool: &DbPool) -> Result<PooledDbClient, anyhow::Error> {
+    Ok(db_pool.get().await?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_create_db_pool_applies_the_config() {
+        let config = DbPoolConfig {
+            max_size: 4,
+            ..Default::default()
+        };
+        let pool = create_db_pool("postgres://chronicle@localhost/chronicle", &config).unwrap();
+
+        assert_eq!(pool.status().max_size, 4);
+        assert_eq!(pool.status().size, 0);
+        assert!(create_db_pool("not a url", &config).is_err());
+    }
+}
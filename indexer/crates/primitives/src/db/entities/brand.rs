@@ -1,5 +1,4 @@
-use super::DB_VERSION;
-use crate::entity_record::BrandRecord;
+use crate::{db::entity::ChainCount, entity_record::BrandRecord};
 
 pub const BRAND_TABLE_NAME: &str = "brand";
 
@@ -8,25 +7,12 @@ pub const BRAND_TABLE_NAME: &str = "brand";
 pub async fn create_brand_table(
     db_client: &mut tokio_postgres::Client,
 ) -> Result<(), anyhow::Error> {
-    let executable = format!(
-        "
-            CREATE TABLE IF NOT EXISTS {BRAND_TABLE_NAME}{DB_VERSION} (
-                id              SERIAL PRIMARY KEY,
-                name         VARCHAR NULL,
-                main_account    VARCHAR NULL,
-                online_presence VARCHAR NULL,
-                brand_protocol_id          VARCHAR NULL,
-                onboarding_manager          VARCHAR NULL,
-                created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
-            )
-        "
-    );
-    db_client.batch_execute(&executable).await?;
-    Ok(())
+    BrandRecord::create_table(db_client).await
 }
 
 /// This function create a new brand enity in the database
 pub async fn create_brand_if_does_not_exist(
+    chain_id: i64,
     name: String,
     main_account: String,
     online_presence: String,
@@ -34,191 +20,79 @@ pub async fn create_brand_if_does_not_exist(
     onboarding_manager: String,
     db_client: &mut tokio_postgres::Client,
 ) -> Result<(), anyhow::Error> {
-    let executable = format!(
-        "
-            INSERT INTO {BRAND_TABLE_NAME}{DB_VERSION} (name, main_account, online_presence, brand_protocol_id, onboarding_manager)
-            VALUES ($1, $2, $3, $4, $5)
-        "
-    );
-
-    if check_brand_exists_by_brand_id(brand_protocol_id.clone(), db_client).await? {
+    if BrandRecord::exists_by_brand_protocol_id(&brand_protocol_id, Some(chain_id), db_client)
+        .await?
+    {
         return Ok(());
     }
 
-    db_client
-        .execute(
-            &executable,
-            &[
-                &name,
-                &main_account,
-                &online_presence,
-                &brand_protocol_id,
-                &onboarding_manager,
-            ],
-        )
-        .await?;
+    BrandRecord {
+        chain_id,
+        brand_name: name,
+        main_account,
+        online_presence,
+        brand_protocol_id,
+        onboarding_manager,
+        ..Default::default()
+    }
+    .insert(db_client)
+    .await?;
 
     Ok(())
 }
 
 // This function returns all the count of brand entities in the database
 pub async fn get_total_count_brands(
+    chain_id: Option<i64>,
     db_client: &mut tokio_postgres::Client,
 ) -> Result<i64, anyhow::Error> {
-    let executable = format!(
-        "
-            SELECT COUNT(*)
-            FROM {BRAND_TABLE_NAME}{DB_VERSION}
-        "
-    );
-
-    let result = db_client.query(&executable, &[]).await?;
-
-    Ok(result[0].get(0))
+    BrandRecord::count(chain_id, db_client).await
 }
 
-async fn check_brand_exists_by_brand_id(
-    brand_protocol_id: String,
+// This function returns the count of brand entities of each chain
+pub async fn get_brand_count_per_chain(
     db_client: &mut tokio_postgres::Client,
-) -> Result<bool, anyhow::Error> {
-    let executable = format!(
-        "
-            SELECT 1
-            FROM {BRAND_TABLE_NAME}{DB_VERSION}
-            WHERE brand_protocol_id = $1
-        "
-    );
-
-    let result = db_client.query(&executable, &[&brand_protocol_id]).await?;
-
-    Ok(!result.is_empty())
+) -> Result<Vec<ChainCount>, anyhow::Error> {
+    BrandRecord::count_per_chain(db_client).await
 }
 
 pub async fn check_brand_exists_by_main_account(
     main_account: String,
+    chain_id: Option<i64>,
     db_client: &mut tokio_postgres::Client,
 ) -> Result<bool, anyhow::Error> {
-    let executable = format!(
-        "
-            SELECT 1
-            FROM {BRAND_TABLE_NAME}{DB_VERSION}
-            WHERE main_account = $1
-        "
-    );
-
-    let result = db_client.query(&executable, &[&main_account]).await?;
-
-    Ok(!result.is_empty())
+    BrandRecord::exists_by_main_account(&main_account, chain_id, db_client).await
 }
 
 pub async fn query_brand_by_id(
     brand_id: String,
+    chain_id: Option<i64>,
     db_client: &mut tokio_postgres::Client,
 ) -> Result<BrandRecord, anyhow::Error> {
-    let executable = format!(
-        "
-            SELECT * 
-            FROM {BRAND_TABLE_NAME}{DB_VERSION} 
-            WHERE brand_protocol_id = $1;
-        "
-    );
-
-    let result = db_client.query_one(&executable, &[&brand_id]).await?;
-
-    let brand_record = BrandRecord {
-        brand_name: result.get(1),
-        main_account: result.get(2),
-        online_presence: result.get(3),
-        brand_protocol_id: result.get(4),
-        onboarding_manager: result.get(5),
-        create_at: result.get(6),
-    };
-
-    Ok(brand_record)
+    BrandRecord::query_one_by_brand_protocol_id(&brand_id, chain_id, db_client).await
 }
 
 pub async fn query_brand_by_main_account(
     main_account: String,
+    chain_id: Option<i64>,
     db_client: &mut tokio_postgres::Client,
 ) -> Result<BrandRecord, anyhow::Error> {
-    let executable = format!(
-        "
-            SELECT * 
-            FROM {BRAND_TABLE_NAME}{DB_VERSION} 
-            WHERE main_account = $1;
-        "
-    );
-
-    let result = db_client.query_one(&executable, &[&main_account]).await?;
-
-    let brand_record = BrandRecord {
-        brand_name: result.get(1),
-        main_account: result.get(2),
-        online_presence: result.get(3),
-        brand_protocol_id: result.get(4),
-        onboarding_manager: result.get(5),
-        create_at: result.get(6),
-    };
-
-    Ok(brand_record)
+    BrandRecord::query_one_by_main_account(&main_account, chain_id, db_client).await
 }
 
 pub async fn query_brand_by_name(
     brand_name: String,
+    chain_id: Option<i64>,
     db_client: &mut tokio_postgres::Client,
 ) -> Result<BrandRecord, anyhow::Error> {
-    let executable = format!(
-        "
-            SELECT *
-            FROM {BRAND_TABLE_NAME}{DB_VERSION} 
-            WHERE name = $1;
-        "
-    );
-
-    let result = db_client.query_one(&executable, &[&brand_name]).await?;
-
-    let brand_record = BrandRecord {
-        brand_name: result.get(1),
-        main_account: result.get(2),
-        online_presence: result.get(3),
-        brand_protocol_id: result.get(4),
-        onboarding_manager: result.get(5),
-        create_at: result.get(6),
-    };
-
-    Ok(brand_record)
+    BrandRecord::query_one_by_brand_name(&brand_name, chain_id, db_client).await
 }
 
 pub async fn query_all_brands_paginated(
     db_client: &mut tokio_postgres::Client,
+    chain_id: Option<i64>,
     page_number: i64,
     page_size: i64,
 ) -> Result<Vec<BrandRecord>, anyhow::Error> {
-    let executable = format!(
-        "
-            SELECT * 
-            FROM {BRAND_TABLE_NAME}{DB_VERSION}
-            ORDER BY id DESC
-            LIMIT $1 OFFSET $2;
-        "
-    );
-
-    let offest = (page_number - 1) * page_size;
-
-    let result = db_client.query(&executable, &[&page_size, &offest]).await?;
-    let mut brand_record_buffer = Vec::new();
-
-    for row in result {
-        let brand_record = BrandRecord {
-            brand_name: row.get(1),
-            main_account: row.get(2),
-            online_presence: row.get(3),
-            brand_protocol_id: row.get(4),
-            onboarding_manager: row.get(5),
-            create_at: row.get(6),
-        };
-        brand_record_buffer.push(brand_record);
-    }
-
-    Ok(brand_record_buffer)
+    BrandRecord::query_paginated(chain_id, db_client, page_number, page_size).await
 }
@@ -0,0 +1,85 @@
+use crate::entity_record::GatewayRecord;
+
+pub const GATEWAY_TABLE_NAME: &str = "gateway";
+
+/// This stores the gateway tokens as a single comma separated column
+pub mod comma_separated {
+    pub fn encode(tokens: &[String]) -> String {
+        tokens.join(",")
+    }
+
+    pub fn decode(tokens: String) -> Vec<String> {
+        tokens
+            .split(',')
+            .filter(|token| !token.is_empty())
+            .map(String::from)
+            .collect()
+    }
+}
+
+/// This function is used to create a new gateway table in the database
+/// if one does not exist already
+pub async fn create_gateway_table(
+    db_client: &mut tokio_postgres::Client,
+) -> Result<(), anyhow::Error> {
+    GatewayRecord::create_table(db_client).await
+}
+
+/// This function creates a new gateway entity in the database,
+/// a gateway that was already stored is ignored
+#[allow(clippy::too_many_arguments)]
+pub async fn create_gateway_if_does_not_exist(
+    chain_id: i64,
+    gateway_address: String,
+    factory_address: String,
+    owner: String,
+    confirmation_block_height: String,
+    tokens: Vec<String>,
+    salt: String,
+    block_number: String,
+    transaction_hash: String,
+    db_client: &mut tokio_postgres::Client,
+) -> Result<(), anyhow::Error> {
+    GatewayRecord {
+        chain_id,
+        gateway_address,
+        factory_address,
+        owner,
+        confirmation_block_height,
+        tokens,
+        salt,
+        block_number,
+        transaction_hash,
+        ..Default::default()
+    }
+    .insert(db_client)
+    .await?;
+
+    Ok(())
+}
+
+/// This function returns the gateways deployed by a factory, in deployment order
+pub async fn query_gateways_by_factory(
+    factory_address: String,
+    chain_id: Option<i64>,
+    db_client: &mut tokio_postgres::Client,
+) -> Result<Vec<GatewayRecord>, anyhow::Error> {
+    GatewayRecord::query_by_factory_address(&factory_address, chain_id, db_client).await
+}
+
+/// This function returns the gateway deployed at `gateway_address`
+pub async fn query_gateway_by_address(
+    gateway_address: String,
+    chain_id: Option<i64>,
+    db_client: &mut tokio_postgres::Client,
+) -> Result<GatewayRecord, anyhow::Error> {
+    GatewayRecord::query_one_by_gateway_address(&gateway_address, chain_id, db_client).await
+}
+
+pub async fn check_gateway_exist_by_address(
+    gateway_address: String,
+    chain_id: Option<i64>,
+    db_client: &mut tokio_postgres::Client,
+) -> Result<bool, anyhow::Error> {
+    GatewayRecord::exists_by_gateway_address(&gateway_address, chain_id, db_client).await
+}
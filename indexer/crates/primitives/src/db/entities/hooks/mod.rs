@@ -1,4 +1,77 @@
 pub mod on_cross_brand_redemption;
 pub mod on_current_pool_state;
+pub mod on_gateway_deployed;
+pub mod on_internal_transfer;
+pub mod on_payment_completed;
 pub mod on_register_brand;
 pub mod on_reward_created_successfullly;
+pub mod on_token_whitelist_changed;
+pub mod on_treasury_withdrawal;
+
+use async_trait::async_trait;
+
+use crate::{indexer::ChronicleEvent, interfaces::ChronicleEventHandler};
+
+/// This wraps an entity hook into a [`ChronicleEventHandler`], so it can be registered
+macro_rules! hook_handler {
+    ($(#[$doc:meta])* $handler:ident => $hook:path) => {
+        $(#[$doc])*
+        pub struct $handler;
+
+        #[async_trait]
+        impl ChronicleEventHandler for $handler {
+            async fn handle(
+                &self,
+                event: &ChronicleEvent,
+                db_client: &mut tokio_postgres::Client,
+            ) -> Result<(), anyhow::Error> {
+                $hook(event, db_client).await
+            }
+        }
+    };
+}
+
+hook_handler!(
+    /// This handles the `rewardMadeConversationWithOtherReward` event of the OpenReward diamond
+    CrossBrandRedemptionHandler => on_cross_brand_redemption::on_cross_brand_redemption
+);
+hook_handler!(
+    /// This handles the `currentPoolsState` event of the OpenReward diamond
+    CurrentPoolStateHandler => on_current_pool_state::on_current_pool_state
+);
+hook_handler!(
+    /// This handles the `registerBrand` event of the OpenReward diamond
+    RegisterBrandHandler => on_register_brand::on_register_brand
+);
+hook_handler!(
+    /// This handles the `fungibleRewardsCreatedSuccessfully` event of the OpenReward diamond
+    RewardCreatedHandler => on_reward_created_successfullly::on_reward_creation
+);
+hook_handler!(
+    /// This handles the `PaymentCompleted` event of an OpenGateway
+    PaymentCompletedHandler => on_payment_completed::on_payment_completed
+);
+hook_handler!(
+    /// This handles the `GatewayDeployed` event of the OpenGatewayFactory
+    GatewayDeployedHandler => on_gateway_deployed::on_gateway_deployed
+);
+hook_handler!(
+    /// This handles the `TokenWhitelisted` event of an OpenGateway
+    TokenWhitelistedHandler => on_token_whitelist_changed::on_token_whitelisted
+);
+hook_handler!(
+    /// This handles the `TokenDelisted` event of an OpenGateway
+    TokenDelistedHandler => on_token_whitelist_changed::on_token_delisted
+);
+hook_handler!(
+    /// This handles the `NativeWithdrawn` event of an OpenGateway
+    NativeWithdrawnHandler => on_treasury_withdrawal::on_native_withdrawn
+);
+hook_handler!(
+    /// This handles the `WithdrawalFailed` event of an OpenGateway
+    WithdrawalFailedHandler => on_treasury_withdrawal::on_withdrawal_failed
+);
+hook_handler!(
+    /// This handles the `InternalTransfer` events the trace indexer reads from call traces
+    InternalTransferHandler => on_internal_transfer::on_internal_transfer
+);
@@ -53,6 +53,7 @@ pub async fn on_cross_brand_redemption(
     let redeemed_at = event.block_timestamp.clone();
 
     create_redemption(
+        event.chain_id as i64,
         source_token.to_string(),
         dest_token.to_string(),
         source_amount.to_string(),
@@ -60,6 +61,7 @@ pub async fn on_cross_brand_redemption(
         user_address.to_string(),
         onchain_tx_hash.to_string(),
         redeemed_at.to_string(),
+        event.log_index.to_string(),
         db_client,
     )
     .await?;
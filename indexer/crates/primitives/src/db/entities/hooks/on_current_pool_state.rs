@@ -72,9 +72,16 @@ pub async fn on_current_pool_state(
         .expect("Failed to decode the reward address of pool");
 
     // Operating on pool-one
-    if check_pool_exist_by_reward_address(reward_one_address.to_string(), db_client).await? {
+    if check_pool_exist_by_reward_address(
+        reward_one_address.to_string(),
+        Some(event.chain_id as i64),
+        db_client,
+    )
+    .await?
+    {
         // just make update
         update_pool_reward_and_me_amount(
+            event.chain_id as i64,
             reward_one_address.to_string(),
             current_amount_of_me_token_reward_one.to_string(),
             current_amount_of_reward_token_reward_one.to_string(),
@@ -84,6 +91,7 @@ pub async fn on_current_pool_state(
     } else {
         // create new pool with default data
         create_pool_if_does_not_exist(
+            event.chain_id as i64,
             Address::ZERO.to_string(),
             reward_one_address.to_string(),
             Address::ZERO.to_string(),
@@ -96,9 +104,16 @@ pub async fn on_current_pool_state(
     }
 
     // Operating on pool-two
-    if check_pool_exist_by_reward_address(reward_two_address.to_string(), db_client).await? {
+    if check_pool_exist_by_reward_address(
+        reward_two_address.to_string(),
+        Some(event.chain_id as i64),
+        db_client,
+    )
+    .await?
+    {
         // just make update
         update_pool_reward_and_me_amount(
+            event.chain_id as i64,
             reward_two_address.to_string(),
             current_amount_of_me_token_reward_two.to_string(),
             current_amount_of_reward_token_reward_two.to_string(),
@@ -108,6 +123,7 @@ pub async fn on_current_pool_state(
     } else {
         // create new pool with default data
         create_pool_if_does_not_exist(
+            event.chain_id as i64,
             Address::ZERO.to_string(),
             reward_two_address.to_string(),
             Address::ZERO.to_string(),
@@ -0,0 +1,172 @@
+use alloy::{
+    dyn_abi::DynSolType,
+    hex::ToHexExt,
+    primitives::{Address, B256},
+};
+
+use crate::{
+    db::entities::{
+        gateway::{create_gateway_if_does_not_exist, create_gateway_table},
+        token_whitelist::{
+            create_token_whitelist_tables, record_token_whitelist_change, TokenListAction,
+        },
+    },
+    indexer::ChronicleEvent,
+    utils::decode_event,
+};
+
+/// This is a decoded `GatewayDeployed(address indexed gateway, address indexed owner,
+/// uint40 confirmationBlockHeight, address[] tokens, bytes32 salt)` event
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GatewayDeployed {
+    pub gateway: Address,
+    pub owner: Address,
+    pub confirmation_block_height: u64,
+    pub tokens: Vec<Address>,
+    pub salt: B256,
+}
+
+/// This function decodes the `GatewayDeployed` event of the OpenGatewayFactory
+pub fn decode_gateway_deployed(event: &ChronicleEvent) -> Result<GatewayDeployed, anyhow::Error> {
+    let decoded_event = decode_event(
+        event.topics.clone(),
+        event.data.clone(),
+        DynSolType::Tuple(vec![
+            DynSolType::Uint(40),
+            DynSolType::Array(Box::new(DynSolType::Address)),
+            DynSolType::FixedBytes(32),
+        ]),
+        vec![DynSolType::Address, DynSolType::Address],
+    )?;
+
+    let gateway = decoded_event.indexed[0]
+        .as_address()
+        .ok_or_else(|| anyhow::anyhow!("Failed to decode gateway"))?;
+    let owner = decoded_event.indexed[1]
+        .as_address()
+        .ok_or_else(|| anyhow::anyhow!("Failed to decode owner"))?;
+    let confirmation_block_height = decoded_event.body[0]
+        .as_uint()
+        .ok_or_else(|| anyhow::anyhow!("Failed to decode confirmation_block_height"))?
+        .0
+        .to::<u64>();
+    let tokens = decoded_event.body[1]
+        .as_array()
+        .ok_or_else(|| anyhow::anyhow!("Failed to decode tokens"))?
+        .iter()
+        .map(|token| {
+            token
+                .as_address()
+                .ok_or_else(|| anyhow::anyhow!("Failed to decode token"))
+        })
+        .collect::<Result<Vec<Address>, _>>()?;
+    let salt = decoded_event.body[2]
+        .as_fixed_bytes()
+        .ok_or_else(|| anyhow::anyhow!("Failed to decode salt"))?
+        .0;
+
+    Ok(GatewayDeployed {
+        gateway,
+        owner,
+        confirmation_block_height,
+        tokens,
+        salt: B256::from_slice(salt),
+    })
+}
+
+/// On gateway deployed, the following action would be made
+/// 1. gateway entity record would be created for the factory that emitted the event
+/// 2. the tokens the gateway was deployed with open its token whitelist history
+pub async fn on_gateway_deployed(
+    event: &ChronicleEvent,
+    db_client: &mut tokio_postgres::Client,
+) -> Result<(), anyhow::Error> {
+    // ====================================
+    // Operations for the Gateway Entity
+    // ====================================
+    create_gateway_table(db_client).await?;
+
+    let deployment = decode_gateway_deployed(event)?;
+
+    create_gateway_if_does_not_exist(
+        event.chain_id as i64,
+        deployment.gateway.to_string(),
+        event.address.to_string(),
+        deployment.owner.to_string(),
+        deployment.confirmation_block_height.to_string(),
+        deployment
+            .tokens
+            .iter()
+            .map(|token| token.to_string())
+            .collect(),
+        deployment.salt.encode_hex_with_prefix(),
+        event.block_number.to_string(),
+        event.transaction_hash.to_string(),
+        db_client,
+    )
+    .await?;
+
+    // ====================================
+    // Operations for the Token Whitelist Entity
+    // ====================================
+    create_token_whitelist_tables(db_client).await?;
+
+    for token in &deployment.tokens {
+        record_token_whitelist_change(
+            event.chain_id as i64,
+            deployment.gateway.to_string(),
+            token.to_string(),
+            TokenListAction::Whitelisted,
+            event.block_timestamp.to_string(),
+            event.block_number as i64,
+            event.transaction_hash.to_string(),
+            event.log_index as i64,
+            db_client,
+        )
+        .await?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy::{
+        dyn_abi::DynSolValue,
+        primitives::{address, b256, U256},
+    };
+
+    use super::*;
+    use crate::config::EVENT_SIX_SIGNATURE;
+
+    #[test]
+    fn test_decode_gateway_deployed() {
+        let gateway = address!("88da6bf26964af9d7eed9e03e53415d37aa96045");
+        let owner = address!("d8da6bf26964af9d7eed9e03e53415d37aa96045");
+        let usdc = address!("a0b86991c6218b36c1d19d4a2e9eb0ce3606eb48");
+        let salt = b256!("00000000000000000000000000000000000000000000000000000000000000aa");
+
+        let data = DynSolValue::Tuple(vec![
+            DynSolValue::Uint(U256::from(12u64), 40),
+            DynSolValue::Array(vec![
+                DynSolValue::Address(Address::ZERO),
+                DynSolValue::Address(usdc),
+            ]),
+            DynSolValue::FixedBytes(salt, 32),
+        ])
+        .abi_encode_params();
+
+        let event = ChronicleEvent {
+            topics: vec![EVENT_SIX_SIGNATURE, gateway.into_word(), owner.into_word()],
+            data: data.into(),
+            ..Default::default()
+        };
+
+        let deployment = decode_gateway_deployed(&event).unwrap();
+        assert_eq!(deployment.gateway, gateway);
+        assert_eq!(deployment.owner, owner);
+        assert_eq!(deployment.confirmation_block_height, 12);
+        assert_eq!(deployment.tokens, vec![Address::ZERO, usdc]);
+        assert_eq!(deployment.salt, salt);
+    }
+}
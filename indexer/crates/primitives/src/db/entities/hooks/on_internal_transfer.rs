@@ -0,0 +1,153 @@
+use alloy::{
+    dyn_abi::DynSolType,
+    primitives::{Address, U256},
+};
+
+use crate::{
+    db::entities::{
+        gateway::check_gateway_exist_by_address,
+        treasury::{
+            create_treasury_entry_if_does_not_exist, create_treasury_ledger_table,
+            TreasuryEntryType,
+        },
+    },
+    indexer::ChronicleEvent,
+    utils::decode_event,
+};
+
+/// This is a decoded `InternalTransfer(address indexed from, address indexed to, uint256 value,
+/// bool withCalldata)` event, the native ETH moved by a call of a traced transaction
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InternalTransfer {
+    pub from: Address,
+    pub to: Address,
+    pub value: U256,
+    /// This is set when the call ran a function of `to`, rather than only sending it ETH
+    pub with_calldata: bool,
+}
+
+/// This function decodes the `InternalTransfer` events of the trace indexer
+pub fn decode_internal_transfer(event: &ChronicleEvent) -> Result<InternalTransfer, anyhow::Error> {
+    let decoded_event = decode_event(
+        event.topics.clone(),
+        event.data.clone(),
+        DynSolType::Tuple(vec![DynSolType::Uint(256), DynSolType::Bool]),
+        vec![DynSolType::Address, DynSolType::Address],
+    )?;
+
+    Ok(InternalTransfer {
+        from: decoded_event.indexed[0]
+            .as_address()
+            .ok_or_else(|| anyhow::anyhow!("Failed to decode from"))?,
+        to: decoded_event.indexed[1]
+            .as_address()
+            .ok_or_else(|| anyhow::anyhow!("Failed to decode to"))?,
+        value: decoded_event.body[0]
+            .as_uint()
+            .ok_or_else(|| anyhow::anyhow!("Failed to decode value"))?
+            .0,
+        with_calldata: decoded_event.body[1]
+            .as_bool()
+            .ok_or_else(|| anyhow::anyhow!("Failed to decode withCalldata"))?,
+    })
+}
+
+/// On internal transfer, the following action would be made
+/// 1. native ETH sent to a gateway without calldata, by a contract or a transaction, is credited
+///    to the treasury ledger of the gateway.
+///    The ETH a gateway sends is debited on `NativeWithdrawn`, and a payment made by calling the
+///    gateway is credited on `PaymentCompleted`, so neither is credited here
+pub async fn on_internal_transfer(
+    event: &ChronicleEvent,
+    db_client: &mut tokio_postgres::Client,
+) -> Result<(), anyhow::Error> {
+    let transfer = decode_internal_transfer(event)?;
+    if transfer.to != event.address || transfer.with_calldata {
+        return Ok(());
+    }
+    if !check_gateway_exist_by_address(
+        event.address.to_string(),
+        Some(event.chain_id as i64),
+        db_client,
+    )
+    .await?
+    {
+        return Ok(());
+    }
+
+    // ====================================
+    // Operations for the Treasury Ledger Entity
+    // ====================================
+    create_treasury_ledger_table(db_client).await?;
+
+    // The log index of an internal transfer is past the logs of the transaction,
+    // so the entry can not collide with the entry of an event
+    create_treasury_entry_if_does_not_exist(
+        event.chain_id as i64,
+        event.address.to_string(),
+        Address::ZERO.to_string(),
+        TreasuryEntryType::Credit,
+        transfer.value.to_string(),
+        transfer.from.to_string(),
+        event.block_number as i64,
+        event.block_timestamp as i64,
+        event.transaction_hash.to_string(),
+        event.log_index as i64,
+        db_client,
+    )
+    .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy::primitives::{address, Bytes};
+
+    use super::*;
+    use crate::{
+        config::INTERNAL_TRANSFER_SIGNATURE,
+        indexer::{ChronicleInternalTransfer, INTERNAL_TRANSFER_LOG_INDEX_OFFSET},
+    };
+
+    #[test]
+    fn test_internal_transfer_is_an_event_of_each_watched_side() {
+        let router = address!("d8da6bf26964af9d7eed9e03e53415d37aa96045");
+        let gateway = address!("88da6bf26964af9d7eed9e03e53415d37aa96045");
+        let transfer = ChronicleInternalTransfer {
+            chain_id: 1,
+            block_number: 19_000_000,
+            trace_index: 3,
+            trace_address: vec![0, 1],
+            call_type: "CALL".to_string(),
+            from: router,
+            to: gateway,
+            value: U256::from(5_000_000_000_000_000_000u128),
+            ..Default::default()
+        };
+
+        let events = transfer.to_chronicle_events(&[gateway]);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].address, gateway);
+        assert_eq!(events[0].topics[0], INTERNAL_TRANSFER_SIGNATURE);
+        assert_eq!(events[0].log_index, INTERNAL_TRANSFER_LOG_INDEX_OFFSET + 3);
+        assert_eq!(
+            decode_internal_transfer(&events[0]).unwrap(),
+            InternalTransfer {
+                from: router,
+                to: gateway,
+                value: transfer.value,
+                with_calldata: false,
+            }
+        );
+        assert_eq!(transfer.to_chronicle_events(&[gateway, router]).len(), 2);
+        assert!(transfer.to_chronicle_events(&[]).is_empty());
+
+        let call = ChronicleInternalTransfer {
+            input: Bytes::from_static(&[0xd0, 0xe3, 0x0d, 0xb0]),
+            ..transfer
+        };
+        let events = call.to_chronicle_events(&[gateway]);
+        assert!(decode_internal_transfer(&events[0]).unwrap().with_calldata);
+    }
+}
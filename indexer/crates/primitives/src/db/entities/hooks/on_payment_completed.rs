@@ -0,0 +1,155 @@
+use alloy::{
+    dyn_abi::DynSolType,
+    hex::ToHexExt,
+    primitives::{Address, B256, U256},
+};
+
+use crate::{
+    db::entities::{
+        payment::{create_payment_if_does_not_exist, create_payment_table},
+        treasury::{
+            create_treasury_entry_if_does_not_exist, create_treasury_ledger_table,
+            TreasuryEntryType,
+        },
+    },
+    indexer::ChronicleEvent,
+    utils::decode_event,
+};
+
+/// This is a decoded `PaymentCompleted(bytes32 indexed paymentId, address indexed payer,
+/// address indexed tokenAddress, uint256 amount)` event
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PaymentCompleted {
+    pub payment_id: B256,
+    pub payer: Address,
+    pub token_address: Address,
+    pub amount: U256,
+}
+
+impl PaymentCompleted {
+    /// The gateway emits `address(0)` as the token of native ETH payments
+    pub fn is_native(&self) -> bool {
+        self.token_address == Address::ZERO
+    }
+}
+
+/// This function decodes the `PaymentCompleted` event of an OpenGateway
+pub fn decode_payment_completed(event: &ChronicleEvent) -> Result<PaymentCompleted, anyhow::Error> {
+    let decoded_event = decode_event(
+        event.topics.clone(),
+        event.data.clone(),
+        DynSolType::Tuple(vec![DynSolType::Uint(256)]),
+        vec![
+            DynSolType::FixedBytes(32),
+            DynSolType::Address,
+            DynSolType::Address,
+        ],
+    )?;
+
+    let payment_id = decoded_event.indexed[0]
+        .as_fixed_bytes()
+        .ok_or_else(|| anyhow::anyhow!("Failed to decode payment_id"))?
+        .0;
+    let payer = decoded_event.indexed[1]
+        .as_address()
+        .ok_or_else(|| anyhow::anyhow!("Failed to decode payer"))?;
+    let token_address = decoded_event.indexed[2]
+        .as_address()
+        .ok_or_else(|| anyhow::anyhow!("Failed to decode token_address"))?;
+    let amount = decoded_event.body[0]
+        .as_uint()
+        .ok_or_else(|| anyhow::anyhow!("Failed to decode amount"))?
+        .0;
+
+    Ok(PaymentCompleted {
+        payment_id: B256::from_slice(payment_id),
+        payer,
+        token_address,
+        amount,
+    })
+}
+
+/// On payment completed, the following action would be made
+/// 1. payment entity record would be created for the gateway that emitted the event
+/// 2. the payment is credited to the treasury ledger of the gateway
+pub async fn on_payment_completed(
+    event: &ChronicleEvent,
+    db_client: &mut tokio_postgres::Client,
+) -> Result<(), anyhow::Error> {
+    // ====================================
+    // Operations for the Payment Entity
+    // ====================================
+    create_payment_table(db_client).await?;
+
+    let payment = decode_payment_completed(event)?;
+
+    create_payment_if_does_not_exist(
+        event.chain_id as i64,
+        payment.payment_id.encode_hex_with_prefix(),
+        event.address.to_string(),
+        payment.payer.to_string(),
+        payment.token_address.to_string(),
+        payment.is_native(),
+        payment.amount.to_string(),
+        event.block_number.to_string(),
+        event.block_timestamp.to_string(),
+        event.transaction_hash.to_string(),
+        event.log_index.to_string(),
+        db_client,
+    )
+    .await?;
+
+    // ====================================
+    // Operations for the Treasury Ledger Entity
+    // ====================================
+    create_treasury_ledger_table(db_client).await?;
+
+    create_treasury_entry_if_does_not_exist(
+        event.chain_id as i64,
+        event.address.to_string(),
+        payment.token_address.to_string(),
+        TreasuryEntryType::Credit,
+        payment.amount.to_string(),
+        payment.payer.to_string(),
+        event.block_number as i64,
+        event.block_timestamp as i64,
+        event.transaction_hash.to_string(),
+        event.log_index as i64,
+        db_client,
+    )
+    .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy::primitives::{address, b256, Bytes};
+
+    use super::*;
+    use crate::config::EVENT_FIVE_SIGNATURE;
+
+    #[test]
+    fn test_decode_payment_completed_treats_zero_token_as_native() {
+        let payment_id = b256!("00000000000000000000000000000000000000000000000000000000000004d2");
+        let payer = address!("d8da6bf26964af9d7eed9e03e53415d37aa96045");
+
+        let event = ChronicleEvent {
+            address: address!("88da6bf26964af9d7eed9e03e53415d37aa96045"),
+            topics: vec![
+                EVENT_FIVE_SIGNATURE,
+                payment_id,
+                payer.into_word(),
+                Address::ZERO.into_word(),
+            ],
+            data: Bytes::from(U256::from(1_000_000_000_000_000_000u128).to_be_bytes_vec()),
+            ..Default::default()
+        };
+
+        let payment = decode_payment_completed(&event).unwrap();
+        assert_eq!(payment.payment_id, payment_id);
+        assert_eq!(payment.payer, payer);
+        assert_eq!(payment.amount, U256::from(1_000_000_000_000_000_000u128));
+        assert!(payment.is_native());
+    }
+}
@@ -55,6 +55,7 @@ pub async fn on_register_brand(
     // Checks for the brand entity if it has been added to the database before
     //If it has, then skip
     create_brand_if_does_not_exist(
+        event.chain_id as i64,
         brand_name.to_string(),
         brand_online_presence.to_string(),
         brand_account.to_string(),
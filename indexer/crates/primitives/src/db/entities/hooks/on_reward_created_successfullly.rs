@@ -55,6 +55,7 @@ pub async fn on_reward_creation(
         .0;
 
     create_reward_if_does_not_exist(
+        event.chain_id as i64,
         brand_id.encode_hex_with_prefix(),
         reward_address.to_string(),
         requestor_address.to_string(),
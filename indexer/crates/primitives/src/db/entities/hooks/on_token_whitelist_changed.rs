@@ -0,0 +1,133 @@
+use alloy::{
+    dyn_abi::DynSolType,
+    primitives::{Address, U256},
+};
+
+use crate::{
+    db::entities::token_whitelist::{
+        create_token_whitelist_tables, record_token_whitelist_change, TokenListAction,
+    },
+    indexer::ChronicleEvent,
+    utils::decode_event,
+};
+
+/// This is a decoded `TokenWhitelisted(address[] token, uint256 timestamp)` or
+/// `TokenDelisted(address[] token, uint256 timestamp)` event
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TokenListChanged {
+    pub tokens: Vec<Address>,
+    pub timestamp: U256,
+}
+
+/// This function decodes the `TokenWhitelisted` and `TokenDelisted` events of an OpenGateway,
+/// both events share the same layout
+pub fn decode_token_list_changed(
+    event: &ChronicleEvent,
+) -> Result<TokenListChanged, anyhow::Error> {
+    let decoded_event = decode_event(
+        event.topics.clone(),
+        event.data.clone(),
+        DynSolType::Tuple(vec![
+            DynSolType::Array(Box::new(DynSolType::Address)),
+            DynSolType::Uint(256),
+        ]),
+        vec![],
+    )?;
+
+    let tokens = decoded_event.body[0]
+        .as_array()
+        .ok_or_else(|| anyhow::anyhow!("Failed to decode tokens"))?
+        .iter()
+        .map(|token| {
+            token
+                .as_address()
+                .ok_or_else(|| anyhow::anyhow!("Failed to decode token"))
+        })
+        .collect::<Result<Vec<Address>, _>>()?;
+    let timestamp = decoded_event.body[1]
+        .as_uint()
+        .ok_or_else(|| anyhow::anyhow!("Failed to decode timestamp"))?
+        .0;
+
+    Ok(TokenListChanged { tokens, timestamp })
+}
+
+/// On token whitelisted, the following action would be made
+/// 1. a whitelisting of every token is appended to the history of the gateway that emitted the event
+/// 2. the tokens are marked as accepted in the current whitelist of the gateway
+pub async fn on_token_whitelisted(
+    event: &ChronicleEvent,
+    db_client: &mut tokio_postgres::Client,
+) -> Result<(), anyhow::Error> {
+    on_token_list_changed(event, TokenListAction::Whitelisted, db_client).await
+}
+
+/// On token delisted, the following action would be made
+/// 1. a delisting of every token is appended to the history of the gateway that emitted the event
+/// 2. the tokens are marked as no longer accepted in the current whitelist of the gateway
+pub async fn on_token_delisted(
+    event: &ChronicleEvent,
+    db_client: &mut tokio_postgres::Client,
+) -> Result<(), anyhow::Error> {
+    on_token_list_changed(event, TokenListAction::Delisted, db_client).await
+}
+
+async fn on_token_list_changed(
+    event: &ChronicleEvent,
+    action: TokenListAction,
+    db_client: &mut tokio_postgres::Client,
+) -> Result<(), anyhow::Error> {
+    // ====================================
+    // Operations for the Token Whitelist Entity
+    // ====================================
+    create_token_whitelist_tables(db_client).await?;
+
+    let change = decode_token_list_changed(event)?;
+
+    for token in change.tokens {
+        record_token_whitelist_change(
+            event.chain_id as i64,
+            event.address.to_string(),
+            token.to_string(),
+            action,
+            change.timestamp.to_string(),
+            event.block_number as i64,
+            event.transaction_hash.to_string(),
+            event.log_index as i64,
+            db_client,
+        )
+        .await?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy::{dyn_abi::DynSolValue, primitives::address};
+
+    use super::*;
+    use crate::config::EVENT_EIGHT_SIGNATURE;
+
+    #[test]
+    fn test_decode_token_list_changed() {
+        let usdc = address!("a0b86991c6218b36c1d19d4a2e9eb0ce3606eb48");
+        let dai = address!("6b175474e89094c44da98b954eedeac495271d0f");
+
+        let data = DynSolValue::Tuple(vec![
+            DynSolValue::Array(vec![DynSolValue::Address(usdc), DynSolValue::Address(dai)]),
+            DynSolValue::Uint(U256::from(1_718_000_000u64), 256),
+        ])
+        .abi_encode_params();
+
+        let event = ChronicleEvent {
+            topics: vec![EVENT_EIGHT_SIGNATURE],
+            data: data.into(),
+            ..Default::default()
+        };
+
+        let change = decode_token_list_changed(&event).unwrap();
+        assert_eq!(change.tokens, vec![usdc, dai]);
+        assert_eq!(change.timestamp, U256::from(1_718_000_000u64));
+    }
+}
@@ -0,0 +1,141 @@
+use alloy::{
+    dyn_abi::DynSolType,
+    primitives::{Address, U256},
+};
+
+use crate::{
+    db::entities::treasury::{
+        create_treasury_entry_if_does_not_exist, create_treasury_ledger_table, TreasuryEntryType,
+    },
+    indexer::ChronicleEvent,
+    utils::decode_event,
+};
+
+/// This is a decoded `NativeWithdrawn(address recipient, uint256 balance)` or
+/// `WithdrawalFailed(address recipient, uint256 amount)` event
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Withdrawal {
+    pub recipient: Address,
+    pub amount: U256,
+}
+
+/// This function decodes the withdrawal events of an OpenGateway,
+/// the recipient is read from the topics when it is indexed and from the data otherwise
+pub fn decode_withdrawal(event: &ChronicleEvent) -> Result<Withdrawal, anyhow::Error> {
+    let recipient_is_indexed = event.topics.len() > 1;
+    let decoded_event = if recipient_is_indexed {
+        decode_event(
+            event.topics.clone(),
+            event.data.clone(),
+            DynSolType::Tuple(vec![DynSolType::Uint(256)]),
+            vec![DynSolType::Address],
+        )?
+    } else {
+        decode_event(
+            event.topics.clone(),
+            event.data.clone(),
+            DynSolType::Tuple(vec![DynSolType::Address, DynSolType::Uint(256)]),
+            vec![],
+        )?
+    };
+
+    let (recipient, amount) = if recipient_is_indexed {
+        (&decoded_event.indexed[0], &decoded_event.body[0])
+    } else {
+        (&decoded_event.body[0], &decoded_event.body[1])
+    };
+
+    Ok(Withdrawal {
+        recipient: recipient
+            .as_address()
+            .ok_or_else(|| anyhow::anyhow!("Failed to decode recipient"))?,
+        amount: amount
+            .as_uint()
+            .ok_or_else(|| anyhow::anyhow!("Failed to decode amount"))?
+            .0,
+    })
+}
+
+/// On native withdrawn, the following action would be made
+/// 1. a debit of the native balance is added to the treasury ledger of the gateway that emitted the event
+pub async fn on_native_withdrawn(
+    event: &ChronicleEvent,
+    db_client: &mut tokio_postgres::Client,
+) -> Result<(), anyhow::Error> {
+    on_withdrawal(event, TreasuryEntryType::Debit, db_client).await
+}
+
+/// On withdrawal failed, the following action would be made
+/// 1. the failed withdrawal is flagged in the treasury ledger of the gateway that emitted the event,
+///    the balance of the gateway is left untouched
+pub async fn on_withdrawal_failed(
+    event: &ChronicleEvent,
+    db_client: &mut tokio_postgres::Client,
+) -> Result<(), anyhow::Error> {
+    on_withdrawal(event, TreasuryEntryType::WithdrawalFailed, db_client).await
+}
+
+async fn on_withdrawal(
+    event: &ChronicleEvent,
+    entry_type: TreasuryEntryType,
+    db_client: &mut tokio_postgres::Client,
+) -> Result<(), anyhow::Error> {
+    // ====================================
+    // Operations for the Treasury Ledger Entity
+    // ====================================
+    create_treasury_ledger_table(db_client).await?;
+
+    let withdrawal = decode_withdrawal(event)?;
+
+    // Only the native balance is withdrawn with an event, its token is `address(0)`
+    create_treasury_entry_if_does_not_exist(
+        event.chain_id as i64,
+        event.address.to_string(),
+        Address::ZERO.to_string(),
+        entry_type,
+        withdrawal.amount.to_string(),
+        withdrawal.recipient.to_string(),
+        event.block_number as i64,
+        event.block_timestamp as i64,
+        event.transaction_hash.to_string(),
+        event.log_index as i64,
+        db_client,
+    )
+    .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy::{dyn_abi::DynSolValue, primitives::address};
+
+    use super::*;
+    use crate::config::EVENT_NINE_SIGNATURE;
+
+    #[test]
+    fn test_decode_withdrawal_with_and_without_indexed_recipient() {
+        let recipient = address!("d8da6bf26964af9d7eed9e03e53415d37aa96045");
+        let amount = U256::from(5_000_000_000_000_000_000u128);
+
+        let indexed = ChronicleEvent {
+            topics: vec![EVENT_NINE_SIGNATURE, recipient.into_word()],
+            data: amount.to_be_bytes_vec().into(),
+            ..Default::default()
+        };
+        let not_indexed = ChronicleEvent {
+            topics: vec![EVENT_NINE_SIGNATURE],
+            data: DynSolValue::Tuple(vec![
+                DynSolValue::Address(recipient),
+                DynSolValue::Uint(amount, 256),
+            ])
+            .abi_encode_params()
+            .into(),
+            ..Default::default()
+        };
+
+        let expected = Withdrawal { recipient, amount };
+        assert_eq!(decode_withdrawal(&indexed).unwrap(), expected);
+        assert_eq!(decode_withdrawal(&not_indexed).unwrap(), expected);
+    }
+}
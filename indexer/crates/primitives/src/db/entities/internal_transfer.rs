@@ -0,0 +1,121 @@
+use alloy::primitives::Address;
+
+use super::{
+    begin_block_transaction, finish_block_transaction,
+    system::{update_last_block_number, CheckpointKey},
+};
+use crate::{
+    calldata::calldata_selector,
+    db::reorg::{record_block_hash, set_journal_block},
+    entity_record::InternalTransferRecord,
+    indexer::ChronicleInternalTransfer,
+    registry::EventHandlerRegistry,
+};
+
+pub const INTERNAL_TRANSFER_TABLE_NAME: &str = "chronicle_internal_transfer";
+
+/// This function is used to create the internal transfer table in the database if one does not exist already.
+/// A transfer is stored once per chain, whichever indexer found it first.
+pub async fn create_internal_transfer_table(
+    db_client: &mut tokio_postgres::Client,
+) -> Result<(), anyhow::Error> {
+    InternalTransferRecord::create_table(db_client).await
+}
+
+/// This function writes the internal transfers of one block, hands them to the handlers and moves
+/// the checkpoint of the indexer to the block, in one transaction. Like the events of a block, the
+/// rows and the entity changes are journaled against the block so they are rolled back on a reorg.
+pub async fn store_block_internal_transfers(
+    transfers: &[ChronicleInternalTransfer],
+    addresses: &[Address],
+    db_client: &mut tokio_postgres::Client,
+    registry: &EventHandlerRegistry,
+    checkpoint: &CheckpointKey,
+) -> Result<(), anyhow::Error> {
+    if transfers.is_empty() {
+        return Ok(());
+    }
+
+    begin_block_transaction(db_client).await?;
+    let result =
+        apply_block_internal_transfers(transfers, addresses, db_client, registry, checkpoint).await;
+    finish_block_transaction(result, db_client).await
+}
+
+/// This function stores the internal transfers of one block, hands each of them to the handlers as
+/// an `InternalTransfer` event of the `addresses` it was sent from or to, and moves the checkpoint
+/// to the block. It does not open a transaction, call it between [`begin_block_transaction`] and
+/// [`finish_block_transaction`]
+pub async fn apply_block_internal_transfers(
+    transfers: &[ChronicleInternalTransfer],
+    addresses: &[Address],
+    db_client: &mut tokio_postgres::Client,
+    registry: &EventHandlerRegistry,
+    checkpoint: &CheckpointKey,
+) -> Result<(), anyhow::Error> {
+    let Some(block) = transfers.first() else {
+        return Ok(());
+    };
+    if transfers
+        .iter()
+        .any(|transfer| transfer.block_number != block.block_number)
+    {
+        anyhow::bail!("The internal transfers of a block write must all be from the same block");
+    }
+
+    set_journal_block(checkpoint, block.block_number, db_client).await?;
+    for transfer in transfers {
+        // The transfers are tagged with the chain of the indexer the block is written by
+        let transfer = ChronicleInternalTransfer {
+            chain_id: checkpoint.chain_id,
+            ..transfer.clone()
+        };
+        create_internal_transfer_if_does_not_exist(&transfer, db_client).await?;
+        for event in transfer.to_chronicle_events(addresses) {
+            registry.dispatch(&event, db_client).await?;
+        }
+    }
+
+    record_block_hash(
+        checkpoint,
+        block.block_number,
+        block.block_hash.to_string(),
+        db_client,
+    )
+    .await?;
+    update_last_block_number(checkpoint, block.block_number.to_string(), db_client).await?;
+
+    Ok(())
+}
+
+/// This function stores an internal transfer, a transfer that was already stored is ignored
+pub async fn create_internal_transfer_if_does_not_exist(
+    transfer: &ChronicleInternalTransfer,
+    db_client: &mut tokio_postgres::Client,
+) -> Result<(), anyhow::Error> {
+    InternalTransferRecord {
+        chain_id: transfer.chain_id as i64,
+        block_number: transfer.block_number.to_string(),
+        block_hash: transfer.block_hash.to_string(),
+        block_timestamp: transfer.block_timestamp.to_string(),
+        transaction_hash: transfer.transaction_hash.to_string(),
+        transaction_index: transfer.transaction_index.to_string(),
+        trace_index: transfer.trace_index.to_string(),
+        trace_address: transfer
+            .trace_address
+            .iter()
+            .map(u64::to_string)
+            .collect::<Vec<_>>()
+            .join(","),
+        call_type: transfer.call_type.clone(),
+        from_address: transfer.from.to_string(),
+        to_address: transfer.to.to_string(),
+        value: transfer.value.to_string(),
+        function_selector: calldata_selector(&transfer.input),
+        ..Default::default()
+    }
+    .insert(db_client)
+    .await?;
+
+    Ok(())
+}
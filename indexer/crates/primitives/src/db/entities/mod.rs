@@ -1,56 +1,177 @@
-use alloy::primitives::B256;
-use hooks::{
-    on_cross_brand_redemption::on_cross_brand_redemption,
-    on_current_pool_state::on_current_pool_state, on_register_brand::on_register_brand,
-    on_reward_created_successfullly::on_reward_creation,
-};
-use system::update_last_block_number;
+use system::{update_last_block_number, CheckpointKey};
 
 use crate::{
-    config::{
-        EVENT_FOUR_SIGNATURE, EVENT_ONE_SIGNATURE, EVENT_THREE_SIGNATURE, EVENT_TWO_SIGNATURE,
-    },
+    db::reorg::{record_block_hash, set_journal_block},
     indexer::ChronicleEvent,
+    registry::EventHandlerRegistry,
 };
 
 pub mod brand;
+pub mod gateway;
 pub mod hooks;
+pub mod internal_transfer;
+pub mod payment;
 pub mod pool;
 pub mod redemption;
 pub mod reward;
+pub mod runtime_event;
 pub mod system;
+pub mod token_whitelist;
+pub mod transaction;
+pub mod treasury;
 
+/// This is the suffix of the chronicle tables. It is no longer bumped on schema changes,
+/// those are migrations, see [`crate::db::migrations`].
 #[cfg(feature = "development")]
-pub const DB_VERSION: &str = "_DEVELOPMENT_30";
+pub const DB_VERSION: &str = "_DEVELOPMENT_31";
 
 // Default to production if no feature is specified
 #[cfg(not(feature = "development"))]
-pub const DB_VERSION: &str = "_PRODUCTION_5";
+pub const DB_VERSION: &str = "_PRODUCTION_6";
 
-/// This function is hit anytime an event is recieved by the indexer.
-/// using the event hash, the enitity that needs to migrated would be triggered
-pub async fn pre_entity_store(
-    event: &ChronicleEvent,
+/// This function is hit with the events the indexer recieved from one block, in log order.
+/// The events are handed to the handlers registered for their (address, topic0) and the
+/// checkpoint of the indexer is moved to the block, all in one transaction, so a block is
+/// either fully applied with its checkpoint or not at all.
+/// The entity changes are journaled against the block so they can be rolled back on a reorg.
+pub async fn store_block_events(
+    events: &[ChronicleEvent],
     db_client: &mut tokio_postgres::Client,
-    event_sig: B256,
+    registry: &EventHandlerRegistry,
+    checkpoint: &CheckpointKey,
 ) -> Result<(), anyhow::Error> {
-    match event_sig {
-        EVENT_ONE_SIGNATURE => {
-            on_cross_brand_redemption(event, db_client).await?;
-        }
-        EVENT_TWO_SIGNATURE => {
-            on_current_pool_state(event, db_client).await?;
+    if events.is_empty() {
+        return Ok(());
+    }
+
+    begin_block_transaction(db_client).await?;
+    let result = apply_block_events(events, db_client, registry, checkpoint).await;
+    finish_block_transaction(result, db_client).await
+}
+
+/// This function applies the events of one block and moves the checkpoint to it, it does not
+/// open a transaction, call it between [`begin_block_transaction`] and [`finish_block_transaction`]
+pub async fn apply_block_events(
+    events: &[ChronicleEvent],
+    db_client: &mut tokio_postgres::Client,
+    registry: &EventHandlerRegistry,
+    checkpoint: &CheckpointKey,
+) -> Result<(), anyhow::Error> {
+    let Some(block) = events.first() else {
+        return Ok(());
+    };
+    if events
+        .iter()
+        .any(|event| event.block_number != block.block_number)
+    {
+        anyhow::bail!("The events of a block write must all be from the same block");
+    }
+
+    set_journal_block(checkpoint, block.block_number, db_client).await?;
+
+    for event in events {
+        // The entities are tagged with the chain of the indexer the block is written by
+        let event = ChronicleEvent {
+            chain_id: checkpoint.chain_id,
+            ..event.clone()
+        };
+        registry.dispatch(&event, db_client).await?;
+    }
+
+    record_block_hash(
+        checkpoint,
+        block.block_number,
+        block.block_hash.to_string(),
+        db_client,
+    )
+    .await?;
+    update_last_block_number(checkpoint, block.block_number.to_string(), db_client).await?;
+
+    Ok(())
+}
+
+/// This function opens the transaction of a block write on the connection.
+/// The handlers take the connection itself, so the transaction is opened with `BEGIN`
+/// rather than as a [`tokio_postgres::Transaction`].
+pub async fn begin_block_transaction(
+    db_client: &mut tokio_postgres::Client,
+) -> Result<(), anyhow::Error> {
+    db_client.batch_execute("BEGIN").await?;
+    Ok(())
+}
+
+/// This function commits the transaction of a block write when `result` is ok, and rolls it
+/// back otherwise, returning the error that failed the write
+pub async fn finish_block_transaction<T>(
+    result: Result<T, anyhow::Error>,
+    db_client: &mut tokio_postgres::Client,
+) -> Result<T, anyhow::Error> {
+    match result {
+        Ok(value) => {
+            db_client.batch_execute("COMMIT").await?;
+            Ok(value)
         }
-        EVENT_THREE_SIGNATURE => {
-            on_register_brand(event, db_client).await?;
+        Err(err) => {
+            if let Err(rollback_err) = db_client.batch_execute("ROLLBACK").await {
+                tracing::warn!("Rolling back a block write failed: {rollback_err}");
+            }
+            Err(err)
         }
-        EVENT_FOUR_SIGNATURE => {
-            on_reward_creation(event, db_client).await?;
+    }
+}
+
+/// This function splits events into the events of each block, in block and log order
+pub fn group_events_by_block(mut events: Vec<ChronicleEvent>) -> Vec<Vec<ChronicleEvent>> {
+    events.sort_by_key(|event| (event.block_number, event.log_index));
+
+    let mut blocks: Vec<Vec<ChronicleEvent>> = Vec::new();
+    for event in events {
+        match blocks.last_mut() {
+            Some(block) if block[0].block_number == event.block_number => block.push(event),
+            _ => blocks.push(vec![event]),
         }
-        _ => {}
     }
 
-    update_last_block_number(event.block_number.to_string(), db_client).await?;
+    blocks
+}
 
-    Ok(())
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_group_events_by_block_orders_the_logs_of_each_block() {
+        let event = |block_number, log_index| ChronicleEvent {
+            block_number,
+            log_index,
+            ..Default::default()
+        };
+        let events = vec![
+            event(12, 3),
+            event(10, 1),
+            event(12, 0),
+            event(10, 0),
+            event(11, 7),
+        ];
+
+        let blocks = group_events_by_block(events)
+            .iter()
+            .map(|block| {
+                block
+                    .iter()
+                    .map(|event| (event.block_number, event.log_index))
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            blocks,
+            vec![
+                vec![(10, 0), (10, 1)],
+                vec![(11, 7)],
+                vec![(12, 0), (12, 3)]
+            ]
+        );
+        assert!(group_events_by_block(Vec::new()).is_empty());
+    }
 }
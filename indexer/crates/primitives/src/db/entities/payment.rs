@@ -0,0 +1,331 @@
+use alloy::hex::ToHexExt;
+use anyhow::anyhow;
+
+use crate::{
+    config::EVENT_FIVE_SIGNATURE,
+    db::{
+        entities::hooks::on_payment_completed::decode_payment_completed,
+        entity::ChainCount,
+        pending_event::{get_pending_events_by_topic, EventStatus},
+    },
+    entity_record::PaymentRecord,
+    indexer::ChronicleEvent,
+};
+
+pub const PAYMENT_TABLE_NAME: &str = "payment";
+
+/// This is the column payments can be listed by
+#[derive(Debug, Clone)]
+pub enum PaymentFilter {
+    Payer(String),
+    Token(String),
+    Gateway(String),
+}
+
+impl PaymentFilter {
+    /// This function checks if the payment matches the filter, addresses are compared case-insensitively
+    fn matches(&self, payment: &PaymentRecord) -> bool {
+        match self {
+            Self::Payer(value) => payment.payer.eq_ignore_ascii_case(value),
+            Self::Token(value) => payment.token_address.eq_ignore_ascii_case(value),
+            Self::Gateway(value) => payment.gateway_address.eq_ignore_ascii_case(value),
+        }
+    }
+}
+
+/// This function is used to create a new payment table in the database
+/// if one does not exist already
+pub async fn create_payment_table(
+    db_client: &mut tokio_postgres::Client,
+) -> Result<(), anyhow::Error> {
+    PaymentRecord::create_table(db_client).await
+}
+
+/// This function creates a new payment entity in the database,
+/// a log that was already stored is ignored
+#[allow(clippy::too_many_arguments)]
+pub async fn create_payment_if_does_not_exist(
+    chain_id: i64,
+    payment_id: String,
+    gateway_address: String,
+    payer: String,
+    token_address: String,
+    is_native: bool,
+    amount: String,
+    block_number: String,
+    block_timestamp: String,
+    transaction_hash: String,
+    log_index: String,
+    db_client: &mut tokio_postgres::Client,
+) -> Result<(), anyhow::Error> {
+    PaymentRecord {
+        chain_id,
+        payment_id,
+        gateway_address,
+        payer,
+        token_address,
+        is_native,
+        amount,
+        block_number,
+        block_timestamp,
+        transaction_hash,
+        log_index,
+        ..Default::default()
+    }
+    .insert(db_client)
+    .await?;
+
+    Ok(())
+}
+
+// This function returns the count of payment entities matching the filter,
+// the pending payments included
+pub async fn get_total_count_payments(
+    filter: &PaymentFilter,
+    chain_id: Option<i64>,
+    db_client: &mut tokio_postgres::Client,
+) -> Result<i64, anyhow::Error> {
+    let pending = query_pending_payments(filter, chain_id, db_client).await?;
+    let stored = match filter {
+        PaymentFilter::Payer(value) => {
+            PaymentRecord::count_by_payer(value, chain_id, db_client).await?
+        }
+        PaymentFilter::Token(value) => {
+            PaymentRecord::count_by_token_address(value, chain_id, db_client).await?
+        }
+        PaymentFilter::Gateway(value) => {
+            PaymentRecord::count_by_gateway_address(value, chain_id, db_client).await?
+        }
+    };
+
+    Ok(pending.len() as i64 + stored)
+}
+
+// This function returns the count of payment entities of each chain
+pub async fn get_payment_count_per_chain(
+    db_client: &mut tokio_postgres::Client,
+) -> Result<Vec<ChainCount>, anyhow::Error> {
+    PaymentRecord::count_per_chain(db_client).await
+}
+
+/// This function returns the payment with the given `paymentId`,
+/// a payment waiting for its confirmations is returned as `pending`
+pub async fn query_payment_by_payment_id(
+    payment_id: String,
+    chain_id: Option<i64>,
+    db_client: &mut tokio_postgres::Client,
+) -> Result<PaymentRecord, anyhow::Error> {
+    if let Some(payment) = PaymentRecord::query_by_payment_id(&payment_id, chain_id, db_client)
+        .await?
+        .into_iter()
+        .next()
+    {
+        return Ok(payment);
+    }
+
+    pending_payments(chain_id, db_client)
+        .await?
+        .into_iter()
+        .find(|payment| payment.payment_id.eq_ignore_ascii_case(&payment_id))
+        .ok_or_else(|| anyhow!("Payment `{payment_id}` not found"))
+}
+
+/// This function returns the payments matching the filter, newest first.
+/// The pending payments are at the tip of the chain, so they come before the stored ones.
+pub async fn query_payments_paginated(
+    filter: &PaymentFilter,
+    chain_id: Option<i64>,
+    db_client: &mut tokio_postgres::Client,
+    page_number: i64,
+    page_size: i64,
+) -> Result<Vec<PaymentRecord>, anyhow::Error> {
+    let pending = query_pending_payments(filter, chain_id, db_client).await?;
+    let offset = ((page_number - 1) * page_size).max(0);
+    let pending_count = pending.len() as i64;
+    let mut payments = pending
+        .into_iter()
+        .skip(offset as usize)
+        .take(page_size.max(0) as usize)
+        .collect::<Vec<_>>();
+
+    let limit = page_size - payments.len() as i64;
+    if limit <= 0 {
+        return Ok(payments);
+    }
+    let offset = (offset - pending_count).max(0);
+    let stored = match filter {
+        PaymentFilter::Payer(value) => {
+            PaymentRecord::query_by_payer_range(value, chain_id, db_client, offset, limit).await?
+        }
+        PaymentFilter::Token(value) => {
+            PaymentRecord::query_by_token_address_range(value, chain_id, db_client, offset, limit)
+                .await?
+        }
+        PaymentFilter::Gateway(value) => {
+            PaymentRecord::query_by_gateway_address_range(value, chain_id, db_client, offset, limit)
+                .await?
+        }
+    };
+    payments.extend(stored);
+
+    Ok(payments)
+}
+
+/// This function returns the pending payments matching the filter, newest first
+async fn query_pending_payments(
+    filter: &PaymentFilter,
+    chain_id: Option<i64>,
+    db_client: &mut tokio_postgres::Client,
+) -> Result<Vec<PaymentRecord>, anyhow::Error> {
+    Ok(pending_payments(chain_id, db_client)
+        .await?
+        .into_iter()
+        .filter(|payment| filter.matches(payment))
+        .collect())
+}
+
+/// This function returns the payments whose `PaymentCompleted` log waits in the pending buffer
+/// for its confirmations, newest first
+async fn pending_payments(
+    chain_id: Option<i64>,
+    db_client: &mut tokio_postgres::Client,
+) -> Result<Vec<PaymentRecord>, anyhow::Error> {
+    let events = get_pending_events_by_topic(EVENT_FIVE_SIGNATURE, chain_id, db_client).await?;
+
+    // A log that does not decode as a payment is never applied as one either
+    Ok(events.iter().filter_map(pending_payment).collect())
+}
+
+fn pending_payment(event: &ChronicleEvent) -> Option<PaymentRecord> {
+    let payment = decode_payment_completed(event).ok()?;
+
+    Some(PaymentRecord {
+        chain_id: event.chain_id as i64,
+        payment_id: payment.payment_id.encode_hex_with_prefix(),
+        gateway_address: event.address.to_string(),
+        payer: payment.payer.to_string(),
+        token_address: payment.token_address.to_string(),
+        is_native: payment.is_native(),
+        amount: payment.amount.to_string(),
+        block_number: event.block_number.to_string(),
+        block_timestamp: event.block_timestamp.to_string(),
+        transaction_hash: event.transaction_hash.to_string(),
+        log_index: event.log_index.to_string(),
+        status: EventStatus::Pending.as_str().to_string(),
+        ..Default::default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy::primitives::{address, b256, Address, Bytes, U256};
+
+    use super::*;
+    use crate::{
+        config::get_db_url_from_env,
+        db::{
+            entities::system::{create_system_table, CheckpointKey},
+            pending_event::{buffer_pending_event, mark_event_confirmed},
+            raw_chronicle_event::create_db_instance,
+        },
+    };
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_pending_payment_is_returned_as_pending() {
+        let mut db_client = create_db_instance(&get_db_url_from_env())
+            .await
+            .expect("Could not create db instance");
+        db_client
+            .batch_execute(
+                "
+                    DROP SCHEMA IF EXISTS chronicle_payment_test CASCADE;
+                    CREATE SCHEMA chronicle_payment_test;
+                    SET search_path TO chronicle_payment_test;
+                ",
+            )
+            .await
+            .unwrap();
+        create_system_table(&mut db_client).await.unwrap();
+
+        let payment_id = b256!("00000000000000000000000000000000000000000000000000000000000004d2");
+        let payer = address!("d8da6bf26964af9d7eed9e03e53415d37aa96045");
+        let event = ChronicleEvent {
+            chain_id: 1,
+            address: address!("88da6bf26964af9d7eed9e03e53415d37aa96045"),
+            block_number: 5,
+            transaction_hash: b256!(
+                "000000000000000000000000d8da6bf26964af9d7eed9e03e53415d37aa96045"
+            ),
+            topics: vec![
+                EVENT_FIVE_SIGNATURE,
+                payment_id,
+                payer.into_word(),
+                Address::ZERO.into_word(),
+            ],
+            data: Bytes::from(U256::from(1_000u64).to_be_bytes_vec()),
+            ..Default::default()
+        };
+        let checkpoint = CheckpointKey::new(
+            "gateway_payments".to_string(),
+            1,
+            event.address.to_string(),
+            EVENT_FIVE_SIGNATURE.to_string(),
+        );
+        buffer_pending_event(&event, &checkpoint, &mut db_client)
+            .await
+            .unwrap();
+
+        let filter = PaymentFilter::Payer(payer.to_string().to_lowercase());
+        let payments = query_payments_paginated(&filter, Some(1), &mut db_client, 1, 10)
+            .await
+            .unwrap();
+        assert_eq!(payments.len(), 1);
+        assert_eq!(payments[0].status, "pending");
+        assert_eq!(payments[0].amount, "1000");
+        assert_eq!(
+            get_total_count_payments(&filter, Some(1), &mut db_client)
+                .await
+                .unwrap(),
+            1
+        );
+        let payment =
+            query_payment_by_payment_id(payment_id.encode_hex_with_prefix(), None, &mut db_client)
+                .await
+                .unwrap();
+        assert_eq!(payment.status, "pending");
+
+        // Once applied, the payment is read from the payment table as confirmed
+        let pending = payments[0].clone();
+        create_payment_if_does_not_exist(
+            pending.chain_id,
+            pending.payment_id,
+            pending.gateway_address,
+            pending.payer,
+            pending.token_address,
+            pending.is_native,
+            pending.amount,
+            pending.block_number,
+            pending.block_timestamp,
+            pending.transaction_hash,
+            pending.log_index,
+            &mut db_client,
+        )
+        .await
+        .unwrap();
+        mark_event_confirmed(&event, &checkpoint, &mut db_client)
+            .await
+            .unwrap();
+
+        let payments = query_payments_paginated(&filter, None, &mut db_client, 1, 10)
+            .await
+            .unwrap();
+        assert_eq!(payments.len(), 1);
+        assert_eq!(payments[0].status, "confirmed");
+
+        db_client
+            .batch_execute("DROP SCHEMA chronicle_payment_test CASCADE")
+            .await
+            .unwrap();
+    }
+}
@@ -1,7 +1,5 @@
-use alloy::primitives::U256;
-
 use super::DB_VERSION;
-use crate::{db::PRECISION, entity_record::PoolRecord};
+use crate::{db::entity::ChainCount, entity_record::PoolRecord};
 
 pub const POOL_TABLE_NAME: &str = "pool";
 
@@ -10,27 +8,13 @@ pub const POOL_TABLE_NAME: &str = "pool";
 pub async fn create_pool_table(
     db_client: &mut tokio_postgres::Client,
 ) -> Result<(), anyhow::Error> {
-    let executable = format!(
-        "
-            CREATE TABLE IF NOT EXISTS {POOL_TABLE_NAME}{DB_VERSION} (
-               id              SERIAL PRIMARY KEY,
-               pool_address    VARCHAR NULL,
-               reward_token  VARCHAR UNIQUE,
-               me_token        VARCHAR NULL,
-               current_amount_of_reward_tokens    VARCHAR NULL,
-               current_amount_of_me_tokens        VARCHAR NULL,
-               r_optimal        VARCHAR NULL,
-               created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
-            )
-        "
-    );
-
-    db_client.batch_execute(&executable).await?;
-    Ok(())
+    PoolRecord::create_table(db_client).await
 }
 
 /// This function creates a new pool entity in the database
+#[allow(clippy::too_many_arguments)]
 pub async fn create_pool_if_does_not_exist(
+    chain_id: i64,
     pool_address: String,
     reward_address: String,
     me_address: String,
@@ -39,87 +23,55 @@ pub async fn create_pool_if_does_not_exist(
     r_optimal: String,
     db_client: &mut tokio_postgres::Client,
 ) -> Result<(), anyhow::Error> {
-    let executable = format!(
-        "
-            INSERT INTO {POOL_TABLE_NAME}{DB_VERSION} (
-                pool_address,
-                reward_token,
-                me_token,
-                current_amount_of_reward_tokens,
-                current_amount_of_me_tokens,
-                r_optimal
-            ) VALUES ($1, $2, $3, $4, $5, $6)
-        "
-    );
-
-    db_client
-        .execute(
-            &executable,
-            &[
-                &pool_address,
-                &reward_address,
-                &me_address,
-                &current_amount_of_reward_tokens,
-                &current_amount_of_me_tokens,
-                &r_optimal,
-            ],
-        )
-        .await?;
+    PoolRecord {
+        chain_id,
+        pool_address: Some(pool_address),
+        reward_token: reward_address,
+        me_token: Some(me_address),
+        current_amount_of_reward_tokens: Some(current_amount_of_reward_tokens),
+        current_amount_of_me_tokens: Some(current_amount_of_me_tokens),
+        r_optimal: Some(r_optimal),
+        ..Default::default()
+    }
+    .insert(db_client)
+    .await?;
 
     Ok(())
 }
 
 /// This function is used to get all the count of pools in the database
 pub async fn get_pool_total_count(
+    chain_id: Option<i64>,
     db_client: &mut tokio_postgres::Client,
 ) -> Result<i64, anyhow::Error> {
-    let executable = format!(
-        "
-            SELECT COUNT(*)
-            FROM {POOL_TABLE_NAME}{DB_VERSION}
-        "
-    );
-
-    let result = db_client.query(&executable, &[]).await?;
+    PoolRecord::count(chain_id, db_client).await
+}
 
-    Ok(result[0].get(0))
+/// This function is used to get the count of pools of each chain
+pub async fn get_pool_count_per_chain(
+    db_client: &mut tokio_postgres::Client,
+) -> Result<Vec<ChainCount>, anyhow::Error> {
+    PoolRecord::count_per_chain(db_client).await
 }
 
 pub async fn check_pool_exist_by_pool_address(
     pool_address: String,
+    chain_id: Option<i64>,
     db_client: &mut tokio_postgres::Client,
 ) -> Result<bool, anyhow::Error> {
-    let executable = format!(
-        "
-            SELECT 1
-            FROM {POOL_TABLE_NAME}{DB_VERSION}
-            WHERE pool_address = $1
-        "
-    );
-
-    let result = db_client.query(&executable, &[&pool_address]).await?;
-
-    Ok(!result.is_empty())
+    PoolRecord::exists_by_pool_address(&pool_address, chain_id, db_client).await
 }
 
 pub async fn check_pool_exist_by_reward_address(
     reward_address: String,
+    chain_id: Option<i64>,
     db_client: &mut tokio_postgres::Client,
 ) -> Result<bool, anyhow::Error> {
-    let executable = format!(
-        "
-            SELECT 1
-            FROM {POOL_TABLE_NAME}{DB_VERSION}
-            WHERE reward_token = $1
-        "
-    );
-
-    let result = db_client.query(&executable, &[&reward_address]).await?;
-
-    Ok(!result.is_empty())
+    PoolRecord::exists_by_reward_token(&reward_address, chain_id, db_client).await
 }
 
 pub async fn update_pool_reward_and_me_amount(
+    chain_id: i64,
     reward_address: String,
     me_token_amount: String,
     reward_amount: String,
@@ -127,9 +79,9 @@ pub async fn update_pool_reward_and_me_amount(
 ) -> Result<(), anyhow::Error> {
     let executable = format!(
         "
-            INSERT INTO {POOL_TABLE_NAME}{DB_VERSION} (reward_token, current_amount_of_reward_tokens, current_amount_of_me_tokens)
-            VALUES ($3, $1, $2)
-            ON CONFLICT (reward_token)
+            INSERT INTO {POOL_TABLE_NAME}{DB_VERSION} (chain_id, reward_token, current_amount_of_reward_tokens, current_amount_of_me_tokens)
+            VALUES ($4, $3, $1::TEXT::NUMERIC, $2::TEXT::NUMERIC)
+            ON CONFLICT (chain_id, reward_token)
             DO UPDATE SET
                 current_amount_of_reward_tokens = EXCLUDED.current_amount_of_reward_tokens,
                 current_amount_of_me_tokens = EXCLUDED.current_amount_of_me_tokens;
@@ -139,7 +91,7 @@ pub async fn update_pool_reward_and_me_amount(
     db_client
         .execute(
             &executable,
-            &[&reward_amount, &me_token_amount, &reward_address],
+            &[&reward_amount, &me_token_amount, &reward_address, &chain_id],
         )
         .await?;
 
@@ -148,71 +100,19 @@ pub async fn update_pool_reward_and_me_amount(
 
 pub async fn query_pool_by_reward_address(
     reward_address: String,
+    chain_id: Option<i64>,
     db_client: &mut tokio_postgres::Client,
 ) -> Result<PoolRecord, anyhow::Error> {
-    let executable = format!(
-        "
-            SELECT * 
-            FROM {POOL_TABLE_NAME}{DB_VERSION} 
-            WHERE reward_token = $1;
-        "
-    );
-
-    let result = db_client.query_one(&executable, &[&reward_address]).await?;
-
-    let pool_record = PoolRecord {
-        pool_address: result.get(1),
-        reward_token: result.get(2),
-        me_token: result.get(3),
-        current_amount_of_reward_tokens: result.get(4),
-        current_amount_of_me_tokens: result.get(5),
-        r_optimal: result.get(6),
-        r: ((U256::from_str_radix(result.get(4), 10).unwrap() * U256::from(PRECISION))
-            / U256::from_str_radix(result.get(5), 10).unwrap())
-        .to_string(),
-        create_at: result.get(7),
-    };
-
-    Ok(pool_record)
+    PoolRecord::query_one_by_reward_token(&reward_address, chain_id, db_client).await
 }
 
 pub async fn query_all_pools_paginated(
     db_client: &mut tokio_postgres::Client,
+    chain_id: Option<i64>,
     page_number: i64,
     page_size: i64,
 ) -> Result<Vec<PoolRecord>, anyhow::Error> {
-    let executable = format!(
-        "
-            SELECT * 
-            FROM {POOL_TABLE_NAME}{DB_VERSION}
-            ORDER BY id DESC
-            LIMIT $1 OFFSET $2;
-        "
-    );
-
-    let offest = (page_number - 1) * page_size;
-
-    let result = db_client.query(&executable, &[&page_size, &offest]).await?;
-    let mut pool_record_buffer = Vec::new();
-
-    for record in result {
-        let pool_record = PoolRecord {
-            pool_address: record.get(1),
-            reward_token: record.get(2),
-            me_token: record.get(3),
-            current_amount_of_reward_tokens: record.get(4),
-            current_amount_of_me_tokens: record.get(5),
-            r_optimal: record.get(6),
-            r: ((U256::from_str_radix(record.get(4), 10).unwrap() * U256::from(PRECISION))
-                / U256::from_str_radix(record.get(5), 10).unwrap())
-            .to_string(),
-            create_at: record.get(6),
-        };
-
-        pool_record_buffer.push(pool_record);
-    }
-
-    Ok(pool_record_buffer)
+    PoolRecord::query_paginated(chain_id, db_client, page_number, page_size).await
 }
 
 // tests
@@ -228,7 +128,7 @@ mod tests {
         let mut db_client = create_db_instance(&url)
             .await
             .expect("Could not create db instance");
-        let pool_count = get_pool_total_count(&mut db_client).await.unwrap();
+        let pool_count = get_pool_total_count(None, &mut db_client).await.unwrap();
 
         println!("This is the pool count: {:?}", pool_count);
     }
@@ -1,5 +1,4 @@
-use super::DB_VERSION;
-use crate::entity_record::RewardRecord;
+use crate::{db::entity::ChainCount, entity_record::RewardRecord};
 
 pub const REWARD_TABLE_NAME: &str = "reward";
 
@@ -8,25 +7,12 @@ pub const REWARD_TABLE_NAME: &str = "reward";
 pub async fn create_reward_table(
     db_client: &mut tokio_postgres::Client,
 ) -> Result<(), anyhow::Error> {
-    let executable = format!(
-        "
-            CREATE TABLE IF NOT EXISTS {REWARD_TABLE_NAME}{DB_VERSION} (
-               id              SERIAL PRIMARY KEY,
-               brand_id        VARCHAR NULL,
-               reward_address        VARCHAR NULL,
-               requestor_address        VARCHAR NULL,
-               initial_supply        VARCHAR NULL,
-               timestamp        VARCHAR NULL,
-               created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
-            )
-        "
-    );
-    db_client.batch_execute(&executable).await?;
-    Ok(())
+    RewardRecord::create_table(db_client).await
 }
 
 // function creates a new reward entity in the database
 pub async fn create_reward_if_does_not_exist(
+    chain_id: i64,
     brand_id: String,
     reward_address: String,
     requestor_address: String,
@@ -34,225 +20,86 @@ pub async fn create_reward_if_does_not_exist(
     timestamp: String,
     db_client: &mut tokio_postgres::Client,
 ) -> Result<(), anyhow::Error> {
-    let executable = format!(
-        "
-           INSERT INTO {REWARD_TABLE_NAME}{DB_VERSION} (brand_id, reward_address, requestor_address, initial_supply, timestamp)
-           VALUES ($1, $2, $3, $4, $5)
-        "
-    );
-
-    if check_if_reward_exist_by_reward_address(reward_address.clone(), db_client).await? {
+    if RewardRecord::exists_by_reward_address(&reward_address, Some(chain_id), db_client).await? {
         return Ok(());
     }
 
-    db_client
-        .execute(
-            &executable,
-            &[
-                &brand_id,
-                &reward_address,
-                &requestor_address,
-                &initial_supply,
-                &timestamp,
-            ],
-        )
-        .await?;
+    RewardRecord {
+        chain_id,
+        brand_id,
+        reward_address,
+        requestor_address,
+        initial_supply,
+        timestamp,
+        ..Default::default()
+    }
+    .insert(db_client)
+    .await?;
 
     Ok(())
 }
 
-// This function checks if the reward exists by the reward address in the database
-async fn check_if_reward_exist_by_reward_address(
-    reward_address: String,
-    db_client: &mut tokio_postgres::Client,
-) -> Result<bool, anyhow::Error> {
-    let executable = format!(
-        "
-            SELECT 1
-            FROM {REWARD_TABLE_NAME}{DB_VERSION}
-            WHERE reward_address = $1
-        "
-    );
-
-    let result = db_client.query(&executable, &[&reward_address]).await?;
-
-    Ok(!result.is_empty())
-}
-
 // This function checks if the reward exists in the database
 pub async fn check_if_reward_exist_by_brand_id(
     brand_id: String,
+    chain_id: Option<i64>,
     db_client: &mut tokio_postgres::Client,
 ) -> Result<bool, anyhow::Error> {
-    let executable = format!(
-        "
-            SELECT 1
-            FROM {REWARD_TABLE_NAME}{DB_VERSION}
-            WHERE brand_id = $1
-        "
-    );
-
-    let result = db_client.query(&executable, &[&brand_id]).await?;
-
-    Ok(!result.is_empty())
+    RewardRecord::exists_by_brand_id(&brand_id, chain_id, db_client).await
 }
 
 // this function returns all the count of reward entities in the database
 pub async fn get_total_rewards_count(
+    chain_id: Option<i64>,
     db_client: &mut tokio_postgres::Client,
 ) -> Result<i64, anyhow::Error> {
-    let executable = format!(
-        "
-            SELECT COUNT(*) as count
-            FROM {REWARD_TABLE_NAME}{DB_VERSION}
-        "
-    );
-
-    let result = db_client.query(&executable, &[]).await?;
+    RewardRecord::count(chain_id, db_client).await
+}
 
-    Ok(result[0].get(0))
+// this function returns the count of reward entities of each chain
+pub async fn get_reward_count_per_chain(
+    db_client: &mut tokio_postgres::Client,
+) -> Result<Vec<ChainCount>, anyhow::Error> {
+    RewardRecord::count_per_chain(db_client).await
 }
 
 pub async fn query_reward_by_brand_id(
     brand_id: String,
+    chain_id: Option<i64>,
     db_client: &mut tokio_postgres::Client,
 ) -> Result<RewardRecord, anyhow::Error> {
-    let executable = format!(
-        "
-            SELECT *
-            FROM {REWARD_TABLE_NAME}{DB_VERSION}
-            WHERE brand_id = $1
-        "
-    );
-
-    let result = db_client.query(&executable, &[&brand_id]).await?;
-
-    let reward_record = RewardRecord {
-        brand_id: result[0].get(1),
-        reward_address: result[0].get(2),
-        requestor_address: result[0].get(3),
-        initial_supply: result[0].get(4),
-        timestamp: result[0].get(5),
-        create_at: result[0].get(6),
-    };
-
-    Ok(reward_record)
+    RewardRecord::query_one_by_brand_id(&brand_id, chain_id, db_client).await
 }
 
 pub async fn query_reward_by_reward_address(
     reward_address: String,
+    chain_id: Option<i64>,
     db_client: &mut tokio_postgres::Client,
 ) -> Result<RewardRecord, anyhow::Error> {
-    let executable = format!(
-        "
-            SELECT *
-            FROM {REWARD_TABLE_NAME}{DB_VERSION}
-            WHERE reward_address = $1
-        "
-    );
-
-    let result = db_client.query(&executable, &[&reward_address]).await?;
-
-    let reward_record = RewardRecord {
-        brand_id: result[0].get(1),
-        reward_address: result[0].get(2),
-        requestor_address: result[0].get(3),
-        initial_supply: result[0].get(4),
-        timestamp: result[0].get(5),
-        create_at: result[0].get(6),
-    };
-
-    Ok(reward_record)
+    RewardRecord::query_one_by_reward_address(&reward_address, chain_id, db_client).await
 }
 
 pub async fn query_reward_by_requestor_address(
     requestor_address: String,
+    chain_id: Option<i64>,
     db_client: &mut tokio_postgres::Client,
 ) -> Result<RewardRecord, anyhow::Error> {
-    let executable = format!(
-        "
-            SELECT *
-            FROM {REWARD_TABLE_NAME}{DB_VERSION}
-            WHERE requestor_address = $1
-        "
-    );
-
-    let result = db_client.query(&executable, &[&requestor_address]).await?;
-
-    let reward_record = RewardRecord {
-        brand_id: result[0].get(1),
-        reward_address: result[0].get(2),
-        requestor_address: result[0].get(3),
-        initial_supply: result[0].get(4),
-        timestamp: result[0].get(5),
-        create_at: result[0].get(6),
-    };
-
-    Ok(reward_record)
+    RewardRecord::query_one_by_requestor_address(&requestor_address, chain_id, db_client).await
 }
 
 pub async fn query_all_rewards_paginated(
     db_client: &mut tokio_postgres::Client,
+    chain_id: Option<i64>,
     page_number: i64,
     page_size: i64,
 ) -> Result<Vec<RewardRecord>, anyhow::Error> {
-    let executable = format!(
-        "
-            SELECT * 
-            FROM {REWARD_TABLE_NAME}{DB_VERSION}
-            ORDER BY id DESC
-            LIMIT $1 OFFSET $2;
-        "
-    );
-
-    let offest = (page_number - 1) * page_size;
-
-    let result = db_client
-        .query(&executable, &[&page_size, &offest])
-        .await?;
-    let mut reward_record_buffer = Vec::new();
-
-    for row in result {
-        let reward_record = RewardRecord {
-            brand_id: row.get(1),
-            reward_address: row.get(2),
-            requestor_address: row.get(3),
-            initial_supply: row.get(4),
-            timestamp: row.get(5),
-            create_at: row.get(6),
-        };
-        reward_record_buffer.push(reward_record);
-    }
-
-    Ok(reward_record_buffer)
+    RewardRecord::query_paginated(chain_id, db_client, page_number, page_size).await
 }
 
 pub async fn query_all_rewards_owned_by_a_brand(
     brand_id: String,
+    chain_id: Option<i64>,
     db_client: &mut tokio_postgres::Client,
 ) -> Result<Vec<RewardRecord>, anyhow::Error> {
-    let executable = format!(
-        "
-            SELECT * 
-            FROM {REWARD_TABLE_NAME}{DB_VERSION}
-            WHERE brand_id = $1
-        "
-    );
-
-    let result = db_client.query(&executable, &[&brand_id]).await?;
-    let mut reward_record_buffer = Vec::new();
-
-    for row in result {
-        let reward_record = RewardRecord {
-            brand_id: row.get(1),
-            reward_address: row.get(2),
-            requestor_address: row.get(3),
-            initial_supply: row.get(4),
-            timestamp: row.get(5),
-            create_at: row.get(6),
-        };
-        reward_record_buffer.push(reward_record);
-    }
-
-    Ok(reward_record_buffer)
+    RewardRecord::query_by_brand_id(&brand_id, chain_id, db_client).await
 }
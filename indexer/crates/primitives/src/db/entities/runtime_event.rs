@@ -0,0 +1,82 @@
+use super::{
+    begin_block_transaction, finish_block_transaction,
+    system::{update_last_block_number, CheckpointKey},
+};
+use crate::{entity_record::RuntimeEventRecord, indexer::ChronicleRuntimeEvent};
+
+pub const RUNTIME_EVENT_TABLE_NAME: &str = "chronicle_runtime_event";
+
+/// This function is used to create the runtime event table in the database if one does not exist already.
+/// The events are keyed by the genesis hash of their chain, Substrate chains have no EIP-155 chain id.
+pub async fn create_runtime_event_table(
+    db_client: &mut tokio_postgres::Client,
+) -> Result<(), anyhow::Error> {
+    RuntimeEventRecord::create_table(db_client).await
+}
+
+/// This function writes the runtime events of one block and moves the checkpoint of the
+/// indexer to the block, in one transaction. The events are only read from finalized blocks,
+/// so unlike the EVM tables the rows are not journaled for a reorg rollback.
+pub async fn store_block_runtime_events(
+    events: &[ChronicleRuntimeEvent],
+    db_client: &mut tokio_postgres::Client,
+    checkpoint: &CheckpointKey,
+) -> Result<(), anyhow::Error> {
+    if events.is_empty() {
+        return Ok(());
+    }
+
+    begin_block_transaction(db_client).await?;
+    let result = apply_block_runtime_events(events, db_client, checkpoint).await;
+    finish_block_transaction(result, db_client).await
+}
+
+/// This function stores the runtime events of one block and moves the checkpoint to it, it does not
+/// open a transaction, call it between [`begin_block_transaction`] and [`finish_block_transaction`]
+pub async fn apply_block_runtime_events(
+    events: &[ChronicleRuntimeEvent],
+    db_client: &mut tokio_postgres::Client,
+    checkpoint: &CheckpointKey,
+) -> Result<(), anyhow::Error> {
+    let Some(block) = events.first() else {
+        return Ok(());
+    };
+    if events
+        .iter()
+        .any(|event| event.block_number != block.block_number)
+    {
+        anyhow::bail!("The runtime events of a block write must all be from the same block");
+    }
+
+    for event in events {
+        create_runtime_event_if_does_not_exist(event, db_client).await?;
+    }
+    update_last_block_number(checkpoint, block.block_number.to_string(), db_client).await?;
+
+    Ok(())
+}
+
+/// This function stores a runtime event, an event that was already stored is ignored
+pub async fn create_runtime_event_if_does_not_exist(
+    event: &ChronicleRuntimeEvent,
+    db_client: &mut tokio_postgres::Client,
+) -> Result<(), anyhow::Error> {
+    RuntimeEventRecord {
+        genesis_hash: event.genesis_hash.to_string(),
+        block_number: event.block_number.to_string(),
+        block_hash: event.block_hash.to_string(),
+        phase: event.phase.clone(),
+        extrinsic_index: event
+            .extrinsic_index
+            .map(|extrinsic_index| extrinsic_index.to_string()),
+        event_index: event.event_index.to_string(),
+        pallet: event.pallet.clone(),
+        event_name: event.event.clone(),
+        fields: event.fields.to_string(),
+        ..Default::default()
+    }
+    .insert(db_client)
+    .await?;
+
+    Ok(())
+}
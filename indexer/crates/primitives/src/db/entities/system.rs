@@ -1,9 +1,44 @@
 //! This function would be used for in-chronicle persistent data management
 pub const SYSTEM_TABLE_NAME: &str = "chronicle_system";
 use super::{
-    brand::create_brand_table, pool::create_pool_table, redemption::create_redemption_table,
-    reward::create_reward_table, DB_VERSION,
+    brand::create_brand_table, gateway::create_gateway_table,
+    internal_transfer::create_internal_transfer_table, payment::create_payment_table,
+    pool::create_pool_table, redemption::create_redemption_table, reward::create_reward_table,
+    runtime_event::create_runtime_event_table, token_whitelist::create_token_whitelist_tables,
+    transaction::create_transaction_table, treasury::create_treasury_ledger_table, DB_VERSION,
 };
+use crate::db::{
+    pending_event::create_pending_event_table,
+    raw_chronicle_event::{create_new_event_db_table, RAW_EVENT_TABLE_NAME},
+    reorg::create_reorg_tables,
+};
+
+/// This identifies the progress of a single indexer, each indexer keeps
+/// its own checkpoint row so indexers running concurrently do not clobber
+/// each other's last indexed block
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CheckpointKey {
+    pub indexer_name: String,
+    pub chain_id: u64,
+    pub contract_address: String,
+    pub event_signature: String,
+}
+
+impl CheckpointKey {
+    pub fn new(
+        indexer_name: String,
+        chain_id: u64,
+        contract_address: String,
+        event_signature: String,
+    ) -> Self {
+        Self {
+            indexer_name,
+            chain_id,
+            contract_address,
+            event_signature,
+        }
+    }
+}
 
 /// This function is literally used to create a new system table in the
 /// database if anyone does not exist already
@@ -14,7 +49,12 @@ pub async fn create_system_table(
         "
                CREATE TABLE IF NOT EXISTS {SYSTEM_TABLE_NAME}{DB_VERSION} (
                    id              SERIAL PRIMARY KEY,
-                   last_block_number    VARCHAR NULL
+                   indexer_name    VARCHAR NOT NULL,
+                   chain_id        BIGINT NOT NULL,
+                   contract_address    VARCHAR NOT NULL,
+                   event_signature     VARCHAR NOT NULL,
+                   last_block_number    BIGINT NULL,
+                   UNIQUE (indexer_name, chain_id, contract_address, event_signature)
             )
         "
     );
@@ -24,44 +64,76 @@ pub async fn create_system_table(
     create_pool_table(db_client).await?;
     create_redemption_table(db_client).await?;
     create_reward_table(db_client).await?;
+    create_payment_table(db_client).await?;
+    create_gateway_table(db_client).await?;
+    create_token_whitelist_tables(db_client).await?;
+    create_treasury_ledger_table(db_client).await?;
+    create_transaction_table(db_client).await?;
+    create_internal_transfer_table(db_client).await?;
+    create_runtime_event_table(db_client).await?;
+    create_new_event_db_table(db_client, &format!("{RAW_EVENT_TABLE_NAME}{DB_VERSION}")).await?;
+    create_reorg_tables(db_client).await?;
+    create_pending_event_table(db_client).await?;
 
     Ok(())
 }
 
-/// This function creates a new system entity in the database
-/// though this would be called only once
+/// This function creates the checkpoint entity of an indexer in the database,
+/// resetting it to `last_block_number` if it already exists
 pub async fn create_system_if_does_not_exist(
+    checkpoint: &CheckpointKey,
     last_block_number: String,
     db_client: &mut tokio_postgres::Client,
 ) -> Result<(), anyhow::Error> {
     let executable = format!(
         "
-            INSERT INTO {SYSTEM_TABLE_NAME}{DB_VERSION} (id, last_block_number)
-            VALUES (1, $1)
-            ON CONFLICT (id) 
+            INSERT INTO {SYSTEM_TABLE_NAME}{DB_VERSION} (indexer_name, chain_id, contract_address, event_signature, last_block_number)
+            VALUES ($1, $2, $3, $4, $5::TEXT::BIGINT)
+            ON CONFLICT (indexer_name, chain_id, contract_address, event_signature)
             DO UPDATE SET last_block_number = EXCLUDED.last_block_number;
         "
     );
 
     db_client
-        .execute(&executable, &[&last_block_number])
+        .execute(
+            &executable,
+            &[
+                &checkpoint.indexer_name,
+                &(checkpoint.chain_id as i64),
+                &checkpoint.contract_address,
+                &checkpoint.event_signature,
+                &last_block_number,
+            ],
+        )
         .await?;
 
     Ok(())
 }
 
-/// This function is used to get the last block number from the system table
-/// in the database
+/// This function is used to get the last block number of an indexer from
+/// the system table in the database, "0" is returned if the indexer has no checkpoint yet
 pub async fn get_last_block_number(
+    checkpoint: &CheckpointKey,
     db_client: &mut tokio_postgres::Client,
 ) -> Result<String, anyhow::Error> {
     let executable = format!(
         "
-            SELECT last_block_number FROM {SYSTEM_TABLE_NAME}{DB_VERSION} WHERE id = 1
+            SELECT COALESCE(last_block_number, 0)::TEXT FROM {SYSTEM_TABLE_NAME}{DB_VERSION}
+            WHERE indexer_name = $1 AND chain_id = $2 AND contract_address = $3 AND event_signature = $4
         "
     );
 
-    let rows = db_client.query(&executable, &[]).await?;
+    let rows = db_client
+        .query(
+            &executable,
+            &[
+                &checkpoint.indexer_name,
+                &(checkpoint.chain_id as i64),
+                &checkpoint.contract_address,
+                &checkpoint.event_signature,
+            ],
+        )
+        .await?;
     let last_block_number: String = if rows.is_empty() {
         "0".to_string()
     } else {
@@ -71,20 +143,31 @@ pub async fn get_last_block_number(
     Ok(last_block_number)
 }
 
-/// This function is used to update the last block number in the system table
-/// in the database
+/// This function is used to update the last block number of an indexer in
+/// the system table in the database
 pub async fn update_last_block_number(
+    checkpoint: &CheckpointKey,
     last_block_number: String,
     db_client: &mut tokio_postgres::Client,
 ) -> Result<(), anyhow::Error> {
     let executable = format!(
         "
-            UPDATE {SYSTEM_TABLE_NAME}{DB_VERSION} SET last_block_number = $1 WHERE id = 1
+            UPDATE {SYSTEM_TABLE_NAME}{DB_VERSION} SET last_block_number = $5::TEXT::BIGINT
+            WHERE indexer_name = $1 AND chain_id = $2 AND contract_address = $3 AND event_signature = $4
         "
     );
 
     db_client
-        .execute(&executable, &[&last_block_number])
+        .execute(
+            &executable,
+            &[
+                &checkpoint.indexer_name,
+                &(checkpoint.chain_id as i64),
+                &checkpoint.contract_address,
+                &checkpoint.event_signature,
+                &last_block_number,
+            ],
+        )
         .await?;
 
     Ok(())
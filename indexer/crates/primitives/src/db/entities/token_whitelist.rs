@@ -0,0 +1,192 @@
+use crate::{
+    db::entity::ChronicleEntity,
+    entity_record::{TokenWhitelistChangeRecord, TokenWhitelistRecord},
+};
+
+pub const TOKEN_WHITELIST_TABLE_NAME: &str = "token_whitelist";
+pub const TOKEN_WHITELIST_HISTORY_TABLE_NAME: &str = "token_whitelist_history";
+
+/// This is the change a whitelist event made to the tokens of a gateway
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenListAction {
+    Whitelisted,
+    Delisted,
+}
+
+impl TokenListAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Whitelisted => "whitelisted",
+            Self::Delisted => "delisted",
+        }
+    }
+}
+
+/// This function is used to create the token whitelist and the token whitelist history
+/// tables in the database if they do not exist already.
+/// The whitelist holds the latest change of every (chain, gateway, token), the history holds every change.
+pub async fn create_token_whitelist_tables(
+    db_client: &mut tokio_postgres::Client,
+) -> Result<(), anyhow::Error> {
+    TokenWhitelistRecord::create_table(db_client).await?;
+    TokenWhitelistChangeRecord::create_table(db_client).await
+}
+
+/// This function records a whitelist change of a gateway token.
+/// The change is appended to the history, a log that was already stored is ignored.
+/// The whitelist is only moved to the change if it is newer than the one it holds, so the
+/// `TokenWhitelisted` and `TokenDelisted` indexers can apply their logs in any order.
+#[allow(clippy::too_many_arguments)]
+pub async fn record_token_whitelist_change(
+    chain_id: i64,
+    gateway_address: String,
+    token_address: String,
+    action: TokenListAction,
+    event_timestamp: String,
+    block_number: i64,
+    transaction_hash: String,
+    log_index: i64,
+    db_client: &mut tokio_postgres::Client,
+) -> Result<(), anyhow::Error> {
+    TokenWhitelistChangeRecord {
+        chain_id,
+        gateway_address: gateway_address.clone(),
+        token_address: token_address.clone(),
+        action: action.as_str().to_string(),
+        event_timestamp,
+        block_number: block_number.to_string(),
+        transaction_hash: transaction_hash.clone(),
+        log_index: log_index.to_string(),
+        ..Default::default()
+    }
+    .insert(db_client)
+    .await?;
+
+    let whitelist_executable = format!(
+        "
+            INSERT INTO {} AS whitelist (chain_id, gateway_address, token_address, whitelisted, block_number, transaction_hash, log_index)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            ON CONFLICT (chain_id, gateway_address, token_address) DO UPDATE
+            SET whitelisted = EXCLUDED.whitelisted,
+                block_number = EXCLUDED.block_number,
+                transaction_hash = EXCLUDED.transaction_hash,
+                log_index = EXCLUDED.log_index
+            WHERE (EXCLUDED.block_number, EXCLUDED.log_index) > (whitelist.block_number, whitelist.log_index)
+        ",
+        TokenWhitelistRecord::table()
+    );
+
+    db_client
+        .execute(
+            &whitelist_executable,
+            &[
+                &chain_id,
+                &gateway_address,
+                &token_address,
+                &(action == TokenListAction::Whitelisted),
+                &block_number,
+                &transaction_hash,
+                &log_index,
+            ],
+        )
+        .await?;
+
+    Ok(())
+}
+
+/// This function returns the tokens the gateway currently accepts,
+/// on every chain when `chain_id` is `None`
+pub async fn query_current_whitelist(
+    gateway_address: String,
+    chain_id: Option<i64>,
+    db_client: &mut tokio_postgres::Client,
+) -> Result<Vec<TokenWhitelistRecord>, anyhow::Error> {
+    // The numbers are selected as text, the order names the table so it sorts the stored numbers
+    let table = TokenWhitelistRecord::table();
+    let executable = format!(
+        "
+            {}
+            WHERE LOWER(gateway_address) = LOWER($1) AND whitelisted
+                AND ($2::BIGINT IS NULL OR chain_id = $2)
+            ORDER BY {table}.chain_id ASC, {table}.block_number ASC, {table}.log_index ASC
+        ",
+        TokenWhitelistRecord::select_sql()
+    );
+
+    let result = db_client
+        .query(&executable, &[&gateway_address, &chain_id])
+        .await?;
+
+    Ok(result
+        .iter()
+        .map(TokenWhitelistRecord::from_row)
+        .collect::<Result<_, _>>()?)
+}
+
+/// This function returns the tokens the gateway accepted at `block_number`,
+/// that is the tokens whose latest change up to and including the block is a whitelisting
+pub async fn query_whitelist_at_block(
+    gateway_address: String,
+    chain_id: Option<i64>,
+    block_number: i64,
+    db_client: &mut tokio_postgres::Client,
+) -> Result<Vec<TokenWhitelistChangeRecord>, anyhow::Error> {
+    let table = TokenWhitelistChangeRecord::table();
+    let executable = format!(
+        "
+            {}
+            WHERE id IN (
+                SELECT DISTINCT ON (chain_id, LOWER(token_address)) id
+                FROM {table}
+                WHERE LOWER(gateway_address) = LOWER($1) AND block_number <= $2
+                    AND ($4::BIGINT IS NULL OR chain_id = $4)
+                ORDER BY chain_id, LOWER(token_address), block_number DESC, log_index DESC
+            ) AND action = $3
+            ORDER BY {table}.chain_id ASC, {table}.block_number ASC, {table}.log_index ASC
+        ",
+        TokenWhitelistChangeRecord::select_sql()
+    );
+
+    let result = db_client
+        .query(
+            &executable,
+            &[
+                &gateway_address,
+                &block_number,
+                &TokenListAction::Whitelisted.as_str(),
+                &chain_id,
+            ],
+        )
+        .await?;
+
+    Ok(result
+        .iter()
+        .map(TokenWhitelistChangeRecord::from_row)
+        .collect::<Result<_, _>>()?)
+}
+
+/// This function returns every whitelist change of the gateway, oldest first
+pub async fn query_whitelist_history(
+    gateway_address: String,
+    chain_id: Option<i64>,
+    db_client: &mut tokio_postgres::Client,
+) -> Result<Vec<TokenWhitelistChangeRecord>, anyhow::Error> {
+    let table = TokenWhitelistChangeRecord::table();
+    let executable = format!(
+        "
+            {}
+            WHERE LOWER(gateway_address) = LOWER($1) AND ($2::BIGINT IS NULL OR chain_id = $2)
+            ORDER BY {table}.chain_id ASC, {table}.block_number ASC, {table}.log_index ASC, id ASC
+        ",
+        TokenWhitelistChangeRecord::select_sql()
+    );
+
+    let result = db_client
+        .query(&executable, &[&gateway_address, &chain_id])
+        .await?;
+
+    Ok(result
+        .iter()
+        .map(TokenWhitelistChangeRecord::from_row)
+        .collect::<Result<_, _>>()?)
+}
@@ -0,0 +1,198 @@
+use super::{
+    begin_block_transaction, finish_block_transaction,
+    system::{update_last_block_number, CheckpointKey},
+};
+use crate::{
+    calldata::calldata_selector,
+    db::{
+        entity::{count_entities, ChronicleEntity},
+        reorg::{record_block_hash, set_journal_block},
+    },
+    entity_record::TransactionRecord,
+    indexer::ChronicleTransaction,
+};
+
+pub const TRANSACTION_TABLE_NAME: &str = "chronicle_transaction";
+
+/// This is the condition of the transactions sent from or to the address bound to `$1`,
+/// on the chain bound to `$2` or on every chain when it is `NULL`
+const ADDRESS_CONDITION: &str = "(LOWER(from_address) = LOWER($1) OR LOWER(to_address) = LOWER($1))
+    AND ($2::BIGINT IS NULL OR chain_id = $2)";
+
+/// This function is used to create the transaction table in the database if one does not exist already.
+/// A transaction is stored once per chain, whichever indexer found it first.
+pub async fn create_transaction_table(
+    db_client: &mut tokio_postgres::Client,
+) -> Result<(), anyhow::Error> {
+    TransactionRecord::create_table(db_client).await
+}
+
+/// This function writes the indexed transactions of one block and moves the checkpoint of the
+/// indexer to the block, in one transaction. Like the events of a block, the rows are journaled
+/// against the block so they are rolled back on a reorg.
+pub async fn store_block_transactions(
+    transactions: &[ChronicleTransaction],
+    db_client: &mut tokio_postgres::Client,
+    checkpoint: &CheckpointKey,
+) -> Result<(), anyhow::Error> {
+    if transactions.is_empty() {
+        return Ok(());
+    }
+
+    begin_block_transaction(db_client).await?;
+    let result = apply_block_transactions(transactions, db_client, checkpoint).await;
+    finish_block_transaction(result, db_client).await
+}
+
+/// This function stores the transactions of one block and moves the checkpoint to it, it does not
+/// open a transaction, call it between [`begin_block_transaction`] and [`finish_block_transaction`]
+pub async fn apply_block_transactions(
+    transactions: &[ChronicleTransaction],
+    db_client: &mut tokio_postgres::Client,
+    checkpoint: &CheckpointKey,
+) -> Result<(), anyhow::Error> {
+    let Some(block) = transactions.first() else {
+        return Ok(());
+    };
+    if transactions
+        .iter()
+        .any(|transaction| transaction.block_number != block.block_number)
+    {
+        anyhow::bail!("The transactions of a block write must all be from the same block");
+    }
+
+    set_journal_block(checkpoint, block.block_number, db_client).await?;
+    for transaction in transactions {
+        // The transactions are tagged with the chain of the indexer the block is written by
+        create_transaction_if_does_not_exist(checkpoint.chain_id as i64, transaction, db_client)
+            .await?;
+    }
+
+    record_block_hash(
+        checkpoint,
+        block.block_number,
+        block.block_hash.to_string(),
+        db_client,
+    )
+    .await?;
+    update_last_block_number(checkpoint, block.block_number.to_string(), db_client).await?;
+
+    Ok(())
+}
+
+/// This function stores a transaction with its receipt and decoded call,
+/// a transaction that was already stored is ignored
+pub async fn create_transaction_if_does_not_exist(
+    chain_id: i64,
+    transaction: &ChronicleTransaction,
+    db_client: &mut tokio_postgres::Client,
+) -> Result<(), anyhow::Error> {
+    let decoded_call = transaction.decoded_call.as_ref();
+
+    TransactionRecord {
+        chain_id,
+        hash: transaction.hash.to_string(),
+        nonce: transaction.nonce.to_string(),
+        block_hash: transaction.block_hash.to_string(),
+        block_number: transaction.block_number.to_string(),
+        block_timestamp: transaction.block_timestamp.to_string(),
+        transaction_index: transaction.transaction_index.to_string(),
+        from_address: transaction.from.to_string(),
+        to_address: transaction
+            .contract_address
+            .is_none()
+            .then(|| transaction.to.to_string()),
+        value: transaction.value.to_string(),
+        gas_price: transaction.gas_price.to_string(),
+        gas: transaction.gas.to_string(),
+        max_fee_per_gas: transaction.max_fee_per_gas.to_string(),
+        input: transaction.data.to_string(),
+        status: transaction.status,
+        gas_used: transaction.gas_used.to_string(),
+        effective_gas_price: transaction.effective_gas_price.to_string(),
+        contract_address: transaction
+            .contract_address
+            .map(|contract_address| contract_address.to_string()),
+        function_selector: calldata_selector(&transaction.data),
+        function_name: decoded_call.map(|call| call.name.clone()),
+        function_args: decoded_call.map(|call| call.args.to_string()),
+        revert_data: transaction
+            .revert_data
+            .as_ref()
+            .map(|revert_data| revert_data.to_string()),
+        revert_reason: transaction.revert_reason.clone(),
+        ..Default::default()
+    }
+    .insert(db_client)
+    .await?;
+
+    Ok(())
+}
+
+// This function returns the count of transactions sent from or to an address
+pub async fn get_total_count_transactions(
+    address: &str,
+    chain_id: Option<i64>,
+    db_client: &mut tokio_postgres::Client,
+) -> Result<i64, anyhow::Error> {
+    count_entities::<TransactionRecord>(Some(ADDRESS_CONDITION), &[&address, &chain_id], db_client)
+        .await
+}
+
+/// This function returns the transactions sent from or to an address, newest first
+pub async fn query_transactions_paginated(
+    address: &str,
+    chain_id: Option<i64>,
+    db_client: &mut tokio_postgres::Client,
+    page_number: i64,
+    page_size: i64,
+) -> Result<Vec<TransactionRecord>, anyhow::Error> {
+    // The numbers are selected as text, the order names the table so it sorts the stored numbers
+    let table = TransactionRecord::table();
+    let executable = format!(
+        "
+            {}
+            WHERE {ADDRESS_CONDITION}
+            ORDER BY {table}.block_number DESC, {table}.transaction_index DESC, {table}.chain_id ASC
+            LIMIT $3 OFFSET $4
+        ",
+        TransactionRecord::select_sql()
+    );
+
+    let offset = (page_number - 1) * page_size;
+
+    let result = db_client
+        .query(&executable, &[&address, &chain_id, &page_size, &offset])
+        .await?;
+
+    Ok(result
+        .iter()
+        .map(TransactionRecord::from_row)
+        .collect::<Result<_, _>>()?)
+}
+
+/// This function returns the transaction with the hash, `None` if it was not indexed
+pub async fn query_transaction_by_hash(
+    hash: &str,
+    chain_id: Option<i64>,
+    db_client: &mut tokio_postgres::Client,
+) -> Result<Option<TransactionRecord>, anyhow::Error> {
+    let executable = format!(
+        "
+            {}
+            WHERE LOWER(hash) = LOWER($1) AND ($2::BIGINT IS NULL OR chain_id = $2)
+            ORDER BY chain_id ASC
+            LIMIT 1
+        ",
+        TransactionRecord::select_sql()
+    );
+
+    let result = db_client
+        .query_opt(&executable, &[&hash, &chain_id])
+        .await?;
+
+    Ok(result
+        .as_ref()
+        .map(TransactionRecord::from_row)
+        .transpose()?)
+}
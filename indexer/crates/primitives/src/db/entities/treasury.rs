@@ -0,0 +1,225 @@
+use super::DB_VERSION;
+use crate::{
+    db::entity::{count_entities, ChronicleEntity},
+    entity_record::{TreasuryDailyRecord, TreasuryEntryRecord},
+};
+
+pub const TREASURY_LEDGER_TABLE_NAME: &str = "treasury_ledger";
+
+/// This is the signed change an entry makes to the balance of its gateway token,
+/// failed withdrawals are only flagged and leave the balance untouched
+pub const ENTRY_DELTA: &str = "
+    CASE entry_type
+        WHEN 'credit' THEN amount
+        WHEN 'debit' THEN -amount
+        ELSE 0
+    END
+";
+
+/// This is the kind of a treasury ledger entry
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TreasuryEntryType {
+    /// A payment received by the gateway
+    Credit,
+    /// A withdrawal out of the gateway
+    Debit,
+    /// A withdrawal the gateway could not send
+    WithdrawalFailed,
+}
+
+impl TreasuryEntryType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Credit => "credit",
+            Self::Debit => "debit",
+            Self::WithdrawalFailed => "withdrawal_failed",
+        }
+    }
+}
+
+/// This is the condition of the entries of a gateway token bound to `$1` and `$2`,
+/// on the chain bound to `$3` or on every chain when it is `NULL`
+const GATEWAY_TOKEN_CONDITION: &str =
+    "LOWER(gateway_address) = LOWER($1) AND LOWER(token_address) = LOWER($2)
+    AND ($3::BIGINT IS NULL OR chain_id = $3)";
+
+/// This function is used to create a new treasury ledger table in the database
+/// if one does not exist already
+pub async fn create_treasury_ledger_table(
+    db_client: &mut tokio_postgres::Client,
+) -> Result<(), anyhow::Error> {
+    TreasuryEntryRecord::create_table(db_client).await
+}
+
+/// This function creates a new treasury ledger entry in the database,
+/// a log that was already stored is ignored.
+/// The `amount` is the decimal string of an unsigned 256 bit integer.
+#[allow(clippy::too_many_arguments)]
+pub async fn create_treasury_entry_if_does_not_exist(
+    chain_id: i64,
+    gateway_address: String,
+    token_address: String,
+    entry_type: TreasuryEntryType,
+    amount: String,
+    counterparty: String,
+    block_number: i64,
+    block_timestamp: i64,
+    transaction_hash: String,
+    log_index: i64,
+    db_client: &mut tokio_postgres::Client,
+) -> Result<(), anyhow::Error> {
+    TreasuryEntryRecord {
+        chain_id,
+        gateway_address,
+        token_address,
+        entry_type: entry_type.as_str().to_string(),
+        amount,
+        counterparty,
+        block_number: block_number.to_string(),
+        block_timestamp: block_timestamp.to_string(),
+        transaction_hash,
+        log_index: log_index.to_string(),
+        ..Default::default()
+    }
+    .insert(db_client)
+    .await?;
+
+    Ok(())
+}
+
+// This function returns the count of ledger entries of a gateway token
+pub async fn get_total_count_treasury_entries(
+    gateway_address: &str,
+    token_address: &str,
+    chain_id: Option<i64>,
+    db_client: &mut tokio_postgres::Client,
+) -> Result<i64, anyhow::Error> {
+    count_entities::<TreasuryEntryRecord>(
+        Some(GATEWAY_TOKEN_CONDITION),
+        &[&gateway_address, &token_address, &chain_id],
+        db_client,
+    )
+    .await
+}
+
+/// This function returns the ledger entries of a gateway token newest first,
+/// each with the balance of the gateway on its chain right after the entry.
+/// The balance is summed before the page is cut, so it covers the entries of earlier pages.
+pub async fn query_treasury_entries_paginated(
+    gateway_address: &str,
+    token_address: &str,
+    chain_id: Option<i64>,
+    db_client: &mut tokio_postgres::Client,
+    page_number: i64,
+    page_size: i64,
+) -> Result<Vec<TreasuryEntryRecord>, anyhow::Error> {
+    // The numbers are selected as text, the order names the table so it sorts the stored numbers
+    let table = TreasuryEntryRecord::table();
+    let executable = format!(
+        "
+            {}
+            WHERE {GATEWAY_TOKEN_CONDITION}
+            ORDER BY {table}.block_number DESC, {table}.log_index DESC, {table}.chain_id ASC
+            LIMIT $4 OFFSET $5
+        ",
+        TreasuryEntryRecord::select_sql()
+    );
+
+    let offset = (page_number - 1) * page_size;
+
+    let result = db_client
+        .query(
+            &executable,
+            &[
+                &gateway_address,
+                &token_address,
+                &chain_id,
+                &page_size,
+                &offset,
+            ],
+        )
+        .await?;
+
+    Ok(result
+        .iter()
+        .map(TreasuryEntryRecord::from_row)
+        .collect::<Result<_, _>>()?)
+}
+
+/// This function returns the daily rollups of a gateway token oldest first, days are in UTC.
+/// Each chain has its own rollups.
+pub async fn query_treasury_daily_rollups(
+    gateway_address: &str,
+    token_address: &str,
+    chain_id: Option<i64>,
+    db_client: &mut tokio_postgres::Client,
+) -> Result<Vec<TreasuryDailyRecord>, anyhow::Error> {
+    let executable = format!(
+        "
+            SELECT
+                chain_id, day, credits::TEXT AS credits, debits::TEXT AS debits,
+                failed_withdrawals, net::TEXT AS net,
+                (SUM(net) OVER (PARTITION BY chain_id ORDER BY day ASC))::TEXT AS closing_balance
+            FROM (
+                SELECT
+                    chain_id,
+                    TO_CHAR(TO_TIMESTAMP(block_timestamp) AT TIME ZONE 'UTC', 'YYYY-MM-DD') AS day,
+                    COALESCE(SUM(amount) FILTER (WHERE entry_type = 'credit'), 0) AS credits,
+                    COALESCE(SUM(amount) FILTER (WHERE entry_type = 'debit'), 0) AS debits,
+                    COUNT(*) FILTER (WHERE entry_type = 'withdrawal_failed') AS failed_withdrawals,
+                    SUM({ENTRY_DELTA}) AS net
+                FROM {TREASURY_LEDGER_TABLE_NAME}{DB_VERSION}
+                WHERE {GATEWAY_TOKEN_CONDITION}
+                GROUP BY chain_id, day
+            ) AS days
+            ORDER BY chain_id ASC, day ASC
+        "
+    );
+
+    let result = db_client
+        .query(&executable, &[&gateway_address, &token_address, &chain_id])
+        .await?;
+
+    Ok(result
+        .iter()
+        .map(|record| TreasuryDailyRecord {
+            chain_id: record.get("chain_id"),
+            gateway_address: gateway_address.to_string(),
+            token_address: token_address.to_string(),
+            day: record.get("day"),
+            credits: record.get("credits"),
+            debits: record.get("debits"),
+            failed_withdrawals: record.get("failed_withdrawals"),
+            net: record.get("net"),
+            closing_balance: record.get("closing_balance"),
+        })
+        .collect())
+}
+
+/// This function returns the indexed balance of a gateway token on a chain at the end of
+/// `block_number`, as a decimal string
+pub async fn query_treasury_balance_at_block(
+    chain_id: i64,
+    gateway_address: &str,
+    token_address: &str,
+    block_number: i64,
+    db_client: &mut tokio_postgres::Client,
+) -> Result<String, anyhow::Error> {
+    let executable = format!(
+        "
+            SELECT (COALESCE(SUM({ENTRY_DELTA}), 0))::TEXT
+            FROM {TREASURY_LEDGER_TABLE_NAME}{DB_VERSION}
+            WHERE LOWER(gateway_address) = LOWER($1) AND LOWER(token_address) = LOWER($2)
+                AND block_number <= $3 AND chain_id = $4
+        "
+    );
+
+    let result = db_client
+        .query_one(
+            &executable,
+            &[&gateway_address, &token_address, &block_number, &chain_id],
+        )
+        .await?;
+
+    Ok(result.get(0))
+}
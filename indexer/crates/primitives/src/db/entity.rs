@@ -0,0 +1,316 @@
+//! This module holds the [`ChronicleEntity`] trait implemented by `#[derive(ChronicleEntity)]`,
+//! and the queries every entity table shares. Rows are mapped to records by column name,
+//! so the records do not depend on the column order of their table.
+pub use chronicle_macros::ChronicleEntity;
+pub use tokio_postgres::{types::ToSql, Client, Error as SqlError, Row};
+
+use super::entities::DB_VERSION;
+
+pub type EntityResult<T> = Result<T, anyhow::Error>;
+
+/// This is a record stored in its own table, see the `ChronicleEntity` derive macro
+pub trait ChronicleEntity: Sized {
+    /// This is the table name, without the `DB_VERSION` suffix
+    const TABLE_NAME: &'static str;
+    /// These are the stored columns, in field order
+    const COLUMNS: &'static [&'static str];
+    /// These are the columns set on insert, the insertion timestamp is set by the database
+    const INSERT_COLUMNS: &'static [&'static str];
+    /// These are the casts of the [`Self::INSERT_COLUMNS`] values, numbers are bound as text
+    const INSERT_CASTS: &'static [&'static str];
+    const COLUMN_DEFINITIONS: &'static [&'static str];
+    const UNIQUE_CONSTRAINTS: &'static [&'static str];
+    /// These are the indexes of the table, by name suffix and indexed expression
+    const INDEXES: &'static [(&'static str, &'static str)];
+
+    /// These are the selected columns and computed fields, numbers are read as text
+    fn select_expressions() -> Vec<String>;
+
+    /// These are the values of [`Self::INSERT_COLUMNS`]
+    fn insert_values(&self) -> Vec<Box<dyn ToSql + Sync + Send>>;
+
+    fn from_row(row: &Row) -> Result<Self, SqlError>;
+
+    fn table() -> String {
+        format!("{}{DB_VERSION}", Self::TABLE_NAME)
+    }
+
+    fn create_table_sql() -> String {
+        let definitions = std::iter::once("id SERIAL PRIMARY KEY".to_string())
+            .chain(
+                Self::COLUMN_DEFINITIONS
+                    .iter()
+                    .map(|column| column.to_string()),
+            )
+            .chain(
+                Self::UNIQUE_CONSTRAINTS
+                    .iter()
+                    .map(|columns| format!("UNIQUE ({columns})")),
+            )
+            .collect::<Vec<_>>()
+            .join(",\n");
+
+        let table = Self::table();
+        let indexes = Self::INDEXES
+            .iter()
+            .map(|(name, expression)| {
+                format!(";\nCREATE INDEX IF NOT EXISTS {table}_{name} ON {table} ({expression})")
+            })
+            .collect::<String>();
+
+        format!("CREATE TABLE IF NOT EXISTS {table} (\n{definitions}\n){indexes}")
+    }
+
+    /// An entity conflicting with a unique constraint is not inserted
+    fn insert_sql() -> String {
+        let placeholders = Self::INSERT_CASTS
+            .iter()
+            .enumerate()
+            .map(|(index, cast)| format!("${}{cast}", index + 1))
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        format!(
+            "INSERT INTO {} ({}) VALUES ({placeholders}) ON CONFLICT DO NOTHING",
+            Self::table(),
+            Self::INSERT_COLUMNS.join(", ")
+        )
+    }
+
+    fn select_sql() -> String {
+        format!(
+            "SELECT id, {} FROM {}",
+            Self::select_expressions().join(", "),
+            Self::table()
+        )
+    }
+}
+
+/// This function creates the table of an entity if one does not exist already
+pub async fn create_entity_table<E: ChronicleEntity>(db_client: &mut Client) -> EntityResult<()> {
+    db_client.batch_execute(&E::create_table_sql()).await?;
+    Ok(())
+}
+
+/// This function stores an entity, it returns false when the entity
+/// conflicted with a stored one and was ignored
+pub async fn insert_entity<E: ChronicleEntity>(
+    entity: &E,
+    db_client: &mut Client,
+) -> EntityResult<bool> {
+    let values = entity.insert_values();
+    let params: Vec<&(dyn ToSql + Sync)> = values
+        .iter()
+        .map(|value| value.as_ref() as &(dyn ToSql + Sync))
+        .collect();
+
+    let inserted = db_client.execute(&E::insert_sql(), &params).await?;
+
+    Ok(inserted > 0)
+}
+
+/// This function returns the count of entities matching the condition
+pub async fn count_entities<E: ChronicleEntity>(
+    condition: Option<&str>,
+    params: &[&(dyn ToSql + Sync)],
+    db_client: &mut Client,
+) -> EntityResult<i64> {
+    let executable = format!(
+        "SELECT COUNT(*) FROM {} {}",
+        E::table(),
+        where_clause(condition)
+    );
+
+    let result = db_client.query_one(&executable, params).await?;
+
+    Ok(result.get(0))
+}
+
+/// This is the count of the entities stored from a chain
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChainCount {
+    pub chain_id: i64,
+    pub total: i64,
+}
+
+/// This function returns the count of the entities stored from each chain, by chain id
+pub async fn count_entities_per_chain<E: ChronicleEntity>(
+    chain_column: &str,
+    db_client: &mut Client,
+) -> EntityResult<Vec<ChainCount>> {
+    let executable = format!(
+        "SELECT {chain_column}, COUNT(*) FROM {} GROUP BY {chain_column} ORDER BY {chain_column} ASC",
+        E::table()
+    );
+
+    let result = db_client.query(&executable, &[]).await?;
+
+    Ok(result
+        .iter()
+        .map(|row| ChainCount {
+            chain_id: row.get(0),
+            total: row.get(1),
+        })
+        .collect())
+}
+
+/// This function returns the entities matching the condition, oldest first
+pub async fn query_entities<E: ChronicleEntity>(
+    condition: Option<&str>,
+    params: &[&(dyn ToSql + Sync)],
+    db_client: &mut Client,
+) -> EntityResult<Vec<E>> {
+    let executable = format!(
+        "{} {} ORDER BY id ASC",
+        E::select_sql(),
+        where_clause(condition)
+    );
+
+    let result = db_client.query(&executable, params).await?;
+
+    Ok(result.iter().map(E::from_row).collect::<Result<_, _>>()?)
+}
+
+/// This function returns the first stored entity matching the condition,
+/// it fails when there is none
+pub async fn query_one_entity<E: ChronicleEntity>(
+    condition: Option<&str>,
+    params: &[&(dyn ToSql + Sync)],
+    db_client: &mut Client,
+) -> EntityResult<E> {
+    let executable = format!(
+        "{} {} ORDER BY id ASC LIMIT 1",
+        E::select_sql(),
+        where_clause(condition)
+    );
+
+    let result = db_client.query_one(&executable, params).await?;
+
+    Ok(E::from_row(&result)?)
+}
+
+/// This function returns a page of the entities matching the condition, newest first.
+/// The condition placeholders are numbered from `$1`.
+pub async fn query_entities_paginated<E: ChronicleEntity>(
+    condition: Option<&str>,
+    params: &[&(dyn ToSql + Sync)],
+    page_number: i64,
+    page_size: i64,
+    db_client: &mut Client,
+) -> EntityResult<Vec<E>> {
+    let offset = (page_number - 1) * page_size;
+
+    query_entities_range(condition, params, offset, page_size, db_client).await
+}
+
+/// This function returns `limit` entities matching the condition from `offset`, newest first.
+/// The condition placeholders are numbered from `$1`.
+pub async fn query_entities_range<E: ChronicleEntity>(
+    condition: Option<&str>,
+    params: &[&(dyn ToSql + Sync)],
+    offset: i64,
+    limit: i64,
+    db_client: &mut Client,
+) -> EntityResult<Vec<E>> {
+    let executable = format!(
+        "{} {} ORDER BY id DESC LIMIT ${} OFFSET ${}",
+        E::select_sql(),
+        where_clause(condition),
+        params.len() + 1,
+        params.len() + 2
+    );
+
+    let mut range_params = params.to_vec();
+    range_params.push(&limit);
+    range_params.push(&offset);
+
+    let result = db_client.query(&executable, &range_params).await?;
+
+    Ok(result.iter().map(E::from_row).collect::<Result<_, _>>()?)
+}
+
+fn where_clause(condition: Option<&str>) -> String {
+    condition
+        .map(|condition| format!("WHERE {condition}"))
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        db::PRECISION,
+        entity_record::{
+            BrandRecord, PaymentRecord, PoolRecord, RuntimeEventRecord, TransactionRecord,
+            TreasuryEntryRecord,
+        },
+    };
+
+    #[test]
+    fn test_derived_entity_sql() {
+        assert_eq!(
+            PaymentRecord::insert_sql(),
+            format!(
+                "INSERT INTO payment{DB_VERSION} (chain_id, payment_id, gateway_address, payer, token_address, is_native, amount, block_number, block_timestamp, transaction_hash, log_index) VALUES ($1, $2, $3, $4, $5, $6, $7::TEXT::NUMERIC, $8::TEXT::BIGINT, $9::TEXT::BIGINT, $10, $11::TEXT::BIGINT) ON CONFLICT DO NOTHING"
+            )
+        );
+        assert!(PaymentRecord::create_table_sql().contains("is_native BOOLEAN NOT NULL,"));
+        assert!(PaymentRecord::create_table_sql().contains("chain_id BIGINT NOT NULL,"));
+        assert!(PaymentRecord::create_table_sql().contains("block_number BIGINT NOT NULL,"));
+        assert!(PaymentRecord::create_table_sql()
+            .contains("UNIQUE (chain_id, transaction_hash, log_index)"));
+
+        // The brand name is stored in the `name` column
+        assert!(BrandRecord::COLUMNS.contains(&"name"));
+        assert!(!BrandRecord::COLUMNS.contains(&"brand_name"));
+    }
+
+    #[test]
+    fn test_derived_entity_maps_columns_by_name() {
+        // `r` is computed, and `create_at` is read from `created_at`, not from its position
+        assert_eq!(
+            PoolRecord::select_sql(),
+            format!(
+                "SELECT id, chain_id, pool_address, reward_token, me_token, current_amount_of_reward_tokens::TEXT AS current_amount_of_reward_tokens, current_amount_of_me_tokens::TEXT AS current_amount_of_me_tokens, r_optimal::TEXT AS r_optimal, (TRUNC(current_amount_of_reward_tokens * {PRECISION} / NULLIF(current_amount_of_me_tokens, 0)))::TEXT AS r, created_at FROM pool{DB_VERSION}"
+            )
+        );
+        assert!(PoolRecord::create_table_sql().contains("reward_token VARCHAR NULL,"));
+        assert!(PoolRecord::create_table_sql().contains("UNIQUE (chain_id, reward_token)"));
+        assert!(!PoolRecord::COLUMNS.contains(&"r"));
+
+        // The stored payments are read as confirmed, the status is not a column
+        assert!(PaymentRecord::select_expressions()
+            .contains(&"('confirmed')::TEXT AS status".to_string()));
+        assert!(!PaymentRecord::COLUMNS.contains(&"status"));
+    }
+
+    #[test]
+    fn test_derived_entity_binds_numbers_as_text() {
+        assert!(PoolRecord::create_table_sql()
+            .contains("current_amount_of_me_tokens NUMERIC(78, 0) NULL,"));
+        assert!(PaymentRecord::create_table_sql().contains("amount NUMERIC(78, 0) NOT NULL,"));
+
+        // The block numbers of the ledger are stored as numbers and read back as text
+        assert!(TreasuryEntryRecord::create_table_sql().contains("block_number BIGINT NOT NULL,"));
+        assert!(TreasuryEntryRecord::insert_sql().contains("$7::TEXT::BIGINT"));
+        assert!(TreasuryEntryRecord::select_expressions()
+            .contains(&"block_number::TEXT AS block_number".to_string()));
+        assert!(!TreasuryEntryRecord::INSERT_COLUMNS.contains(&"balance"));
+    }
+
+    #[test]
+    fn test_derived_entity_indexes() {
+        let table = TransactionRecord::table();
+        assert!(TransactionRecord::create_table_sql().ends_with(&format!(
+            "UNIQUE (chain_id, hash)\n);\nCREATE INDEX IF NOT EXISTS {table}_from_address ON {table} (LOWER(from_address));\nCREATE INDEX IF NOT EXISTS {table}_to_address ON {table} (LOWER(to_address))"
+        )));
+        assert!(TransactionRecord::insert_sql().contains("$21::TEXT::JSONB"));
+        assert!(PaymentRecord::INDEXES.is_empty());
+
+        // An index over several columns is named after all of them
+        assert_eq!(
+            RuntimeEventRecord::INDEXES,
+            &[("pallet_event_name", "pallet, event_name")]
+        );
+    }
+}
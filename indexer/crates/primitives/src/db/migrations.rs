@@ -0,0 +1,328 @@
+//! This module holds the versioned schema migrations of the chronicle tables.
+//!
+//! The tables are first created by their `create_*_table` functions, which is the baseline
+//! schema. The changes made to the schema after the baseline are the migration files of the
+//! `migrations` directory, `<version>_<name>.up.sql` and `<version>_<name>.down.sql`,
+//! registered in [`MIGRATIONS`]. `{DB_VERSION}` in a migration file is replaced with the
+//! table suffix. The applied migrations are tracked in the `chronicle_migrations` table.
+use super::entities::DB_VERSION;
+
+pub const MIGRATIONS_TABLE_NAME: &str = "chronicle_migrations";
+
+/// This is a schema change, `down` undoes `up`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Migration {
+    pub version: i64,
+    pub name: &'static str,
+    pub up: &'static str,
+    pub down: &'static str,
+}
+
+impl Migration {
+    pub fn up_sql(&self) -> String {
+        self.up.replace("{DB_VERSION}", DB_VERSION)
+    }
+
+    pub fn down_sql(&self) -> String {
+        self.down.replace("{DB_VERSION}", DB_VERSION)
+    }
+}
+
+macro_rules! migration {
+    ($version:literal, $file:literal) => {
+        Migration {
+            version: $version,
+            name: $file,
+            up: include_str!(concat!("../../migrations/", $file, ".up.sql")),
+            down: include_str!(concat!("../../migrations/", $file, ".down.sql")),
+        }
+    };
+}
+
+/// These are the migrations, in the order they are applied
+pub const MIGRATIONS: &[Migration] = &[
+    migration!(1, "0001_add_redemption_log_index"),
+    migration!(2, "0002_add_redemption_unique_log"),
+    migration!(3, "0003_store_amounts_as_numeric"),
+    migration!(4, "0004_tag_records_with_chain_id"),
+    migration!(5, "0005_decode_transaction_calldata"),
+    migration!(6, "0006_add_raw_event_unique_log"),
+    migration!(7, "0007_store_payment_blocks_as_bigint"),
+    migration!(8, "0008_store_gateway_block_as_bigint"),
+];
+
+/// This is a migration and whether it was applied to the database
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MigrationStatus {
+    pub migration: Migration,
+    pub applied: bool,
+}
+
+/// This function creates the table tracking the applied migrations if one does not exist already.
+/// The migrations of each table suffix are tracked apart.
+pub async fn create_migrations_table(
+    db_client: &mut tokio_postgres::Client,
+) -> Result<(), anyhow::Error> {
+    let executable = format!(
+        "
+            CREATE TABLE IF NOT EXISTS {MIGRATIONS_TABLE_NAME} (
+                db_version      VARCHAR NOT NULL,
+                version         BIGINT NOT NULL,
+                name            VARCHAR NOT NULL,
+                applied_at      TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+                PRIMARY KEY (db_version, version)
+            )
+        "
+    );
+
+    db_client.batch_execute(&executable).await?;
+    Ok(())
+}
+
+/// This function returns the versions of the applied migrations, in ascending order
+pub async fn applied_migration_versions(
+    db_client: &mut tokio_postgres::Client,
+) -> Result<Vec<i64>, anyhow::Error> {
+    let executable = format!(
+        "
+            SELECT version FROM {MIGRATIONS_TABLE_NAME}
+            WHERE db_version = $1
+            ORDER BY version ASC
+        "
+    );
+
+    let result = db_client.query(&executable, &[&DB_VERSION]).await?;
+
+    Ok(result.iter().map(|row| row.get(0)).collect())
+}
+
+/// This function returns every migration with whether it was applied
+pub async fn migration_status(
+    db_client: &mut tokio_postgres::Client,
+) -> Result<Vec<MigrationStatus>, anyhow::Error> {
+    create_migrations_table(db_client).await?;
+    let applied = applied_migration_versions(db_client).await?;
+
+    Ok(MIGRATIONS
+        .iter()
+        .map(|migration| MigrationStatus {
+            migration: *migration,
+            applied: applied.contains(&migration.version),
+        })
+        .collect())
+}
+
+/// This function applies the pending migrations up to `target_version`, all of them when it
+/// is `None`. Each migration is applied in its own transaction, it returns the applied ones.
+pub async fn run_pending_migrations(
+    target_version: Option<i64>,
+    db_client: &mut tokio_postgres::Client,
+) -> Result<Vec<Migration>, anyhow::Error> {
+    create_migrations_table(db_client).await?;
+    let applied = applied_migration_versions(db_client).await?;
+
+    let pending = pending_migrations(MIGRATIONS, &applied, target_version);
+    let executable = format!(
+        "
+            INSERT INTO {MIGRATIONS_TABLE_NAME} (db_version, version, name)
+            VALUES ($1, $2, $3)
+        "
+    );
+
+    for migration in &pending {
+        let transaction = db_client.transaction().await?;
+        transaction.batch_execute(&migration.up_sql()).await?;
+        transaction
+            .execute(
+                &executable,
+                &[&DB_VERSION, &migration.version, &migration.name],
+            )
+            .await?;
+        transaction.commit().await?;
+
+        tracing::info!("Applied migration {}", migration.name);
+    }
+
+    Ok(pending)
+}
+
+/// This function reverts the `steps` last applied migrations, newest first.
+/// Each migration is reverted in its own transaction, it returns the reverted ones.
+pub async fn revert_migrations(
+    steps: usize,
+    db_client: &mut tokio_postgres::Client,
+) -> Result<Vec<Migration>, anyhow::Error> {
+    create_migrations_table(db_client).await?;
+    let applied = applied_migration_versions(db_client).await?;
+
+    let reverted = migrations_to_revert(MIGRATIONS, &applied, steps)?;
+    let executable = format!(
+        "
+            DELETE FROM {MIGRATIONS_TABLE_NAME}
+            WHERE db_version = $1 AND version = $2
+        "
+    );
+
+    for migration in &reverted {
+        let transaction = db_client.transaction().await?;
+        transaction.batch_execute(&migration.down_sql()).await?;
+        transaction
+            .execute(&executable, &[&DB_VERSION, &migration.version])
+            .await?;
+        transaction.commit().await?;
+
+        tracing::info!("Reverted migration {}", migration.name);
+    }
+
+    Ok(reverted)
+}
+
+/// This returns the migrations not applied yet up to `target_version`, oldest first
+fn pending_migrations(
+    migrations: &[Migration],
+    applied: &[i64],
+    target_version: Option<i64>,
+) -> Vec<Migration> {
+    migrations
+        .iter()
+        .filter(|migration| !applied.contains(&migration.version))
+        .filter(|migration| migration.version <= target_version.unwrap_or(i64::MAX))
+        .copied()
+        .collect()
+}
+
+/// This returns the `steps` last applied migrations, newest first. An applied
+/// migration this build does not know can not be reverted.
+fn migrations_to_revert(
+    migrations: &[Migration],
+    applied: &[i64],
+    steps: usize,
+) -> Result<Vec<Migration>, anyhow::Error> {
+    applied
+        .iter()
+        .rev()
+        .take(steps)
+        .map(|version| {
+            migrations
+                .iter()
+                .find(|migration| migration.version == *version)
+                .copied()
+                .ok_or_else(|| anyhow::anyhow!("Applied migration {version} is unknown"))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::get_db_url_from_env,
+        db::{entities::system::create_system_table, raw_chronicle_event::create_db_instance},
+    };
+
+    #[test]
+    fn test_migrations_are_ordered_and_reversible() {
+        for pair in MIGRATIONS.windows(2) {
+            assert!(pair[0].version < pair[1].version);
+        }
+        for migration in MIGRATIONS {
+            assert!(migration
+                .name
+                .starts_with(&format!("{:04}_", migration.version)));
+            assert!(!migration.up.trim().is_empty());
+            assert!(!migration.down.trim().is_empty());
+        }
+
+        assert!(MIGRATIONS[0]
+            .up_sql()
+            .contains(&format!("ALTER TABLE redemption{DB_VERSION} ADD COLUMN")));
+        assert!(MIGRATIONS[1].up_sql().contains(&format!(
+            "ADD CONSTRAINT redemption{DB_VERSION}_onchain_tx_hash_log_index_key"
+        )));
+        assert!(MIGRATIONS[3]
+            .up_sql()
+            .contains(&format!("LOWER('event\\_%{DB_VERSION}')")));
+        assert!(MIGRATIONS[3]
+            .up_sql()
+            .contains("('chronicle_raw_event', NULL)"));
+        assert!(MIGRATIONS[4].up_sql().contains(&format!(
+            "ALTER TABLE chronicle_transaction{DB_VERSION} ADD COLUMN IF NOT EXISTS function_args JSONB"
+        )));
+        assert!(MIGRATIONS[5].up_sql().contains(&format!(
+            "chronicle_raw_event{DB_VERSION}_unique_log UNIQUE (chain_id, transaction_hash, log_index)"
+        )));
+        assert!(MIGRATIONS[6].up_sql().contains(&format!(
+            "ALTER TABLE payment{DB_VERSION}\n    ALTER COLUMN block_number TYPE BIGINT"
+        )));
+        assert!(MIGRATIONS[7].up_sql().contains(&format!(
+            "ALTER TABLE gateway{DB_VERSION} ALTER COLUMN block_number TYPE BIGINT"
+        )));
+    }
+
+    #[test]
+    fn test_pending_and_reverted_migrations() {
+        let migration = |version| Migration {
+            version,
+            name: "",
+            up: "",
+            down: "",
+        };
+        let migrations = [migration(1), migration(2), migration(3)];
+
+        let versions = |migrations: Vec<Migration>| {
+            migrations
+                .iter()
+                .map(|migration| migration.version)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            versions(pending_migrations(&migrations, &[1], None)),
+            vec![2, 3]
+        );
+        assert_eq!(
+            versions(pending_migrations(&migrations, &[], Some(2))),
+            vec![1, 2]
+        );
+        assert_eq!(
+            versions(migrations_to_revert(&migrations, &[1, 2, 3], 2).unwrap()),
+            vec![3, 2]
+        );
+        assert!(migrations_to_revert(&migrations, &[4], 1).is_err());
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_migrations_apply_to_freshly_created_tables() {
+        let mut db_client = create_db_instance(&get_db_url_from_env())
+            .await
+            .expect("Could not create db instance");
+        // The tables are created in an empty schema, like on a fresh database chronicle starts on
+        db_client
+            .batch_execute(
+                "
+                    DROP SCHEMA IF EXISTS chronicle_migrations_test CASCADE;
+                    CREATE SCHEMA chronicle_migrations_test;
+                    SET search_path TO chronicle_migrations_test;
+                ",
+            )
+            .await
+            .unwrap();
+
+        create_system_table(&mut db_client).await.unwrap();
+        let applied = run_pending_migrations(None, &mut db_client).await.unwrap();
+        assert_eq!(applied.len(), MIGRATIONS.len());
+
+        let reverted = revert_migrations(MIGRATIONS.len(), &mut db_client)
+            .await
+            .unwrap();
+        assert_eq!(reverted.len(), MIGRATIONS.len());
+        let applied = run_pending_migrations(None, &mut db_client).await.unwrap();
+        assert_eq!(applied.len(), MIGRATIONS.len());
+
+        db_client
+            .batch_execute("DROP SCHEMA chronicle_migrations_test CASCADE")
+            .await
+            .unwrap();
+    }
+}
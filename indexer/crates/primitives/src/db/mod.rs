@@ -1,3 +1,9 @@
+pub mod abi_event;
+pub mod connection_pool;
 pub mod entities;
+pub mod entity;
+pub mod migrations;
+pub mod pending_event;
 pub mod raw_chronicle_event;
+pub mod reorg;
 pub const PRECISION: u64 = 1000000;
@@ -0,0 +1,364 @@
+//! This module holds the pending buffer of indexers running with a confirmation depth.
+//! Logs wait in the buffer as `pending` until their block is deep enough, then they are
+//! applied to the entity tables and kept as `confirmed`, so both can be queried.
+use std::str::FromStr;
+
+use alloy::primitives::{Address, Bytes, B256};
+
+use super::entities::{system::CheckpointKey, DB_VERSION};
+use crate::{entity_record::BufferedEventRecord, indexer::ChronicleEvent};
+
+pub const PENDING_EVENT_TABLE_NAME: &str = "chronicle_pending_event";
+
+/// This is the status of a buffered log
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventStatus {
+    /// The log was seen but its block is not deep enough yet
+    Pending,
+    /// The log was applied to the entity tables
+    Confirmed,
+}
+
+impl EventStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Pending => "pending",
+            Self::Confirmed => "confirmed",
+        }
+    }
+}
+
+impl FromStr for EventStatus {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pending" => Ok(Self::Pending),
+            "confirmed" => Ok(Self::Confirmed),
+            _ => anyhow::bail!("Invalid event status `{s}`, expected `pending` or `confirmed`"),
+        }
+    }
+}
+
+/// This function creates the pending buffer table if it does not exist already
+pub async fn create_pending_event_table(
+    db_client: &mut tokio_postgres::Client,
+) -> Result<(), anyhow::Error> {
+    let executable = format!(
+        "
+            CREATE TABLE IF NOT EXISTS {PENDING_EVENT_TABLE_NAME}{DB_VERSION} (
+                id                  SERIAL PRIMARY KEY,
+                indexer_name        VARCHAR NOT NULL,
+                chain_id            BIGINT NOT NULL,
+                address             VARCHAR NOT NULL,
+                block_number        BIGINT NOT NULL,
+                block_hash          VARCHAR NOT NULL,
+                transaction_hash    VARCHAR NOT NULL,
+                log_index           BIGINT NOT NULL,
+                topics              VARCHAR NOT NULL,
+                data                VARCHAR NOT NULL,
+                block_timestamp     BIGINT NOT NULL,
+                status              VARCHAR NOT NULL,
+                created_at          TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+                UNIQUE (indexer_name, chain_id, transaction_hash, log_index)
+            )
+        "
+    );
+    db_client.batch_execute(&executable).await?;
+
+    Ok(())
+}
+
+/// This function holds a log in the pending buffer. A log delivered again, from another
+/// block after a reorg, replaces the buffered one as long as it is still pending.
+pub async fn buffer_pending_event(
+    event: &ChronicleEvent,
+    checkpoint: &CheckpointKey,
+    db_client: &mut tokio_postgres::Client,
+) -> Result<(), anyhow::Error> {
+    let executable = format!(
+        "
+            INSERT INTO {PENDING_EVENT_TABLE_NAME}{DB_VERSION} (indexer_name, chain_id, address, block_number, block_hash, transaction_hash, log_index, topics, data, block_timestamp, status)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+            ON CONFLICT (indexer_name, chain_id, transaction_hash, log_index)
+            DO UPDATE SET block_number = EXCLUDED.block_number, block_hash = EXCLUDED.block_hash, block_timestamp = EXCLUDED.block_timestamp
+            WHERE {PENDING_EVENT_TABLE_NAME}{DB_VERSION}.status = $11;
+        "
+    );
+    let stringified_topics: String = event
+        .topics
+        .iter()
+        .map(|topic| topic.to_string())
+        .collect::<Vec<String>>()
+        .join(",");
+
+    db_client
+        .execute(
+            &executable,
+            &[
+                &checkpoint.indexer_name,
+                &(checkpoint.chain_id as i64),
+                &event.address.to_string(),
+                &(event.block_number as i64),
+                &event.block_hash.to_string(),
+                &event.transaction_hash.to_string(),
+                &(event.log_index as i64),
+                &stringified_topics,
+                &event.data.to_string(),
+                &(event.block_timestamp as i64),
+                &EventStatus::Pending.as_str(),
+            ],
+        )
+        .await?;
+
+    Ok(())
+}
+
+/// This function drops a pending log, it was removed from the canonical chain
+pub async fn remove_pending_event(
+    event: &ChronicleEvent,
+    checkpoint: &CheckpointKey,
+    db_client: &mut tokio_postgres::Client,
+) -> Result<(), anyhow::Error> {
+    let executable = format!(
+        "
+            DELETE FROM {PENDING_EVENT_TABLE_NAME}{DB_VERSION}
+            WHERE indexer_name = $1 AND chain_id = $2 AND transaction_hash = $3 AND log_index = $4 AND status = $5;
+        "
+    );
+
+    db_client
+        .execute(
+            &executable,
+            &[
+                &checkpoint.indexer_name,
+                &(checkpoint.chain_id as i64),
+                &event.transaction_hash.to_string(),
+                &(event.log_index as i64),
+                &EventStatus::Pending.as_str(),
+            ],
+        )
+        .await?;
+
+    Ok(())
+}
+
+/// This function drops the pending logs at or below `block_number`,
+/// the backfill applies the canonical ones for those blocks
+pub async fn remove_pending_events_up_to(
+    block_number: u64,
+    checkpoint: &CheckpointKey,
+    db_client: &mut tokio_postgres::Client,
+) -> Result<(), anyhow::Error> {
+    let executable = format!(
+        "
+            DELETE FROM {PENDING_EVENT_TABLE_NAME}{DB_VERSION}
+            WHERE indexer_name = $1 AND chain_id = $2 AND block_number <= $3 AND status = $4;
+        "
+    );
+
+    db_client
+        .execute(
+            &executable,
+            &[
+                &checkpoint.indexer_name,
+                &(checkpoint.chain_id as i64),
+                &(block_number as i64),
+                &EventStatus::Pending.as_str(),
+            ],
+        )
+        .await?;
+
+    Ok(())
+}
+
+/// This function returns the pending logs at or below `block_number`, in chain order
+pub async fn get_pending_events_up_to(
+    block_number: u64,
+    checkpoint: &CheckpointKey,
+    db_client: &mut tokio_postgres::Client,
+) -> Result<Vec<ChronicleEvent>, anyhow::Error> {
+    let executable = format!(
+        "
+            SELECT chain_id, address, block_number, block_hash, transaction_hash, log_index, topics, data, block_timestamp
+            FROM {PENDING_EVENT_TABLE_NAME}{DB_VERSION}
+            WHERE indexer_name = $1 AND chain_id = $2 AND block_number <= $3 AND status = $4
+            ORDER BY block_number ASC, log_index ASC
+        "
+    );
+
+    let rows = db_client
+        .query(
+            &executable,
+            &[
+                &checkpoint.indexer_name,
+                &(checkpoint.chain_id as i64),
+                &(block_number as i64),
+                &EventStatus::Pending.as_str(),
+            ],
+        )
+        .await?;
+
+    rows.iter().map(pending_event).collect()
+}
+
+/// This function returns the pending logs whose first topic is `topic`, newest first,
+/// optionally only the ones from the given chain. A log buffered by several indexers is returned once.
+pub async fn get_pending_events_by_topic(
+    topic: B256,
+    chain_id: Option<i64>,
+    db_client: &mut tokio_postgres::Client,
+) -> Result<Vec<ChronicleEvent>, anyhow::Error> {
+    let executable = format!(
+        "
+            SELECT chain_id, address, block_number, block_hash, transaction_hash, log_index, topics, data, block_timestamp
+            FROM (
+                SELECT DISTINCT ON (chain_id, transaction_hash, log_index) *
+                FROM {PENDING_EVENT_TABLE_NAME}{DB_VERSION}
+                WHERE status = $1 AND SPLIT_PART(topics, ',', 1) = $2
+                    AND ($3::BIGINT IS NULL OR chain_id = $3)
+                ORDER BY chain_id, transaction_hash, log_index, block_number DESC
+            ) pending
+            ORDER BY block_number DESC, log_index DESC
+        "
+    );
+
+    let rows = db_client
+        .query(
+            &executable,
+            &[
+                &EventStatus::Pending.as_str(),
+                &topic.to_string(),
+                &chain_id,
+            ],
+        )
+        .await?;
+
+    rows.iter().map(pending_event).collect()
+}
+
+fn pending_event(row: &tokio_postgres::Row) -> Result<ChronicleEvent, anyhow::Error> {
+    let chain_id: i64 = row.get(0);
+    let address: String = row.get(1);
+    let block_number: i64 = row.get(2);
+    let block_hash: String = row.get(3);
+    let transaction_hash: String = row.get(4);
+    let log_index: i64 = row.get(5);
+    let topics: String = row.get(6);
+    let data: String = row.get(7);
+    let block_timestamp: i64 = row.get(8);
+
+    let topics = topics
+        .split(',')
+        .filter(|topic| !topic.is_empty())
+        .map(B256::from_str)
+        .collect::<Result<Vec<B256>, _>>()?;
+
+    Ok(ChronicleEvent {
+        chain_id: chain_id as u64,
+        address: Address::from_str(&address)?,
+        block_number: block_number as u64,
+        block_hash: B256::from_str(&block_hash)?,
+        transaction_hash: B256::from_str(&transaction_hash)?,
+        topics,
+        data: Bytes::from_str(&data)?,
+        block_timestamp: block_timestamp as u64,
+        log_index: log_index as u64,
+        removed: false,
+    })
+}
+
+/// This function marks a buffered log as applied to the entity tables
+pub async fn mark_event_confirmed(
+    event: &ChronicleEvent,
+    checkpoint: &CheckpointKey,
+    db_client: &mut tokio_postgres::Client,
+) -> Result<(), anyhow::Error> {
+    let executable = format!(
+        "
+            UPDATE {PENDING_EVENT_TABLE_NAME}{DB_VERSION}
+            SET status = $5
+            WHERE indexer_name = $1 AND chain_id = $2 AND transaction_hash = $3 AND log_index = $4;
+        "
+    );
+
+    db_client
+        .execute(
+            &executable,
+            &[
+                &checkpoint.indexer_name,
+                &(checkpoint.chain_id as i64),
+                &event.transaction_hash.to_string(),
+                &(event.log_index as i64),
+                &EventStatus::Confirmed.as_str(),
+            ],
+        )
+        .await?;
+
+    Ok(())
+}
+
+/// This function returns the buffered logs of an indexer, newest first,
+/// optionally only the ones with the given status or from the given chain
+pub async fn query_buffered_events(
+    indexer_name: String,
+    status: Option<EventStatus>,
+    chain_id: Option<i64>,
+    db_client: &mut tokio_postgres::Client,
+) -> Result<Vec<BufferedEventRecord>, anyhow::Error> {
+    let executable = format!(
+        "
+            SELECT indexer_name, chain_id, address, block_number, block_hash, transaction_hash, log_index, topics, data, status
+            FROM {PENDING_EVENT_TABLE_NAME}{DB_VERSION}
+            WHERE indexer_name = $1 AND ($2::VARCHAR IS NULL OR status = $2)
+                AND ($3::BIGINT IS NULL OR chain_id = $3)
+            ORDER BY block_number DESC, log_index DESC
+        "
+    );
+    let status = status.map(|status| status.as_str().to_string());
+
+    let rows = db_client
+        .query(&executable, &[&indexer_name, &status, &chain_id])
+        .await?;
+
+    Ok(rows.iter().map(buffered_event_record).collect())
+}
+
+/// This function returns the buffered logs emitted by a transaction, with their status
+pub async fn query_buffered_events_by_tx_hash(
+    transaction_hash: String,
+    db_client: &mut tokio_postgres::Client,
+) -> Result<Vec<BufferedEventRecord>, anyhow::Error> {
+    let executable = format!(
+        "
+            SELECT indexer_name, chain_id, address, block_number, block_hash, transaction_hash, log_index, topics, data, status
+            FROM {PENDING_EVENT_TABLE_NAME}{DB_VERSION}
+            WHERE transaction_hash = $1
+            ORDER BY log_index ASC
+        "
+    );
+
+    let rows = db_client.query(&executable, &[&transaction_hash]).await?;
+
+    Ok(rows.iter().map(buffered_event_record).collect())
+}
+
+fn buffered_event_record(row: &tokio_postgres::Row) -> BufferedEventRecord {
+    let chain_id: i64 = row.get(1);
+    let block_number: i64 = row.get(3);
+    let log_index: i64 = row.get(6);
+    let topics: String = row.get(7);
+
+    BufferedEventRecord {
+        indexer_name: row.get(0),
+        chain_id: chain_id.to_string(),
+        address: row.get(2),
+        block_number: block_number.to_string(),
+        block_hash: row.get(4),
+        transaction_hash: row.get(5),
+        log_index: log_index.to_string(),
+        topics: topics.split(',').map(String::from).collect(),
+        data: row.get(8),
+        status: row.get(9),
+    }
+}
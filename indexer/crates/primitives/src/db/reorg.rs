@@ -0,0 +1,360 @@
+//! This module keeps what is needed to undo the entity changes of orphaned blocks.
+//!
+//! Every indexer records the hash of the blocks it applied events from, and a trigger on
+//! the entity tables journals each insert, update and delete together with the block that
+//! caused it. When a reorg is detected the journal is replayed backwards to restore the
+//! entity tables, then the events of the canonical chain can be applied again.
+use super::{
+    entities::{
+        brand::BRAND_TABLE_NAME,
+        gateway::GATEWAY_TABLE_NAME,
+        internal_transfer::INTERNAL_TRANSFER_TABLE_NAME,
+        payment::PAYMENT_TABLE_NAME,
+        pool::POOL_TABLE_NAME,
+        redemption::REDEMPTION_TABLE_NAME,
+        reward::REWARD_TABLE_NAME,
+        system::CheckpointKey,
+        token_whitelist::{TOKEN_WHITELIST_HISTORY_TABLE_NAME, TOKEN_WHITELIST_TABLE_NAME},
+        transaction::TRANSACTION_TABLE_NAME,
+        treasury::TREASURY_LEDGER_TABLE_NAME,
+        DB_VERSION,
+    },
+    raw_chronicle_event::RAW_EVENT_TABLE_NAME,
+};
+
+pub const BLOCK_HASH_TABLE_NAME: &str = "chronicle_block_hash";
+pub const JOURNAL_TABLE_NAME: &str = "chronicle_journal";
+pub const JOURNAL_FUNCTION_NAME: &str = "chronicle_journal_changes";
+
+/// These are the entity tables whose changes are journaled
+pub const JOURNALED_TABLE_NAMES: [&str; 12] = [
+    BRAND_TABLE_NAME,
+    GATEWAY_TABLE_NAME,
+    PAYMENT_TABLE_NAME,
+    POOL_TABLE_NAME,
+    REDEMPTION_TABLE_NAME,
+    REWARD_TABLE_NAME,
+    TOKEN_WHITELIST_TABLE_NAME,
+    TOKEN_WHITELIST_HISTORY_TABLE_NAME,
+    TREASURY_LEDGER_TABLE_NAME,
+    TRANSACTION_TABLE_NAME,
+    INTERNAL_TRANSFER_TABLE_NAME,
+    RAW_EVENT_TABLE_NAME,
+];
+
+/// This function creates the block hash and journal tables, and attaches the journal
+/// trigger to the entity tables. The entity tables must exist already.
+pub async fn create_reorg_tables(
+    db_client: &mut tokio_postgres::Client,
+) -> Result<(), anyhow::Error> {
+    let executable = format!(
+        "
+            CREATE TABLE IF NOT EXISTS {BLOCK_HASH_TABLE_NAME}{DB_VERSION} (
+                indexer_name    VARCHAR NOT NULL,
+                chain_id        BIGINT NOT NULL,
+                block_number    BIGINT NOT NULL,
+                block_hash      VARCHAR NOT NULL,
+                PRIMARY KEY (indexer_name, chain_id, block_number)
+            );
+
+            CREATE TABLE IF NOT EXISTS {JOURNAL_TABLE_NAME}{DB_VERSION} (
+                id              BIGSERIAL PRIMARY KEY,
+                indexer_name    VARCHAR NOT NULL,
+                chain_id        BIGINT NOT NULL,
+                block_number    BIGINT NOT NULL,
+                table_name      VARCHAR NOT NULL,
+                row_id          INTEGER NOT NULL,
+                operation       VARCHAR NOT NULL,
+                old_row         JSONB NULL
+            );
+
+            CREATE OR REPLACE FUNCTION {JOURNAL_FUNCTION_NAME}{DB_VERSION}() RETURNS TRIGGER AS $$
+            DECLARE
+                journal_block TEXT := current_setting('chronicle.block_number', true);
+            BEGIN
+                IF journal_block IS NULL OR journal_block = '' THEN
+                    RETURN NULL;
+                END IF;
+
+                IF TG_OP = 'INSERT' THEN
+                    INSERT INTO {JOURNAL_TABLE_NAME}{DB_VERSION} (indexer_name, chain_id, block_number, table_name, row_id, operation, old_row)
+                    VALUES (current_setting('chronicle.indexer_name'), current_setting('chronicle.chain_id')::BIGINT, journal_block::BIGINT, TG_TABLE_NAME, NEW.id, TG_OP, NULL);
+                ELSE
+                    INSERT INTO {JOURNAL_TABLE_NAME}{DB_VERSION} (indexer_name, chain_id, block_number, table_name, row_id, operation, old_row)
+                    VALUES (current_setting('chronicle.indexer_name'), current_setting('chronicle.chain_id')::BIGINT, journal_block::BIGINT, TG_TABLE_NAME, OLD.id, TG_OP, to_jsonb(OLD));
+                END IF;
+
+                RETURN NULL;
+            END;
+            $$ LANGUAGE plpgsql;
+        "
+    );
+    db_client.batch_execute(&executable).await?;
+
+    for table_name in JOURNALED_TABLE_NAMES {
+        attach_journal_trigger(&format!("{table_name}{DB_VERSION}"), db_client).await?;
+    }
+
+    Ok(())
+}
+
+/// This function attaches the journal trigger to an entity table, entity tables
+/// must have a `SERIAL` `id` column for their changes to be rolled back
+pub async fn attach_journal_trigger(
+    table: &str,
+    db_client: &mut tokio_postgres::Client,
+) -> Result<(), anyhow::Error> {
+    let executable = format!(
+        "
+            DROP TRIGGER IF EXISTS {JOURNAL_FUNCTION_NAME} ON {table};
+            CREATE TRIGGER {JOURNAL_FUNCTION_NAME}
+                AFTER INSERT OR UPDATE OR DELETE ON {table}
+                FOR EACH ROW EXECUTE FUNCTION {JOURNAL_FUNCTION_NAME}{DB_VERSION}();
+        "
+    );
+    db_client.batch_execute(&executable).await?;
+
+    Ok(())
+}
+
+/// This function tells the journal trigger which block the following entity changes of the
+/// open transaction belong to. The settings end with the transaction, so a connection returned
+/// to the pool does not journal the changes of its next user against the block
+pub async fn set_journal_block(
+    checkpoint: &CheckpointKey,
+    block_number: u64,
+    db_client: &mut tokio_postgres::Client,
+) -> Result<(), anyhow::Error> {
+    db_client
+        .execute(
+            "SELECT set_config('chronicle.block_number', $1, true),
+                    set_config('chronicle.indexer_name', $2, true),
+                    set_config('chronicle.chain_id', $3, true)",
+            &[
+                &block_number.to_string(),
+                &checkpoint.indexer_name,
+                &checkpoint.chain_id.to_string(),
+            ],
+        )
+        .await?;
+
+    Ok(())
+}
+
+/// This function records the hash of a block the indexer applied events from
+pub async fn record_block_hash(
+    checkpoint: &CheckpointKey,
+    block_number: u64,
+    block_hash: String,
+    db_client: &mut tokio_postgres::Client,
+) -> Result<(), anyhow::Error> {
+    let executable = format!(
+        "
+            INSERT INTO {BLOCK_HASH_TABLE_NAME}{DB_VERSION} (indexer_name, chain_id, block_number, block_hash)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (indexer_name, chain_id, block_number)
+            DO UPDATE SET block_hash = EXCLUDED.block_hash;
+        "
+    );
+
+    db_client
+        .execute(
+            &executable,
+            &[
+                &checkpoint.indexer_name,
+                &(checkpoint.chain_id as i64),
+                &(block_number as i64),
+                &block_hash,
+            ],
+        )
+        .await?;
+
+    Ok(())
+}
+
+/// This function returns the recorded block hashes of the indexer, newest first
+pub async fn get_recent_block_hashes(
+    checkpoint: &CheckpointKey,
+    db_client: &mut tokio_postgres::Client,
+) -> Result<Vec<(u64, String)>, anyhow::Error> {
+    let executable = format!(
+        "
+            SELECT block_number, block_hash
+            FROM {BLOCK_HASH_TABLE_NAME}{DB_VERSION}
+            WHERE indexer_name = $1 AND chain_id = $2
+            ORDER BY block_number DESC
+        "
+    );
+
+    let rows = db_client
+        .query(
+            &executable,
+            &[&checkpoint.indexer_name, &(checkpoint.chain_id as i64)],
+        )
+        .await?;
+
+    let block_hashes = rows
+        .iter()
+        .map(|row| {
+            let block_number: i64 = row.get(0);
+            (block_number as u64, row.get(1))
+        })
+        .collect();
+
+    Ok(block_hashes)
+}
+
+/// This function returns the recorded hash of a block, if the indexer applied events from it
+pub async fn get_block_hash(
+    checkpoint: &CheckpointKey,
+    block_number: u64,
+    db_client: &mut tokio_postgres::Client,
+) -> Result<Option<String>, anyhow::Error> {
+    let executable = format!(
+        "
+            SELECT block_hash
+            FROM {BLOCK_HASH_TABLE_NAME}{DB_VERSION}
+            WHERE indexer_name = $1 AND chain_id = $2 AND block_number = $3
+        "
+    );
+
+    let rows = db_client
+        .query(
+            &executable,
+            &[
+                &checkpoint.indexer_name,
+                &(checkpoint.chain_id as i64),
+                &(block_number as i64),
+            ],
+        )
+        .await?;
+
+    Ok(rows.first().map(|row| row.get(0)))
+}
+
+/// This function forgets the block hashes and journal entries older than `keep_from_block`,
+/// blocks that deep are not expected to be reorganized anymore
+pub async fn prune_reorg_window(
+    checkpoint: &CheckpointKey,
+    keep_from_block: u64,
+    db_client: &mut tokio_postgres::Client,
+) -> Result<(), anyhow::Error> {
+    let executable = format!(
+        "
+            DELETE FROM {BLOCK_HASH_TABLE_NAME}{DB_VERSION}
+            WHERE indexer_name = $1 AND chain_id = $2 AND block_number < $3;
+        "
+    );
+    let journal_executable = format!(
+        "
+            DELETE FROM {JOURNAL_TABLE_NAME}{DB_VERSION}
+            WHERE indexer_name = $1 AND chain_id = $2 AND block_number < $3;
+        "
+    );
+    let params: [&(dyn tokio_postgres::types::ToSql + Sync); 3] = [
+        &checkpoint.indexer_name,
+        &(checkpoint.chain_id as i64),
+        &(keep_from_block as i64),
+    ];
+
+    db_client.execute(&executable, &params).await?;
+    db_client.execute(&journal_executable, &params).await?;
+
+    Ok(())
+}
+
+/// This function undoes every entity change the indexer made from `fork_block` onwards,
+/// forgets the orphaned block hashes and moves the checkpoint back to `fork_block - 1`
+pub async fn rollback_from_block(
+    checkpoint: &CheckpointKey,
+    fork_block: u64,
+    db_client: &mut tokio_postgres::Client,
+) -> Result<(), anyhow::Error> {
+    // The rollback itself is not journaled, its transaction has no journal block
+    db_client.batch_execute("BEGIN").await?;
+
+    match undo_journal_from_block(checkpoint, fork_block, db_client).await {
+        Ok(()) => {
+            db_client.batch_execute("COMMIT").await?;
+            Ok(())
+        }
+        Err(err) => {
+            db_client.batch_execute("ROLLBACK").await?;
+            Err(err)
+        }
+    }
+}
+
+async fn undo_journal_from_block(
+    checkpoint: &CheckpointKey,
+    fork_block: u64,
+    db_client: &mut tokio_postgres::Client,
+) -> Result<(), anyhow::Error> {
+    let executable = format!(
+        "
+            SELECT table_name, row_id, operation, old_row::TEXT
+            FROM {JOURNAL_TABLE_NAME}{DB_VERSION}
+            WHERE indexer_name = $1 AND chain_id = $2 AND block_number >= $3
+            ORDER BY id DESC
+        "
+    );
+    let params: [&(dyn tokio_postgres::types::ToSql + Sync); 3] = [
+        &checkpoint.indexer_name,
+        &(checkpoint.chain_id as i64),
+        &(fork_block as i64),
+    ];
+    let journal = db_client.query(&executable, &params).await?;
+
+    for entry in journal {
+        let table_name: String = entry.get(0);
+        let row_id: i32 = entry.get(1);
+        let operation: String = entry.get(2);
+        let old_row: Option<String> = entry.get(3);
+
+        // Whatever the operation was, the row as it is now has to go
+        db_client
+            .execute(
+                &format!("DELETE FROM {table_name} WHERE id = $1"),
+                &[&row_id],
+            )
+            .await?;
+
+        // Updated and deleted rows are restored from their journaled state
+        if operation != "INSERT" {
+            let old_row =
+                old_row.ok_or_else(|| anyhow::anyhow!("Journal entry is missing the old row"))?;
+            db_client
+                .execute(
+                    &format!(
+                        "INSERT INTO {table_name} SELECT * FROM jsonb_populate_record(NULL::{table_name}, $1::TEXT::JSONB)"
+                    ),
+                    &[&old_row],
+                )
+                .await?;
+        }
+    }
+
+    let journal_executable = format!(
+        "
+            DELETE FROM {JOURNAL_TABLE_NAME}{DB_VERSION}
+            WHERE indexer_name = $1 AND chain_id = $2 AND block_number >= $3;
+        "
+    );
+    let block_hash_executable = format!(
+        "
+            DELETE FROM {BLOCK_HASH_TABLE_NAME}{DB_VERSION}
+            WHERE indexer_name = $1 AND chain_id = $2 AND block_number >= $3;
+        "
+    );
+    db_client.execute(&journal_executable, &params).await?;
+    db_client.execute(&block_hash_executable, &params).await?;
+
+    super::entities::system::update_last_block_number(
+        checkpoint,
+        fork_block.saturating_sub(1).to_string(),
+        db_client,
+    )
+    .await?;
+
+    Ok(())
+}
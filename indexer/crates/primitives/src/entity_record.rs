@@ -1,53 +1,429 @@
 //! This role of this module is to take database data and convert it to a rust type
 //! that can easily be serialized and deserialized for other chornicle component to make
 //! use of.
+use async_graphql::SimpleObject;
 use chrono::NaiveDateTime;
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+use crate::db::{
+    entities::{
+        brand::BRAND_TABLE_NAME,
+        gateway::{comma_separated, GATEWAY_TABLE_NAME},
+        internal_transfer::INTERNAL_TRANSFER_TABLE_NAME,
+        payment::PAYMENT_TABLE_NAME,
+        pool::POOL_TABLE_NAME,
+        redemption::REDEMPTION_TABLE_NAME,
+        reward::REWARD_TABLE_NAME,
+        runtime_event::RUNTIME_EVENT_TABLE_NAME,
+        token_whitelist::{TOKEN_WHITELIST_HISTORY_TABLE_NAME, TOKEN_WHITELIST_TABLE_NAME},
+        transaction::TRANSACTION_TABLE_NAME,
+        treasury::{ENTRY_DELTA, TREASURY_LEDGER_TABLE_NAME},
+    },
+    entity::ChronicleEntity,
+    pending_event::EventStatus,
+    PRECISION,
+};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, ChronicleEntity)]
 #[serde(rename_all = "camelCase")]
+#[chronicle(table = POOL_TABLE_NAME, unique(chain_id, reward_token))]
 pub struct PoolRecord {
-    pub pool_address: String,
+    #[chronicle(chain_id)]
+    pub chain_id: i64,
+    /// This is `None` for the pools whose amounts were stored before the pool itself
+    #[chronicle(lookup)]
+    pub pool_address: Option<String>,
+    #[chronicle(nullable, lookup)]
     pub reward_token: String,
-    pub me_token: String,
-    pub current_amount_of_reward_tokens: String,
-    pub current_amount_of_me_tokens: String,
-    pub r_optimal: String,
-    pub r: String,
+    pub me_token: Option<String>,
+    #[chronicle(numeric)]
+    pub current_amount_of_reward_tokens: Option<String>,
+    #[chronicle(numeric)]
+    pub current_amount_of_me_tokens: Option<String>,
+    #[chronicle(numeric)]
+    pub r_optimal: Option<String>,
+    /// This is the reward tokens per me token, scaled by `PRECISION`.
+    /// It is `None` while the pool holds no me tokens.
+    #[chronicle(
+        select = format!("TRUNC(current_amount_of_reward_tokens * {PRECISION} / NULLIF(current_amount_of_me_tokens, 0))")
+    )]
+    pub r: Option<String>,
+    #[chronicle(created_at)]
     pub create_at: NaiveDateTime,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default, ChronicleEntity)]
 #[serde(rename_all = "camelCase")]
+#[chronicle(table = BRAND_TABLE_NAME)]
 pub struct BrandRecord {
+    #[chronicle(chain_id)]
+    pub chain_id: i64,
+    #[chronicle(column = "name", nullable, lookup)]
     pub brand_name: String,
+    #[chronicle(nullable, lookup)]
     pub main_account: String,
+    #[chronicle(nullable)]
     pub online_presence: String,
+    #[chronicle(nullable, lookup)]
     pub brand_protocol_id: String,
+    #[chronicle(nullable)]
     pub onboarding_manager: String,
+    #[chronicle(created_at)]
     pub create_at: NaiveDateTime,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default, ChronicleEntity)]
 #[serde(rename_all = "camelCase")]
+#[chronicle(table = REDEMPTION_TABLE_NAME, unique(chain_id, onchain_tx_hash, log_index))]
 pub struct RedepmtionRecord {
+    #[chronicle(chain_id)]
+    pub chain_id: i64,
+    #[chronicle(nullable)]
     pub source_token: String,
+    #[chronicle(nullable)]
     pub dest_token: String,
+    #[chronicle(nullable, numeric)]
     pub source_amount: String,
+    #[chronicle(nullable, numeric)]
     pub dest_amount: String,
+    #[chronicle(nullable, lookup)]
     pub user_address: String,
+    #[chronicle(nullable, lookup)]
     pub onchain_tx_hash: String,
+    #[chronicle(nullable)]
     pub redeemed_at: String,
+    /// This is `None` for the redemptions stored before it was tracked
+    pub log_index: Option<String>,
+    #[chronicle(created_at)]
     pub create_at: NaiveDateTime,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default, ChronicleEntity)]
 #[serde(rename_all = "camelCase")]
+#[chronicle(table = REWARD_TABLE_NAME)]
 pub struct RewardRecord {
+    #[chronicle(chain_id)]
+    pub chain_id: i64,
+    #[chronicle(nullable, lookup)]
     pub brand_id: String,
+    #[chronicle(nullable, lookup)]
     pub reward_address: String,
+    #[chronicle(nullable, lookup)]
     pub requestor_address: String,
+    #[chronicle(nullable, numeric)]
     pub initial_supply: String,
+    #[chronicle(nullable)]
     pub timestamp: String,
+    #[chronicle(created_at)]
+    pub create_at: NaiveDateTime,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, SimpleObject, ChronicleEntity)]
+#[serde(rename_all = "camelCase")]
+#[chronicle(table = PAYMENT_TABLE_NAME, unique(chain_id, transaction_hash, log_index))]
+pub struct PaymentRecord {
+    #[chronicle(chain_id)]
+    pub chain_id: i64,
+    #[chronicle(lookup, ignore_case)]
+    pub payment_id: String,
+    #[chronicle(lookup, ignore_case)]
+    pub gateway_address: String,
+    #[chronicle(lookup, ignore_case)]
+    pub payer: String,
+    /// This is `address(0)` for native ETH payments
+    #[chronicle(lookup, ignore_case)]
+    pub token_address: String,
+    pub is_native: bool,
+    #[chronicle(numeric)]
+    pub amount: String,
+    #[chronicle(sql_type = "BIGINT", as_text)]
+    pub block_number: String,
+    #[chronicle(sql_type = "BIGINT", as_text)]
+    pub block_timestamp: String,
+    pub transaction_hash: String,
+    #[chronicle(sql_type = "BIGINT", as_text)]
+    pub log_index: String,
+    /// This is `pending` while the log of the payment waits for its confirmations in the
+    /// pending buffer, the stored payments are `confirmed`
+    #[chronicle(select = format!("'{}'", EventStatus::Confirmed.as_str()))]
+    pub status: String,
+    #[graphql(skip)]
+    #[chronicle(created_at)]
+    pub create_at: NaiveDateTime,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, SimpleObject, ChronicleEntity)]
+#[serde(rename_all = "camelCase")]
+#[chronicle(table = GATEWAY_TABLE_NAME, unique(chain_id, gateway_address))]
+pub struct GatewayRecord {
+    #[chronicle(chain_id)]
+    pub chain_id: i64,
+    #[chronicle(lookup, ignore_case)]
+    pub gateway_address: String,
+    #[chronicle(lookup, ignore_case)]
+    pub factory_address: String,
+    pub owner: String,
+    pub confirmation_block_height: String,
+    /// These are the tokens the gateway was deployed with
+    #[chronicle(with = comma_separated)]
+    pub tokens: Vec<String>,
+    pub salt: String,
+    #[chronicle(sql_type = "BIGINT", as_text)]
+    pub block_number: String,
+    pub transaction_hash: String,
+    #[graphql(skip)]
+    #[chronicle(created_at)]
+    pub create_at: NaiveDateTime,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, SimpleObject, ChronicleEntity)]
+#[serde(rename_all = "camelCase")]
+#[chronicle(
+    table = TOKEN_WHITELIST_TABLE_NAME,
+    unique(chain_id, gateway_address, token_address)
+)]
+pub struct TokenWhitelistRecord {
+    #[chronicle(chain_id)]
+    pub chain_id: i64,
+    pub gateway_address: String,
+    pub token_address: String,
+    /// This is false once the latest change delisted the token
+    pub whitelisted: bool,
+    #[chronicle(sql_type = "BIGINT", as_text)]
+    pub block_number: String,
+    pub transaction_hash: String,
+    #[chronicle(sql_type = "BIGINT", as_text)]
+    pub log_index: String,
+    #[graphql(skip)]
+    #[chronicle(created_at)]
+    pub create_at: NaiveDateTime,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, SimpleObject, ChronicleEntity)]
+#[serde(rename_all = "camelCase")]
+#[chronicle(
+    table = TOKEN_WHITELIST_HISTORY_TABLE_NAME,
+    unique(chain_id, transaction_hash, log_index, token_address)
+)]
+pub struct TokenWhitelistChangeRecord {
+    #[chronicle(chain_id)]
+    pub chain_id: i64,
+    pub gateway_address: String,
+    pub token_address: String,
+    /// This is either `whitelisted` or `delisted`
+    pub action: String,
+    /// This is the timestamp emitted with the event
+    pub event_timestamp: String,
+    #[chronicle(sql_type = "BIGINT", as_text)]
+    pub block_number: String,
+    pub transaction_hash: String,
+    #[chronicle(sql_type = "BIGINT", as_text)]
+    pub log_index: String,
+    #[graphql(skip)]
+    #[chronicle(created_at)]
+    pub create_at: NaiveDateTime,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, SimpleObject, ChronicleEntity)]
+#[serde(rename_all = "camelCase")]
+#[chronicle(
+    table = TREASURY_LEDGER_TABLE_NAME,
+    unique(chain_id, transaction_hash, log_index)
+)]
+pub struct TreasuryEntryRecord {
+    #[chronicle(chain_id)]
+    pub chain_id: i64,
+    pub gateway_address: String,
+    /// This is `address(0)` for native ETH
+    pub token_address: String,
+    /// This is either `credit`, `debit` or `withdrawal_failed`
+    pub entry_type: String,
+    #[chronicle(numeric)]
+    pub amount: String,
+    /// This is the balance of the gateway token on its chain right after this entry
+    #[chronicle(
+        select = format!("SUM({ENTRY_DELTA}) OVER (PARTITION BY chain_id, LOWER(gateway_address), LOWER(token_address) ORDER BY block_number ASC, log_index ASC)")
+    )]
+    pub balance: String,
+    /// This is the payer of a credit, or the recipient of a withdrawal
+    pub counterparty: String,
+    #[chronicle(sql_type = "BIGINT", as_text)]
+    pub block_number: String,
+    #[chronicle(sql_type = "BIGINT", as_text)]
+    pub block_timestamp: String,
+    pub transaction_hash: String,
+    #[chronicle(sql_type = "BIGINT", as_text)]
+    pub log_index: String,
+    #[graphql(skip)]
+    #[chronicle(created_at)]
     pub create_at: NaiveDateTime,
 }
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, SimpleObject)]
+#[serde(rename_all = "camelCase")]
+pub struct TreasuryDailyRecord {
+    pub chain_id: i64,
+    pub gateway_address: String,
+    pub token_address: String,
+    /// This is the UTC day, formatted as `YYYY-MM-DD`
+    pub day: String,
+    pub credits: String,
+    pub debits: String,
+    pub failed_withdrawals: i64,
+    pub net: String,
+    /// This is the balance of the gateway token at the end of the day
+    pub closing_balance: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, SimpleObject)]
+#[serde(rename_all = "camelCase")]
+pub struct TreasuryReconciliationRecord {
+    /// This is the chain of the RPC the on-chain balance was read from
+    pub chain_id: i64,
+    pub gateway_address: String,
+    pub token_address: String,
+    pub block_number: String,
+    /// This is the balance summed from the ledger entries up to the block
+    pub indexed_balance: String,
+    /// This is the balance read from the chain at the block
+    pub onchain_balance: String,
+    /// This is `onchain_balance - indexed_balance`
+    pub difference: String,
+    pub reconciled: bool,
+    /// This is false for ERC20 tokens, `withdrawERC20` emits no event so the ledger
+    /// has no debits and `difference` includes the amounts withdrawn
+    pub withdrawals_indexed: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, SimpleObject, ChronicleEntity)]
+#[serde(rename_all = "camelCase")]
+#[chronicle(table = TRANSACTION_TABLE_NAME, unique(chain_id, hash))]
+pub struct TransactionRecord {
+    #[chronicle(chain_id)]
+    pub chain_id: i64,
+    pub hash: String,
+    #[chronicle(sql_type = "BIGINT", as_text)]
+    pub nonce: String,
+    pub block_hash: String,
+    #[chronicle(sql_type = "BIGINT", as_text)]
+    pub block_number: String,
+    #[chronicle(sql_type = "BIGINT", as_text)]
+    pub block_timestamp: String,
+    #[chronicle(sql_type = "BIGINT", as_text)]
+    pub transaction_index: String,
+    #[chronicle(index, ignore_case)]
+    pub from_address: String,
+    /// This is `None` for contract creations
+    #[chronicle(index, ignore_case)]
+    pub to_address: Option<String>,
+    #[chronicle(numeric)]
+    pub value: String,
+    #[chronicle(numeric)]
+    pub gas_price: String,
+    #[chronicle(numeric)]
+    pub gas: String,
+    #[chronicle(numeric)]
+    pub max_fee_per_gas: String,
+    pub input: String,
+    /// This is false when the transaction reverted
+    pub status: bool,
+    #[chronicle(numeric)]
+    pub gas_used: String,
+    #[chronicle(numeric)]
+    pub effective_gas_price: String,
+    /// This is the contract the transaction created, if it is a contract creation
+    pub contract_address: Option<String>,
+    #[graphql(skip)]
+    #[chronicle(created_at)]
+    pub create_at: NaiveDateTime,
+    /// This is the 4 byte selector of the calldata, `None` for plain transfers
+    pub function_selector: Option<String>,
+    /// This is `None` when the selector is not known to the calldata decoder
+    pub function_name: Option<String>,
+    /// These are the JSON encoded arguments keyed by parameter name
+    #[chronicle(sql_type = "JSONB", as_text)]
+    pub function_args: Option<String>,
+    /// This is the data a failed transaction reverted with
+    pub revert_data: Option<String>,
+    pub revert_reason: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, ChronicleEntity)]
+#[serde(rename_all = "camelCase")]
+#[chronicle(
+    table = INTERNAL_TRANSFER_TABLE_NAME,
+    unique(chain_id, transaction_hash, trace_index)
+)]
+pub struct InternalTransferRecord {
+    #[chronicle(chain_id)]
+    pub chain_id: i64,
+    #[chronicle(sql_type = "BIGINT", as_text)]
+    pub block_number: String,
+    pub block_hash: String,
+    #[chronicle(sql_type = "BIGINT", as_text)]
+    pub block_timestamp: String,
+    pub transaction_hash: String,
+    #[chronicle(sql_type = "BIGINT", as_text)]
+    pub transaction_index: String,
+    #[chronicle(sql_type = "BIGINT", as_text)]
+    pub trace_index: String,
+    /// This is the path to the call in the call tree, its indexes separated by commas
+    pub trace_address: String,
+    /// This is `CALL`, `CREATE`, `CREATE2` or `SELFDESTRUCT`
+    pub call_type: String,
+    #[chronicle(index, ignore_case)]
+    pub from_address: String,
+    #[chronicle(index, ignore_case)]
+    pub to_address: String,
+    #[chronicle(numeric)]
+    pub value: String,
+    /// This is the 4 byte selector of the calldata, `None` for plain transfers
+    pub function_selector: Option<String>,
+    #[chronicle(created_at)]
+    pub create_at: NaiveDateTime,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, ChronicleEntity)]
+#[serde(rename_all = "camelCase")]
+#[chronicle(
+    table = RUNTIME_EVENT_TABLE_NAME,
+    unique(genesis_hash, block_number, event_index),
+    index(pallet, event_name)
+)]
+pub struct RuntimeEventRecord {
+    /// This is the genesis hash of the chain, Substrate chains have no EIP-155 chain id
+    pub genesis_hash: String,
+    #[chronicle(sql_type = "BIGINT", as_text)]
+    pub block_number: String,
+    pub block_hash: String,
+    /// This is `ApplyExtrinsic`, `Initialization` or `Finalization`
+    pub phase: String,
+    /// This is `None` for the events not emitted by an extrinsic
+    #[chronicle(sql_type = "BIGINT", as_text)]
+    pub extrinsic_index: Option<String>,
+    #[chronicle(sql_type = "BIGINT", as_text)]
+    pub event_index: String,
+    pub pallet: String,
+    pub event_name: String,
+    /// These are the JSON encoded fields of the event
+    #[chronicle(sql_type = "JSONB", as_text)]
+    pub fields: String,
+    #[chronicle(created_at)]
+    pub create_at: NaiveDateTime,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, SimpleObject)]
+#[serde(rename_all = "camelCase")]
+pub struct BufferedEventRecord {
+    pub indexer_name: String,
+    pub chain_id: String,
+    pub address: String,
+    pub block_number: String,
+    pub block_hash: String,
+    pub transaction_hash: String,
+    pub log_index: String,
+    pub topics: Vec<String>,
+    pub data: String,
+    /// This is either `pending` or `confirmed`
+    pub status: String,
+}
@@ -3,7 +3,10 @@
 use async_graphql::SimpleObject;
 use serde::{Deserialize, Serialize};
 
-use crate::entity_record::{BrandRecord, RewardRecord};
+use crate::{
+    db::entity::ChainCount,
+    entity_record::{BrandRecord, RewardRecord},
+};
 
 #[derive(Serialize, Deserialize, Debug, Clone, Default, SimpleObject)]
 #[serde(rename_all = "camelCase")]
@@ -28,6 +31,41 @@ pub struct GetRedemptionTransactionData {
 #[serde(rename_all = "camelCase")]
 pub struct TotalDetail {
     pub total: String,
+    /// This is the count of every chain that has records
+    pub chains: Vec<ChainTotal>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq, SimpleObject)]
+#[serde(rename_all = "camelCase")]
+pub struct ChainTotal {
+    pub chain_id: i64,
+    pub total: String,
+}
+
+impl TotalDetail {
+    /// This builds the total from the count of each chain,
+    /// only the count of `chain_id` is kept when it is given
+    pub fn from_chain_counts(counts: Vec<ChainCount>, chain_id: Option<i64>) -> Self {
+        let chains = counts
+            .into_iter()
+            .filter(|count| chain_id.is_none() || chain_id == Some(count.chain_id))
+            .collect::<Vec<ChainCount>>();
+
+        Self {
+            total: chains
+                .iter()
+                .map(|count| count.total)
+                .sum::<i64>()
+                .to_string(),
+            chains: chains
+                .iter()
+                .map(|count| ChainTotal {
+                    chain_id: count.chain_id,
+                    total: count.total.to_string(),
+                })
+                .collect(),
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, Default)]
@@ -64,3 +102,42 @@ impl<T> PaginatedData<T> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_total_detail_sums_the_counts_of_the_filtered_chains() {
+        let counts = || {
+            vec![
+                ChainCount {
+                    chain_id: 1,
+                    total: 4,
+                },
+                ChainCount {
+                    chain_id: 10,
+                    total: 3,
+                },
+            ]
+        };
+
+        let every_chain = TotalDetail::from_chain_counts(counts(), None);
+        assert_eq!(every_chain.total, "7");
+        assert_eq!(every_chain.chains.len(), 2);
+
+        let optimism = TotalDetail::from_chain_counts(counts(), Some(10));
+        assert_eq!(optimism.total, "3");
+        assert_eq!(
+            optimism.chains,
+            vec![ChainTotal {
+                chain_id: 10,
+                total: "3".to_string(),
+            }]
+        );
+
+        let unknown = TotalDetail::from_chain_counts(counts(), Some(56));
+        assert_eq!(unknown.total, "0");
+        assert!(unknown.chains.is_empty());
+    }
+}
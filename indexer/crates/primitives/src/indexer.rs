@@ -1,24 +1,103 @@
+use std::str::FromStr;
+
 use alloy::{
+    dyn_abi::DynSolValue,
     primitives::{Address, Bytes, B256, U256},
-    rpc::types::eth::{Log, Transaction},
+    rpc::types::eth::{Log, Transaction, TransactionReceipt},
 };
 use async_graphql::SimpleObject;
 use serde::{Deserialize, Serialize};
 
-use crate::db::raw_chronicle_event::store_event_to_db;
+use crate::{
+    calldata::{CalldataDecoder, DecodedCall},
+    config::INTERNAL_TRANSFER_SIGNATURE,
+    db::raw_chronicle_event::store_event_to_db,
+};
 
 #[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct ChronicleEvent {
+    /// This is the chain the log was emitted on
+    pub chain_id: u64,
     pub address: Address,
     pub block_number: u64,
+    pub block_hash: B256,
     pub transaction_hash: B256,
     pub topics: Vec<B256>,
     pub data: Bytes,
     pub block_timestamp: u64,
+    pub log_index: u64,
+    /// This is set when the log was removed from the canonical chain by a reorg
+    pub removed: bool,
+}
+
+/// This is an event emitted by a Substrate runtime, decoded with the runtime metadata
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct ChronicleRuntimeEvent {
+    /// This is the genesis hash of the chain, Substrate chains have no EIP-155 chain id
+    pub genesis_hash: B256,
+    pub block_number: u64,
+    pub block_hash: B256,
+    /// This is the phase of the block the event was emitted in, `ApplyExtrinsic`,
+    /// `Initialization` or `Finalization`
+    pub phase: String,
+    /// This is the index of the extrinsic that emitted the event, in the `ApplyExtrinsic` phase
+    pub extrinsic_index: Option<u32>,
+    /// This is the position of the event among the events of the block
+    pub event_index: u32,
+    pub pallet: String,
+    pub event: String,
+    /// These are the fields of the event, integers are decimal strings and byte arrays hex strings
+    pub fields: serde_json::Value,
+}
+
+/// This selects the runtime events an indexer stores, every event of `pallet`
+/// or only its `event`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct RuntimeEventFilter {
+    pub pallet: String,
+    pub event: Option<String>,
+}
+
+impl RuntimeEventFilter {
+    /// This function checks if the event of the pallet is selected by this filter
+    pub fn matches(&self, pallet: &str, event: &str) -> bool {
+        self.pallet == pallet && self.event.as_ref().map_or(true, |name| name == event)
+    }
+
+    /// This function checks if the event is selected by any of `filters`,
+    /// no filters select every event
+    pub fn any_matches(filters: &[Self], pallet: &str, event: &str) -> bool {
+        filters.is_empty() || filters.iter().any(|filter| filter.matches(pallet, event))
+    }
+}
+
+impl FromStr for RuntimeEventFilter {
+    type Err = anyhow::Error;
+
+    /// This parses `Pallet` or `Pallet.Event`
+    fn from_str(filter: &str) -> Result<Self, Self::Err> {
+        let (pallet, event) = match filter.trim().split_once('.') {
+            Some((pallet, event)) => (pallet, Some(event)),
+            None => (filter.trim(), None),
+        };
+        let is_name = |name: &str| {
+            !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        };
+        if !is_name(pallet) || !event.map_or(true, is_name) {
+            anyhow::bail!("invalid runtime event `{filter}`, expected `Pallet` or `Pallet.Event`");
+        }
+
+        Ok(Self {
+            pallet: pallet.to_string(),
+            event: event.map(str::to_string),
+        })
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, Default, SimpleObject)]
 pub struct DisplayChronicleEvent {
+    /// This is the chain the event was emitted on
+    pub chain_id: i64,
     pub address: String,
     pub block_number: String,
     pub transaction_hash: String,
@@ -28,32 +107,178 @@ pub struct DisplayChronicleEvent {
 
 #[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct ChronicleTransaction {
+    /// This is the chain the transaction was included on
+    pub chain_id: u64,
     pub hash: B256,
     pub nonce: u64,
     pub block_hash: B256,
     pub block_number: u64,
+    pub block_timestamp: u64,
+    pub transaction_index: u64,
     pub from: Address,
+    /// This is `address(0)` for contract creations
     pub to: Address,
     pub value: U256,
     pub gas_price: u128,
     pub gas: u128,
     pub max_fee_per_gas: u128,
     pub data: Bytes,
+    /// This is false when the transaction reverted, it is read from the receipt
+    pub status: bool,
+    pub gas_used: u128,
+    pub effective_gas_price: u128,
+    /// This is the contract the transaction created, if it is a contract creation
+    pub contract_address: Option<Address>,
+    /// This is the function the calldata called, when it is known to the calldata decoder
+    pub decoded_call: Option<DecodedCall>,
+    /// This is the data the transaction reverted with, it is only read for failed transactions
+    pub revert_data: Option<Bytes>,
+    /// This is the revert data decoded from an `Error(string)`, a `Panic(uint256)` or a known custom error
+    pub revert_reason: Option<String>,
+}
+
+impl ChronicleTransaction {
+    /// This function fills in the outcome of the transaction from its receipt
+    pub fn with_receipt(self, receipt: &TransactionReceipt) -> Self {
+        Self {
+            status: receipt.status(),
+            gas_used: receipt.gas_used,
+            effective_gas_price: receipt.effective_gas_price,
+            contract_address: receipt.contract_address,
+            ..self
+        }
+    }
+
+    /// This function decodes the function the transaction called
+    pub fn with_decoded_call(self, decoder: &CalldataDecoder) -> Self {
+        Self {
+            decoded_call: decoder.decode_call(&self.data),
+            ..self
+        }
+    }
+
+    /// This function sets the data the transaction reverted with, and the reason decoded from it
+    pub fn with_revert_data(self, revert_data: Bytes, decoder: &CalldataDecoder) -> Self {
+        Self {
+            revert_reason: decoder.decode_revert(&revert_data),
+            revert_data: Some(revert_data),
+            ..self
+        }
+    }
+
+    /// This function checks if the transaction was sent from or to one of `addresses`
+    pub fn involves_any(&self, addresses: &[Address]) -> bool {
+        addresses.iter().any(|address| {
+            self.from == *address || (self.to != Address::ZERO && self.to == *address)
+        })
+    }
+}
+
+/// This is the offset of the log index of an internal transfer, the transfers of a transaction
+/// are handed to the handlers as events, their log index must not collide with the real logs
+pub const INTERNAL_TRANSFER_LOG_INDEX_OFFSET: u64 = 1 << 32;
+
+/// This is native ETH moved by a call, read from the call traces of a block.
+/// Unlike the value of a transaction, a transfer made by a contract is not in the block itself.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChronicleInternalTransfer {
+    /// This is the chain the transfer was made on
+    pub chain_id: u64,
+    pub block_number: u64,
+    pub block_hash: B256,
+    pub block_timestamp: u64,
+    pub transaction_hash: B256,
+    pub transaction_index: u64,
+    /// This is the position of the call in the call tree of the transaction, depth first
+    pub trace_index: u64,
+    /// This is the path to the call in the call tree, the root call of the transaction is `[]`
+    pub trace_address: Vec<u64>,
+    /// This is `CALL`, `CREATE`, `CREATE2` or `SELFDESTRUCT`
+    pub call_type: String,
+    pub from: Address,
+    /// This is the created contract for a `CREATE`, and the beneficiary for a `SELFDESTRUCT`
+    pub to: Address,
+    pub value: U256,
+    pub input: Bytes,
+}
+
+impl ChronicleInternalTransfer {
+    /// This function returns the log index of the transfer when it is handed to the handlers
+    pub fn log_index(&self) -> u64 {
+        INTERNAL_TRANSFER_LOG_INDEX_OFFSET + self.trace_index
+    }
+
+    /// This function checks if the transfer was sent from or to one of `addresses`
+    pub fn involves_any(&self, addresses: &[Address]) -> bool {
+        addresses
+            .iter()
+            .any(|address| self.from == *address || self.to == *address)
+    }
+
+    /// This function returns the transfer as an `InternalTransfer(address indexed from,
+    /// address indexed to, uint256 value, bool withCalldata)` event of each of `addresses`
+    /// it was sent from or to, so it can be handed to the handlers like a log
+    pub fn to_chronicle_events(&self, addresses: &[Address]) -> Vec<ChronicleEvent> {
+        let data = DynSolValue::Tuple(vec![
+            DynSolValue::Uint(self.value, 256),
+            DynSolValue::Bool(!self.input.is_empty()),
+        ])
+        .abi_encode_params();
+
+        [self.from, self.to]
+            .into_iter()
+            .filter(|side| addresses.contains(side))
+            .fold(Vec::new(), |mut sides, side| {
+                // A transfer to itself is one event of the address
+                if !sides.contains(&side) {
+                    sides.push(side);
+                }
+                sides
+            })
+            .into_iter()
+            .map(|address| ChronicleEvent {
+                chain_id: self.chain_id,
+                address,
+                block_number: self.block_number,
+                block_hash: self.block_hash,
+                transaction_hash: self.transaction_hash,
+                topics: vec![
+                    INTERNAL_TRANSFER_SIGNATURE,
+                    self.from.into_word(),
+                    self.to.into_word(),
+                ],
+                data: data.clone().into(),
+                block_timestamp: self.block_timestamp,
+                log_index: self.log_index(),
+                removed: false,
+            })
+            .collect()
+    }
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+/// This is what an indexer indexes, the logs of an event or the transactions of addresses
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
 pub enum ChronicleIndexingMode {
+    /// Full blocks are fetched and the transactions sent from or to the indexed addresses are stored
     Transaction,
+    /// The call traces of blocks are fetched and the native ETH moved into or out of the indexed
+    /// addresses by any call is stored and handed to the handlers
+    Trace,
+    #[default]
     Event,
 }
 
 impl From<Transaction> for ChronicleTransaction {
     fn from(tx: Transaction) -> Self {
         Self {
+            chain_id: 0,
             hash: tx.hash,
             nonce: tx.nonce,
             block_hash: tx.block_hash.unwrap_or(B256::default()),
             block_number: tx.block_number.unwrap_or(0),
+            block_timestamp: 0,
+            transaction_index: tx.transaction_index.unwrap_or(0),
             from: tx.from,
             to: tx.to.unwrap_or(Address::ZERO),
             value: tx.value,
@@ -61,6 +286,14 @@ impl From<Transaction> for ChronicleTransaction {
             gas: tx.gas,
             data: tx.input,
             max_fee_per_gas: tx.max_fee_per_gas.unwrap_or(0),
+            // The outcome is only known from the receipt, see `with_receipt`
+            status: false,
+            gas_used: 0,
+            effective_gas_price: 0,
+            contract_address: None,
+            decoded_call: None,
+            revert_data: None,
+            revert_reason: None,
         }
     }
 }
@@ -68,18 +301,23 @@ impl From<Transaction> for ChronicleTransaction {
 impl From<Log> for ChronicleEvent {
     fn from(log: Log) -> Self {
         Self {
+            chain_id: 0,
             address: log.address(),
             block_number: log.block_number.unwrap_or(0),
+            block_hash: log.block_hash.unwrap_or(B256::default()),
             transaction_hash: log.transaction_hash.unwrap_or(B256::default()),
             topics: log.data().clone().topics().to_vec(),
             data: log.inner.data.data,
             block_timestamp: log.block_timestamp.unwrap_or(0),
+            log_index: log.log_index.unwrap_or(0),
+            removed: log.removed,
         }
     }
 }
 
 impl DisplayChronicleEvent {
     pub fn new(
+        chain_id: i64,
         address: String,
         block_number: String,
         transaction_hash: String,
@@ -87,6 +325,7 @@ impl DisplayChronicleEvent {
         data: String,
     ) -> Self {
         Self {
+            chain_id,
             address,
             block_number,
             transaction_hash,
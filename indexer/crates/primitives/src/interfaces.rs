@@ -1,6 +1,9 @@
 use async_trait::async_trait;
 
-use crate::indexer::ChronicleTransaction;
+use crate::{
+    db::connection_pool::DbPool,
+    indexer::{ChronicleEvent, ChronicleTransaction},
+};
 
 /// This event indexer triat would be shared across all supported chains
 #[async_trait]
@@ -12,14 +15,15 @@ pub trait ChronicleEventIndexer {
     type EventDecoder;
 
     /// This function queries events from a specified block number
-    /// `[Filter]`, having `address`, `last_block` and `event_signature` as parameters
+    /// `[Filter]`, having `address`, `last_block` and `event_signature` as parameters.
+    /// A connection is taken from `db_pool` for each write, not for the whole run
     async fn query_then_subscribe_to_events(
         &self,
         provider: Self::SubProvider,
         addr: Self::ContractAddress,
         event_sig: Self::EventSignature,
         block_nuber: Self::BlockNumber,
-        db_client: &mut tokio_postgres::Client,
+        db_pool: &DbPool,
     ) -> Result<(), anyhow::Error>;
 
     /// This creates a filter and subscribes to an event returning the event
@@ -29,6 +33,21 @@ pub trait ChronicleEventIndexer {
         provider: Self::SubProvider,
         addr: Vec<Self::ContractAddress>,
         event_sig: Self::EventSignature,
+        db_pool: &DbPool,
+    ) -> Result<(), anyhow::Error>;
+}
+
+/// This event handler trait is implemented by anything that turns an indexed event into entities.
+/// Handlers are registered in the [`crate::registry::EventHandlerRegistry`] at startup,
+/// so downstream crates can index their own contracts without editing chronicle.
+#[async_trait]
+pub trait ChronicleEventHandler: Send + Sync {
+    /// This function is called with every event matching the (address, topic0) the handler
+    /// is registered for. The changes it makes to tables the journal trigger is attached to
+    /// (see [`crate::db::reorg::attach_journal_trigger`]) are rolled back on a reorg.
+    async fn handle(
+        &self,
+        event: &ChronicleEvent,
         db_client: &mut tokio_postgres::Client,
     ) -> Result<(), anyhow::Error>;
 }
@@ -39,7 +58,7 @@ pub trait ChronicleTransactionIndexer {
     type SubProvider;
     type TargetAddress;
 
-    /// This function subscribes to blocks and filters transactions based on the index address.
+    /// This function follows new blocks and filters their transactions based on the index address.
     /// Uses a callback closure to output the filter tx
     async fn subscribe_transactions<F>(
         &self,
@@ -48,5 +67,5 @@ pub trait ChronicleTransactionIndexer {
         callback: F,
     ) -> Result<(), anyhow::Error>
     where
-        F: FnMut(Vec<ChronicleTransaction>);
+        F: FnMut(Vec<ChronicleTransaction>) + Send;
 }
@@ -1,3 +1,8 @@
+// This lets `#[derive(ChronicleEntity)]` refer to this crate by name inside it
+extern crate self as chronicle_primitives;
+
+pub mod abi;
+pub mod calldata;
 pub mod config;
 pub mod db;
 pub mod entity_record;
@@ -5,8 +10,11 @@ pub mod errors;
 pub mod handler_payload;
 pub mod indexer;
 pub mod interfaces;
+pub mod metrics;
+pub mod registry;
 pub mod utils;
 
+use indexer::ChronicleIndexingMode;
 use serde::{Deserialize, Serialize};
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -25,12 +33,18 @@ pub struct Config {
     pub server: ServerConfig,
     /// This is the URL of the database
     pub db_url: String,
+    /// This is the pool of database connections shared by the server and the indexers
+    #[serde(default)]
+    pub db_pool: DbPoolConfig,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ServerConfig {
     /// This is the URL of the server
     pub server_url: String,
+    /// This is the RPC the server reads on-chain state from, when reconciling balances
+    #[serde(default)]
+    pub rpc_url: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -41,12 +55,204 @@ pub struct IndexerConfig {
     pub state_machine: String,
     /// This is the RPC url of the state machine
     pub rpc_url: String,
-    /// This is the address of the contract that is to be indexed
+    /// This is the address of the contract that is to be indexed,
+    /// or the address of the factory when `factory` is set
     pub address: String,
-    /// This is the event signature of the event that is to be indexed
+    /// This is the event signature of the event that is to be indexed,
+    /// it can be left out when `event` is set
+    #[serde(default)]
     pub event_signature: String,
+    /// This is a human-readable event like `Transfer(address indexed from,address indexed to,uint256 value)`,
+    /// or the name of an event of `abi_path`. When set, the events are decoded into a typed table
+    #[serde(default)]
+    pub event: Option<String>,
+    /// This is the path of a JSON ABI, or of a compiler artifact, `event` is looked up in
+    #[serde(default)]
+    pub abi_path: Option<String>,
     /// This is the block number to start indexing from
     pub block_number: u64,
+    /// This controls how historical events are fetched with `eth_getLogs`
+    #[serde(default)]
+    pub backfill: BackfillConfig,
+    /// This is how many recent blocks are kept to detect and roll back chain reorganizations
+    #[serde(default = "default_reorg_window")]
+    pub reorg_window: u64,
+    /// This is how deep a log must be before it is applied to the entity tables,
+    /// shallower logs are held in the pending buffer
+    #[serde(default)]
+    pub confirmations: Confirmations,
+    /// When set, `address` is an OpenGatewayFactory. Every gateway it deploys is discovered
+    /// from `GatewayDeployed` and `event_signature` is indexed on it from its deployment block
+    #[serde(default)]
+    pub factory: bool,
+    /// This is what happens to an event no handler is registered for
+    #[serde(default)]
+    pub unmatched_events: UnmatchedEventPolicy,
+    /// This controls how the indexer reconnects when its WebSocket connection drops
+    #[serde(default)]
+    pub reconnect: ReconnectConfig,
+    /// This is how live logs are received, subscribed to over a WebSocket or polled over HTTP
+    #[serde(default)]
+    pub ingestion: IngestionMode,
+    /// This is how often in milliseconds the head is polled for new logs, in the poll ingestion mode
+    #[serde(default = "default_poll_interval_ms")]
+    pub poll_interval_ms: u64,
+    /// This is what the indexer indexes, the logs of the event or the transactions of the addresses
+    #[serde(default)]
+    pub indexing_mode: ChronicleIndexingMode,
+    /// These are the addresses whose transactions are indexed along with `address`, in the transaction indexing mode
+    #[serde(default)]
+    pub addresses: Vec<String>,
+    /// These are the runtime events indexed on a `RUNTIME` state machine, as `Pallet` or
+    /// `Pallet.Event`. Every event of the runtime is indexed when it is empty
+    #[serde(default)]
+    pub runtime_events: Vec<String>,
+    /// This is the tracing API the call traces are read with, in the trace indexing mode
+    #[serde(default)]
+    pub trace_api: TraceApi,
+}
+
+fn default_reorg_window() -> u64 {
+    64
+}
+
+fn default_poll_interval_ms() -> u64 {
+    2_000
+}
+
+/// This is how an indexer receives the live logs
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum IngestionMode {
+    /// Logs are polled when `rpc_url` is an HTTP endpoint and subscribed to otherwise
+    #[default]
+    Auto,
+    /// Logs are subscribed to with `eth_subscribe`, this needs a WebSocket or IPC endpoint
+    Subscribe,
+    /// New blocks are polled with `eth_blockNumber` and their logs fetched with `eth_getLogs`
+    Poll,
+}
+
+/// This is the node API the call traces of a block are read with
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TraceApi {
+    /// `debug_traceBlockByNumber` with the `callTracer`, the call tree of every transaction
+    #[default]
+    Debug,
+    /// `trace_block`, the flat Parity style traces of erigon, reth and nethermind
+    Parity,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct BackfillConfig {
+    /// This is the largest block range requested in a single `eth_getLogs` call
+    pub max_block_range: u64,
+    /// This is the smallest block range the window can shrink to when the provider rejects a range
+    pub min_block_range: u64,
+    /// This is the number of times a failing window is retried before the backfill gives up
+    pub max_retries: u32,
+    /// This is the base delay in milliseconds between retries, it doubles on every retry
+    pub retry_backoff_ms: u64,
+}
+
+impl Default for BackfillConfig {
+    fn default() -> Self {
+        Self {
+            max_block_range: 2_000,
+            min_block_range: 1,
+            max_retries: 5,
+            retry_backoff_ms: 500,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct ReconnectConfig {
+    /// This is the delay in milliseconds before the first reconnection attempt, it doubles on every attempt
+    pub initial_backoff_ms: u64,
+    /// This is the longest delay in milliseconds between reconnection attempts
+    pub max_backoff_ms: u64,
+    /// This is the number of attempts in a row before the indexer gives up, it never does when unset
+    pub max_attempts: Option<u32>,
+}
+
+impl Default for ReconnectConfig {
+    fn default() -> Self {
+        Self {
+            initial_backoff_ms: 1_000,
+            max_backoff_ms: 60_000,
+            max_attempts: None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct DbPoolConfig {
+    /// This is the largest number of open connections
+    pub max_size: usize,
+    /// This is how long in milliseconds a request waits for a free connection before failing
+    pub wait_timeout_ms: u64,
+    /// This is how long in milliseconds opening a new connection may take
+    pub create_timeout_ms: u64,
+    /// This is how long in milliseconds the reset of a returned connection may take
+    pub recycle_timeout_ms: u64,
+}
+
+impl Default for DbPoolConfig {
+    fn default() -> Self {
+        Self {
+            max_size: 16,
+            wait_timeout_ms: 5_000,
+            create_timeout_ms: 5_000,
+            recycle_timeout_ms: 1_000,
+        }
+    }
+}
+
+/// This is either a number of blocks built on top of the block of a log,
+/// or the `safe`/`finalized` block tag reported by the node
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum Confirmations {
+    Blocks(u64),
+    Tag(FinalityTag),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum FinalityTag {
+    Safe,
+    Finalized,
+}
+
+impl Confirmations {
+    /// Logs are applied as soon as they are received, nothing is held in the pending buffer
+    pub fn is_immediate(&self) -> bool {
+        matches!(self, Self::Blocks(0))
+    }
+}
+
+impl Default for Confirmations {
+    fn default() -> Self {
+        Self::Blocks(0)
+    }
+}
+
+/// This is what the indexer does with an event that no registered handler matches
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum UnmatchedEventPolicy {
+    /// The indexer stops with an error
+    Error,
+    /// The event is logged and skipped
+    #[default]
+    Warn,
+    /// The event is written to the raw event table
+    Raw,
 }
 
 impl From<String> for StateMachine {
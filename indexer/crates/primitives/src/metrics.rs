@@ -0,0 +1,114 @@
+//! This module holds the counters chronicle reports about its indexers.
+//! The indexers and the server run in the same process, the indexers count into
+//! [`metrics`] and the server renders the counters in the Prometheus text format.
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    sync::{Mutex, OnceLock},
+};
+
+/// This counts the WebSocket reconnections of an indexer
+pub const INDEXER_RECONNECTS: Metric = Metric {
+    name: "chronicle_indexer_reconnects_total",
+    help: "WebSocket reconnections of the indexer",
+};
+
+/// This counts the failed connection attempts of an indexer
+pub const INDEXER_CONNECTION_FAILURES: Metric = Metric {
+    name: "chronicle_indexer_connection_failures_total",
+    help: "Failed WebSocket connection attempts of the indexer",
+};
+
+/// This is a counter kept per indexer
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Metric {
+    pub name: &'static str,
+    pub help: &'static str,
+}
+
+/// These are the counters of the indexers, by metric and indexer name
+#[derive(Debug, Default)]
+pub struct ChronicleMetrics {
+    counters: Mutex<BTreeMap<Metric, BTreeMap<String, u64>>>,
+}
+
+/// This returns the counters of this process
+pub fn metrics() -> &'static ChronicleMetrics {
+    static METRICS: OnceLock<ChronicleMetrics> = OnceLock::new();
+    METRICS.get_or_init(ChronicleMetrics::default)
+}
+
+impl ChronicleMetrics {
+    /// This function adds one to the counter of the indexer, it returns the new count
+    pub fn increment(&self, metric: Metric, indexer_name: &str) -> u64 {
+        let mut counters = self.counters.lock().expect("metrics lock poisoned");
+        let count = counters
+            .entry(metric)
+            .or_default()
+            .entry(indexer_name.to_string())
+            .or_default();
+        *count += 1;
+
+        *count
+    }
+
+    /// This function returns the counter of the indexer
+    pub fn get(&self, metric: Metric, indexer_name: &str) -> u64 {
+        let counters = self.counters.lock().expect("metrics lock poisoned");
+        counters
+            .get(&metric)
+            .and_then(|counts| counts.get(indexer_name))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// This function renders the counters in the Prometheus text exposition format
+    pub fn render(&self) -> String {
+        let counters = self.counters.lock().expect("metrics lock poisoned");
+        let mut rendered = String::new();
+        for (metric, counts) in counters.iter() {
+            let _ = writeln!(rendered, "# HELP {} {}", metric.name, metric.help);
+            let _ = writeln!(rendered, "# TYPE {} counter", metric.name);
+            for (indexer_name, count) in counts {
+                let _ = writeln!(
+                    rendered,
+                    "{}{{indexer=\"{}\"}} {count}",
+                    metric.name,
+                    escape_label(indexer_name)
+                );
+            }
+        }
+
+        rendered
+    }
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counters_are_kept_per_indexer_and_rendered() {
+        let metrics = ChronicleMetrics::default();
+        assert_eq!(metrics.increment(INDEXER_RECONNECTS, "transfers"), 1);
+        assert_eq!(metrics.increment(INDEXER_RECONNECTS, "transfers"), 2);
+        metrics.increment(INDEXER_RECONNECTS, "say \"hi\"");
+
+        assert_eq!(metrics.get(INDEXER_RECONNECTS, "transfers"), 2);
+        assert_eq!(metrics.get(INDEXER_CONNECTION_FAILURES, "transfers"), 0);
+        assert_eq!(
+            metrics.render(),
+            "# HELP chronicle_indexer_reconnects_total WebSocket reconnections of the indexer\n\
+             # TYPE chronicle_indexer_reconnects_total counter\n\
+             chronicle_indexer_reconnects_total{indexer=\"say \\\"hi\\\"\"} 1\n\
+             chronicle_indexer_reconnects_total{indexer=\"transfers\"} 2\n"
+        );
+    }
+}
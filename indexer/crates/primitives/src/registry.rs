@@ -0,0 +1,251 @@
+//! This module routes indexed events to the handlers that turn them into entities.
+//! Handlers are keyed by the (address, topic0) of the events they handle, a handler
+//! registered without an address handles the event whichever contract emitted it.
+use std::{collections::HashMap, fmt, sync::Arc};
+
+use alloy::primitives::{Address, B256};
+use tracing::warn;
+
+use crate::{
+    config::{
+        EVENT_EIGHT_SIGNATURE, EVENT_FIVE_SIGNATURE, EVENT_FOUR_SIGNATURE, EVENT_NINE_SIGNATURE,
+        EVENT_ONE_SIGNATURE, EVENT_SEVEN_SIGNATURE, EVENT_SIX_SIGNATURE, EVENT_TEN_SIGNATURE,
+        EVENT_THREE_SIGNATURE, EVENT_TWO_SIGNATURE, INTERNAL_TRANSFER_SIGNATURE,
+    },
+    db::{
+        entities::{
+            hooks::{
+                CrossBrandRedemptionHandler, CurrentPoolStateHandler, GatewayDeployedHandler,
+                InternalTransferHandler, NativeWithdrawnHandler, PaymentCompletedHandler,
+                RegisterBrandHandler, RewardCreatedHandler, TokenDelistedHandler,
+                TokenWhitelistedHandler, WithdrawalFailedHandler,
+            },
+            DB_VERSION,
+        },
+        raw_chronicle_event::{store_event_to_db, RAW_EVENT_TABLE_NAME},
+    },
+    indexer::ChronicleEvent,
+    interfaces::ChronicleEventHandler,
+    UnmatchedEventPolicy,
+};
+
+#[derive(Clone, Default)]
+pub struct EventHandlerRegistry {
+    handlers: HashMap<(Option<Address>, B256), Arc<dyn ChronicleEventHandler>>,
+    unmatched_events: UnmatchedEventPolicy,
+}
+
+impl EventHandlerRegistry {
+    /// This creates an empty registry, events are handled by the unmatched event policy
+    /// until handlers are registered
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// This creates a registry with the handlers of the OpenReward and OpenGateway entities,
+    /// for the events emitted by `addresses` only
+    pub fn with_entity_handlers(addresses: &[Address]) -> Self {
+        let mut registry = Self::new();
+        for address in addresses {
+            for (topic0, handler) in entity_handlers() {
+                registry.handlers.insert((Some(*address), topic0), handler);
+            }
+        }
+
+        registry
+    }
+
+    /// This creates a registry with the handlers of the OpenReward and OpenGateway entities,
+    /// for the events emitted by any contract. It is meant for factory indexers only, the
+    /// gateways of a factory are only known once they are deployed
+    pub fn with_factory_entity_handlers() -> Self {
+        let mut registry = Self::new();
+        for (topic0, handler) in entity_handlers() {
+            registry.handlers.insert((None, topic0), handler);
+        }
+
+        registry
+    }
+
+    /// This function registers the handler of the `topic0` events emitted by `address`,
+    /// replacing the handler registered for them before
+    pub fn register(
+        &mut self,
+        address: Address,
+        topic0: B256,
+        handler: impl ChronicleEventHandler + 'static,
+    ) -> &mut Self {
+        self.handlers
+            .insert((Some(address), topic0), Arc::new(handler));
+        self
+    }
+
+    /// This function registers the handler of the `topic0` events emitted by any contract,
+    /// a handler registered for the emitting address takes precedence
+    pub fn register_any_address(
+        &mut self,
+        topic0: B256,
+        handler: impl ChronicleEventHandler + 'static,
+    ) -> &mut Self {
+        self.handlers.insert((None, topic0), Arc::new(handler));
+        self
+    }
+
+    /// This sets what happens to the events no handler is registered for
+    pub fn with_unmatched_events(mut self, unmatched_events: UnmatchedEventPolicy) -> Self {
+        self.unmatched_events = unmatched_events;
+        self
+    }
+
+    /// This function returns the handler of the event, if one is registered
+    pub fn handler(&self, event: &ChronicleEvent) -> Option<&Arc<dyn ChronicleEventHandler>> {
+        let topic0 = *event.topics.first()?;
+
+        self.handlers
+            .get(&(Some(event.address), topic0))
+            .or_else(|| self.handlers.get(&(None, topic0)))
+    }
+
+    /// This function hands the event to its handler, events without one are
+    /// handled according to the unmatched event policy
+    pub async fn dispatch(
+        &self,
+        event: &ChronicleEvent,
+        db_client: &mut tokio_postgres::Client,
+    ) -> Result<(), anyhow::Error> {
+        if let Some(handler) = self.handler(event) {
+            return handler.handle(event, db_client).await;
+        }
+
+        let topic0 = event.topics.first().copied().unwrap_or_default();
+        match self.unmatched_events {
+            UnmatchedEventPolicy::Error => anyhow::bail!(
+                "No handler registered for the event {topic0} emitted by {} in {}",
+                event.address,
+                event.transaction_hash
+            ),
+            UnmatchedEventPolicy::Warn => {
+                warn!(
+                    "Skipping the event {topic0} emitted by {} in {}, no handler is registered for it",
+                    event.address, event.transaction_hash
+                );
+                Ok(())
+            }
+            UnmatchedEventPolicy::Raw => {
+                store_event_to_db(
+                    event,
+                    db_client,
+                    &format!("{RAW_EVENT_TABLE_NAME}{DB_VERSION}"),
+                )
+                .await
+            }
+        }
+    }
+}
+
+/// These are the handlers of the OpenReward and OpenGateway entities, by topic0
+fn entity_handlers() -> [(B256, Arc<dyn ChronicleEventHandler>); 11] {
+    [
+        (EVENT_ONE_SIGNATURE, Arc::new(CrossBrandRedemptionHandler)),
+        (EVENT_TWO_SIGNATURE, Arc::new(CurrentPoolStateHandler)),
+        (EVENT_THREE_SIGNATURE, Arc::new(RegisterBrandHandler)),
+        (EVENT_FOUR_SIGNATURE, Arc::new(RewardCreatedHandler)),
+        (EVENT_FIVE_SIGNATURE, Arc::new(PaymentCompletedHandler)),
+        (EVENT_SIX_SIGNATURE, Arc::new(GatewayDeployedHandler)),
+        (EVENT_SEVEN_SIGNATURE, Arc::new(TokenWhitelistedHandler)),
+        (EVENT_EIGHT_SIGNATURE, Arc::new(TokenDelistedHandler)),
+        (EVENT_NINE_SIGNATURE, Arc::new(NativeWithdrawnHandler)),
+        (EVENT_TEN_SIGNATURE, Arc::new(WithdrawalFailedHandler)),
+        (
+            INTERNAL_TRANSFER_SIGNATURE,
+            Arc::new(InternalTransferHandler),
+        ),
+    ]
+}
+
+impl fmt::Debug for EventHandlerRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EventHandlerRegistry")
+            .field("handlers", &self.handlers.keys().collect::<Vec<_>>())
+            .field("unmatched_events", &self.unmatched_events)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy::primitives::address;
+    use async_trait::async_trait;
+
+    use super::*;
+
+    struct NoopHandler;
+
+    #[async_trait]
+    impl ChronicleEventHandler for NoopHandler {
+        async fn handle(
+            &self,
+            _event: &ChronicleEvent,
+            _db_client: &mut tokio_postgres::Client,
+        ) -> Result<(), anyhow::Error> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_handler_prefers_the_emitting_address_over_any_address() {
+        let gateway = address!("88da6bf26964af9d7eed9e03e53415d37aa96045");
+        let mut registry = EventHandlerRegistry::new();
+        registry
+            .register_any_address(EVENT_FIVE_SIGNATURE, NoopHandler)
+            .register(gateway, EVENT_FIVE_SIGNATURE, NoopHandler);
+        let any_address = &registry.handlers[&(None, EVENT_FIVE_SIGNATURE)];
+        let gateway_address = &registry.handlers[&(Some(gateway), EVENT_FIVE_SIGNATURE)];
+
+        let from_gateway = ChronicleEvent {
+            address: gateway,
+            topics: vec![EVENT_FIVE_SIGNATURE],
+            ..Default::default()
+        };
+        let from_other = ChronicleEvent {
+            topics: vec![EVENT_FIVE_SIGNATURE],
+            ..Default::default()
+        };
+        let unmatched = ChronicleEvent {
+            address: gateway,
+            topics: vec![EVENT_SIX_SIGNATURE],
+            ..Default::default()
+        };
+
+        assert!(Arc::ptr_eq(
+            registry.handler(&from_gateway).unwrap(),
+            gateway_address
+        ));
+        assert!(Arc::ptr_eq(
+            registry.handler(&from_other).unwrap(),
+            any_address
+        ));
+        assert!(registry.handler(&unmatched).is_none());
+        assert!(registry.handler(&ChronicleEvent::default()).is_none());
+    }
+
+    #[test]
+    fn test_entity_handlers_only_handle_the_indexed_addresses() {
+        let gateway = address!("88da6bf26964af9d7eed9e03e53415d37aa96045");
+        let copycat = address!("d8da6bf26964af9d7eed9e03e53415d37aa96045");
+        let payment_from = |address| ChronicleEvent {
+            address,
+            topics: vec![EVENT_FIVE_SIGNATURE],
+            ..Default::default()
+        };
+
+        let registry = EventHandlerRegistry::with_entity_handlers(&[gateway]);
+        assert!(registry.handler(&payment_from(gateway)).is_some());
+        assert!(registry.handler(&payment_from(copycat)).is_none());
+
+        // The gateways of a factory are not known up front
+        let registry = EventHandlerRegistry::with_factory_entity_handlers();
+        assert!(registry.handler(&payment_from(gateway)).is_some());
+        assert!(registry.handler(&payment_from(copycat)).is_some());
+    }
+}
@@ -0,0 +1,439 @@
+//! This module builds the GraphQL schema of the events indexed from an ABI.
+//! The schema is built at startup from the event tables: every event gets a typed object,
+//! a filter input with equality, range and `in` filters per field, an order enum and
+//! a cursor paginated connection.
+use std::sync::Arc;
+
+use async_graphql::{
+    dynamic::{
+        Enum, Field, FieldFuture, FieldValue, InputObject, InputValue, Object, ResolverContext,
+        Scalar, Schema, TypeRef, ValueAccessor,
+    },
+    Value,
+};
+use chronicle_primitives::{
+    abi::{AbiColumnType, AbiColumnValue, AbiEventTable},
+    db::{
+        abi_event::{
+            abi_event_query_columns, query_abi_events, AbiEventCursor, AbiEventFilter,
+            AbiEventPage, AbiEventQuery, AbiEventRow, AbiFilterOp, AbiFilterValue, AbiQueryColumn,
+        },
+        connection_pool::{get_db_client, DbPool},
+    },
+};
+
+const JSON_SCALAR: &str = "JSON";
+const PAGE_INFO: &str = "PageInfo";
+const ORDER_DIRECTION: &str = "OrderDirection";
+
+/// These are the range filters of numeric fields, with the comparison they stand for
+const RANGE_FILTERS: [(&str, AbiFilterOp); 4] = [
+    ("Gt", AbiFilterOp::Gt),
+    ("Gte", AbiFilterOp::Gte),
+    ("Lt", AbiFilterOp::Lt),
+    ("Lte", AbiFilterOp::Lte),
+];
+
+/// This function builds the schema of the ABI event tables,
+/// there is no schema to serve when no indexer decodes its events from an ABI
+pub fn build_abi_event_schema(
+    tables: &[AbiEventTable],
+    db_pool: &DbPool,
+) -> Result<Option<Schema>, anyhow::Error> {
+    if tables.is_empty() {
+        return Ok(None);
+    }
+
+    let mut query = Object::new("Query");
+    let mut schema = Schema::build("Query", None, None)
+        .register(Scalar::new(JSON_SCALAR))
+        .register(Enum::new(ORDER_DIRECTION).item("ASC").item("DESC"))
+        .register(page_info_object());
+
+    for table in tables {
+        let table = Arc::new(table.clone());
+        let type_name = format!("{}Event", to_pascal_case(event_name(&table)));
+        let columns = abi_event_query_columns(&table);
+
+        schema = schema
+            .register(event_object(&type_name, &table))
+            .register(filter_input(&type_name, &columns))
+            .register(order_enum(&type_name, &columns))
+            .register(edge_object(&type_name))
+            .register(connection_object(&type_name));
+        query = query.field(events_field(&type_name, table, columns));
+    }
+
+    let schema = schema
+        .register(query)
+        .data(db_pool.clone())
+        .finish()
+        .map_err(|e| anyhow::anyhow!("Could not build the ABI event schema: {e:?}"))?;
+
+    Ok(Some(schema))
+}
+
+/// This is the query field of an event, returning a page of its events
+fn events_field(type_name: &str, table: Arc<AbiEventTable>, columns: Vec<AbiQueryColumn>) -> Field {
+    let field_name = to_camel_case(event_name(&table));
+    let description = format!("Returns the `{}` events", table.event.signature());
+    let columns = Arc::new(columns);
+
+    Field::new(
+        field_name,
+        TypeRef::named_nn(format!("{type_name}Connection")),
+        move |ctx| {
+            let table = table.clone();
+            let columns = columns.clone();
+            FieldFuture::new(async move {
+                let query = abi_event_query(&ctx, &columns)?;
+                let db_pool = ctx.data::<DbPool>()?;
+                let mut db_client = get_db_client(db_pool).await?;
+                let page = query_abi_events(&table, &query, &mut db_client).await?;
+
+                Ok(Some(FieldValue::owned_any(page)))
+            })
+        },
+    )
+    .description(description)
+    .argument(InputValue::new(
+        "where",
+        TypeRef::named(format!("{type_name}Filter")),
+    ))
+    .argument(
+        InputValue::new("orderBy", TypeRef::named(format!("{type_name}OrderBy")))
+            .default_value(Value::Enum(async_graphql::Name::new("BLOCK_NUMBER"))),
+    )
+    .argument(
+        InputValue::new("orderDirection", TypeRef::named(ORDER_DIRECTION))
+            .default_value(Value::Enum(async_graphql::Name::new("DESC"))),
+    )
+    .argument(InputValue::new("first", TypeRef::named(TypeRef::INT)).default_value(100))
+    .argument(InputValue::new("after", TypeRef::named(TypeRef::STRING)))
+}
+
+/// This function reads the arguments of an events field into the query of its table
+fn abi_event_query(
+    ctx: &ResolverContext<'_>,
+    columns: &[AbiQueryColumn],
+) -> async_graphql::Result<AbiEventQuery> {
+    let mut query = AbiEventQuery::default();
+
+    if let Some(filter) = ctx.args.get("where").filter(|filter| !filter.is_null()) {
+        let filter = filter.object()?;
+        for column in columns {
+            let field_name = to_camel_case(&column.name);
+            let mut push = |op: AbiFilterOp, value: AbiFilterValue| {
+                query.filters.push(AbiEventFilter {
+                    column: column.name.clone(),
+                    op,
+                    value,
+                })
+            };
+
+            if let Some(value) = non_null(filter.get(&field_name)) {
+                let value = match column.column_type {
+                    AbiColumnType::Boolean => AbiFilterValue::Boolean(value.boolean()?),
+                    _ => AbiFilterValue::Text(value.string()?.to_string()),
+                };
+                push(AbiFilterOp::Eq, value);
+            }
+            if column.column_type == AbiColumnType::Numeric {
+                for (suffix, op) in RANGE_FILTERS {
+                    if let Some(value) = non_null(filter.get(&format!("{field_name}{suffix}"))) {
+                        push(op, AbiFilterValue::Text(value.string()?.to_string()));
+                    }
+                }
+            }
+            if column.is_address {
+                if let Some(values) = non_null(filter.get(&format!("{field_name}In"))) {
+                    let values = values
+                        .list()?
+                        .iter()
+                        .map(|value| value.string().map(str::to_string))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    push(AbiFilterOp::In, AbiFilterValue::TextList(values));
+                }
+            }
+        }
+    }
+
+    if let Some(order_by) = non_null(ctx.args.get("orderBy")) {
+        let order_by = order_by.enum_name()?;
+        query.order_by = columns
+            .iter()
+            .find(|column| to_screaming_snake_case(&column.name) == order_by)
+            .map(|column| column.name.clone())
+            .ok_or_else(|| format!("Unknown orderBy `{order_by}`"))?;
+    }
+    if let Some(direction) = non_null(ctx.args.get("orderDirection")) {
+        query.descending = direction.enum_name()? == "DESC";
+    }
+    if let Some(first) = non_null(ctx.args.get("first")) {
+        query.first = first.i64()?;
+    }
+    if let Some(after) = non_null(ctx.args.get("after")) {
+        query.after = Some(AbiEventCursor::decode(after.string()?)?);
+    }
+
+    Ok(query)
+}
+
+fn non_null(value: Option<ValueAccessor<'_>>) -> Option<ValueAccessor<'_>> {
+    value.filter(|value| !value.is_null())
+}
+
+/// This is the typed object of an event, the log metadata then a field per parameter
+fn event_object(type_name: &str, table: &AbiEventTable) -> Object {
+    let metadata: [(&str, &str, fn(&AbiEventRow) -> Value); 8] = [
+        ("id", TypeRef::INT, |row| row.id.into()),
+        ("chainId", TypeRef::INT, |row| row.chain_id.into()),
+        ("address", TypeRef::STRING, |row| row.address.clone().into()),
+        ("blockNumber", TypeRef::INT, |row| row.block_number.into()),
+        ("blockHash", TypeRef::STRING, |row| {
+            row.block_hash.clone().into()
+        }),
+        ("transactionHash", TypeRef::STRING, |row| {
+            row.transaction_hash.clone().into()
+        }),
+        ("logIndex", TypeRef::INT, |row| row.log_index.into()),
+        ("blockTimestamp", TypeRef::INT, |row| {
+            row.block_timestamp.into()
+        }),
+    ];
+
+    let mut object = Object::new(type_name)
+        .description(format!("This is a `{}` event", table.event.signature()));
+    for (name, type_ref, value) in metadata {
+        object = object.field(Field::new(name, TypeRef::named_nn(type_ref), move |ctx| {
+            FieldFuture::new(async move {
+                let row = ctx.parent_value.try_downcast_ref::<AbiEventRow>()?;
+                Ok(Some(value(row)))
+            })
+        }));
+    }
+
+    for (index, column) in table.columns.iter().enumerate() {
+        let column_type = column.column_type;
+        let type_ref = match column_type {
+            AbiColumnType::Boolean => TypeRef::BOOLEAN,
+            AbiColumnType::Jsonb => JSON_SCALAR,
+            _ => TypeRef::STRING,
+        };
+        object = object.field(
+            Field::new(
+                to_camel_case(&column.name),
+                TypeRef::named(type_ref),
+                move |ctx| {
+                    FieldFuture::new(async move {
+                        let row = ctx.parent_value.try_downcast_ref::<AbiEventRow>()?;
+                        let value = match (&row.values[index], column_type) {
+                            (None, _) => return Ok(None),
+                            (Some(AbiColumnValue::Text(json)), AbiColumnType::Jsonb) => {
+                                Value::from_json(serde_json::from_str(json)?)?
+                            }
+                            (Some(AbiColumnValue::Text(text)), _) => text.clone().into(),
+                            (Some(AbiColumnValue::Boolean(boolean)), _) => (*boolean).into(),
+                            (Some(AbiColumnValue::Bytes(bytes)), _) => {
+                                alloy::primitives::hex::encode_prefixed(bytes).into()
+                            }
+                        };
+                        Ok(Some(value))
+                    })
+                },
+            )
+            .description(format!("`{} {}`", column.ty.sol_type_name(), column.param)),
+        );
+    }
+
+    object
+}
+
+/// This is the filter input of an event: equality on every field that has one,
+/// ranges on numeric fields and `in` lists on addresses
+fn filter_input(type_name: &str, columns: &[AbiQueryColumn]) -> InputObject {
+    let mut input = InputObject::new(format!("{type_name}Filter"));
+    for column in columns {
+        let field_name = to_camel_case(&column.name);
+        match column.column_type {
+            AbiColumnType::Boolean => {
+                input = input.field(InputValue::new(
+                    &field_name,
+                    TypeRef::named(TypeRef::BOOLEAN),
+                ));
+            }
+            AbiColumnType::Numeric | AbiColumnType::Text => {
+                input = input.field(InputValue::new(
+                    &field_name,
+                    TypeRef::named(TypeRef::STRING),
+                ));
+            }
+            AbiColumnType::Bytea | AbiColumnType::Jsonb => {}
+        }
+        if column.column_type == AbiColumnType::Numeric {
+            for (suffix, _) in RANGE_FILTERS {
+                input = input.field(InputValue::new(
+                    format!("{field_name}{suffix}"),
+                    TypeRef::named(TypeRef::STRING),
+                ));
+            }
+        }
+        if column.is_address {
+            input = input.field(InputValue::new(
+                format!("{field_name}In"),
+                TypeRef::named_nn_list(TypeRef::STRING),
+            ));
+        }
+    }
+
+    input
+}
+
+/// This is the enum of the fields an event can be ordered by
+fn order_enum(type_name: &str, columns: &[AbiQueryColumn]) -> Enum {
+    columns
+        .iter()
+        .filter(|column| column.is_orderable())
+        .fold(Enum::new(format!("{type_name}OrderBy")), |order, column| {
+            order.item(to_screaming_snake_case(&column.name))
+        })
+}
+
+fn edge_object(type_name: &str) -> Object {
+    Object::new(format!("{type_name}Edge"))
+        .field(Field::new(
+            "cursor",
+            TypeRef::named_nn(TypeRef::STRING),
+            |ctx| {
+                FieldFuture::new(async move {
+                    let row = ctx.parent_value.try_downcast_ref::<AbiEventRow>()?;
+                    Ok(Some(Value::from(row.cursor.encode())))
+                })
+            },
+        ))
+        .field(Field::new("node", TypeRef::named_nn(type_name), |ctx| {
+            FieldFuture::new(async move {
+                let row = ctx.parent_value.try_downcast_ref::<AbiEventRow>()?;
+                Ok(Some(FieldValue::borrowed_any(row)))
+            })
+        }))
+}
+
+fn connection_object(type_name: &str) -> Object {
+    Object::new(format!("{type_name}Connection"))
+        .field(Field::new(
+            "edges",
+            TypeRef::named_nn_list_nn(format!("{type_name}Edge")),
+            |ctx| {
+                FieldFuture::new(async move {
+                    let page = ctx.parent_value.try_downcast_ref::<AbiEventPage>()?;
+                    Ok(Some(FieldValue::list(
+                        page.rows.iter().map(|row| FieldValue::borrowed_any(row)),
+                    )))
+                })
+            },
+        ))
+        .field(Field::new(
+            "pageInfo",
+            TypeRef::named_nn(PAGE_INFO),
+            |ctx| {
+                FieldFuture::new(async move {
+                    let page = ctx.parent_value.try_downcast_ref::<AbiEventPage>()?;
+                    Ok(Some(FieldValue::borrowed_any(page)))
+                })
+            },
+        ))
+}
+
+fn page_info_object() -> Object {
+    Object::new(PAGE_INFO)
+        .field(Field::new(
+            "hasNextPage",
+            TypeRef::named_nn(TypeRef::BOOLEAN),
+            |ctx| {
+                FieldFuture::new(async move {
+                    let page = ctx.parent_value.try_downcast_ref::<AbiEventPage>()?;
+                    Ok(Some(Value::from(page.has_next_page)))
+                })
+            },
+        ))
+        .field(Field::new(
+            "endCursor",
+            TypeRef::named(TypeRef::STRING),
+            |ctx| {
+                FieldFuture::new(async move {
+                    let page = ctx.parent_value.try_downcast_ref::<AbiEventPage>()?;
+                    Ok(page.rows.last().map(|row| Value::from(row.cursor.encode())))
+                })
+            },
+        ))
+}
+
+/// The event tables are named `event_<event_name>`, the schema names are built from `<event_name>`
+fn event_name(table: &AbiEventTable) -> &str {
+    table
+        .table_name
+        .strip_prefix("event_")
+        .unwrap_or(&table.table_name)
+}
+
+fn to_camel_case(snake: &str) -> String {
+    let pascal = to_pascal_case(snake);
+    let mut characters = pascal.chars();
+    match characters.next() {
+        Some(first) => first.to_ascii_lowercase().to_string() + characters.as_str(),
+        None => pascal,
+    }
+}
+
+fn to_pascal_case(snake: &str) -> String {
+    snake
+        .split('_')
+        .filter(|word| !word.is_empty())
+        .map(|word| {
+            let mut characters = word.chars();
+            match characters.next() {
+                Some(first) => first.to_ascii_uppercase().to_string() + characters.as_str(),
+                None => String::new(),
+            }
+        })
+        .collect()
+}
+
+fn to_screaming_snake_case(snake: &str) -> String {
+    snake.trim_matches('_').to_ascii_uppercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use chronicle_primitives::{
+        abi::{abi_event_table_name, resolve_event},
+        db::connection_pool::create_db_pool,
+        DbPoolConfig,
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_schema_has_a_typed_connection_per_event() {
+        let event = resolve_event(
+            "Transfer(address indexed from, address indexed to, uint256 value)",
+            None,
+        )
+        .unwrap();
+        let table = AbiEventTable::new(abi_event_table_name("uni_transfers"), event).unwrap();
+
+        let db_pool = create_db_pool("host=localhost", &DbPoolConfig::default()).unwrap();
+        let schema = build_abi_event_schema(&[table], &db_pool).unwrap().unwrap();
+        let sdl = schema.sdl();
+
+        assert!(sdl.contains("uniTransfers("));
+        assert!(sdl.contains("type UniTransfersEvent"));
+        assert!(sdl.contains("valueGte: String"));
+        assert!(sdl.contains("chainId: Int!"));
+        assert!(sdl.contains("chainIdGte: String"));
+        assert!(sdl.contains("fromIn: [String!]"));
+        assert!(sdl.contains("enum UniTransfersEventOrderBy"));
+        assert!(build_abi_event_schema(&[], &db_pool).unwrap().is_none());
+    }
+}
@@ -7,26 +7,29 @@ use axum::{
 };
 use chronicle_primitives::{
     db::{
+        connection_pool::get_db_client,
         entities::{
             brand::{
-                get_total_count_brands, query_all_brands_paginated, query_brand_by_id,
-                query_brand_by_name,
+                get_brand_count_per_chain, get_total_count_brands, query_all_brands_paginated,
+                query_brand_by_id, query_brand_by_name,
             },
             reward::query_all_rewards_owned_by_a_brand,
         },
-        raw_chronicle_event::create_db_instance,
     },
     entity_record::BrandRecord,
     handler_payload::{BrandAndItRewards, PaginatedData, TotalDetail},
 };
 use serde::Deserialize;
 
+use super::ChainFilter;
 use crate::{utils::AppError, AppState};
 
 #[derive(Deserialize)]
 pub struct GetAllBrandsFilter {
     pub page: i64,
     pub limit: i64,
+    #[serde(rename = "chainId")]
+    pub chain_id: Option<i64>,
 }
 
 /// This function is used to query  brand by name.
@@ -34,9 +37,10 @@ pub struct GetAllBrandsFilter {
 pub async fn get_brand_by_name(
     State(state): State<Arc<AppState>>,
     Path(brand_name): Path<String>,
+    Query(chain): Query<ChainFilter>,
 ) -> Result<Json<BrandRecord>, AppError> {
-    let mut db_client = create_db_instance(&state.db_url).await?;
-    let brand_record = query_brand_by_name(brand_name, &mut db_client).await?;
+    let mut db_client = get_db_client(&state.db_pool).await?;
+    let brand_record = query_brand_by_name(brand_name, chain.chain_id, &mut db_client).await?;
 
     Ok(Json(brand_record))
 }
@@ -47,9 +51,10 @@ pub async fn get_brand_by_name(
 pub async fn get_brand_by_id(
     State(state): State<Arc<AppState>>,
     Path(brand_id): Path<String>,
+    Query(chain): Query<ChainFilter>,
 ) -> Result<Json<BrandRecord>, AppError> {
-    let mut db_client = create_db_instance(&state.db_url).await?;
-    let brand_record = query_brand_by_id(brand_id, &mut db_client).await?;
+    let mut db_client = get_db_client(&state.db_pool).await?;
+    let brand_record = query_brand_by_id(brand_id, chain.chain_id, &mut db_client).await?;
 
     Ok(Json(brand_record))
 }
@@ -60,11 +65,12 @@ pub async fn get_all_brands_paginated(
     State(state): State<Arc<AppState>>,
     Query(filter): Query<GetAllBrandsFilter>,
 ) -> Result<Json<PaginatedData<BrandRecord>>, AppError> {
-    let mut db_client = create_db_instance(&state.db_url).await?;
+    let mut db_client = get_db_client(&state.db_pool).await?;
     let brand_records =
-        query_all_brands_paginated(&mut db_client, filter.page, filter.limit).await?;
+        query_all_brands_paginated(&mut db_client, filter.chain_id, filter.page, filter.limit)
+            .await?;
 
-    let total_items = get_total_count_brands(&mut db_client).await?;
+    let total_items = get_total_count_brands(filter.chain_id, &mut db_client).await?;
     let page_size = filter.limit;
     let total_page = (total_items + page_size - 1) / page_size;
     let current_page = filter.page;
@@ -82,13 +88,12 @@ pub async fn get_all_brands_paginated(
 
 pub async fn get_brand_count(
     State(state): State<Arc<AppState>>,
+    Query(chain): Query<ChainFilter>,
 ) -> Result<Json<TotalDetail>, AppError> {
-    let mut db_client = create_db_instance(&state.db_url).await?;
-    let brand_count = get_total_count_brands(&mut db_client).await?;
+    let mut db_client = get_db_client(&state.db_pool).await?;
+    let brand_counts = get_brand_count_per_chain(&mut db_client).await?;
 
-    let total_detail = TotalDetail {
-        total: brand_count.to_string(),
-    };
+    let total_detail = TotalDetail::from_chain_counts(brand_counts, chain.chain_id);
 
     Ok(Json(total_detail))
 }
@@ -96,10 +101,12 @@ pub async fn get_brand_count(
 pub async fn get_all_rewards_owned_by_a_brand(
     State(state): State<Arc<AppState>>,
     Path(brand_id): Path<String>,
+    Query(chain): Query<ChainFilter>,
 ) -> Result<Json<BrandAndItRewards>, AppError> {
-    let mut db_client = create_db_instance(&state.db_url).await?;
-    let brand_record = query_brand_by_id(brand_id.clone(), &mut db_client).await?;
-    let rewards = query_all_rewards_owned_by_a_brand(brand_id, &mut db_client).await?;
+    let mut db_client = get_db_client(&state.db_pool).await?;
+    let brand_record = query_brand_by_id(brand_id.clone(), chain.chain_id, &mut db_client).await?;
+    let rewards =
+        query_all_rewards_owned_by_a_brand(brand_id, chain.chain_id, &mut db_client).await?;
 
     let brand_and_it_rewards = BrandAndItRewards {
         brand_detail: brand_record,
@@ -0,0 +1,57 @@
+//! This module holds the handlers for the buffered events of indexers
+//! running with a confirmation depth.
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, Query, State},
+    Json,
+};
+use chronicle_primitives::{
+    db::{
+        connection_pool::get_db_client,
+        pending_event::{query_buffered_events, query_buffered_events_by_tx_hash, EventStatus},
+    },
+    entity_record::BufferedEventRecord,
+};
+use serde::Deserialize;
+
+use crate::{utils::AppError, AppState};
+
+#[derive(Deserialize)]
+pub struct GetEventsByStatusFilter {
+    /// This is either `pending` or `confirmed`, every buffered event is returned when missing
+    pub status: Option<String>,
+    /// This is the chain the events were emitted on, the events of every chain are returned when missing
+    #[serde(rename = "chainId")]
+    pub chain_id: Option<i64>,
+}
+
+/// This function is used to get the buffered events of an indexer.
+/// this `indexer_name` is specified in the path, and the `status` in the query parameter.
+pub async fn get_events_by_status(
+    State(state): State<Arc<AppState>>,
+    Path(indexer_name): Path<String>,
+    Query(filter): Query<GetEventsByStatusFilter>,
+) -> Result<Json<Vec<BufferedEventRecord>>, AppError> {
+    let status = filter
+        .status
+        .map(|status| status.parse::<EventStatus>())
+        .transpose()?;
+    let mut db_client = get_db_client(&state.db_pool).await?;
+    let events =
+        query_buffered_events(indexer_name, status, filter.chain_id, &mut db_client).await?;
+
+    Ok(Json(events))
+}
+
+/// This function is used to get the status of the events emitted by a transaction
+/// this `transaction_hash` is specified in the path.
+pub async fn get_event_status_by_tx_hash(
+    State(state): State<Arc<AppState>>,
+    Path(transaction_hash): Path<String>,
+) -> Result<Json<Vec<BufferedEventRecord>>, AppError> {
+    let mut db_client = get_db_client(&state.db_pool).await?;
+    let events = query_buffered_events_by_tx_hash(transaction_hash, &mut db_client).await?;
+
+    Ok(Json(events))
+}
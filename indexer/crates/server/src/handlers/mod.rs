@@ -2,6 +2,20 @@
 //! Returning userable and well structured data.
 
 pub mod brand;
+pub mod event;
+pub mod payment;
 pub mod pool;
 pub mod reward;
+pub mod token_whitelist;
 pub mod transaction;
+pub mod treasury;
+
+use serde::Deserialize;
+
+/// This is the `chainId` query parameter of the handlers,
+/// the records of every chain are returned when it is not given
+#[derive(Deserialize, Default)]
+pub struct ChainFilter {
+    #[serde(rename = "chainId")]
+    pub chain_id: Option<i64>,
+}
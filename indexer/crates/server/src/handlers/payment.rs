@@ -0,0 +1,107 @@
+//! This module holds the handlers for OpenGateway payment entities.
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, Query, State},
+    Json,
+};
+use chronicle_primitives::{
+    db::{
+        connection_pool::get_db_client,
+        entities::payment::{
+            get_total_count_payments, query_payment_by_payment_id, query_payments_paginated,
+            PaymentFilter,
+        },
+    },
+    entity_record::PaymentRecord,
+    handler_payload::PaginatedData,
+};
+use serde::Deserialize;
+
+use super::ChainFilter;
+use crate::{utils::AppError, AppState};
+
+#[derive(Deserialize)]
+pub struct GetPaymentsFilter {
+    pub page: i64,
+    pub limit: i64,
+    #[serde(rename = "chainId")]
+    pub chain_id: Option<i64>,
+}
+
+/// This function is used to get a payment by its `paymentId`
+/// this `payment_id` is specified in the path.
+pub async fn get_payment_by_payment_id(
+    State(state): State<Arc<AppState>>,
+    Path(payment_id): Path<String>,
+    Query(chain): Query<ChainFilter>,
+) -> Result<Json<PaymentRecord>, AppError> {
+    let mut db_client = get_db_client(&state.db_pool).await?;
+    let payment_record =
+        query_payment_by_payment_id(payment_id, chain.chain_id, &mut db_client).await?;
+
+    Ok(Json(payment_record))
+}
+
+/// This function is used to get the payments made by a payer
+/// this query is in the paginated form
+pub async fn get_payments_by_payer(
+    State(state): State<Arc<AppState>>,
+    Path(payer): Path<String>,
+    Query(filter): Query<GetPaymentsFilter>,
+) -> Result<Json<PaginatedData<PaymentRecord>>, AppError> {
+    get_payments_paginated(&state, PaymentFilter::Payer(payer), filter).await
+}
+
+/// This function is used to get the payments made with a token,
+/// `0x0000000000000000000000000000000000000000` lists the native ETH payments
+/// this query is in the paginated form
+pub async fn get_payments_by_token(
+    State(state): State<Arc<AppState>>,
+    Path(token_address): Path<String>,
+    Query(filter): Query<GetPaymentsFilter>,
+) -> Result<Json<PaginatedData<PaymentRecord>>, AppError> {
+    get_payments_paginated(&state, PaymentFilter::Token(token_address), filter).await
+}
+
+/// This function is used to get the payments received by a gateway
+/// this query is in the paginated form
+pub async fn get_payments_by_gateway(
+    State(state): State<Arc<AppState>>,
+    Path(gateway_address): Path<String>,
+    Query(filter): Query<GetPaymentsFilter>,
+) -> Result<Json<PaginatedData<PaymentRecord>>, AppError> {
+    get_payments_paginated(&state, PaymentFilter::Gateway(gateway_address), filter).await
+}
+
+async fn get_payments_paginated(
+    state: &AppState,
+    payment_filter: PaymentFilter,
+    filter: GetPaymentsFilter,
+) -> Result<Json<PaginatedData<PaymentRecord>>, AppError> {
+    let mut db_client = get_db_client(&state.db_pool).await?;
+    let payment_records = query_payments_paginated(
+        &payment_filter,
+        filter.chain_id,
+        &mut db_client,
+        filter.page,
+        filter.limit,
+    )
+    .await?;
+
+    let total_items =
+        get_total_count_payments(&payment_filter, filter.chain_id, &mut db_client).await?;
+    let page_size = filter.limit;
+    let total_page = (total_items + page_size - 1) / page_size;
+    let current_page = filter.page;
+
+    let paginated_data = PaginatedData::new(
+        payment_records,
+        total_page,
+        total_items,
+        current_page,
+        page_size,
+    );
+
+    Ok(Json(paginated_data))
+}
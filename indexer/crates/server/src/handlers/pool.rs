@@ -7,32 +7,38 @@ use axum::{
 };
 use chronicle_primitives::{
     db::{
+        connection_pool::get_db_client,
         entities::pool::{
-            get_pool_total_count, query_all_pools_paginated, query_pool_by_reward_address,
+            get_pool_count_per_chain, get_pool_total_count, query_all_pools_paginated,
+            query_pool_by_reward_address,
         },
-        raw_chronicle_event::create_db_instance,
     },
     entity_record::PoolRecord,
     handler_payload::{PaginatedData, TotalDetail},
 };
 use serde::Deserialize;
 
+use super::ChainFilter;
 use crate::{utils::AppError, AppState};
 
 #[derive(Deserialize)]
 pub struct GetAllPoolFilter {
     pub page: i64,
     pub limit: i64,
+    #[serde(rename = "chainId")]
+    pub chain_id: Option<i64>,
 }
 
 pub async fn get_all_pools(
     State(state): State<Arc<AppState>>,
     Query(filter): Query<GetAllPoolFilter>,
 ) -> Result<Json<PaginatedData<PoolRecord>>, AppError> {
-    let mut db_client = create_db_instance(&state.db_url).await?;
-    let pool_records = query_all_pools_paginated(&mut db_client, filter.page, filter.limit).await?;
+    let mut db_client = get_db_client(&state.db_pool).await?;
+    let pool_records =
+        query_all_pools_paginated(&mut db_client, filter.chain_id, filter.page, filter.limit)
+            .await?;
 
-    let total_items = get_pool_total_count(&mut db_client).await?;
+    let total_items = get_pool_total_count(filter.chain_id, &mut db_client).await?;
     let page_size = filter.limit;
     let current_page = filter.page;
     let total_page = (total_items + page_size - 1) / page_size;
@@ -51,22 +57,23 @@ pub async fn get_all_pools(
 pub async fn get_pool_by_reward_address(
     State(state): State<Arc<AppState>>,
     Path(reward_address): Path<String>,
+    Query(chain): Query<ChainFilter>,
 ) -> Result<Json<PoolRecord>, AppError> {
-    let mut db_client = create_db_instance(&state.db_url).await?;
-    let pool_record = query_pool_by_reward_address(reward_address, &mut db_client).await?;
+    let mut db_client = get_db_client(&state.db_pool).await?;
+    let pool_record =
+        query_pool_by_reward_address(reward_address, chain.chain_id, &mut db_client).await?;
 
     Ok(Json(pool_record))
 }
 
 pub async fn get_pool_count(
     State(state): State<Arc<AppState>>,
+    Query(chain): Query<ChainFilter>,
 ) -> Result<Json<TotalDetail>, AppError> {
-    let mut db_client = create_db_instance(&state.db_url).await?;
-    let pool_count = get_pool_total_count(&mut db_client).await?;
+    let mut db_client = get_db_client(&state.db_pool).await?;
+    let pool_counts = get_pool_count_per_chain(&mut db_client).await?;
 
-    let total_detail = TotalDetail {
-        total: pool_count.to_string(),
-    };
+    let total_detail = TotalDetail::from_chain_counts(pool_counts, chain.chain_id);
 
     Ok(Json(total_detail))
 }
@@ -7,23 +7,26 @@ use axum::{
 };
 use chronicle_primitives::{
     db::{
+        connection_pool::get_db_client,
         entities::reward::{
-            get_total_rewards_count, query_all_rewards_paginated, query_reward_by_brand_id,
-            query_reward_by_requestor_address,
+            get_reward_count_per_chain, get_total_rewards_count, query_all_rewards_paginated,
+            query_reward_by_brand_id, query_reward_by_requestor_address,
         },
-        raw_chronicle_event::create_db_instance,
     },
     entity_record::RewardRecord,
     handler_payload::{PaginatedData, TotalDetail},
 };
 use serde::Deserialize;
 
+use super::ChainFilter;
 use crate::{utils::AppError, AppState};
 
 #[derive(Deserialize)]
 pub struct GetAllRewardsFilter {
     pub page: i64,
     pub limit: i64,
+    #[serde(rename = "chainId")]
+    pub chain_id: Option<i64>,
 }
 
 /// This function is used to get all rewards in paginated form
@@ -31,11 +34,12 @@ pub async fn get_all_rewards(
     State(state): State<Arc<AppState>>,
     Query(filter): Query<GetAllRewardsFilter>,
 ) -> Result<Json<PaginatedData<RewardRecord>>, AppError> {
-    let mut db_client = create_db_instance(&state.db_url).await?;
+    let mut db_client = get_db_client(&state.db_pool).await?;
     let reward_record =
-        query_all_rewards_paginated(&mut db_client, filter.page, filter.limit).await?;
+        query_all_rewards_paginated(&mut db_client, filter.chain_id, filter.page, filter.limit)
+            .await?;
 
-    let total_items = get_total_rewards_count(&mut db_client).await?;
+    let total_items = get_total_rewards_count(filter.chain_id, &mut db_client).await?;
     let page_size = filter.limit;
     let total_page = (total_items + page_size - 1) / page_size;
     let current_page = filter.page;
@@ -56,9 +60,10 @@ pub async fn get_all_rewards(
 pub async fn get_reward_by_brand_id(
     State(state): State<Arc<AppState>>,
     Path(brand_id): Path<String>,
+    Query(chain): Query<ChainFilter>,
 ) -> Result<Json<RewardRecord>, AppError> {
-    let mut db_client = create_db_instance(&state.db_url).await?;
-    let reward_record = query_reward_by_brand_id(brand_id, &mut db_client).await?;
+    let mut db_client = get_db_client(&state.db_pool).await?;
+    let reward_record = query_reward_by_brand_id(brand_id, chain.chain_id, &mut db_client).await?;
 
     Ok(Json(reward_record))
 }
@@ -68,9 +73,11 @@ pub async fn get_reward_by_brand_id(
 pub async fn get_reward_by_reward_address(
     State(state): State<Arc<AppState>>,
     Path(reward_address): Path<String>,
+    Query(chain): Query<ChainFilter>,
 ) -> Result<Json<RewardRecord>, AppError> {
-    let mut db_client = create_db_instance(&state.db_url).await?;
-    let reward_record = query_reward_by_requestor_address(reward_address, &mut db_client).await?;
+    let mut db_client = get_db_client(&state.db_pool).await?;
+    let reward_record =
+        query_reward_by_requestor_address(reward_address, chain.chain_id, &mut db_client).await?;
 
     Ok(Json(reward_record))
 }
@@ -80,12 +87,11 @@ pub async fn get_reward_by_reward_address(
 
 pub async fn get_reward_count(
     State(state): State<Arc<AppState>>,
+    Query(chain): Query<ChainFilter>,
 ) -> Result<Json<TotalDetail>, AppError> {
-    let mut db_client = create_db_instance(&state.db_url).await?;
-    let reward_count = get_total_rewards_count(&mut db_client).await?;
+    let mut db_client = get_db_client(&state.db_pool).await?;
+    let reward_counts = get_reward_count_per_chain(&mut db_client).await?;
 
-    let total_detail = TotalDetail {
-        total: reward_count.to_string(),
-    };
+    let total_detail = TotalDetail::from_chain_counts(reward_counts, chain.chain_id);
     Ok(Json(total_detail))
 }
@@ -0,0 +1,64 @@
+//! This module holds the handlers for the token whitelist of OpenGateways.
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, Query, State},
+    Json,
+};
+use chronicle_primitives::{
+    db::{
+        connection_pool::get_db_client,
+        entities::token_whitelist::{
+            query_current_whitelist, query_whitelist_at_block, query_whitelist_history,
+        },
+    },
+    entity_record::{TokenWhitelistChangeRecord, TokenWhitelistRecord},
+};
+
+use super::ChainFilter;
+use crate::{utils::AppError, AppState};
+
+/// This function is used to get the tokens a gateway currently accepts
+/// this `gateway_address` is specified in the path.
+pub async fn get_token_whitelist(
+    State(state): State<Arc<AppState>>,
+    Path(gateway_address): Path<String>,
+    Query(chain): Query<ChainFilter>,
+) -> Result<Json<Vec<TokenWhitelistRecord>>, AppError> {
+    let mut db_client = get_db_client(&state.db_pool).await?;
+    let whitelist =
+        query_current_whitelist(gateway_address, chain.chain_id, &mut db_client).await?;
+
+    Ok(Json(whitelist))
+}
+
+/// This function is used to get the tokens a gateway accepted at a block,
+/// every token comes with the change that whitelisted it
+pub async fn get_token_whitelist_at_block(
+    State(state): State<Arc<AppState>>,
+    Path((gateway_address, block_number)): Path<(String, i64)>,
+    Query(chain): Query<ChainFilter>,
+) -> Result<Json<Vec<TokenWhitelistChangeRecord>>, AppError> {
+    let mut db_client = get_db_client(&state.db_pool).await?;
+    let whitelist = query_whitelist_at_block(
+        gateway_address,
+        chain.chain_id,
+        block_number,
+        &mut db_client,
+    )
+    .await?;
+
+    Ok(Json(whitelist))
+}
+
+/// This function is used to get every whitelist change of a gateway, oldest first
+pub async fn get_token_whitelist_history(
+    State(state): State<Arc<AppState>>,
+    Path(gateway_address): Path<String>,
+    Query(chain): Query<ChainFilter>,
+) -> Result<Json<Vec<TokenWhitelistChangeRecord>>, AppError> {
+    let mut db_client = get_db_client(&state.db_pool).await?;
+    let history = query_whitelist_history(gateway_address, chain.chain_id, &mut db_client).await?;
+
+    Ok(Json(history))
+}
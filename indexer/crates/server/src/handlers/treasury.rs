@@ -0,0 +1,178 @@
+//! This module holds the handlers for the treasury ledger of OpenGateways.
+use std::sync::Arc;
+
+use alloy::{
+    eips::BlockId,
+    primitives::{Address, I256, U256},
+    providers::{Provider, ProviderBuilder},
+    sol,
+};
+use axum::{
+    extract::{Path, Query, State},
+    Json,
+};
+use chronicle_primitives::{
+    db::{
+        connection_pool::{get_db_client, DbPool},
+        entities::treasury::{
+            get_total_count_treasury_entries, query_treasury_balance_at_block,
+            query_treasury_daily_rollups, query_treasury_entries_paginated,
+        },
+    },
+    entity_record::{TreasuryDailyRecord, TreasuryEntryRecord, TreasuryReconciliationRecord},
+    handler_payload::PaginatedData,
+};
+use serde::Deserialize;
+
+use super::ChainFilter;
+use crate::{utils::AppError, AppState};
+
+sol! {
+    #[sol(rpc)]
+    interface IERC20 {
+        function balanceOf(address account) external view returns (uint256);
+    }
+}
+
+#[derive(Deserialize)]
+pub struct GetTreasuryEntriesFilter {
+    pub page: i64,
+    pub limit: i64,
+    #[serde(rename = "chainId")]
+    pub chain_id: Option<i64>,
+}
+
+/// This function is used to get the ledger entries of a gateway token, newest first,
+/// `0x0000000000000000000000000000000000000000` is the native ETH ledger
+/// this query is in the paginated form
+pub async fn get_treasury_entries(
+    State(state): State<Arc<AppState>>,
+    Path((gateway_address, token_address)): Path<(String, String)>,
+    Query(filter): Query<GetTreasuryEntriesFilter>,
+) -> Result<Json<PaginatedData<TreasuryEntryRecord>>, AppError> {
+    let mut db_client = get_db_client(&state.db_pool).await?;
+    let entry_records = query_treasury_entries_paginated(
+        &gateway_address,
+        &token_address,
+        filter.chain_id,
+        &mut db_client,
+        filter.page,
+        filter.limit,
+    )
+    .await?;
+
+    let total_items = get_total_count_treasury_entries(
+        &gateway_address,
+        &token_address,
+        filter.chain_id,
+        &mut db_client,
+    )
+    .await?;
+    let page_size = filter.limit;
+    let total_page = (total_items + page_size - 1) / page_size;
+    let current_page = filter.page;
+
+    let paginated_data = PaginatedData::new(
+        entry_records,
+        total_page,
+        total_items,
+        current_page,
+        page_size,
+    );
+
+    Ok(Json(paginated_data))
+}
+
+/// This function is used to get the daily rollups of a gateway token
+pub async fn get_treasury_daily_rollups(
+    State(state): State<Arc<AppState>>,
+    Path((gateway_address, token_address)): Path<(String, String)>,
+    Query(chain): Query<ChainFilter>,
+) -> Result<Json<Vec<TreasuryDailyRecord>>, AppError> {
+    let mut db_client = get_db_client(&state.db_pool).await?;
+    let rollups = query_treasury_daily_rollups(
+        &gateway_address,
+        &token_address,
+        chain.chain_id,
+        &mut db_client,
+    )
+    .await?;
+
+    Ok(Json(rollups))
+}
+
+/// This function is used to compare the indexed balance of a gateway token
+/// with its on-chain balance at a block
+pub async fn get_treasury_reconciliation(
+    State(state): State<Arc<AppState>>,
+    Path((gateway_address, token_address, block_number)): Path<(String, String, u64)>,
+) -> Result<Json<TreasuryReconciliationRecord>, AppError> {
+    let reconciliation = reconcile_treasury_balance(
+        &state.db_pool,
+        state.rpc_url.as_deref(),
+        gateway_address,
+        token_address,
+        block_number,
+    )
+    .await?;
+
+    Ok(Json(reconciliation))
+}
+
+/// This function reads the balance of a gateway token at `block_number` from the chain,
+/// with `getBalance` for native ETH and `balanceOf` for ERC20 tokens,
+/// and compares it with the balance summed from the ledger.
+/// `withdrawERC20` emits no event, so an ERC20 ledger has no debit for the withdrawals and
+/// its difference includes every amount withdrawn, this is flagged by `withdrawals_indexed`
+pub async fn reconcile_treasury_balance(
+    db_pool: &DbPool,
+    rpc_url: Option<&str>,
+    gateway_address: String,
+    token_address: String,
+    block_number: u64,
+) -> Result<TreasuryReconciliationRecord, anyhow::Error> {
+    let rpc_url = rpc_url
+        .ok_or_else(|| anyhow::anyhow!("`server.rpc_url` must be set to reconcile balances"))?;
+    let gateway = gateway_address.parse::<Address>()?;
+    let token = token_address.parse::<Address>()?;
+    let block = BlockId::number(block_number);
+
+    let provider = ProviderBuilder::new().on_builtin(rpc_url).await?;
+    // The ledger is read on the chain of the RPC the balance is read from
+    let chain_id = provider.get_chain_id().await? as i64;
+    let onchain_balance: U256 = if token == Address::ZERO {
+        provider.get_balance(gateway).block_id(block).await?
+    } else {
+        IERC20::new(token, &provider)
+            .balanceOf(gateway)
+            .block(block)
+            .call()
+            .await?
+            ._0
+    };
+
+    let mut db_client = get_db_client(db_pool).await?;
+    let indexed_balance = query_treasury_balance_at_block(
+        chain_id,
+        &gateway_address,
+        &token_address,
+        block_number as i64,
+        &mut db_client,
+    )
+    .await?;
+
+    let difference =
+        I256::try_from(onchain_balance)?.saturating_sub(I256::from_dec_str(&indexed_balance)?);
+
+    Ok(TreasuryReconciliationRecord {
+        chain_id,
+        gateway_address,
+        token_address,
+        block_number: block_number.to_string(),
+        indexed_balance,
+        onchain_balance: onchain_balance.to_string(),
+        difference: difference.to_string(),
+        reconciled: difference.is_zero(),
+        withdrawals_indexed: token == Address::ZERO,
+    })
+}
@@ -1,27 +1,40 @@
+pub mod abi_schema;
 pub mod handlers;
 pub mod query;
 pub mod utils;
 use std::sync::Arc;
 
+use abi_schema::build_abi_event_schema;
 use async_graphql::{http::GraphiQLSource, EmptyMutation, EmptySubscription, ObjectType, Schema};
 use async_graphql_axum::GraphQL;
 use axum::{
-    http::Method,
+    http::{header, Method},
     response::{self, IntoResponse},
     routing::get,
     Router,
 };
-use chronicle_primitives::ServerConfig;
+use chronicle_primitives::{
+    abi::AbiEventTable, db::connection_pool::DbPool, metrics::metrics, ServerConfig,
+};
 use handlers::{
     brand::{get_all_brands_paginated, get_brand_by_id, get_brand_by_name, get_brand_count},
+    event::{get_event_status_by_tx_hash, get_events_by_status},
+    payment::{
+        get_payment_by_payment_id, get_payments_by_gateway, get_payments_by_payer,
+        get_payments_by_token,
+    },
     pool::{get_all_pools, get_pool_by_reward_address, get_pool_count},
     reward::{
         get_all_rewards, get_reward_by_brand_id, get_reward_by_reward_address, get_reward_count,
     },
+    token_whitelist::{
+        get_token_whitelist, get_token_whitelist_at_block, get_token_whitelist_history,
+    },
     transaction::{
         get_all_redemption_by_user_address, get_all_redemptions, get_all_redemptions_by_reward_id,
         get_redemption_by_onchain_tx_hash, get_redeption_count,
     },
+    treasury::{get_treasury_daily_rollups, get_treasury_entries, get_treasury_reconciliation},
 };
 use tokio::net::TcpListener;
 use tower_http::cors::{Any, CorsLayer};
@@ -31,24 +44,42 @@ async fn graphiql() -> impl IntoResponse {
     response::Html(GraphiQLSource::build().endpoint("/").finish())
 }
 
+/// This function is used to serve the GraphiQL IDE of the ABI event schema.
+async fn abi_event_graphiql() -> impl IntoResponse {
+    response::Html(GraphiQLSource::build().endpoint("/graphql/events").finish())
+}
+
+/// This function serves the counters of the indexers in the Prometheus text format.
+async fn prometheus_metrics() -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        metrics().render(),
+    )
+}
+
 /// This function is used to run the chronicle server.
-/// `[DB]` This is a generic type, which is used to store the database.
+/// `[db_pool]` This is the pool of database connections shared by the handlers and the GraphQL resolvers.
 /// `[Query]` This is a gaint Query entity, for all the Events enitities and all the tx enitities.
+/// `[abi_event_tables]` These are the tables of the events decoded from an ABI, served from `/graphql/events`.
 pub async fn run_chronicle_server<Query>(
     config: ServerConfig,
     query: Query,
-    db_url: String,
+    db_pool: DbPool,
+    abi_event_tables: Vec<AbiEventTable>,
 ) -> Result<(), anyhow::Error>
 where
     Query: ObjectType + 'static,
 {
     let url = config.server_url.clone();
     let schema = Schema::build(query, EmptyMutation, EmptySubscription)
-        .data(db_url.clone())
+        .data(db_pool.clone())
+        .data(config.clone())
         .finish();
+    let abi_event_schema = build_abi_event_schema(&abi_event_tables, &db_pool)?;
 
     let app_state = Arc::new(AppState {
-        db_url: db_url.clone(),
+        db_pool,
+        rpc_url: config.rpc_url.clone(),
     });
 
     let cors = CorsLayer::new()
@@ -58,6 +89,7 @@ where
 
     let app = Router::new()
         .route("/", get(|| async { "Open reward Indexer." }))
+        .route("/metrics", get(prometheus_metrics))
         // graphql routes
         .route("/graphql", get(graphiql).post_service(GraphQL::new(schema)))
         // pool routes
@@ -98,9 +130,65 @@ where
             get(get_reward_by_brand_id),
         )
         .route("/get-all-rewards", get(get_all_rewards))
-        // misc
-        .layer(cors)
-        .with_state(app_state);
+        // payment routes
+        .route(
+            "/get-payment-by-payment-id/:payment_id",
+            get(get_payment_by_payment_id),
+        )
+        .route("/get-payments-by-payer/:payer", get(get_payments_by_payer))
+        .route(
+            "/get-payments-by-token/:token_address",
+            get(get_payments_by_token),
+        )
+        .route(
+            "/get-payments-by-gateway/:gateway_address",
+            get(get_payments_by_gateway),
+        )
+        // token whitelist routes
+        .route(
+            "/get-token-whitelist/:gateway_address",
+            get(get_token_whitelist),
+        )
+        .route(
+            "/get-token-whitelist-at-block/:gateway_address/:block_number",
+            get(get_token_whitelist_at_block),
+        )
+        .route(
+            "/get-token-whitelist-history/:gateway_address",
+            get(get_token_whitelist_history),
+        )
+        // treasury routes
+        .route(
+            "/get-treasury-entries/:gateway_address/:token_address",
+            get(get_treasury_entries),
+        )
+        .route(
+            "/get-treasury-daily-rollups/:gateway_address/:token_address",
+            get(get_treasury_daily_rollups),
+        )
+        .route(
+            "/reconcile-treasury/:gateway_address/:token_address/:block_number",
+            get(get_treasury_reconciliation),
+        )
+        // buffered event routes
+        .route(
+            "/get-events-by-status/:indexer_name",
+            get(get_events_by_status),
+        )
+        .route(
+            "/get-event-status-by-tx-hash/:transaction_hash",
+            get(get_event_status_by_tx_hash),
+        );
+    // abi event graphql routes
+    let app = match abi_event_schema {
+        Some(abi_event_schema) => app.route(
+            "/graphql/events",
+            get(abi_event_graphiql).post_service(GraphQL::new(abi_event_schema)),
+        ),
+        None => app,
+    };
+    // misc
+    let app = app.layer(cors).with_state(app_state);
 
     tracing::info!(url);
     axum::serve(TcpListener::bind(url).await.unwrap(), app)
@@ -111,5 +199,8 @@ where
 }
 
 pub struct AppState {
-    pub db_url: String,
+    /// This is the pool the handlers take their database connection from
+    pub db_pool: DbPool,
+    /// This is the RPC on-chain state is read from
+    pub rpc_url: Option<String>,
 }
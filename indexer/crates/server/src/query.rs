@@ -1,11 +1,27 @@
 use async_graphql::{Context, Object};
 use chronicle_primitives::{
-    db::raw_chronicle_event::{
-        create_db_instance, get_all_events, get_events_by_block_number, get_events_by_tx_hash,
+    db::{
+        connection_pool::{get_db_client, DbPool},
+        entities::{
+            payment::{query_payment_by_payment_id, query_payments_paginated, PaymentFilter},
+            token_whitelist::{
+                query_current_whitelist, query_whitelist_at_block, query_whitelist_history,
+            },
+            treasury::{query_treasury_daily_rollups, query_treasury_entries_paginated},
+        },
+        pending_event::{query_buffered_events, query_buffered_events_by_tx_hash, EventStatus},
+        raw_chronicle_event::{get_all_events, get_events_by_block_number, get_events_by_tx_hash},
+    },
+    entity_record::{
+        BufferedEventRecord, PaymentRecord, TokenWhitelistChangeRecord, TokenWhitelistRecord,
+        TreasuryDailyRecord, TreasuryEntryRecord, TreasuryReconciliationRecord,
     },
     indexer::DisplayChronicleEvent,
+    ServerConfig,
 };
 
+use crate::handlers::treasury::reconcile_treasury_balance;
+
 pub struct ChronicleQuery;
 
 #[Object]
@@ -14,16 +30,13 @@ impl ChronicleQuery {
         &self,
         cxt: &Context<'a>,
         name: String,
-    ) -> Vec<DisplayChronicleEvent> {
-        let db_url = cxt.data_unchecked::<String>();
-        let mut db_client = create_db_instance(&db_url)
-            .await
-            .expect("Could not connect to the db");
-        let events = get_all_events(&mut db_client, &name)
-            .await
-            .expect("Could not get events from db");
+        chain_id: Option<i64>,
+    ) -> async_graphql::Result<Vec<DisplayChronicleEvent>> {
+        let db_pool = cxt.data_unchecked::<DbPool>();
+        let mut db_client = get_db_client(db_pool).await?;
+        let events = get_all_events(&mut db_client, &name, chain_id).await?;
 
-        events
+        Ok(events)
     }
 
     async fn get_events_by_tx_hash<'a>(
@@ -31,16 +44,14 @@ impl ChronicleQuery {
         cxt: &Context<'a>,
         name: String,
         transaction_hash: String,
-    ) -> Vec<DisplayChronicleEvent> {
-        let db_url = cxt.data_unchecked::<String>();
-        let mut db_client = create_db_instance(&db_url)
-            .await
-            .expect("Could not connect to the db");
-        let events = get_events_by_tx_hash(&mut db_client, &name, transaction_hash)
-            .await
-            .expect("Could not get events from db");
+        chain_id: Option<i64>,
+    ) -> async_graphql::Result<Vec<DisplayChronicleEvent>> {
+        let db_pool = cxt.data_unchecked::<DbPool>();
+        let mut db_client = get_db_client(db_pool).await?;
+        let events =
+            get_events_by_tx_hash(&mut db_client, &name, transaction_hash, chain_id).await?;
 
-        events
+        Ok(events)
     }
 
     async fn get_events_by_block_number<'a>(
@@ -48,15 +59,237 @@ impl ChronicleQuery {
         cxt: &Context<'a>,
         name: String,
         block_number: String,
-    ) -> Vec<DisplayChronicleEvent> {
-        let db_url = cxt.data_unchecked::<String>();
-        let mut db_client = create_db_instance(&db_url)
-            .await
-            .expect("Could not connect to the db");
-        let events = get_events_by_block_number(&mut db_client, &name, block_number)
-            .await
-            .expect("Could not get events from db");
+        chain_id: Option<i64>,
+    ) -> async_graphql::Result<Vec<DisplayChronicleEvent>> {
+        let db_pool = cxt.data_unchecked::<DbPool>();
+        let mut db_client = get_db_client(db_pool).await?;
+        let events =
+            get_events_by_block_number(&mut db_client, &name, block_number, chain_id).await?;
+
+        Ok(events)
+    }
+
+    /// Returns the buffered events of an indexer, `status` is either `pending` or `confirmed`
+    async fn get_buffered_events<'a>(
+        &self,
+        cxt: &Context<'a>,
+        indexer_name: String,
+        status: Option<String>,
+        chain_id: Option<i64>,
+    ) -> async_graphql::Result<Vec<BufferedEventRecord>> {
+        let status = status
+            .map(|status| status.parse::<EventStatus>())
+            .transpose()?;
+        let db_pool = cxt.data_unchecked::<DbPool>();
+        let mut db_client = get_db_client(db_pool).await?;
+        let events = query_buffered_events(indexer_name, status, chain_id, &mut db_client).await?;
+
+        Ok(events)
+    }
+
+    /// Returns the status of the buffered events emitted by a transaction
+    async fn get_event_status_by_tx_hash<'a>(
+        &self,
+        cxt: &Context<'a>,
+        transaction_hash: String,
+    ) -> async_graphql::Result<Vec<BufferedEventRecord>> {
+        let db_pool = cxt.data_unchecked::<DbPool>();
+        let mut db_client = get_db_client(db_pool).await?;
+        let events = query_buffered_events_by_tx_hash(transaction_hash, &mut db_client).await?;
+
+        Ok(events)
+    }
+
+    /// Returns the payment with the given `paymentId`, its `status` is `pending` until
+    /// its log has the confirmations of the indexer
+    async fn get_payment_by_payment_id<'a>(
+        &self,
+        cxt: &Context<'a>,
+        payment_id: String,
+        chain_id: Option<i64>,
+    ) -> async_graphql::Result<PaymentRecord> {
+        let db_pool = cxt.data_unchecked::<DbPool>();
+        let mut db_client = get_db_client(db_pool).await?;
+        let payment = query_payment_by_payment_id(payment_id, chain_id, &mut db_client).await?;
+
+        Ok(payment)
+    }
+
+    /// Returns the payments made by a payer, newest first
+    async fn get_payments_by_payer<'a>(
+        &self,
+        cxt: &Context<'a>,
+        payer: String,
+        chain_id: Option<i64>,
+        page: i64,
+        limit: i64,
+    ) -> async_graphql::Result<Vec<PaymentRecord>> {
+        query_payments(cxt, PaymentFilter::Payer(payer), chain_id, page, limit).await
+    }
 
-        events
+    /// Returns the payments made with a token, `address(0)` for native ETH, newest first
+    async fn get_payments_by_token<'a>(
+        &self,
+        cxt: &Context<'a>,
+        token_address: String,
+        chain_id: Option<i64>,
+        page: i64,
+        limit: i64,
+    ) -> async_graphql::Result<Vec<PaymentRecord>> {
+        query_payments(
+            cxt,
+            PaymentFilter::Token(token_address),
+            chain_id,
+            page,
+            limit,
+        )
+        .await
+    }
+
+    /// Returns the payments received by a gateway, newest first
+    async fn get_payments_by_gateway<'a>(
+        &self,
+        cxt: &Context<'a>,
+        gateway_address: String,
+        chain_id: Option<i64>,
+        page: i64,
+        limit: i64,
+    ) -> async_graphql::Result<Vec<PaymentRecord>> {
+        query_payments(
+            cxt,
+            PaymentFilter::Gateway(gateway_address),
+            chain_id,
+            page,
+            limit,
+        )
+        .await
     }
+
+    /// Returns the tokens a gateway currently accepts
+    async fn get_token_whitelist<'a>(
+        &self,
+        cxt: &Context<'a>,
+        gateway_address: String,
+        chain_id: Option<i64>,
+    ) -> async_graphql::Result<Vec<TokenWhitelistRecord>> {
+        let db_pool = cxt.data_unchecked::<DbPool>();
+        let mut db_client = get_db_client(db_pool).await?;
+        let whitelist = query_current_whitelist(gateway_address, chain_id, &mut db_client).await?;
+
+        Ok(whitelist)
+    }
+
+    /// Returns the tokens a gateway accepted at `blockNumber`, with the change that whitelisted each
+    async fn get_token_whitelist_at_block<'a>(
+        &self,
+        cxt: &Context<'a>,
+        gateway_address: String,
+        block_number: i64,
+        chain_id: Option<i64>,
+    ) -> async_graphql::Result<Vec<TokenWhitelistChangeRecord>> {
+        let db_pool = cxt.data_unchecked::<DbPool>();
+        let mut db_client = get_db_client(db_pool).await?;
+        let whitelist =
+            query_whitelist_at_block(gateway_address, chain_id, block_number, &mut db_client)
+                .await?;
+
+        Ok(whitelist)
+    }
+
+    /// Returns every whitelist change of a gateway, oldest first
+    async fn get_token_whitelist_history<'a>(
+        &self,
+        cxt: &Context<'a>,
+        gateway_address: String,
+        chain_id: Option<i64>,
+    ) -> async_graphql::Result<Vec<TokenWhitelistChangeRecord>> {
+        let db_pool = cxt.data_unchecked::<DbPool>();
+        let mut db_client = get_db_client(db_pool).await?;
+        let history = query_whitelist_history(gateway_address, chain_id, &mut db_client).await?;
+
+        Ok(history)
+    }
+
+    /// Returns the ledger entries of a gateway token newest first, `address(0)` for native ETH
+    async fn get_treasury_entries<'a>(
+        &self,
+        cxt: &Context<'a>,
+        gateway_address: String,
+        token_address: String,
+        chain_id: Option<i64>,
+        page: i64,
+        limit: i64,
+    ) -> async_graphql::Result<Vec<TreasuryEntryRecord>> {
+        let db_pool = cxt.data_unchecked::<DbPool>();
+        let mut db_client = get_db_client(db_pool).await?;
+        let entries = query_treasury_entries_paginated(
+            &gateway_address,
+            &token_address,
+            chain_id,
+            &mut db_client,
+            page,
+            limit,
+        )
+        .await?;
+
+        Ok(entries)
+    }
+
+    /// Returns the daily rollups of a gateway token, oldest first
+    async fn get_treasury_daily_rollups<'a>(
+        &self,
+        cxt: &Context<'a>,
+        gateway_address: String,
+        token_address: String,
+        chain_id: Option<i64>,
+    ) -> async_graphql::Result<Vec<TreasuryDailyRecord>> {
+        let db_pool = cxt.data_unchecked::<DbPool>();
+        let mut db_client = get_db_client(db_pool).await?;
+        let rollups = query_treasury_daily_rollups(
+            &gateway_address,
+            &token_address,
+            chain_id,
+            &mut db_client,
+        )
+        .await?;
+
+        Ok(rollups)
+    }
+
+    /// Compares the indexed balance of a gateway token with its on-chain balance at `blockNumber`,
+    /// `withdrawalsIndexed` is false for ERC20 tokens as their withdrawals emit no event
+    async fn reconcile_treasury<'a>(
+        &self,
+        cxt: &Context<'a>,
+        gateway_address: String,
+        token_address: String,
+        block_number: u64,
+    ) -> async_graphql::Result<TreasuryReconciliationRecord> {
+        let db_pool = cxt.data_unchecked::<DbPool>();
+        let server_config = cxt.data_unchecked::<ServerConfig>();
+        let reconciliation = reconcile_treasury_balance(
+            db_pool,
+            server_config.rpc_url.as_deref(),
+            gateway_address,
+            token_address,
+            block_number,
+        )
+        .await?;
+
+        Ok(reconciliation)
+    }
+}
+
+async fn query_payments(
+    cxt: &Context<'_>,
+    filter: PaymentFilter,
+    chain_id: Option<i64>,
+    page: i64,
+    limit: i64,
+) -> async_graphql::Result<Vec<PaymentRecord>> {
+    let db_pool = cxt.data_unchecked::<DbPool>();
+    let mut db_client = get_db_client(db_pool).await?;
+    let payments = query_payments_paginated(&filter, chain_id, &mut db_client, page, limit).await?;
+
+    Ok(payments)
 }
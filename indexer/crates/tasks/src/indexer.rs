@@ -1,12 +1,24 @@
-use alloy::{providers::ProviderBuilder, rpc::client::WsConnect};
+use std::{future::Future, sync::Arc, time::Duration};
+
 use anyhow::bail;
 use async_trait::async_trait;
-use chronicle_indexer::events::evm::EvmEventIndexer;
+use chronicle_indexer::{
+    events::{
+        evm::{utils::LogSource, EvmEventIndexer},
+        runtime::RuntimeEventIndexer,
+    },
+    tx::{evm::EvmTransactionIndexer, trace::EvmTraceIndexer},
+};
 use chronicle_primitives::{
-    db::raw_chronicle_event::create_db_instance, interfaces::ChronicleEventIndexer, IndexerConfig,
-    StateMachine,
+    db::{
+        abi_event::{create_abi_event_table, AbiEventHandler},
+        connection_pool::{get_db_client, DbPool},
+    },
+    indexer::ChronicleIndexingMode,
+    registry::EventHandlerRegistry,
+    IndexerConfig, StateMachine,
 };
-use tokio::{select, try_join};
+use tokio::select;
 use tokio_util::sync::CancellationToken;
 use tracing::info;
 
@@ -15,59 +27,233 @@ use crate::Task;
 #[derive(Debug)]
 pub struct IndexerTask {
     pub config: IndexerConfig,
-    pub db_url: String,
+    /// This is the pool the indexer takes a database connection from for each write
+    pub db_pool: DbPool,
+    /// These are the handlers the indexed events are dispatched to
+    pub registry: EventHandlerRegistry,
 }
 
 #[async_trait]
 impl Task for IndexerTask {
-    async fn run(mut self: Box<Self>, shutdown_token: CancellationToken) -> anyhow::Result<()> {
-        let mut client = create_db_instance(&self.db_url)
-            .await
-            .expect("Could not create db instance");
-        let ws = WsConnect::new(self.config.rpc_url.clone());
-        let provider = ProviderBuilder::new().on_ws(ws).await.unwrap();
-        match self.config.state_machine.clone().into() {
-            StateMachine::EVM => {
-                let evm_event_indexer = EvmEventIndexer {};
-
-                // This queries events that have happened from this block number and stores them in the database
-                // It also subscribes to new events and stores them in the database
-                let evm_indexer_handle = tokio::spawn(async move {
-                    select! {
-                        event_n_sub = evm_event_indexer.query_then_subscribe_to_events(
-                            provider.clone(),
-                            self.config.address.clone().parse().expect("CONFIG address could not be parsed"),
-                            self.config.event_signature.clone().parse().expect("CONFIG event signature is missing"),
-                            self.config.block_number.into(),
-                            &mut client,
-                        ) => {
-                            // Want this indexing to halt before
-                            if event_n_sub.is_err() {
-                                info!("Event subscription error, exitting now. ERROR: {:?}", event_n_sub.err().unwrap());
-                            }
-                        }
-                        _ = shutdown_token.cancelled() => {
-                            info!("Shutting down chronicle indexer");
-                        }
-                    }
-                });
-
-                match try_join!(evm_indexer_handle) {
-                    Ok(_) => {
-                        info!("Server task completed");
-                    }
-                    Err(e) => bail!("Error running server: {:?}", e),
-                }
+    async fn run(self: Box<Self>, shutdown_token: CancellationToken) -> anyhow::Result<()> {
+        // `RUNTIME` indexers only index events, this is checked when the config is validated
+        match (
+            StateMachine::from(self.config.state_machine.clone()),
+            self.config.indexing_mode,
+        ) {
+            (StateMachine::RUNTIME, _) => self.run_runtime_indexer(shutdown_token).await,
+            (StateMachine::EVM, ChronicleIndexingMode::Event) => {
+                self.run_event_indexer(shutdown_token).await
+            }
+            (StateMachine::EVM, ChronicleIndexingMode::Transaction) => {
+                self.run_transaction_indexer(shutdown_token).await
+            }
+            (StateMachine::EVM, ChronicleIndexingMode::Trace) => {
+                self.run_trace_indexer(shutdown_token).await
             }
-            StateMachine::RUNTIME => {}
         }
-        Ok(())
     }
 }
 
 impl IndexerTask {
-    pub fn new(config: IndexerConfig, db_url: String) -> Self {
-        Self { config, db_url }
+    /// HTTP endpoints can not push logs or blocks, the new blocks are polled instead
+    fn log_source(&self) -> LogSource {
+        if self.config.polls() {
+            LogSource::Poll {
+                interval: Duration::from_millis(self.config.poll_interval_ms),
+                max_block_range: self.config.backfill.max_block_range,
+            }
+        } else {
+            LogSource::Subscribe
+        }
+    }
+
+    /// This runs the indexer in the event indexing mode, the logs of the event are dispatched
+    /// to the handlers from `block_number` and then followed live
+    async fn run_event_indexer(
+        self: Box<Self>,
+        shutdown_token: CancellationToken,
+    ) -> anyhow::Result<()> {
+        let address = self.config.address.parse()?;
+        let event_topic = self.config.event_topic()?;
+        let mut registry = self
+            .registry
+            .clone()
+            .with_unmatched_events(self.config.unmatched_events);
+        // Events decoded from an ABI are stored in their own table, whichever handler the registry had for them
+        if let Some(table) = self.config.abi_event_table()? {
+            create_abi_event_table(&table, &mut get_db_client(&self.db_pool).await?).await?;
+            if self.config.factory {
+                registry.register_any_address(event_topic, AbiEventHandler::new(table));
+            } else {
+                registry.register(address, event_topic, AbiEventHandler::new(table));
+            }
+        }
+
+        let evm_event_indexer = EvmEventIndexer::new(
+            self.config.event_name.clone(),
+            self.config.backfill.clone(),
+            self.config.reorg_window,
+            self.config.confirmations.clone(),
+            self.config.factory,
+            Arc::new(registry),
+            self.log_source(),
+        );
+
+        // This queries events that have happened from this block number and stores them in the database
+        // It also subscribes to new events and stores them in the database, reconnecting when the connection drops
+        let indexer_name = self.config.event_name.clone();
+        run_until_shutdown(
+            &indexer_name,
+            async move {
+                evm_event_indexer
+                    .index_with_reconnect(
+                        &self.config.rpc_url,
+                        address,
+                        event_topic,
+                        self.config.block_number.into(),
+                        &self.config.reconnect,
+                        &self.db_pool,
+                    )
+                    .await
+            },
+            shutdown_token,
+        )
+        .await
+    }
+
+    /// This runs the indexer in the transaction indexing mode, the transactions sent from or
+    /// to the configured addresses are stored from `block_number` and then followed live
+    async fn run_transaction_indexer(
+        self: Box<Self>,
+        shutdown_token: CancellationToken,
+    ) -> anyhow::Result<()> {
+        let addresses = self.config.transaction_addresses()?;
+        let evm_transaction_indexer = EvmTransactionIndexer::new(
+            self.config.event_name.clone(),
+            self.config.reorg_window,
+            self.config.confirmations.clone(),
+            self.log_source(),
+            self.config.calldata_decoder()?,
+        );
+
+        let indexer_name = self.config.event_name.clone();
+        run_until_shutdown(
+            &indexer_name,
+            async move {
+                evm_transaction_indexer
+                    .index_with_reconnect(
+                        &self.config.rpc_url,
+                        addresses,
+                        self.config.block_number,
+                        &self.config.reconnect,
+                        &self.db_pool,
+                    )
+                    .await
+            },
+            shutdown_token,
+        )
+        .await
+    }
+
+    /// This runs the indexer in the trace indexing mode, the native ETH moved into or out of
+    /// the configured addresses by any call is stored and dispatched from `block_number`,
+    /// and then followed live
+    async fn run_trace_indexer(
+        self: Box<Self>,
+        shutdown_token: CancellationToken,
+    ) -> anyhow::Result<()> {
+        let addresses = self.config.transaction_addresses()?;
+        let registry = self
+            .registry
+            .clone()
+            .with_unmatched_events(self.config.unmatched_events);
+        let evm_trace_indexer = EvmTraceIndexer::new(
+            self.config.event_name.clone(),
+            self.config.reorg_window,
+            self.config.confirmations.clone(),
+            self.log_source(),
+            self.config.trace_api,
+            Arc::new(registry),
+        );
+
+        let indexer_name = self.config.event_name.clone();
+        run_until_shutdown(
+            &indexer_name,
+            async move {
+                evm_trace_indexer
+                    .index_with_reconnect(
+                        &self.config.rpc_url,
+                        addresses,
+                        self.config.block_number,
+                        &self.config.reconnect,
+                        &self.db_pool,
+                    )
+                    .await
+            },
+            shutdown_token,
+        )
+        .await
+    }
+
+    /// This runs the indexer on a Substrate runtime, the selected runtime events are stored
+    /// from `block_number` and then followed through the finalized heads
+    async fn run_runtime_indexer(
+        self: Box<Self>,
+        shutdown_token: CancellationToken,
+    ) -> anyhow::Result<()> {
+        let runtime_event_indexer = RuntimeEventIndexer::new(
+            self.config.event_name.clone(),
+            self.config.runtime_event_filters()?,
+        );
+
+        let indexer_name = self.config.event_name.clone();
+        run_until_shutdown(
+            &indexer_name,
+            async move {
+                runtime_event_indexer
+                    .index_with_reconnect(
+                        &self.config.rpc_url,
+                        self.config.block_number,
+                        &self.config.reconnect,
+                        &self.db_pool,
+                    )
+                    .await
+            },
+            shutdown_token,
+        )
+        .await
+    }
+
+    /// This creates an indexer task dispatching to the OpenReward and OpenGateway entity handlers,
+    /// only the events of the indexed addresses, or of the gateways of the factory, are handled
+    pub fn new(config: IndexerConfig, db_pool: DbPool) -> anyhow::Result<Self> {
+        let registry = match StateMachine::from(config.state_machine.clone()) {
+            StateMachine::EVM if config.factory => {
+                EventHandlerRegistry::with_factory_entity_handlers()
+            }
+            StateMachine::EVM => {
+                EventHandlerRegistry::with_entity_handlers(&config.transaction_addresses()?)
+            }
+            // Runtime events are stored by the runtime indexer, they are not dispatched
+            StateMachine::RUNTIME => EventHandlerRegistry::new(),
+        };
+
+        Ok(Self::with_registry(config, db_pool, registry))
+    }
+
+    /// This creates an indexer task dispatching to the handlers of `registry`,
+    /// the unmatched event policy is taken from the indexer config
+    pub fn with_registry(
+        config: IndexerConfig,
+        db_pool: DbPool,
+        registry: EventHandlerRegistry,
+    ) -> Self {
+        Self {
+            config,
+            db_pool,
+            registry,
+        }
     }
 
     /// Converts the task into a boxed trait object.
@@ -75,3 +261,32 @@ impl IndexerTask {
         Box::new(self)
     }
 }
+
+/// This function runs an indexer until it stops or chronicle shuts down. An indexer that
+/// stops with an error is logged, it does not stop the other tasks
+async fn run_until_shutdown(
+    indexer_name: &str,
+    indexer: impl Future<Output = anyhow::Result<()>> + Send + 'static,
+    shutdown_token: CancellationToken,
+) -> anyhow::Result<()> {
+    let name = indexer_name.to_string();
+    let indexer_handle = tokio::spawn(async move {
+        select! {
+            result = indexer => {
+                if let Err(err) = result {
+                    info!("Indexer {name} stopped, exiting now. ERROR: {:?}", err);
+                }
+            }
+            _ = shutdown_token.cancelled() => {
+                info!("Shutting down chronicle indexer {name}");
+            }
+        }
+    });
+
+    if let Err(e) = indexer_handle.await {
+        bail!("Error running indexer {indexer_name}: {:?}", e);
+    }
+    info!("Indexer {indexer_name} task completed");
+
+    Ok(())
+}
@@ -1,6 +1,6 @@
 use anyhow::bail;
 use async_trait::async_trait;
-use chronicle_primitives::ServerConfig;
+use chronicle_primitives::{abi::AbiEventTable, db::connection_pool::DbPool, ServerConfig};
 use chronicle_server::{query::ChronicleQuery, run_chronicle_server};
 use tokio::{select, try_join};
 use tokio_util::sync::CancellationToken;
@@ -11,7 +11,10 @@ use crate::Task;
 #[derive(Debug)]
 pub struct ServerTask {
     pub config: ServerConfig,
-    pub db_url: String,
+    /// This is the pool the server takes its database connections from
+    pub db_pool: DbPool,
+    /// These are the tables of the events decoded from an ABI, served from their own GraphQL schema
+    pub abi_event_tables: Vec<AbiEventTable>,
 }
 
 #[async_trait]
@@ -19,7 +22,7 @@ impl Task for ServerTask {
     async fn run(mut self: Box<Self>, shutdown_token: CancellationToken) -> anyhow::Result<()> {
         let server_handle = tokio::spawn(async move {
             select! {
-                server = run_chronicle_server(self.config, ChronicleQuery, self.db_url) => {
+                server = run_chronicle_server(self.config, ChronicleQuery, self.db_pool, self.abi_event_tables) => {
                     // Want this indexing to halt before
                     if server.is_err() {
                         info!("GraphQL server failed to start");
@@ -42,8 +45,16 @@ impl Task for ServerTask {
 }
 
 impl ServerTask {
-    pub fn new(config: ServerConfig, db_url: String) -> Self {
-        Self { config, db_url }
+    pub fn new(
+        config: ServerConfig,
+        db_pool: DbPool,
+        abi_event_tables: Vec<AbiEventTable>,
+    ) -> Self {
+        Self {
+            config,
+            db_pool,
+            abi_event_tables,
+        }
     }
 
     /// Converts the task into a boxed trait object.